@@ -0,0 +1,421 @@
+// --------------------------------------------------------------------------------------------------------------
+// Mock device simulator
+//
+// Serves plausible HomeWizard P1 and Indevolt PowerFlex2000 responses from one local HTTP port so the whole EMS
+// can be exercised without hardware:
+//
+//   cargo run --example simulator [sim.json]
+//
+// and point the EMS at it:
+//
+//   "p1_url":       "http://127.0.0.1:8080/api/v1/data",
+//   "indevolt_url": "http://127.0.0.1:8080",
+//
+// A simple physics model drives the values: a solar curve over the (simulated) day, a household load with an
+// evening bump and seeded noise, and a battery whose SOC is integrated from its power. SetData commands for the
+// working mode (47005) and real-time control (47015) change how the simulated battery behaves, exactly like the
+// real inverter. With the same seed and the same request timing, a run is reproducible.
+// --------------------------------------------------------------------------------------------------------------
+
+use std::collections::HashMap;
+use std::f64::consts::PI;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use serde::Deserialize;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+
+use energy_management_system::handlers::indevolt::registers::{
+    ACTION_CHARGE, ACTION_DISCHARGE, MODE_REALTIME, MODE_SCHEDULE, MODE_SELF_CONSUMED, REG_CONTROL,
+    REG_WORKING_MODE,
+};
+use energy_management_system::handlers::indevolt::reader::*;
+use energy_management_system::models::p1_models::{ExternalMeasurement, P1Data};
+use energy_management_system::runtime::clock::local_now;
+
+// --------------------------------------------------------------------------------------------------------------
+
+/// Simulator settings, read from the JSON file given as first argument. Missing fields use the defaults.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+struct SimConfig {
+    /// Address to listen on for both the P1 and the Indevolt API.
+    listen:             String,
+    /// Seed for the load/solar noise generator.
+    seed:               u64,
+    /// Simulated seconds per real second (e.g. 60 = one simulated hour per real minute).
+    time_scale:         f64,
+    /// Simulated local hour of day at start-up.
+    start_hour:         f64,
+
+    capacity_kwh:       f64,
+    initial_soc:        f64,
+    min_soc:            f64,
+    max_soc:            f64,
+    max_charge_w:       f64,
+    max_discharge_w:    f64,
+
+    solar_peak_w:       f64,
+    base_load_w:        f64,
+    evening_load_w:     f64,
+    noise_w:            f64,
+}
+
+impl Default for SimConfig {
+    fn default() -> Self {
+        Self {
+            listen:          "127.0.0.1:8080".to_string(),
+            seed:            42,
+            time_scale:      1.0,
+            start_hour:      8.0,
+            capacity_kwh:    12.0,
+            initial_soc:     50.0,
+            min_soc:         10.0,
+            max_soc:         100.0,
+            max_charge_w:    2400.0,
+            max_discharge_w: 2400.0,
+            solar_peak_w:    3000.0,
+            base_load_w:     300.0,
+            evening_load_w:  1500.0,
+            noise_w:         150.0,
+        }
+    }
+}
+
+// --------------------------------------------------------------------------------------------------------------
+
+/// xorshift64* - small, dependency-free and deterministic for a given seed.
+struct Rng(u64);
+
+impl Rng {
+    fn new(seed: u64) -> Self {
+        Self(seed.max(1))
+    }
+
+    /// Uniform value in [-1.0, 1.0).
+    fn signed_unit(&mut self) -> f64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        let v = self.0.wrapping_mul(0x2545_F491_4F6C_DD1D);
+        (v >> 11) as f64 / (1u64 << 53) as f64 * 2.0 - 1.0
+    }
+}
+
+// --------------------------------------------------------------------------------------------------------------
+
+/// Simulated household: solar, load, battery and grid connection.
+struct Sim {
+    cfg:         SimConfig,
+    rng:         Rng,
+    last_step:   Instant,
+    sim_seconds: f64,
+
+    // battery
+    soc:             f64,
+    working_mode:    i64,         // register 47005 value
+    command:         [i64; 3],    // register 47015: [action, watts, soc_limit]
+    battery_power_w: f64,         // positive = charging, negative = discharging
+
+    // flows of the last step
+    solar_w: f64,
+    load_w:  f64,
+    grid_w:  f64,                 // positive = import
+
+    // counters (kWh)
+    import_kwh:      f64,
+    export_kwh:      f64,
+    production_kwh:  f64,
+    charged_kwh:     f64,
+    discharged_kwh:  f64,
+    ac_input_kwh:    f64,
+    monthly_peak_w:  f64,
+}
+
+impl Sim {
+    fn new(cfg: SimConfig) -> Self {
+        Self {
+            rng:             Rng::new(cfg.seed),
+            last_step:       Instant::now(),
+            sim_seconds:     0.0,
+            soc:             cfg.initial_soc,
+            working_mode:    MODE_SELF_CONSUMED,
+            command:         [0, 0, 0],
+            battery_power_w: 0.0,
+            solar_w:         0.0,
+            load_w:          0.0,
+            grid_w:          0.0,
+            import_kwh:      1000.0,
+            export_kwh:      500.0,
+            production_kwh:  0.0,
+            charged_kwh:     0.0,
+            discharged_kwh:  0.0,
+            ac_input_kwh:    0.0,
+            monthly_peak_w:  0.0,
+            cfg,
+        }
+    }
+
+    fn hour_of_day(&self) -> f64 {
+        (self.cfg.start_hour + self.sim_seconds / 3600.0).rem_euclid(24.0)
+    }
+
+    /// Advance the model to "now" (scaled real time since the previous step).
+    fn step(&mut self) {
+        let dt_s = self.last_step.elapsed().as_secs_f64() * self.cfg.time_scale;
+        self.last_step = Instant::now();
+        self.sim_seconds += dt_s;
+        let hour = self.hour_of_day();
+
+        // Solar: half-sine between 06:00 and 20:00 with a little cloud noise.
+        let sun = ((hour - 6.0) * PI / 14.0).sin().max(0.0);
+        self.solar_w = (self.cfg.solar_peak_w * sun * (0.9 + 0.1 * self.rng.signed_unit())).max(0.0);
+
+        // Load: base load, evening bump between 17:00 and 22:00, noise.
+        let evening = if (17.0..22.0).contains(&hour) { self.cfg.evening_load_w } else { 0.0 };
+        self.load_w = (self.cfg.base_load_w + evening + self.cfg.noise_w * self.rng.signed_unit()).max(50.0);
+
+        // Battery: follow the real-time command, or behave like self-consumption mode.
+        let wanted = if self.working_mode == MODE_REALTIME {
+            let [action, watts, limit] = self.command;
+            match action {
+                ACTION_CHARGE    if self.soc < limit as f64 => watts as f64,
+                ACTION_DISCHARGE if self.soc > limit as f64 => -(watts as f64),
+                _ => 0.0,
+            }
+        } else {
+            self.solar_w - self.load_w
+        };
+        let mut power = wanted.clamp(-self.cfg.max_discharge_w, self.cfg.max_charge_w);
+        if (power > 0.0 && self.soc >= self.cfg.max_soc) || (power < 0.0 && self.soc <= self.cfg.min_soc) {
+            power = 0.0;
+        }
+        self.battery_power_w = power;
+
+        let kwh = |w: f64| w * dt_s / 3_600_000.0;
+        self.soc = (self.soc + kwh(power) / self.cfg.capacity_kwh * 100.0).clamp(0.0, 100.0);
+        self.grid_w = self.load_w - self.solar_w + power;
+
+        if self.grid_w > 0.0 { self.import_kwh += kwh(self.grid_w); } else { self.export_kwh += kwh(-self.grid_w); }
+        if power > 0.0 { self.charged_kwh += kwh(power); } else { self.discharged_kwh += kwh(-power); }
+        self.production_kwh += kwh(self.solar_w);
+        self.ac_input_kwh   += kwh((power - self.solar_w).max(0.0));
+        self.monthly_peak_w  = self.monthly_peak_w.max(self.grid_w);
+    }
+
+    fn battery_state_code(&self) -> i64 {
+        if self.battery_power_w > 1.0 {
+            1001
+        } else if self.battery_power_w < -1.0 {
+            1002
+        } else {
+            1000
+        }
+    }
+
+    /// All Indevolt sensor values by ID, in the units the real firmware uses.
+    fn indevolt_values(&self) -> HashMap<u32, f64> {
+        HashMap::from([
+            (ID_WORKING_MODE,          self.working_mode as f64),
+            (ID_DC_INPUT1,             (self.solar_w * 0.55).round()),
+            (ID_DC_INPUT2,             (self.solar_w * 0.45).round()),
+            (ID_TOTAL_DC_OUTPUT,       self.solar_w.round()),
+            (ID_TOTAL_AC_OUTPUT,       (self.solar_w - self.battery_power_w).max(0.0).round()),
+            (ID_DAILY_PRODUCTION,      self.production_kwh),
+            (ID_CUMULATIVE_PRODUCTION, self.production_kwh * 1000.0),
+            (ID_TOTAL_AC_INPUT,        (self.battery_power_w - self.solar_w).max(0.0).round()),
+            (ID_TOTAL_AC_INPUT_ENERGY, self.ac_input_kwh),
+            (ID_BATTERY_POWER,         self.battery_power_w.round()),
+            (ID_BATTERY_STATE,         self.battery_state_code() as f64),
+            (ID_BATTERY_SOC,           self.soc.round()),
+            (ID_DAILY_CHARGING,        self.charged_kwh),
+            (ID_DAILY_DISCHARGING,     self.discharged_kwh),
+            (ID_TOTAL_CHARGING,        self.charged_kwh),
+            (ID_TOTAL_DISCHARGING,     self.discharged_kwh),
+            (ID_METER_POWER,           self.grid_w.round()),
+        ])
+    }
+
+    fn p1_data(&self) -> P1Data {
+        let now = local_now().format("%y%m%d%H%M%S").to_string();
+        let per_phase = self.grid_w / 3.0;
+        P1Data {
+            wifi_ssid:                   "simulator".to_string(),
+            wifi_strength:               100,
+            smr_version:                 50,
+            meter_model:                 "Simulated P1".to_string(),
+            unique_id:                   "53494d554c41544f52".to_string(),
+            active_tariff:               if (7.0..21.0).contains(&self.hour_of_day()) { 1 } else { 2 },
+            total_power_import_kwh:      self.import_kwh,
+            total_power_import_t1_kwh:   self.import_kwh * 0.6,
+            total_power_import_t2_kwh:   self.import_kwh * 0.4,
+            total_power_export_kwh:      self.export_kwh,
+            total_power_export_t1_kwh:   self.export_kwh * 0.6,
+            total_power_export_t2_kwh:   self.export_kwh * 0.4,
+            active_power_w:              self.grid_w.round(),
+            active_power_l1_w:           per_phase.round(),
+            active_power_l2_w:           per_phase.round(),
+            active_power_l3_w:           per_phase.round(),
+            active_voltage_l1_v:         230.0,
+            active_voltage_l2_v:         230.0,
+            active_voltage_l3_v:         230.0,
+            active_current_a:            (self.grid_w.abs() / 230.0 * 10.0).round() / 10.0,
+            active_current_l1_a:         (per_phase.abs() / 230.0 * 10.0).round() / 10.0,
+            active_current_l2_a:         (per_phase.abs() / 230.0 * 10.0).round() / 10.0,
+            active_current_l3_a:         (per_phase.abs() / 230.0 * 10.0).round() / 10.0,
+            active_power_average_w:      self.grid_w.round(),
+            montly_power_peak_w:         self.monthly_peak_w.round(),
+            montly_power_peak_timestamp: now.clone(),
+            total_gas_m3:                0.0,
+            gas_timestamp:               now.clone(),
+            gas_unique_id:               String::new(),
+            external:                    Vec::<ExternalMeasurement>::new(),
+        }
+    }
+
+    /// Apply a SetData write: {"f":16,"t":<register>,"v":[...]}.
+    fn apply_set_data(&mut self, config: &serde_json::Value) -> Result<(), String> {
+        let register = config["t"].as_u64().ok_or("missing register 't'")? as u32;
+        let values: Vec<i64> = config["v"]
+            .as_array()
+            .ok_or("missing values 'v'")?
+            .iter()
+            .filter_map(|v| v.as_i64())
+            .collect();
+
+        match (register, values.as_slice()) {
+            (REG_WORKING_MODE, [mode])
+                if matches!(*mode, MODE_SELF_CONSUMED | MODE_REALTIME | MODE_SCHEDULE) =>
+            {
+                self.working_mode = *mode;
+                if *mode != MODE_REALTIME {
+                    self.command = [0, 0, 0];
+                }
+                println!("[Sim] Working mode → {}", mode);
+                Ok(())
+            }
+            (REG_CONTROL, [action, watts, limit]) => {
+                self.command = [*action, *watts, *limit];
+                println!("[Sim] Real-time command → action={} {} W limit={}%", action, watts, limit);
+                Ok(())
+            }
+            _ => Err(format!("unsupported write t={} v={:?}", register, values)),
+        }
+    }
+}
+
+// --------------------------------------------------------------------------------------------------------------
+// Minimal HTTP/1.1 server (GET only, keep-alive) - enough for the EMS' reqwest clients.
+
+fn response(status: &str, body: &str) -> String {
+    format!(
+        "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: keep-alive\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    )
+}
+
+fn route(sim: &Mutex<Sim>, target: &str) -> String {
+    let url = match reqwest::Url::parse(&format!("http://sim{}", target)) {
+        Ok(u) => u,
+        Err(_) => return response("400 Bad Request", r#"{"error":"bad request target"}"#),
+    };
+    let config: Option<serde_json::Value> = url
+        .query_pairs()
+        .find(|(k, _)| k == "config")
+        .and_then(|(_, v)| serde_json::from_str(&v).ok());
+
+    let mut sim = sim.lock().unwrap_or_else(|e| e.into_inner());
+    sim.step();
+
+    match url.path() {
+        "/api/v1/data" => {
+            let body = serde_json::to_string(&sim.p1_data()).unwrap_or_default();
+            response("200 OK", &body)
+        }
+        "/rpc/Indevolt.GetData" => {
+            let values = sim.indevolt_values();
+            let requested: Vec<u32> = config
+                .as_ref()
+                .and_then(|c| c["t"].as_array().cloned())
+                .map(|ids| ids.iter().filter_map(|id| id.as_u64()).map(|id| id as u32).collect())
+                .unwrap_or_else(|| values.keys().copied().collect());
+            let body: HashMap<String, f64> = requested
+                .into_iter()
+                .filter_map(|id| values.get(&id).map(|v| (id.to_string(), *v)))
+                .collect();
+            response("200 OK", &serde_json::to_string(&body).unwrap_or_default())
+        }
+        "/rpc/Indevolt.SetData" => match config.as_ref().map(|c| sim.apply_set_data(c)) {
+            Some(Ok(()))   => response("200 OK", r#"{"result":true}"#),
+            Some(Err(msg)) => response("400 Bad Request", &serde_json::json!({ "error": msg }).to_string()),
+            None           => response("400 Bad Request", r#"{"error":"missing config"}"#),
+        },
+        _ => response("404 Not Found", r#"{"error":"not found"}"#),
+    }
+}
+
+async fn serve_connection(stream: TcpStream, sim: Arc<Mutex<Sim>>) {
+    let (read_half, mut write_half) = stream.into_split();
+    let mut reader = BufReader::new(read_half);
+
+    loop {
+        let mut request_line = String::new();
+        match reader.read_line(&mut request_line).await {
+            Ok(0) | Err(_) => return,
+            Ok(_) => {}
+        }
+        // Skip headers up to the blank line; GET requests carry no body.
+        loop {
+            let mut header = String::new();
+            match reader.read_line(&mut header).await {
+                Ok(0) | Err(_) => return,
+                Ok(_) if header == "\r\n" || header == "\n" => break,
+                Ok(_) => {}
+            }
+        }
+
+        let mut parts = request_line.split_whitespace();
+        let reply = match (parts.next(), parts.next()) {
+            (Some("GET"), Some(target)) => route(&sim, target),
+            _ => response("405 Method Not Allowed", r#"{"error":"only GET is supported"}"#),
+        };
+        if write_half.write_all(reply.as_bytes()).await.is_err() {
+            return;
+        }
+    }
+}
+
+// --------------------------------------------------------------------------------------------------------------
+
+#[tokio::main]
+async fn main() {
+    let cfg: SimConfig = match std::env::args().nth(1) {
+        Some(path) => {
+            let text = std::fs::read_to_string(&path)
+                .unwrap_or_else(|e| panic!("Cannot read simulator config {}: {}", path, e));
+            serde_json::from_str(&text)
+                .unwrap_or_else(|e| panic!("Cannot parse simulator config {}: {}", path, e))
+        }
+        None => SimConfig::default(),
+    };
+
+    let listener = TcpListener::bind(&cfg.listen)
+        .await
+        .unwrap_or_else(|e| panic!("Cannot listen on {}: {}", cfg.listen, e));
+    println!("[Sim] Listening on http://{} (seed={} time_scale={}x)", cfg.listen, cfg.seed, cfg.time_scale);
+    println!("[Sim]   P1:       http://{}/api/v1/data", cfg.listen);
+    println!("[Sim]   Indevolt: http://{}", cfg.listen);
+
+    let sim = Arc::new(Mutex::new(Sim::new(cfg)));
+    loop {
+        match listener.accept().await {
+            Ok((stream, _)) => {
+                tokio::spawn(serve_connection(stream, sim.clone()));
+            }
+            Err(e) => eprintln!("[Sim] accept failed: {}", e),
+        }
+    }
+}
@@ -0,0 +1,109 @@
+use crate::configuration::config::Config;
+use crate::handlers::p1::reader::{P1Error, P1Reading};
+use crate::models::indevolt_models::BatterySnapshot;
+
+// --------------------------------------------------------------------------------------------------------------
+// Per-cycle data quality score
+//
+// One 0-100 number saying how far this cycle's readings can be trusted. Four penalties, each 0.0-1.0, are
+// weighted with `data_quality_weights`:
+//   p1              no usable P1 reading (down, timed out, stale or malformed)
+//   battery_keys    fraction of requested Indevolt sensors missing from the snapshot
+//   reconciliation  |P1 - Indevolt meter| relative to `data_quality_reconciliation_tolerance_w` (full penalty at
+//                   the tolerance); not scored without P1
+//   consistency     the snapshot contradicts itself: raw SOC outside 0-100 %, or the reported battery state
+//                   disagrees with the sign of the battery power
+// score = 100 × (1 - Σ weight × penalty / Σ weight)
+// --------------------------------------------------------------------------------------------------------------
+
+/// Battery power (W) below which the reported state is not checked against the power sign.
+const STATE_POWER_DEADBAND_W: i32 = 50;
+
+/// Individual penalties behind a score, for logging.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DataQuality {
+    pub score:          u8,
+    pub p1:             f64,
+    pub battery_keys:   f64,
+    pub reconciliation: f64,
+    pub consistency:    f64,
+}
+
+pub fn data_quality(p1: Result<&P1Reading, &P1Error>, battery: &BatterySnapshot, config: &Config) -> DataQuality {
+    let p1_penalty = if p1.is_ok() { 0.0 } else { 1.0 };
+
+    let battery_keys = if battery.keys_requested == 0 {
+        1.0
+    } else {
+        battery.keys_missing as f64 / battery.keys_requested as f64
+    };
+
+    let reconciliation = match p1 {
+        Ok(reading) if config.data_quality_reconciliation_tolerance_w > 0 => {
+            let diff_w = (reading.raw.active_power_w - battery.meter_power_w as f64).abs();
+            (diff_w / config.data_quality_reconciliation_tolerance_w as f64).min(1.0)
+        }
+        _ => 0.0,
+    };
+
+    let soc_out_of_range = !(0.0..=100.0).contains(&battery.battery_soc_raw);
+    let state_mismatch = match battery.battery_state.as_str() {
+        "Charging"    => battery.battery_power_w < -STATE_POWER_DEADBAND_W,
+        "Discharging" => battery.battery_power_w > STATE_POWER_DEADBAND_W,
+        _             => false,
+    };
+    let consistency = if soc_out_of_range || state_mismatch { 1.0 } else { 0.0 };
+
+    let w = &config.data_quality_weights;
+    let total_weight = w.p1 + w.battery_keys + w.reconciliation + w.consistency;
+    let weighted = w.p1 * p1_penalty
+        + w.battery_keys * battery_keys
+        + w.reconciliation * reconciliation
+        + w.consistency * consistency;
+    let score = if total_weight > 0.0 { 100.0 * (1.0 - weighted / total_weight) } else { 100.0 };
+
+    DataQuality {
+        score: score.round().clamp(0.0, 100.0) as u8,
+        p1: p1_penalty,
+        battery_keys,
+        reconciliation,
+        consistency,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::optimiser::testing::{battery, p1_reading};
+
+    fn snapshot(meter_power_w: i32, keys_missing: usize) -> BatterySnapshot {
+        BatterySnapshot { meter_power_w, keys_requested: 20, keys_missing, ..battery(60.0, 0) }
+    }
+
+    #[test]
+    fn a_perfect_cycle_scores_100() {
+        let p1      = p1_reading(800.0, Duration::ZERO);
+        let quality = data_quality(Ok(&p1), &snapshot(800, 0), &Config::default());
+        assert_eq!(quality.score, 100);
+        assert_eq!([quality.p1, quality.battery_keys, quality.reconciliation, quality.consistency], [0.0; 4]);
+    }
+
+    #[test]
+    fn a_degraded_cycle_loses_the_weighted_penalties() {
+        // 5 of 20 keys missing, 250 W of 500 W tolerance off, and "Charging" while discharging 400 W:
+        // 30 × 0.25 + 15 × 0.5 + 15 × 1 = 30 of 100.
+        let p1      = p1_reading(800.0, Duration::ZERO);
+        let mut bad = snapshot(550, 5);
+        bad.battery_state   = "Charging".to_string();
+        bad.battery_power_w = -400;
+        let quality = data_quality(Ok(&p1), &bad, &Config::default());
+        assert_eq!((quality.battery_keys, quality.reconciliation, quality.consistency), (0.25, 0.5, 1.0));
+        assert_eq!(quality.score, 70);
+
+        // Without P1 its 40 are lost too, and reconciliation isn't scored: 40 + 7.5 + 15 = 62.5.
+        let quality = data_quality(Err(&P1Error::Timeout), &bad, &Config::default());
+        assert_eq!((quality.p1, quality.reconciliation, quality.score), (1.0, 0.0, 38));
+    }
+}
@@ -0,0 +1,153 @@
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+
+use crate::handlers::indevolt::reader::{ID_DAILY_CHARGING, ID_DAILY_DISCHARGING, ID_DAILY_PRODUCTION};
+use crate::models::indevolt_models::BatterySnapshot;
+use crate::runtime::clock::LOCAL_TZ;
+use super::energy::{kwh_to_wh, wh_to_kwh, RESET_TOLERANCE_WH};
+
+// --------------------------------------------------------------------------------------------------------------
+// Device day totals
+//
+// The Indevolt's daily production, charging and discharging counters restart from zero at the device's own
+// midnight, which need not match the host clock. Instead of reading them at the EMS's midnight, the tracker
+// follows the highest value of each counter since its last reset and closes the device day when a counter
+// falls back towards zero: below half its peak and by more than `RESET_TOLERANCE_WH`. A smaller dip is noise
+// and keeps the peak. The peaks at that moment are the day's totals; all three counters then start a new day.
+//
+// The day is labelled with the local date halfway between its first and last reading, which stays right when
+// the device clock runs an hour or two off the host's. The peaks and the last `MAX_DAYS` closed days are kept
+// in the state file, so a restart neither loses nor double-counts a day.
+// --------------------------------------------------------------------------------------------------------------
+
+/// Closed device days kept.
+const MAX_DAYS: usize = 31;
+
+/// One device day, as its daily counters showed it just before they reset.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct DeviceDay {
+    pub date:            NaiveDate,
+    /// Last reading before the reset; a long gap to `closed_at` means the end of the day may be missing.
+    pub last_sample:     DateTime<Utc>,
+    pub closed_at:       DateTime<Utc>,
+    pub production_kwh:  f64,
+    pub charging_kwh:    f64,
+    pub discharging_kwh: f64,
+}
+
+/// Highest value (Wh) of each daily counter since it last reset.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default)]
+struct Peaks {
+    production_wh:  i64,
+    charging_wh:    i64,
+    discharging_wh: i64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct DeviceDayTracker {
+    peaks:        Peaks,
+    first_sample: Option<DateTime<Utc>>,
+    last_sample:  Option<DateTime<Utc>>,
+    days:         VecDeque<DeviceDay>,
+}
+
+/// Whether a counter at `current` has reset since it peaked at `peak` (both Wh).
+fn has_reset(peak: i64, current: i64) -> bool {
+    peak - current > RESET_TOLERANCE_WH && current * 2 < peak
+}
+
+impl DeviceDayTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Closed days, oldest first.
+    pub fn days(&self) -> impl Iterator<Item = &DeviceDay> {
+        self.days.iter()
+    }
+
+    /// Feed one cycle. Returns the device day that closed with this reading, if the counters reset.
+    pub fn observe(&mut self, now: DateTime<Utc>, battery: &BatterySnapshot) -> Option<DeviceDay> {
+        let read = |id: u32, kwh: f64| battery.is_fresh(id).then(|| kwh_to_wh(kwh));
+        let readings = [
+            (read(ID_DAILY_PRODUCTION, battery.daily_production_kwh), self.peaks.production_wh),
+            (read(ID_DAILY_CHARGING, battery.daily_charging_kwh), self.peaks.charging_wh),
+            (read(ID_DAILY_DISCHARGING, battery.daily_discharging_kwh), self.peaks.discharging_wh),
+        ];
+        if readings.iter().all(|(current, _)| current.is_none()) {
+            return None;
+        }
+
+        let reset = readings.iter().any(|&(current, peak)| current.is_some_and(|wh| has_reset(peak, wh)));
+        let closed = match (self.first_sample, self.last_sample) {
+            (Some(first_sample), Some(last_sample)) if reset => {
+                let midpoint = first_sample + (last_sample - first_sample) / 2;
+                let day = DeviceDay {
+                    date:            midpoint.with_timezone(&LOCAL_TZ).date_naive(),
+                    last_sample,
+                    closed_at:       now,
+                    production_kwh:  wh_to_kwh(self.peaks.production_wh),
+                    charging_kwh:    wh_to_kwh(self.peaks.charging_wh),
+                    discharging_kwh: wh_to_kwh(self.peaks.discharging_wh),
+                };
+                if self.days.len() >= MAX_DAYS {
+                    self.days.pop_front();
+                }
+                self.days.push_back(day);
+                self.peaks        = Peaks::default();
+                self.first_sample = Some(now);
+                Some(day)
+            }
+            _ => None,
+        };
+
+        let [production, charging, discharging] = readings.map(|(current, _)| current.unwrap_or(0));
+        self.peaks.production_wh  = self.peaks.production_wh.max(production);
+        self.peaks.charging_wh    = self.peaks.charging_wh.max(charging);
+        self.peaks.discharging_wh = self.peaks.discharging_wh.max(discharging);
+        self.first_sample.get_or_insert(now);
+        self.last_sample = Some(now);
+        closed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+    use crate::optimiser::testing::battery;
+
+    fn counters(production_kwh: f64, charging_kwh: f64, discharging_kwh: f64) -> BatterySnapshot {
+        BatterySnapshot {
+            daily_production_kwh:  production_kwh,
+            daily_charging_kwh:    charging_kwh,
+            daily_discharging_kwh: discharging_kwh,
+            ..battery(50.0, 0)
+        }
+    }
+
+    #[test]
+    fn counters_that_climb_and_reset_record_the_peak_as_the_day_total() {
+        let start       = Utc.with_ymd_and_hms(2026, 10, 15, 6, 0, 0).unwrap();
+        let mut tracker = DeviceDayTracker::new();
+        let day = [(0.0, 0.0, 0.0), (4.2, 2.0, 0.0), (9.8, 5.5, 1.0), (9.8, 5.501, 4.3), (9.8, 5.5, 6.1)];
+        for (hour, &(production, charging, discharging)) in day.iter().enumerate() {
+            let at = start + chrono::Duration::hours(3 * hour as i64);
+            assert_eq!(tracker.observe(at, &counters(production, charging, discharging)), None);
+        }
+
+        // The device's midnight, 00:30 local: all three counters restart.
+        let reset = Utc.with_ymd_and_hms(2026, 10, 15, 22, 30, 0).unwrap();
+        let day   = tracker.observe(reset, &counters(0.0, 0.1, 0.0)).expect("a closed day");
+        assert_eq!(day.date, NaiveDate::from_ymd_opt(2026, 10, 15).unwrap());
+        assert_eq!((day.production_kwh, day.charging_kwh, day.discharging_kwh), (9.8, 5.501, 6.1));
+        assert_eq!(day.last_sample, start + chrono::Duration::hours(12));
+        assert_eq!(tracker.days().count(), 1);
+
+        // The next day starts from the reset reading.
+        let next = tracker.observe(reset + chrono::Duration::hours(2), &counters(0.0, 1.2, 0.0));
+        assert_eq!(next, None);
+    }
+}
@@ -0,0 +1,62 @@
+// --------------------------------------------------------------------------------------------------------------
+// Energy counter arithmetic
+//
+// The devices report cumulative energy as f64 kWh. Differencing those directly accumulates rounding error, and a
+// reading a hair below the previous one (float noise, or a counter re-reported with less precision) would look
+// like a counter reset. Accounting therefore works in whole Wh (`i64`), and only a drop larger than
+// `RESET_TOLERANCE_WH` counts as a reset. Convert back to kWh only for display.
+// --------------------------------------------------------------------------------------------------------------
+
+/// A counter drop of at most this many Wh is treated as noise, not a reset.
+pub const RESET_TOLERANCE_WH: i64 = 5;
+
+/// kWh reading → whole Wh.
+pub fn kwh_to_wh(kwh: f64) -> i64 {
+    (kwh * 1000.0).round() as i64
+}
+
+/// Whole Wh → kWh, for display.
+pub fn wh_to_kwh(wh: i64) -> f64 {
+    wh as f64 / 1000.0
+}
+
+/// Energy (Wh) added between two readings of a cumulative kWh counter.
+///
+/// A drop within `RESET_TOLERANCE_WH` yields 0. A larger drop means the counter was reset (e.g. the daily
+/// counters at midnight) and restarted from zero, so everything it now shows is new energy.
+pub fn energy_delta_wh(prev_kwh: f64, cur_kwh: f64) -> i64 {
+    let prev  = kwh_to_wh(prev_kwh);
+    let cur   = kwh_to_wh(cur_kwh);
+    let delta = cur - prev;
+    if delta >= 0 {
+        delta
+    } else if -delta <= RESET_TOLERANCE_WH {
+        0
+    } else {
+        cur
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn float_noise_below_the_previous_reading_is_not_a_reset() {
+        assert_eq!(energy_delta_wh(1234.5671, 1234.5670), 0);
+        assert_eq!(energy_delta_wh(12.345, 12.341), 0);
+    }
+
+    #[test]
+    fn a_real_drop_is_a_reset_and_counts_from_zero() {
+        assert_eq!(energy_delta_wh(14.2, 0.3), 300);
+        assert_eq!(energy_delta_wh(14.2, 0.0), 0);
+    }
+
+    #[test]
+    fn a_rise_counts_in_whole_wh() {
+        assert_eq!(energy_delta_wh(0.1, 0.3), 200);
+        assert_eq!(energy_delta_wh(10.0004, 10.0016), 2);
+        assert_eq!(wh_to_kwh(kwh_to_wh(6.1)), 6.1);
+    }
+}
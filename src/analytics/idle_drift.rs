@@ -0,0 +1,135 @@
+use chrono::{DateTime, Utc};
+use log::{info, warn};
+
+use crate::models::indevolt_models::BatterySnapshot;
+
+// --------------------------------------------------------------------------------------------------------------
+// Idle SOC drift detector
+//
+// While the inverter reports the battery as "Static" the SOC should hardly move. A steady fall during such a
+// stretch points at self-discharge, standby consumption, or a battery state that is reported wrongly.
+// The detector measures the SOC change over each uninterrupted idle stretch and warns (once per stretch) when
+// the rate exceeds the configured threshold. Short stretches are ignored: the SOC register has 1% resolution,
+// so a single step over a few minutes says nothing about the real rate.
+// --------------------------------------------------------------------------------------------------------------
+
+const IDLE_STATE: &str = "Static";
+
+/// Tracks SOC drift across consecutive idle cycles.
+#[derive(Debug, Clone)]
+pub struct IdleDriftDetector {
+    warn_percent_per_hour: f64,
+    min_window_minutes:    i64,
+    start:                 Option<(DateTime<Utc>, f64)>,
+    warned:                bool,
+    estimate:              Option<f64>,
+}
+
+impl IdleDriftDetector {
+    pub fn new(warn_percent_per_hour: f64, min_window_minutes: u64) -> Self {
+        Self {
+            warn_percent_per_hour,
+            min_window_minutes: min_window_minutes as i64,
+            start:              None,
+            warned:             false,
+            estimate:           None,
+        }
+    }
+
+    /// Take new thresholds (after a config reload) without losing the idle stretch in progress.
+    pub fn set_thresholds(&mut self, warn_percent_per_hour: f64, min_window_minutes: u64) {
+        self.warn_percent_per_hour = warn_percent_per_hour;
+        self.min_window_minutes    = min_window_minutes as i64;
+    }
+
+    /// Feed one cycle's snapshot. Returns the drift rate (%/h, positive = SOC falling) once the current
+    /// idle stretch is long enough to measure, `None` otherwise.
+    pub fn observe(&mut self, now: DateTime<Utc>, battery: &BatterySnapshot) -> Option<f64> {
+        if battery.battery_state != IDLE_STATE {
+            self.start  = None;
+            self.warned = false;
+            return None;
+        }
+
+        let (since, start_soc) = *self.start.get_or_insert((now, battery.battery_soc));
+        let minutes = (now - since).num_minutes();
+        if minutes < self.min_window_minutes || minutes <= 0 {
+            return None;
+        }
+
+        let rate = (start_soc - battery.battery_soc) * 60.0 / minutes as f64;
+        self.estimate = Some(rate);
+
+        if rate > self.warn_percent_per_hour && !self.warned {
+            self.warned = true;
+            warn!(
+                "[Analytics] SOC fell {:.1}% over {} min while battery reports Static ({:.2}%/h > {:.2}%/h) - \
+                 self-discharge or wrong battery state?",
+                start_soc - battery.battery_soc, minutes, rate, self.warn_percent_per_hour
+            );
+        } else if self.warned && rate <= self.warn_percent_per_hour {
+            self.warned = false;
+            info!("[Analytics] Idle SOC drift back within limits ({:.2}%/h)", rate);
+        }
+
+        Some(rate)
+    }
+
+    /// Last measured self-discharge estimate in %/h (positive = SOC falling), if any idle stretch has
+    /// been long enough to measure.
+    pub fn self_discharge_percent_per_hour(&self) -> Option<f64> {
+        self.estimate
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::optimiser::testing::battery;
+    use crate::runtime::log_capture::{capture, warnings};
+    use chrono::{Duration, TimeZone};
+
+    fn at(minutes: i64) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2026, 3, 1, 2, 0, 0).unwrap() + Duration::minutes(minutes)
+    }
+
+    #[test]
+    fn a_fast_idle_fall_warns_once_and_records_the_estimate() {
+        let mut detector = IdleDriftDetector::new(1.0, 60);
+        let (rates, lines) = capture(|| {
+            [(0, 80.0), (30, 79.0), (60, 78.0), (90, 77.0)]
+                .map(|(minutes, soc)| detector.observe(at(minutes), &battery(soc, 0)))
+        });
+
+        assert_eq!(rates, [None, None, Some(2.0), Some(2.0)]);
+        let warned = warnings(&lines);
+        assert_eq!(warned.len(), 1, "{lines:?}");
+        assert!(warned[0].contains("self-discharge"), "{}", warned[0]);
+        assert_eq!(detector.self_discharge_percent_per_hour(), Some(2.0));
+    }
+
+    #[test]
+    fn a_slow_drift_does_not_warn() {
+        let mut detector = IdleDriftDetector::new(1.0, 60);
+        let (_, lines) = capture(|| {
+            detector.observe(at(0), &battery(80.0, 0));
+            detector.observe(at(120), &battery(79.0, 0))
+        });
+
+        assert!(warnings(&lines).is_empty(), "{lines:?}");
+        assert_eq!(detector.self_discharge_percent_per_hour(), Some(0.5));
+    }
+
+    #[test]
+    fn a_non_idle_cycle_starts_a_new_stretch() {
+        let mut detector = IdleDriftDetector::new(1.0, 60);
+        detector.observe(at(0), &battery(80.0, 0));
+        let mut charging = battery(79.0, 1200);
+        charging.battery_state = "Charging".to_string();
+        assert_eq!(detector.observe(at(30), &charging), None);
+
+        // The stretch restarts at 79%: 60 minutes later only the fall since then counts.
+        assert_eq!(detector.observe(at(40), &battery(79.0, 0)), None);
+        assert_eq!(detector.observe(at(100), &battery(78.0, 0)), Some(1.0));
+    }
+}
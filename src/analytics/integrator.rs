@@ -0,0 +1,171 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::handlers::indevolt::reader::{ID_BATTERY_POWER, ID_METER_POWER};
+use crate::handlers::p1::reader::P1Reading;
+use crate::models::indevolt_models::BatterySnapshot;
+
+// --------------------------------------------------------------------------------------------------------------
+// Energy from power
+//
+// Where a cumulative counter is missing, energy can still be estimated by integrating power over time: each
+// reading adds the previous power × the time actually elapsed since the previous reading, so an overrunning
+// cycle adds more energy than a punctual one instead of being counted as one nominal interval. Positive and
+// negative power go to separate totals (import / export, charge / discharge). A missing reading or a gap longer
+// than `MAX_INTEGRATION_GAP_S` is not bridged, so an outage or a restart doesn't add a guessed block of energy.
+//
+// The counters remain the better source: they are exact and cover gaps. Integrated totals are an estimate, off
+// by whatever the power does between two readings.
+// --------------------------------------------------------------------------------------------------------------
+
+/// Readings further apart than this (s) are not integrated.
+pub const MAX_INTEGRATION_GAP_S: f64 = 300.0;
+
+/// Integrates one signed power into Wh, positive and negative separately.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct EnergyIntegrator {
+    positive_wh:     f64,
+    negative_wh:     f64,
+    covered_seconds: f64,
+    last:            Option<(DateTime<Utc>, f64)>,
+}
+
+impl EnergyIntegrator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add `power_w` held for `seconds`.
+    pub fn add(&mut self, power_w: f64, seconds: f64) {
+        if seconds <= 0.0 {
+            return;
+        }
+        let wh = power_w * seconds / 3600.0;
+        if wh >= 0.0 {
+            self.positive_wh += wh;
+        } else {
+            self.negative_wh -= wh;
+        }
+        self.covered_seconds += seconds;
+    }
+
+    /// Feed a reading of `power_w` taken at `at`: the previous power is added over the time since then.
+    pub fn observe(&mut self, at: DateTime<Utc>, power_w: f64) {
+        if let Some((previous_at, previous_w)) = self.last {
+            let seconds = (at - previous_at).num_milliseconds() as f64 / 1000.0;
+            if seconds <= MAX_INTEGRATION_GAP_S {
+                self.add(previous_w, seconds);
+            }
+        }
+        self.last = Some((at, power_w));
+    }
+
+    /// No reading this cycle: the next one starts a new stretch.
+    pub fn skip(&mut self) {
+        self.last = None;
+    }
+
+    /// Energy of positive power (Wh).
+    pub fn positive_wh(&self) -> f64 {
+        self.positive_wh
+    }
+
+    /// Energy of negative power (Wh), as a positive number.
+    pub fn negative_wh(&self) -> f64 {
+        self.negative_wh
+    }
+
+    /// Time actually integrated (s); 0 means the totals mean nothing yet.
+    pub fn covered_seconds(&self) -> f64 {
+        self.covered_seconds
+    }
+
+    /// Zero totals that continue from the same last reading, so the stretch across the cut is not lost.
+    pub fn carry_over(&self) -> Self {
+        Self { last: self.last, ..Self::default() }
+    }
+}
+
+/// Grid and battery power integrated side by side.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct PowerIntegrators {
+    /// Positive = import, negative = export.
+    pub grid:    EnergyIntegrator,
+    /// Positive = charge, negative = discharge.
+    pub battery: EnergyIntegrator,
+}
+
+impl PowerIntegrators {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Feed one cycle. The grid power comes from P1, or from the Indevolt meter while P1 is missing.
+    pub fn observe(&mut self, now: DateTime<Utc>, p1: Option<&P1Reading>, battery: &BatterySnapshot) {
+        match p1 {
+            Some(p1) => self.grid.observe(now, p1.raw.active_power_w),
+            None if battery.is_fresh(ID_METER_POWER) => self.grid.observe(now, battery.meter_power_w as f64),
+            None => self.grid.skip(),
+        }
+        if battery.is_fresh(ID_BATTERY_POWER) {
+            self.battery.observe(now, battery.battery_power_w as f64);
+        } else {
+            self.battery.skip();
+        }
+    }
+
+    /// Both integrators with zero totals, continuing from their last readings.
+    pub fn carry_over(&self) -> Self {
+        Self { grid: self.grid.carry_over(), battery: self.battery.carry_over() }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Duration, TimeZone};
+
+    fn at(seconds: i64) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2026, 3, 10, 12, 0, 0).unwrap() + Duration::seconds(seconds)
+    }
+
+    #[test]
+    fn power_is_integrated_over_the_time_actually_elapsed() {
+        let mut integrator = EnergyIntegrator::new();
+        // 1200 W for 30 s, then 1200 W again, this time for an overrunning 45 s, then -600 W for 60 s:
+        // 1200 × 75 / 3600 = 25 Wh positive, 600 × 60 / 3600 = 10 Wh negative.
+        for (seconds, power_w) in [(0, 1200.0), (30, 1200.0), (75, -600.0), (135, 0.0)] {
+            integrator.observe(at(seconds), power_w);
+        }
+
+        assert!((integrator.positive_wh() - 25.0).abs() < 1e-9, "{}", integrator.positive_wh());
+        assert!((integrator.negative_wh() - 10.0).abs() < 1e-9, "{}", integrator.negative_wh());
+        assert_eq!(integrator.covered_seconds(), 135.0);
+    }
+
+    #[test]
+    fn a_gap_or_a_skipped_reading_is_not_bridged() {
+        let mut integrator = EnergyIntegrator::new();
+        integrator.observe(at(0), 1000.0);
+        integrator.observe(at(600), 1000.0);
+        integrator.skip();
+        integrator.observe(at(630), 1000.0);
+        integrator.observe(at(666), 0.0);
+
+        // Only the last 36 s count: 1000 × 36 / 3600 = 10 Wh.
+        assert!((integrator.positive_wh() - 10.0).abs() < 1e-9, "{}", integrator.positive_wh());
+        assert_eq!(integrator.covered_seconds(), 36.0);
+    }
+
+    #[test]
+    fn a_carry_over_starts_from_zero_but_keeps_the_last_reading() {
+        let mut integrator = EnergyIntegrator::new();
+        integrator.observe(at(0), 3600.0);
+        integrator.observe(at(10), 3600.0);
+
+        let mut next = integrator.carry_over();
+        assert_eq!(next.positive_wh(), 0.0);
+        next.observe(at(20), 0.0);
+        assert!((next.positive_wh() - 10.0).abs() < 1e-9, "{}", next.positive_wh());
+    }
+}
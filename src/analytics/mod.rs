@@ -0,0 +1,7 @@
+pub mod idle_drift;
+pub mod energy;
+pub mod integrator;
+pub mod data_quality;
+pub mod soh;
+pub mod pv_strings;
+pub mod device_days;
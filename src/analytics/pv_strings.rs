@@ -0,0 +1,166 @@
+use chrono::{DateTime, NaiveDate, Utc};
+use log::{info, warn};
+
+use crate::handlers::indevolt::reader::{ID_DC_INPUT1, ID_DC_INPUT2};
+use crate::models::indevolt_models::BatterySnapshot;
+
+// --------------------------------------------------------------------------------------------------------------
+// Per-string PV production and imbalance detection
+//
+// The inverter reports the power of both DC inputs but no per-string energy, so energy is integrated from power
+// (previous power × time since the previous reading). Gaps longer than `MAX_INTEGRATION_GAP_S` are not bridged,
+// so a restart or an outage doesn't add a guessed block of energy. The totals reset at local midnight, and the
+// previous day's totals are logged then.
+//
+// A string producing less than `imbalance_ratio` × the other one, while the stronger string delivers at least
+// `min_power_w`, counts as imbalanced. The floor keeps dusk, dawn and heavy overcast (where both are near zero
+// and the ratio is meaningless) out of it. Only an imbalance that lasts `min_minutes` is reported - passing
+// shade on one string is normal - once per stretch, with a note when the strings are balanced again.
+// Strings of different size or orientation never produce equal power; lower the ratio to match.
+// --------------------------------------------------------------------------------------------------------------
+
+/// Readings further apart than this (s) are not integrated.
+const MAX_INTEGRATION_GAP_S: f64 = 300.0;
+
+/// Tracks per-string daily energy and sustained imbalance.
+#[derive(Debug, Clone)]
+pub struct PvStringMonitor {
+    imbalance_ratio: f64,
+    min_power_w:     f64,
+    min_minutes:     i64,
+    day:             Option<NaiveDate>,
+    energy_wh:       [f64; 2],
+    last:            Option<(DateTime<Utc>, [f64; 2])>,
+    imbalanced:      Option<DateTime<Utc>>,
+    warned:          bool,
+}
+
+impl PvStringMonitor {
+    pub fn new(imbalance_ratio: f64, min_power_w: i32, min_minutes: u64) -> Self {
+        Self {
+            imbalance_ratio,
+            min_power_w: min_power_w as f64,
+            min_minutes: min_minutes as i64,
+            day:         None,
+            energy_wh:   [0.0; 2],
+            last:        None,
+            imbalanced:  None,
+            warned:      false,
+        }
+    }
+
+    /// Take new thresholds (after a config reload) without losing the day's energy or an imbalance in progress.
+    pub fn set_thresholds(&mut self, imbalance_ratio: f64, min_power_w: i32, min_minutes: u64) {
+        self.imbalance_ratio = imbalance_ratio;
+        self.min_power_w     = min_power_w as f64;
+        self.min_minutes     = min_minutes as i64;
+    }
+
+    /// Feed one cycle's snapshot. `today` is the local date, for the midnight reset.
+    pub fn observe(&mut self, now: DateTime<Utc>, today: NaiveDate, battery: &BatterySnapshot) {
+        if !battery.is_fresh(ID_DC_INPUT1) || !battery.is_fresh(ID_DC_INPUT2) {
+            self.last = None;
+            return;
+        }
+        let power = [battery.dc_input_power1_w.max(0) as f64, battery.dc_input_power2_w.max(0) as f64];
+
+        if self.day != Some(today) {
+            if let Some(day) = self.day {
+                info!(
+                    "[Analytics] PV strings {}: PV1 {:.2} kWh, PV2 {:.2} kWh",
+                    day, self.energy_wh[0] / 1000.0, self.energy_wh[1] / 1000.0
+                );
+            }
+            self.day       = Some(today);
+            self.energy_wh = [0.0; 2];
+        }
+
+        if let Some((at, previous)) = self.last {
+            let seconds = (now - at).num_milliseconds() as f64 / 1000.0;
+            if seconds > 0.0 && seconds <= MAX_INTEGRATION_GAP_S {
+                for (energy, p) in self.energy_wh.iter_mut().zip(previous) {
+                    *energy += p * seconds / 3600.0;
+                }
+            }
+        }
+        self.last = Some((now, power));
+
+        self.check_imbalance(now, power);
+    }
+
+    /// Energy (Wh) produced by each string so far today.
+    pub fn daily_energy_wh(&self) -> [f64; 2] {
+        self.energy_wh
+    }
+
+    fn check_imbalance(&mut self, now: DateTime<Utc>, power: [f64; 2]) {
+        let strong = power[0].max(power[1]);
+        let weak   = power[0].min(power[1]);
+        if strong < self.min_power_w {
+            // Too little light to judge; neither confirms nor clears an imbalance.
+            return;
+        }
+
+        if weak >= strong * self.imbalance_ratio {
+            if self.warned {
+                info!("[Analytics] PV strings balanced again (PV1 {:.0}W, PV2 {:.0}W)", power[0], power[1]);
+            }
+            self.imbalanced = None;
+            self.warned     = false;
+            return;
+        }
+
+        let since   = *self.imbalanced.get_or_insert(now);
+        let minutes = (now - since).num_minutes();
+        if minutes >= self.min_minutes && !self.warned {
+            self.warned = true;
+            let weak_string = if power[0] < power[1] { "PV1" } else { "PV2" };
+            warn!(
+                "[Analytics] {} underperforming for {} min: PV1 {:.0}W, PV2 {:.0}W (ratio {:.2} < {:.2}) - \
+                 shading or a string fault?",
+                weak_string, minutes, power[0], power[1], weak / strong, self.imbalance_ratio
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, TimeZone};
+
+    use super::*;
+    use crate::optimiser::testing::battery;
+    use crate::runtime::log_capture::{capture, warnings};
+
+    fn strings(pv1_w: i32, pv2_w: i32) -> BatterySnapshot {
+        BatterySnapshot { dc_input_power1_w: pv1_w, dc_input_power2_w: pv2_w, ..battery(50.0, 0) }
+    }
+
+    /// An hour of readings every 5 minutes at `pv1_w` / `pv2_w`; the log lines it produced.
+    fn an_hour(monitor: &mut PvStringMonitor, pv1_w: i32, pv2_w: i32) -> Vec<(log::Level, String)> {
+        let start = Utc.with_ymd_and_hms(2026, 6, 15, 10, 0, 0).unwrap();
+        let today = start.date_naive();
+        let ((), lines) = capture(|| {
+            for step in 0..=12 {
+                monitor.observe(start + Duration::minutes(5 * step), today, &strings(pv1_w, pv2_w));
+            }
+        });
+        lines
+    }
+
+    #[test]
+    fn balanced_strings_integrate_their_energy_without_a_warning() {
+        let mut monitor = PvStringMonitor::new(0.5, 200, 30);
+        let lines       = an_hour(&mut monitor, 1800, 1500);
+        assert!(lines.is_empty(), "{lines:?}");
+        assert_eq!(monitor.daily_energy_wh(), [1800.0, 1500.0]);
+    }
+
+    #[test]
+    fn one_string_down_is_reported_once_it_lasts() {
+        let mut monitor = PvStringMonitor::new(0.5, 200, 30);
+        let lines       = an_hour(&mut monitor, 1800, 40);
+        assert_eq!(warnings(&lines).len(), 1);
+        assert!(warnings(&lines)[0].starts_with("[Analytics] PV2 underperforming for 30 min"), "{lines:?}");
+    }
+}
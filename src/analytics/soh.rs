@@ -0,0 +1,242 @@
+use chrono::{Datelike, NaiveDate};
+use log::info;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::fmt;
+
+use crate::configuration::config::Config;
+use crate::models::indevolt_models::BatterySnapshot;
+use super::energy::{energy_delta_wh, wh_to_kwh};
+
+// --------------------------------------------------------------------------------------------------------------
+// Battery state-of-health trend
+//
+// Every uninterrupted charging stretch that raises the SOC by at least `soh_min_soc_span_percent` yields one
+// capacity sample: the energy charged (from the lifetime `total_charging_kwh` counter, corrected for charge
+// losses with the charge efficiency, see `Config::efficiencies`) divided by the SOC gained.
+//
+//   capacity_wh = charged_wh × charge_efficiency × 100 / (soc_end − soc_start)
+//
+// The estimate is the median of the most recent `SOH_WINDOW` samples relative to `battery_rated_capacity_kwh`.
+// The 1% SOC resolution and the efficiency guess make single samples noisy; this is a directional indicator,
+// not a lab measurement. With fewer than `soh_min_samples` samples the estimate is "unknown". A summary is
+// logged once per month, and the samples are kept in the state file so the trend survives restarts.
+// --------------------------------------------------------------------------------------------------------------
+
+const CHARGING_STATE: &str = "Charging";
+
+/// Samples the moving estimate is taken over.
+pub const SOH_WINDOW: usize = 10;
+
+/// Samples kept (oldest dropped first) - a few years of data at a handful of deep charges per month.
+const MAX_SAMPLES: usize = 200;
+
+/// One capacity measurement from a charging stretch.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct CapacitySample {
+    pub date:             NaiveDate,
+    pub capacity_wh:      i64,
+    pub soc_span_percent: f64,
+}
+
+/// Current state-of-health estimate.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SohEstimate {
+    Unknown { samples: usize, needed: usize },
+    Known { usable_kwh: f64, soh_percent: f64, samples: usize },
+}
+
+impl fmt::Display for SohEstimate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SohEstimate::Unknown { samples, needed } => {
+                write!(f, "unknown ({} of {} capacity samples)", samples, needed)
+            }
+            SohEstimate::Known { usable_kwh, soh_percent, samples } => {
+                write!(f, "{:.0}% ({:.2} kWh usable, {} samples)", soh_percent, usable_kwh, samples)
+            }
+        }
+    }
+}
+
+/// Charging stretch in progress: SOC and lifetime charge counter at its start and at the latest cycle.
+#[derive(Debug, Clone, Copy)]
+struct ChargeStretch {
+    start_soc:     f64,
+    start_charged: f64,
+    last_soc:      f64,
+    last_charged:  f64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct SohTracker {
+    samples:       VecDeque<CapacitySample>,
+    /// Month (year, month) the last summary covered up to; the next month change triggers a summary.
+    summary_month: Option<(i32, u32)>,
+    #[serde(skip)]
+    stretch:       Option<ChargeStretch>,
+}
+
+impl SohTracker {
+    /// Feed one cycle's snapshot. Returns a new capacity sample when a long enough charging stretch ended.
+    pub fn observe(
+        &mut self,
+        battery: &BatterySnapshot,
+        today: NaiveDate,
+        config: &Config,
+    ) -> Option<CapacitySample> {
+        self.summarise_month(today, config);
+
+        if battery.battery_state == CHARGING_STATE {
+            let stretch = self.stretch.get_or_insert(ChargeStretch {
+                start_soc:     battery.battery_soc,
+                start_charged: battery.total_charging_kwh,
+                last_soc:      battery.battery_soc,
+                last_charged:  battery.total_charging_kwh,
+            });
+            stretch.last_soc     = battery.battery_soc;
+            stretch.last_charged = battery.total_charging_kwh;
+            return None;
+        }
+
+        let stretch = self.stretch.take()?;
+        let sample  = capacity_sample(
+            stretch.last_soc - stretch.start_soc,
+            energy_delta_wh(stretch.start_charged, stretch.last_charged),
+            today,
+            config,
+        )?;
+        if self.samples.len() == MAX_SAMPLES {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(sample);
+        info!(
+            "[Analytics] Capacity sample {:.2} kWh from a {:.0}% charge - SOH {}",
+            wh_to_kwh(sample.capacity_wh), sample.soc_span_percent, self.estimate(config)
+        );
+        Some(sample)
+    }
+
+    /// Moving state-of-health estimate over the most recent samples.
+    pub fn estimate(&self, config: &Config) -> SohEstimate {
+        estimate(&self.samples, config.battery_rated_capacity_kwh, config.soh_min_samples)
+    }
+
+    /// Log a summary when `today` is in a later month than the previous summary.
+    fn summarise_month(&mut self, today: NaiveDate, config: &Config) {
+        let month = (today.year(), today.month());
+        match self.summary_month {
+            Some(previous) if previous != month => {
+                let in_month = self
+                    .samples
+                    .iter()
+                    .filter(|s| (s.date.year(), s.date.month()) == previous)
+                    .count();
+                info!(
+                    "[Analytics] SOH summary {}-{:02}: {} ({} new samples, rated {:.1} kWh)",
+                    previous.0, previous.1, self.estimate(config), in_month, config.battery_rated_capacity_kwh
+                );
+            }
+            Some(_) => return,
+            None    => {}
+        }
+        self.summary_month = Some(month);
+    }
+}
+
+/// Capacity implied by charging `charged_wh` over `soc_span_percent`, or `None` when the span is too short
+/// to be meaningful or the counter didn't move.
+pub fn capacity_sample(
+    soc_span_percent: f64,
+    charged_wh: i64,
+    date: NaiveDate,
+    config: &Config,
+) -> Option<CapacitySample> {
+    if soc_span_percent < config.soh_min_soc_span_percent || soc_span_percent <= 0.0 || charged_wh <= 0 {
+        return None;
+    }
+    let stored_wh = charged_wh as f64 * config.efficiencies().0;
+    Some(CapacitySample {
+        date,
+        capacity_wh: (stored_wh * 100.0 / soc_span_percent).round() as i64,
+        soc_span_percent,
+    })
+}
+
+/// Median of the last `SOH_WINDOW` samples relative to the rated capacity.
+pub fn estimate(samples: &VecDeque<CapacitySample>, rated_kwh: f64, min_samples: usize) -> SohEstimate {
+    let needed = min_samples.max(1);
+    if samples.len() < needed || rated_kwh <= 0.0 {
+        return SohEstimate::Unknown { samples: samples.len(), needed };
+    }
+
+    let mut recent: Vec<i64> = samples.iter().rev().take(SOH_WINDOW).map(|s| s.capacity_wh).collect();
+    recent.sort_unstable();
+    let mid       = recent.len() / 2;
+    let median_wh = if recent.len().is_multiple_of(2) {
+        (recent[mid - 1] + recent[mid]) as f64 / 2.0
+    } else {
+        recent[mid] as f64
+    };
+
+    let usable_kwh = median_wh / 1000.0;
+    SohEstimate::Known {
+        usable_kwh,
+        soh_percent: usable_kwh / rated_kwh * 100.0,
+        samples:     samples.len(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::optimiser::testing::battery;
+
+    fn config() -> Config {
+        Config { charge_efficiency: Some(0.95), discharge_efficiency: Some(0.90), ..Config::default() }
+    }
+
+    fn snapshot(state: &str, soc: f64, charged_kwh: f64) -> BatterySnapshot {
+        BatterySnapshot {
+            battery_state:      state.to_string(),
+            total_charging_kwh: charged_kwh,
+            ..battery(soc, 0)
+        }
+    }
+
+    /// One charging stretch from `soc` to `soc + span` charging `kwh` from lifetime counter `counter`.
+    fn charge(tracker: &mut SohTracker, soc: f64, span: f64, counter: f64, kwh: f64) -> Option<CapacitySample> {
+        let today = NaiveDate::from_ymd_opt(2026, 10, 15).unwrap();
+        assert_eq!(tracker.observe(&snapshot("Charging", soc, counter), today, &config()), None);
+        assert_eq!(tracker.observe(&snapshot("Charging", soc + span, counter + kwh), today, &config()), None);
+        tracker.observe(&snapshot("Static", soc + span, counter + kwh), today, &config())
+    }
+
+    #[test]
+    fn a_charging_stretch_yields_the_hand_computed_capacity() {
+        // 6 kWh charged × 0.95 = 5.7 kWh stored over 50 % SOC: 11.4 kWh capacity.
+        let mut tracker = SohTracker::default();
+        let sample      = charge(&mut tracker, 20.0, 50.0, 500.0, 6.0).expect("a sample");
+        assert_eq!((sample.capacity_wh, sample.soc_span_percent), (11_400, 50.0));
+        assert_eq!(tracker.estimate(&config()), SohEstimate::Unknown { samples: 1, needed: 3 });
+
+        // Too short a stretch says nothing.
+        assert_eq!(charge(&mut tracker, 40.0, 20.0, 506.0, 2.5), None);
+    }
+
+    #[test]
+    fn the_estimate_is_the_median_against_the_rated_capacity() {
+        // 10.26, 11.4 and 11.97 kWh: the median 11.4 kWh is 95 % of the rated 12 kWh.
+        let mut tracker = SohTracker::default();
+        for kwh in [5.4, 6.0, 6.3] {
+            charge(&mut tracker, 20.0, 50.0, 500.0, kwh).expect("a sample");
+        }
+        let estimate = tracker.estimate(&config());
+        let SohEstimate::Known { usable_kwh, soh_percent, samples } = estimate else {
+            panic!("{estimate}");
+        };
+        assert_eq!(samples, 3);
+        assert!((usable_kwh - 11.4).abs() < 1e-9 && (soh_percent - 95.0).abs() < 1e-9);
+        assert_eq!(estimate.to_string(), "95% (11.40 kWh usable, 3 samples)");
+    }
+}
@@ -1,19 +1,207 @@
-use serde::Deserialize;
-use std::fs;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
+use std::{env, fmt, fs, io};
+
+use crate::optimiser::allowed_modes::RESTRICTABLE_MODES;
+use crate::optimiser::pre_peak::PeakWindow;
+use super::curtailment::CurtailmentWindow;
+use super::floor_schedule::FloorWindow;
+use super::profiles::Profile;
+
+// --------------------------------------------------------------------------------------------------------------
+
+/// Which optimiser strategy drives the battery.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Strategy {
+    /// Read and log only; never send a command.
+    Monitor,
+    /// Store solar surplus, cover household load, never exceed the capacity-tariff peak. No price input.
+    SelfConsumption,
+    /// Self-consumption that follows a plan over the price curve: charge from the grid in the cheapest hours,
+    /// keep that energy for the most expensive ones. Needs a `price_source`.
+    PriceArbitrage,
+}
+
+/// Where the capacity-tariff shaving target comes from.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PeakTargetMode {
+    /// Always `battery_max_desired_grid_peak_w`.
+    Static,
+    /// Track the monthly peak reported by the P1 meter.
+    MonthlyPeak,
+}
+
+/// Unit used for power values in human-readable log lines.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PowerUnit {
+    #[serde(rename = "W")]
+    W,
+    #[serde(rename = "kW")]
+    KW,
+}
+
+/// How fractional SOC targets are turned into the whole percent the control register takes.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SocRounding {
+    Round,
+    Floor,
+    Ceil,
+}
+
+/// What the cycle cadence does after a cycle overran its interval (see `runtime/cadence.rs`).
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum MissedTicks {
+    /// Drop the missed ticks; the next cycle starts on the original grid.
+    Skip,
+    /// Start the next cycle immediately and shift the grid from there.
+    Delay,
+}
+
+/// How a P1 timestamp inside the spring-forward gap (a local time that doesn't exist) is read.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DstGap {
+    /// Read with the offset in force before the change, i.e. moved forward by the gap (02:30 → 03:30).
+    ShiftForward,
+    /// Treat it as a parse error, like any other invalid timestamp.
+    Reject,
+}
+
+/// What the grid-charge gate counts as when it can't be read (see `handlers/gate.rs`).
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum GateFallback {
+    /// No grid charging until the gate can be read again.
+    Deny,
+    /// Grid charging as configured.
+    Allow,
+    /// The last value read; deny before the first one.
+    Last,
+}
+
+impl GateFallback {
+    /// The gate value to use, `None` for the last one read.
+    pub fn value(self) -> Option<bool> {
+        match self {
+            GateFallback::Deny  => Some(false),
+            GateFallback::Allow => Some(true),
+            GateFallback::Last  => None,
+        }
+    }
+}
+
+/// Provider of the hourly import prices (see `prices/`).
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PriceSourceKind {
+    /// No prices: price-dependent features stay off.
+    None,
+    /// Tibber GraphQL API (`tibber_token`).
+    Tibber,
+    /// ENTSO-E Transparency Platform day-ahead prices (`entsoe_token`, `entsoe_area`).
+    Entsoe,
+    /// Local CSV file (`price_csv_path`).
+    Csv,
+}
+
+/// File format of the daily report (see `reporting/`).
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ReportFormat {
+    Text,
+    Markdown,
+    Json,
+}
+
+impl ReportFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ReportFormat::Text     => "txt",
+            ReportFormat::Markdown => "md",
+            ReportFormat::Json     => "json",
+        }
+    }
+}
+
+/// Relative weights of the penalties in the per-cycle data quality score (see `analytics/data_quality.rs`).
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(default)]
+pub struct DataQualityWeights {
+    pub p1:             f64,
+    pub battery_keys:   f64,
+    pub reconciliation: f64,
+    pub consistency:    f64,
+}
+
+impl Default for DataQualityWeights {
+    fn default() -> Self {
+        Self { p1: 40.0, battery_keys: 30.0, reconciliation: 15.0, consistency: 15.0 }
+    }
+}
 
 // --------------------------------------------------------------------------------------------------------------
 
-#[derive(Deserialize, Debug, Clone)]
+/// Fields missing from config.json fall back to the values in `Config::default()`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
 pub struct Config {
     // --- connectivity ---
 
     /// HomeWizard P1 meter local API endpoint, e.g. "http://192.168.1.x/api/v1/data"
+    /// (API v2: "https://192.168.1.x/api/measurement").
     pub p1_url: String,
+    /// HomeWizard local API version: 1 (plain HTTP, default) or 2 (HTTPS + bearer token).
+    pub p1_api_version: u8,
+    /// Bearer token for API v2, obtained by pairing with the meter. Unused for v1.
+    pub p1_api_token: String,
+    /// PEM file with the HomeWizard CA certificate; API v2 then trusts only certificates signed by it.
+    pub p1_ca_cert_path: Option<String>,
+    /// Accept any certificate from the meter (API v2) - no protection against a spoofed device.
+    pub p1_accept_invalid_certs: bool,
     /// Indevolt PowerFlex base URL, e.g. "http://192.168.1.y"
     pub indevolt_url: String,
     /// Single loop interval: P1 read -> battery read -> optimiser -> sleep.
     /// 30s matches the HomeWizard P1 update rate.
     pub poll_interval_seconds: u64,
+    /// Start each cycle on a wall-clock multiple of `poll_interval_seconds` (e.g. :00 and :30)
+    /// instead of `poll_interval_seconds` after the previous cycle started.
+    pub align_to_wallclock: bool,
+    /// After an overrun, "skip" to the next tick on the original cadence or "delay" the cadence.
+    pub missed_tick_behavior: MissedTicks,
+    /// Restore self-consumption mode when no cycle has started for this long (s). 0 disables the watchdog.
+    /// Keep it well above `poll_interval_seconds`.
+    pub watchdog_timeout_seconds: u64,
+    /// After the watchdog fired, exit the process so the service manager restarts it.
+    pub watchdog_abort: bool,
+    /// On SIGINT/SIGTERM: time (s) for the loop to stop, the inverter to be handed back and the sinks to flush.
+    pub shutdown_timeout_seconds: u64,
+    /// Maps the `unique_id` of a P1 external meter to a logical role, e.g. "gas" or "water".
+    /// Needed when the meter reports several externals of the same type.
+    pub p1_external_roles: HashMap<String, String>,
+    /// How long an idle keep-alive connection to the P1 meter is kept open (s). Keep this above
+    /// `poll_interval_seconds` so every poll reuses the same connection.
+    pub pool_idle_timeout_seconds: u64,
+    /// Maximum idle keep-alive connections kept per host. The P1 dongle only needs one.
+    pub pool_max_idle_per_host: usize,
+    /// Timeout (ms) for one P1 request, connect included.
+    pub p1_timeout_ms: u64,
+    /// Time (ms) per cycle that failed P1 and battery reads may spend on retries, all reads together.
+    /// 0: no retries.
+    pub cycle_retry_budget_ms: u64,
+    /// A P1 reading whose import, export and power haven't changed for this long (s) counts as stale.
+    /// 0 disables the check.
+    pub p1_stale_after_seconds: u64,
+    /// Warn when the P1 meter clock and the host clock disagree by more than this (s). 0 disables the check.
+    pub clock_skew_warn_seconds: u64,
+    /// P1 timestamp in the spring-forward DST gap: "shift_forward" to the valid time, or "reject".
+    pub p1_dst_gap: DstGap,
+    /// P1 fields (v1 names) a reading must contain to be usable; any other field may be missing or malformed.
+    pub p1_critical_fields: Vec<String>,
 
     // --- battery physical parameters ---
 
@@ -24,6 +212,46 @@ pub struct Config {
     pub battery_min_soc_percent: f64,
     /// Maximum SOC target (%). Normally 100, lower it to extend cycle life if desired.
     pub battery_max_soc_percent: f64,
+    /// GetData sensor IDs of the MinSOC / MaxSOC set on the device itself, if your firmware exposes them.
+    /// Not polled when unset; when set, the config limits are reconciled with them.
+    pub device_min_soc_sensor_id: Option<u32>,
+    pub device_max_soc_sensor_id: Option<u32>,
+    /// Keep the config's min/max SOC when the device's are stricter, instead of using the device's.
+    pub trust_config_soc_limits: bool,
+    /// Offset added to the SOC reported by the inverter (%). Use a negative value when the
+    /// device over-reports, e.g. -3.0 if it reads ~3% high compared to the real usable range.
+    pub soc_calibration_offset_percent: f64,
+    /// [min, max] range the calibrated SOC is clamped into (%). Always kept within 0..=100.
+    pub soc_clamp: [f64; 2],
+    /// Rounding of fractional SOC targets to the device's whole percent: "round", "floor" or "ceil".
+    pub soc_target_rounding: SocRounding,
+    /// A SOC reading further than this (%) from the last trusted one is held as a glitch. 0 disables.
+    pub max_soc_jump_per_cycle_percent: f64,
+    /// Same for the battery power (W). Commands change it in steps, so keep it above the power limits.
+    pub max_battery_power_jump_w: i32,
+
+    // --- temperature ---
+
+    /// GetData sensor ID of the inverter temperature (°C) on your firmware. Not polled when unset.
+    pub inverter_temperature_sensor_id: Option<u32>,
+    /// GetData sensor ID of the battery temperature (°C) on your firmware. Not polled when unset.
+    pub battery_temperature_sensor_id: Option<u32>,
+    /// Above this temperature (°C) the charge/discharge power is reduced linearly...
+    pub thermal_derate_start_c: f64,
+    /// ...down to zero at this temperature (°C).
+    pub thermal_cutoff_c: f64,
+
+    // --- grid outage and grid quality ---
+
+    /// GetData sensor ID of the grid/backup state on your firmware. Not polled when unset.
+    pub grid_state_sensor_id: Option<u32>,
+    /// Values of that sensor that mean the inverter is running off-grid (backup).
+    pub off_grid_state_values: Vec<i64>,
+    /// GetData sensor IDs of the AC grid frequency (Hz) and voltage (V) on your firmware. Not polled when unset.
+    pub grid_frequency_sensor_id: Option<u32>,
+    pub grid_voltage_sensor_id: Option<u32>,
+    /// [min, max] nominal grid frequency (Hz). A reading outside it is logged as grid stress.
+    pub grid_frequency_band_hz: [f64; 2],
 
     // --- grid power limits ---
 
@@ -36,20 +264,255 @@ pub struct Config {
 
     // --- optimiser thresholds ---
 
+    /// Optimiser strategy: "self_consumption" (default), "price_arbitrage" (follow a plan over the price curve)
+    /// or "monitor" (observe only, no commands).
+    pub strategy: Strategy,
+    /// Second strategy that decides on the same inputs each cycle and is only logged, never dispatched.
+    pub shadow_strategy: Option<Strategy>,
+    /// For this long (h) after the first start, decisions are only logged and recorded, never sent. 0 = off.
+    pub learning_period_hours: f64,
+    /// Household load is only covered from the battery above this SOC (%). The band between
+    /// `battery_min_soc_percent` and this value is kept for capacity-tariff peak shaving.
+    pub peak_shaving_reserve_soc_percent: f64,
+    /// Peak shaving stops this far (%) above `battery_min_soc_percent` and tapers its power over the band of
+    /// the same width above that. 0 shaves at full power down to the minimum SOC.
+    pub peak_shave_min_soc_headroom_percent: f64,
+    /// Local-time windows that replace the reserve above with a per-window discharge floor.
+    pub discharge_floor_schedule: Vec<FloorWindow>,
+    /// After a command, the optimiser won't reverse charge ↔ discharge for this long (ms) while the
+    /// inverter ramps and `battery_power_w` may still read the old direction.
+    pub post_command_settle_ms: u64,
+    /// Maximum charge ↔ discharge changes in any rolling hour, to spare the inverter relays. 0 = unlimited.
+    pub max_direction_changes_per_hour: u32,
+    /// Once a charge reached its target (a discharge its floor), the same direction only restarts after the
+    /// SOC moved this far (%) away from it. 0 = off.
+    pub soc_hold_band_percent: f64,
+    /// Charge/discharge decisions the EMS may send ("ChargingFromSolar", "ChargingFromGrid",
+    /// "DischargingToLoad", "DischargingToGrid"). Idle and self-consumption are always allowed.
+    pub allowed_working_modes: Vec<String>,
+    /// Largest rise (W) of the commanded charge/discharge power from one cycle to the next. 0 = no ramp.
+    pub max_power_step_w: i32,
+    /// Grid import (W) that triggers an immediate discharge between cycles (load-spike fast path). 0 disables.
+    pub fast_discharge_trigger_w: i32,
+    /// How often (ms) P1 is re-read between cycles while the fast path is enabled.
+    pub fast_discharge_poll_ms: u64,
+    /// After this long (s) of uninterrupted Idle, hand control back to the inverter's self-consumption mode.
+    /// 0 keeps real-time mode engaged (warm standby) indefinitely.
+    pub auto_handback_idle_seconds: u64,
+    /// A maintenance charge starts once the SOC has stayed below this (%) for `maintenance_interval_hours`.
+    pub maintenance_soc_percent: f64,
+    /// Hours of uninterrupted low SOC before a maintenance charge (0 disables maintenance charging).
+    pub maintenance_interval_hours: u64,
+    /// SOC (%) a maintenance charge charges up to.
+    pub maintenance_target_soc_percent: f64,
+    /// Grid charging power (W) for a maintenance charge.
+    pub maintenance_charge_power_w: i32,
+    /// Local-time window in which the daily peak is expected; the battery is topped up before it to cover the
+    /// peak shaving of previous days. None: off.
+    pub pre_peak_window: Option<PeakWindow>,
+    /// How long (min) before `pre_peak_window` the top-up may run.
+    pub pre_peak_lead_minutes: u32,
+    /// Grid charging power (W) of a pre-peak top-up.
+    pub pre_peak_charge_power_w: i32,
+    /// Days of peak-window history the energy to keep is estimated from; the largest day counts.
+    pub pre_peak_history_days: usize,
+    /// Energy (kWh) kept for the peak window at least, also before there is any history.
+    pub pre_peak_min_energy_kwh: f64,
+    /// Allow charging from the grid. Without it, maintenance charges wait for solar surplus.
+    pub grid_charging_enabled: bool,
+    /// URL read each cycle (GET) whose body, "allow" or "deny", switches grid charging at runtime. Empty: none.
+    pub grid_charge_gate_url: String,
+    /// File read each cycle instead, when no gate URL is set. Empty: none.
+    pub grid_charge_gate_file: String,
+    /// Gate value when it can't be read: "deny", "allow" or "last".
+    pub grid_charge_gate_on_error: GateFallback,
+    /// URL read each cycle (GET) whose body, "on" or "off", says whether the EV is charging. While it is, the
+    /// battery only discharges for peak shaving. Empty: none.
+    pub ev_charging_url: String,
+    /// File read each cycle instead, when no EV charging URL is set. Empty: none.
+    pub ev_charging_file: String,
+    /// Grid charging needs a P1 reading whose values changed within this many seconds (0: any non-stale one).
+    pub grid_charge_max_p1_age_seconds: u64,
+    /// Grid charging needs a SOC read this cycle, at most this many seconds before the decision (0: no limit).
+    pub grid_charge_max_soc_age_seconds: u64,
+    /// Most energy (kWh) charged from the grid per local day; further grid charging waits for midnight.
+    /// 0 disables the cap.
+    pub max_daily_grid_charge_kwh: f64,
+    /// Most energy (kWh) charged plus discharged per local day; past it, grid charges and exports wait for
+    /// midnight. 0 disables the cap.
+    pub max_daily_throughput_kwh: f64,
+    /// GetData sensor IDs (action, power, SOC limit) exposing the active control command, if the firmware has
+    /// them. When set, every control write is read back and re-sent once on a mismatch.
+    pub control_readback_sensor_ids: Option<[u32; 3]>,
+    /// After this many cycles in a row whose command failed, self-consumption mode is restored as a safe
+    /// state. 0 never does.
+    pub control_failure_threshold: u32,
+    /// Solar surplus (W) that must be exceeded before charging from surplus starts.
+    pub surplus_charge_threshold_w: i32,
+    /// Once charging, surplus (W) at or below which charging from surplus stops. Keep below the threshold.
+    pub surplus_charge_exit_w: i32,
+    /// Run the optimiser off the Indevolt's own meter reading when P1 is unavailable.
+    pub fallback_to_indevolt_meter: bool,
+    /// Deadband (W) applied to the house load while running off the Indevolt meter.
+    pub indevolt_meter_deadband_w: i32,
+    /// P1 vs Indevolt meter difference (W) above which the optimiser flags a disagreement. P1 is used anyway.
+    /// 0 disables the check.
+    pub grid_meter_disagreement_w: i32,
+    /// Local-time windows during which feed-in is curtailed and all solar surplus goes into the battery.
+    pub curtailment_windows: Vec<CurtailmentWindow>,
+    /// Charge ceiling (SOC %) while curtailed; raised above `battery_max_soc_percent` to absorb more surplus.
+    pub curtailment_max_soc_percent: f64,
+    /// Set per cycle while inside a curtailment window; not read from the config file.
+    #[serde(skip)]
+    pub feed_in_curtailed: bool,
+    /// The configured `battery_max_soc_percent` the curtailment ceiling replaced this cycle; not read from
+    /// the config file.
+    #[serde(skip)]
+    pub uncurtailed_max_soc_percent: Option<f64>,
     /// Belgian capacity tariff peak limit (W). The optimiser will not let total grid import
     /// exceed this during peak hours to avoid a higher monthly capacity bill.
     pub battery_max_desired_grid_peak_w: i32,
+    /// "static" uses `battery_max_desired_grid_peak_w`; "monthly_peak" follows the meter's monthly peak.
+    pub peak_target_mode: PeakTargetMode,
+    /// Extra import (W) allowed above the recorded monthly peak in "monthly_peak" mode.
+    pub monthly_peak_headroom_w: i32,
+    /// The learned target never drops below this (W) - the capacity tariff's billed minimum.
+    pub monthly_peak_min_target_w: i32,
     /// Minimum price spread required to justify a grid charge/discharge cycle (%).
     /// Covers round-trip efficiency losses (~85%). Default 25% from your BatteryConfig table.
     pub battery_min_price_spread_percent: f64,
     /// Round-trip efficiency of the battery (0.0-1.0). Used by the optimiser when calculating
     /// whether a charge/discharge cycle is profitable at a given price spread.
     pub battery_round_trip_efficiency: f64,
+    /// Measured charge efficiency (0.0-1.0). Used together with `discharge_efficiency` instead of the
+    /// round-trip value when both are set.
+    pub charge_efficiency: Option<f64>,
+    /// Measured discharge efficiency (0.0-1.0), see `charge_efficiency`.
+    pub discharge_efficiency: Option<f64>,
+    /// Taxes and grid fees (EUR/kWh) paid on top of the curve price for imported energy. 0 when the price
+    /// source already quotes all-in prices.
+    pub import_adder_per_kwh: f64,
+    /// Taken off the curve price (EUR/kWh) for exported energy.
+    pub export_deduction_per_kwh: f64,
+    /// "price_arbitrage" re-plans when the SOC is this far (percentage points) off the planned trajectory.
+    pub plan_replan_soc_drift_percent: f64,
+    /// "price_arbitrage" plans across midnight into tomorrow's prices once they are published.
+    pub plan_include_tomorrow: bool,
+    /// "price_arbitrage": below this curve price (EUR/kWh) household load comes from the grid and the battery
+    /// only shaves peaks. `None` disables the rule.
+    pub cheap_price_threshold: Option<f64>,
+    /// "price_arbitrage": export when the export price beats the dearest later import price by this much (%).
+    /// `None` disables export priority.
+    pub export_priority_bias_percent: Option<f64>,
+    /// Most the battery may feed into the grid (W) under export priority. `None` means no limit beyond
+    /// `battery_max_discharge_power_w`.
+    pub max_feed_in_w: Option<i32>,
+
+    // --- calendar profiles ---
+
+    /// Named overrides selected by weekday/weekend and/or month range (see `profiles.rs`).
+    /// Evaluated every cycle against the current Brussels date.
+    pub profiles: BTreeMap<String, Profile>,
+
+    // --- analytics ---
+
+    /// Warn when the SOC falls faster than this (%/h) while the battery reports Static.
+    pub idle_drift_warn_percent_per_hour: f64,
+    /// Minimum length of an idle stretch (minutes) before the drift rate is evaluated.
+    pub idle_drift_min_window_minutes: u64,
+    /// A PV string producing less than this fraction of the other one counts as underperforming.
+    pub pv_string_imbalance_ratio: f64,
+    /// The stronger string must deliver at least this (W) before an imbalance is judged (dusk/dawn filter).
+    pub pv_string_min_power_w: i32,
+    /// An imbalance must last this long (minutes) before it is reported.
+    pub pv_string_imbalance_minutes: u64,
+    /// Estimate the battery's state of health from charging stretches (logged, summarised monthly).
+    pub soh_tracking_enabled: bool,
+    /// Minimum SOC rise (%) of a charging stretch before it counts as a capacity sample.
+    pub soh_min_soc_span_percent: f64,
+    /// Capacity samples needed before the state of health is reported instead of "unknown".
+    pub soh_min_samples: usize,
+    /// Weights of the data quality score penalties.
+    pub data_quality_weights: DataQualityWeights,
+    /// P1 vs Indevolt meter difference (W) that counts as a full reconciliation penalty.
+    pub data_quality_reconciliation_tolerance_w: i32,
+    /// Below this score (0-100) the optimiser scales its commanded power down by the score.
+    pub data_quality_min_score: u8,
+
+    // --- persistence ---
+
+    /// File the loop state is saved to so a restart continues where it left off. Empty disables it.
+    pub state_file_path: String,
+    /// How often the state file is rewritten (s).
+    pub state_save_interval_seconds: u64,
+    /// A state file older than this (s) is ignored at start-up.
+    pub state_max_age_seconds: u64,
+
+    // --- daily report ---
+
+    /// Directory the report of each day is written to after local midnight. Empty disables reports.
+    pub report_dir: String,
+    /// "text", "markdown" or "json".
+    pub report_format: ReportFormat,
+
+    // --- InfluxDB export ---
+
+    /// InfluxDB v2 base URL, e.g. "http://192.168.1.z:8086". Empty disables the sink.
+    pub influxdb_url: String,
+    /// API token with write access to the bucket.
+    pub influxdb_token: String,
+    /// Target bucket.
+    pub influxdb_bucket: String,
+    /// Organisation that owns the bucket.
+    pub influxdb_org: String,
+
+    // --- recent-cycles API ---
+
+    /// Address the HTTP API listens on, e.g. "127.0.0.1:8081". Empty disables it.
+    pub api_listen: String,
+    /// Cycle outcomes kept in memory for `GET /recent` (120 ≈ one hour at 30 s).
+    pub recent_cycles: usize,
+    /// Serve the built-in dashboard page at `GET /` of the API.
+    pub dashboard_enabled: bool,
+
+    // --- dynamic prices ---
+
+    /// Where hourly import prices come from: "none", "tibber", "entsoe" or "csv".
+    pub price_source: PriceSourceKind,
+    /// Tibber API token (developer.tibber.com).
+    pub tibber_token: String,
+    /// Tibber GraphQL endpoint.
+    pub tibber_api_url: String,
+    /// ENTSO-E Transparency Platform security token.
+    pub entsoe_token: String,
+    /// ENTSO-E API endpoint.
+    pub entsoe_api_url: String,
+    /// ENTSO-E bidding zone EIC code, e.g. "10YBE----------2" for Belgium.
+    pub entsoe_area: String,
+    /// CSV file with "starts_at,price_eur_per_kwh" lines.
+    pub price_csv_path: String,
 
     // --- logging ---
 
     /// Log level: "Trace", "Debug", "Info", "Warn", "Error"
     pub log_level: String,
+    /// Unit for power values in log lines: "W" or "kW". Exported data always stays in W.
+    pub power_display_unit: PowerUnit,
+    /// Decimal places for logged power values. Defaults to 0 for W and 2 for kW.
+    pub power_decimals: Option<usize>,
+    /// OTLP/HTTP traces endpoint, e.g. "http://collector:4318/v1/traces". Empty disables span export;
+    /// requires a build with the "otel" feature.
+    pub otel_endpoint: String,
+    /// Log the per-cycle lines at debug level while the decision is `Idle` and the readings are complete.
+    pub quiet_when_idle: bool,
+    /// While quiet, an info heartbeat is logged this often (min).
+    pub idle_heartbeat_minutes: u64,
+
+    // --- environment ---
+
+    /// Environment variables that overrode a setting at load time; not read from the config file.
+    #[serde(skip)]
+    pub env_overrides: Vec<String>,
 }
 
 impl Default for Config {
@@ -57,21 +520,160 @@ impl Default for Config {
         Self {
             // connectivity
             p1_url:               "http://127.0.0.1/api/v1/data".to_string(),
+            p1_api_version:       1,
+            p1_api_token:         String::new(),
+            p1_ca_cert_path:      None,
+            p1_accept_invalid_certs: false,
             indevolt_url:         "http://127.0.0.1".to_string(),
             poll_interval_seconds: 30,
+            align_to_wallclock:   false,
+            missed_tick_behavior: MissedTicks::Skip,
+            watchdog_timeout_seconds: 0,
+            watchdog_abort:       false,
+            shutdown_timeout_seconds: 10,
+            p1_external_roles:    HashMap::new(),
+            pool_idle_timeout_seconds: 90,
+            pool_max_idle_per_host:    1,
+            p1_timeout_ms:             5000,
+            cycle_retry_budget_ms:     0,
+            p1_stale_after_seconds:    60,
+            clock_skew_warn_seconds:   120,
+            p1_dst_gap:                DstGap::ShiftForward,
+            p1_critical_fields:        ["active_power_w", "active_tariff", "total_power_import_kwh",
+                                        "total_power_export_kwh"].map(String::from).to_vec(),
             // battery physical - values from your live BatteryConfig table
             battery_rated_capacity_kwh:    12.0,
             battery_min_soc_percent:       10.0,
             battery_max_soc_percent:       100.0,
+            device_min_soc_sensor_id:      None,
+            device_max_soc_sensor_id:      None,
+            trust_config_soc_limits:       false,
+            soc_calibration_offset_percent: 0.0,
+            soc_clamp:                     [0.0, 100.0],
+            soc_target_rounding:           SocRounding::Round,
+            max_soc_jump_per_cycle_percent: 10.0,
+            max_battery_power_jump_w:      0,
+            // temperature - sensors not polled until their IDs are configured
+            inverter_temperature_sensor_id: None,
+            battery_temperature_sensor_id:  None,
+            thermal_derate_start_c:         45.0,
+            thermal_cutoff_c:               55.0,
+            // grid outage and quality - sensors not polled until their IDs are configured
+            grid_state_sensor_id:           None,
+            off_grid_state_values:          Vec::new(),
+            grid_frequency_sensor_id:       None,
+            grid_voltage_sensor_id:         None,
+            grid_frequency_band_hz:         [49.8, 50.2],
             // grid power limits - current 2400 W hardware; raise to 7200 after upgrade
             battery_max_charge_power_w:    2400,
             battery_max_discharge_power_w: 2400,
             // optimiser thresholds - from your live BatteryConfig table
+            strategy:                         Strategy::SelfConsumption,
+            shadow_strategy:                  None,
+            learning_period_hours:            0.0,
+            peak_shaving_reserve_soc_percent: 20.0,
+            peak_shave_min_soc_headroom_percent: 0.0,
+            discharge_floor_schedule:         Vec::new(),
+            post_command_settle_ms:           5000,
+            max_direction_changes_per_hour:   0,
+            soc_hold_band_percent:            0.0,
+            allowed_working_modes:            RESTRICTABLE_MODES.map(String::from).to_vec(),
+            max_power_step_w:                 0,
+            fast_discharge_trigger_w:         0,
+            fast_discharge_poll_ms:           2000,
+            auto_handback_idle_seconds:       0,
+            maintenance_soc_percent:          15.0,
+            maintenance_interval_hours:       0,
+            maintenance_target_soc_percent:   30.0,
+            maintenance_charge_power_w:       1000,
+            pre_peak_window:                  None,
+            pre_peak_lead_minutes:            180,
+            pre_peak_charge_power_w:          1500,
+            pre_peak_history_days:            7,
+            pre_peak_min_energy_kwh:          0.0,
+            grid_charging_enabled:            false,
+            grid_charge_gate_url:             String::new(),
+            grid_charge_gate_file:            String::new(),
+            grid_charge_gate_on_error:        GateFallback::Deny,
+            ev_charging_url:                  String::new(),
+            ev_charging_file:                 String::new(),
+            grid_charge_max_p1_age_seconds:   30,
+            grid_charge_max_soc_age_seconds:  10,
+            max_daily_grid_charge_kwh:        0.0,
+            max_daily_throughput_kwh:         0.0,
+            control_readback_sensor_ids:      None,
+            control_failure_threshold:        3,
+            surplus_charge_threshold_w:       0,
+            surplus_charge_exit_w:            0,
+            fallback_to_indevolt_meter:       false,
+            indevolt_meter_deadband_w:        150,
+            grid_meter_disagreement_w:        300,
+            curtailment_windows:              Vec::new(),
+            curtailment_max_soc_percent:      100.0,
+            feed_in_curtailed:                false,
+            uncurtailed_max_soc_percent:      None,
             battery_max_desired_grid_peak_w:  3381,
+            peak_target_mode:                 PeakTargetMode::Static,
+            monthly_peak_headroom_w:          0,
+            monthly_peak_min_target_w:        2500,
             battery_min_price_spread_percent: 25.0,
             battery_round_trip_efficiency:    0.80,
+            charge_efficiency:                None,
+            discharge_efficiency:             None,
+            import_adder_per_kwh:             0.0,
+            export_deduction_per_kwh:         0.0,
+            plan_replan_soc_drift_percent:    10.0,
+            plan_include_tomorrow:            true,
+            cheap_price_threshold:            None,
+            export_priority_bias_percent:     None,
+            max_feed_in_w:                    None,
+            // calendar profiles - none by default
+            profiles: BTreeMap::new(),
+            // analytics
+            idle_drift_warn_percent_per_hour: 1.0,
+            idle_drift_min_window_minutes:    60,
+            pv_string_imbalance_ratio:        0.5,
+            pv_string_min_power_w:            200,
+            pv_string_imbalance_minutes:      30,
+            soh_tracking_enabled:             false,
+            soh_min_soc_span_percent:         30.0,
+            soh_min_samples:                  3,
+            data_quality_weights:             DataQualityWeights::default(),
+            data_quality_reconciliation_tolerance_w: 500,
+            data_quality_min_score:           50,
+            // persistence
+            state_file_path:             "ems_state.json".to_string(),
+            state_save_interval_seconds: 60,
+            state_max_age_seconds:       6 * 3600,
+            // daily report - disabled unless a directory is set
+            report_dir:    String::new(),
+            report_format: ReportFormat::Markdown,
+            // InfluxDB export - disabled unless a URL is set
+            influxdb_url:    String::new(),
+            influxdb_token:  String::new(),
+            influxdb_bucket: String::new(),
+            influxdb_org:    String::new(),
+            // recent-cycles API - disabled unless an address is set
+            api_listen:        String::new(),
+            recent_cycles:     120,
+            dashboard_enabled: false,
+            // dynamic prices - none unless a source is selected
+            price_source:   PriceSourceKind::None,
+            tibber_token:   String::new(),
+            tibber_api_url: "https://api.tibber.com/v1-beta/gql".to_string(),
+            entsoe_token:   String::new(),
+            entsoe_api_url: "https://web-api.tp.entsoe.eu/api".to_string(),
+            entsoe_area:    "10YBE----------2".to_string(),
+            price_csv_path: String::new(),
             // logging
-            log_level: "Info".to_string(),
+            log_level:              "Info".to_string(),
+            power_display_unit:     PowerUnit::W,
+            power_decimals:         None,
+            otel_endpoint:          String::new(),
+            quiet_when_idle:        false,
+            idle_heartbeat_minutes: 15,
+            // environment
+            env_overrides: Vec::new(),
         }
     }
 }
@@ -83,14 +685,402 @@ impl Config {
             * (self.battery_max_soc_percent - self.battery_min_soc_percent)
             / 100.0
     }
+
+    /// Apply the configured calibration offset to a raw device SOC and clamp the result
+    /// into `soc_clamp`, never leaving 0..=100.
+    pub fn calibrate_soc(&self, raw_soc_percent: f64) -> f64 {
+        let [lo, hi] = self.soc_clamp;
+        (raw_soc_percent + self.soc_calibration_offset_percent)
+            .clamp(lo.min(hi), hi.max(lo))
+            .clamp(0.0, 100.0)
+    }
+
+    /// Whole-percent SOC target for the control register: `percent` rounded per `soc_target_rounding`, then
+    /// kept within [`battery_min_soc_percent`, `battery_max_soc_percent`] so rounding can't cross a limit.
+    pub fn soc_target(&self, percent: f64) -> u8 {
+        let rounded = match self.soc_target_rounding {
+            SocRounding::Round => percent.round(),
+            SocRounding::Floor => percent.floor(),
+            SocRounding::Ceil  => percent.ceil(),
+        };
+        let target = rounded
+            .max(self.battery_min_soc_percent.ceil())
+            .min(self.battery_max_soc_percent.floor())
+            .clamp(0.0, 100.0) as u8;
+        if target as f64 != percent {
+            log::debug!("[Config] SOC target {:.2}% → {}%", percent, target);
+        }
+        target
+    }
+
+    /// (charge, discharge) efficiency: the measured pair when both are set, otherwise the round-trip
+    /// efficiency split evenly (√ each way).
+    pub fn efficiencies(&self) -> (f64, f64) {
+        match (self.charge_efficiency, self.discharge_efficiency) {
+            (Some(charge), Some(discharge)) => (charge, discharge),
+            _ => {
+                let one_way = self.battery_round_trip_efficiency.clamp(0.0, 1.0).sqrt();
+                (one_way, one_way)
+            }
+        }
+    }
+
+    /// Round-trip efficiency in effect: the product of the charge and discharge efficiencies.
+    pub fn round_trip_efficiency(&self) -> f64 {
+        let (charge, discharge) = self.efficiencies();
+        charge * discharge
+    }
+
+    /// What a kWh imported at curve price `price` costs, taxes and grid fees included (EUR).
+    pub fn import_price(&self, price: f64) -> f64 {
+        price + self.import_adder_per_kwh
+    }
+
+    /// What a kWh exported at curve price `price` earns (EUR).
+    pub fn export_price(&self, price: f64) -> f64 {
+        price - self.export_deduction_per_kwh
+    }
+
+    /// Price spread (%) at which a grid charge/discharge cycle breaks even: energy bought at price p comes
+    /// back as η × energy, so it must sell for p / η, i.e. (1/η − 1) × 100 % above the purchase price.
+    pub fn break_even_spread_percent(&self) -> f64 {
+        (1.0 / self.round_trip_efficiency() - 1.0) * 100.0
+    }
+
+    /// Check the invariants the field types can't express. Returns every violation, not just the first.
+    pub fn validate(&self) -> Result<(), Vec<String>> {
+        let mut problems = Vec::new();
+
+        let soc_limits = [
+            ("battery_min_soc_percent", self.battery_min_soc_percent),
+            ("battery_max_soc_percent", self.battery_max_soc_percent),
+        ];
+        for (name, value) in soc_limits {
+            if !(0.0..=100.0).contains(&value) {
+                problems.push(format!("{} must be within 0-100, got {}", name, value));
+            }
+        }
+        if self.battery_min_soc_percent >= self.battery_max_soc_percent {
+            problems.push(format!(
+                "battery_min_soc_percent ({}) must be below battery_max_soc_percent ({})",
+                self.battery_min_soc_percent, self.battery_max_soc_percent
+            ));
+        }
+        let efficiencies = [
+            ("battery_round_trip_efficiency", Some(self.battery_round_trip_efficiency)),
+            ("charge_efficiency",             self.charge_efficiency),
+            ("discharge_efficiency",          self.discharge_efficiency),
+        ];
+        for (name, value) in efficiencies {
+            if let Some(v) = value.filter(|v| !(0.0..=1.0).contains(v)) {
+                problems.push(format!("{} must be within 0.0-1.0, got {}", name, v));
+            }
+        }
+        for (name, url) in [("p1_url", &self.p1_url), ("indevolt_url", &self.indevolt_url)] {
+            if let Err(e) = reqwest::Url::parse(url) {
+                problems.push(format!("{} '{}' is not a valid URL: {}", name, url, e));
+            }
+        }
+        if self.p1_api_version == 2 && self.p1_ca_cert_path.is_none() && !self.p1_accept_invalid_certs {
+            problems.push(
+                "p1_api_version 2 needs p1_ca_cert_path (or p1_accept_invalid_certs) for the meter's certificate"
+                    .to_string(),
+            );
+        }
+        if self.poll_interval_seconds < 1 {
+            problems.push("poll_interval_seconds must be at least 1, got 0".to_string());
+        }
+        let power_limits = [
+            ("battery_max_charge_power_w",    self.battery_max_charge_power_w),
+            ("battery_max_discharge_power_w", self.battery_max_discharge_power_w),
+        ];
+        for (name, value) in power_limits {
+            if value < 0 {
+                problems.push(format!("{} must not be negative, got {}", name, value));
+            }
+        }
+        let price_terms = [
+            ("import_adder_per_kwh",     self.import_adder_per_kwh),
+            ("export_deduction_per_kwh", self.export_deduction_per_kwh),
+        ];
+        for (name, value) in price_terms {
+            if value < 0.0 {
+                problems.push(format!("{} must not be negative, got {}", name, value));
+            }
+        }
+        if let Some(bias) = self.export_priority_bias_percent.filter(|b| *b < 0.0) {
+            problems.push(format!("export_priority_bias_percent must not be negative, got {}", bias));
+        }
+        if let Some(cap) = self.max_feed_in_w.filter(|c| *c < 0) {
+            problems.push(format!("max_feed_in_w must not be negative, got {}", cap));
+        }
+        for mode in &self.allowed_working_modes {
+            if !RESTRICTABLE_MODES.contains(&mode.as_str()) {
+                problems.push(format!(
+                    "allowed_working_modes: unknown mode '{}', expected one of {:?}", mode, RESTRICTABLE_MODES
+                ));
+            }
+        }
+
+        let profile_problems = self.profile_problems(&problems);
+        problems.extend(profile_problems);
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(problems)
+        }
+    }
+
+    /// Override settings from `EMS_<SETTING>` environment variables, e.g. `EMS_POLL_INTERVAL_SECONDS=10`.
+    /// Values are read as JSON, so numbers, booleans, lists and objects are written as in the file; a string
+    /// setting takes the value verbatim. A value that doesn't fit its setting is an error. The variables that
+    /// were applied are kept in `env_overrides`.
+    pub fn apply_env_overrides(&mut self) -> Result<(), ConfigError> {
+        let Ok(Value::Object(mut settings)) = serde_json::to_value(&*self) else {
+            return Ok(());
+        };
+        let mut overridden = Vec::new();
+        let keys = settings.keys().cloned().collect::<Vec<_>>();
+        for key in keys {
+            let var = format!("{}{}", ENV_PREFIX, key.to_uppercase());
+            let raw = match env::var(&var) {
+                Ok(raw)                        => raw,
+                Err(env::VarError::NotPresent) => continue,
+                Err(e)                         => return Err(ConfigError::Env(var, e.to_string())),
+            };
+            let value = if settings[&key].is_string() {
+                Value::from(raw)
+            } else {
+                serde_json::from_str(&raw).unwrap_or_else(|_| Value::from(raw))
+            };
+            settings.insert(key, value);
+            // Checked one at a time, so the error names the variable at fault.
+            if let Err(e) = serde_json::from_value::<Config>(Value::Object(settings.clone())) {
+                return Err(ConfigError::Env(var, e.to_string()));
+            }
+            overridden.push(var);
+        }
+        if !overridden.is_empty() {
+            *self = serde_json::from_value(Value::Object(settings))
+                .map_err(|e| ConfigError::Env(overridden.join(", "), e.to_string()))?;
+            self.env_overrides = overridden;
+        }
+        Ok(())
+    }
 }
 
 // --------------------------------------------------------------------------------------------------------------
 
-pub fn load_config() -> Config {
-    let config_file = "config.json";
-    let config_data = fs::read_to_string(config_file)
-        .expect("Failed to read configuration file");
-    serde_json::from_str(&config_data)
-        .expect("Failed to parse configuration file")
+const CONFIG_FILE: &str = "config.json";
+/// Prefix of the environment variables that override a setting: `EMS_` + the setting name in upper case.
+const ENV_PREFIX:  &str = "EMS_";
+
+/// Held by the tests that set `EMS_*` variables or load a config, as `apply_env_overrides` reads every one.
+#[cfg(test)]
+pub(crate) static ENV_LOCK: std::sync::Mutex<()> = std::sync::Mutex::new(());
+
+/// Why the configuration file could not be loaded.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConfigError {
+    /// The file doesn't exist: its path.
+    NotFound(String),
+    /// The file exists but could not be read: path + message.
+    Io(String, String),
+    /// The file isn't a valid configuration: path, serde message, line and column.
+    Parse(String, String, usize, usize),
+    /// The file parsed, but some settings are out of range: path + one line per problem.
+    Invalid(String, Vec<String>),
+    /// An `EMS_*` environment variable doesn't fit its setting: variable + message.
+    Env(String, String),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConfigError::NotFound(path)                  => write!(f, "{} not found", path),
+            ConfigError::Io(path, msg)                   => write!(f, "cannot read {}: {}", path, msg),
+            ConfigError::Parse(path, msg, line, column) => {
+                write!(f, "invalid {} at line {}, column {}: {}", path, line, column, msg)
+            }
+            ConfigError::Invalid(path, problems) => {
+                write!(f, "{} has {} invalid setting(s):", path, problems.len())?;
+                problems.iter().try_for_each(|problem| write!(f, "\n  - {}", problem))
+            }
+            ConfigError::Env(var, msg) => write!(f, "invalid environment variable {}: {}", var, msg),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl ConfigError {
+    fn read(path: &str, e: io::Error) -> Self {
+        match e.kind() {
+            io::ErrorKind::NotFound => ConfigError::NotFound(path.to_string()),
+            _                       => ConfigError::Io(path.to_string(), e.to_string()),
+        }
+    }
+
+    fn parse(path: &str, e: serde_json::Error) -> Self {
+        // serde_json appends the position to its message; it is kept in the line and column instead.
+        let position = format!(" at line {} column {}", e.line(), e.column());
+        let message  = e.to_string();
+        let message  = message.strip_suffix(&position).unwrap_or(&message).to_string();
+        ConfigError::Parse(path.to_string(), message, e.line(), e.column())
+    }
+}
+
+/// Load `config.json` from the working directory, apply the environment overrides and validate the result.
+pub fn load_config() -> Result<Config, ConfigError> {
+    load_config_from(CONFIG_FILE)
+}
+
+/// `load_config` from the file at `path`.
+pub fn load_config_from(path: &str) -> Result<Config, ConfigError> {
+    let config_data = fs::read_to_string(path).map_err(|e| ConfigError::read(path, e))?;
+    let mut config: Config = serde_json::from_str(&config_data).map_err(|e| ConfigError::parse(path, e))?;
+
+    config.apply_env_overrides()?;
+    config.validate().map_err(|problems| ConfigError::Invalid(path.to_string(), problems))?;
+    Ok(config)
+}
+
+/// `load_config`, falling back to the defaults when the file is missing or invalid.
+pub fn load_config_or_default() -> Config {
+    load_config().unwrap_or_else(|e| {
+        eprintln!("Configuration: {} - using the defaults", e);
+        Config::default()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `apply_env_overrides` on the defaults with `vars` set, removed again afterwards.
+    fn with_env(vars: &[(&str, &str)]) -> Result<Config, ConfigError> {
+        let _env = ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        for (var, value) in vars {
+            env::set_var(var, value);
+        }
+        let mut config = Config::default();
+        let result     = config.apply_env_overrides().map(|()| config);
+        for (var, _) in vars {
+            env::remove_var(var);
+        }
+        result
+    }
+
+    #[test]
+    fn calibrate_soc_applies_the_offset() {
+        let config = Config { soc_calibration_offset_percent: -3.0, ..Config::default() };
+        assert_eq!(config.calibrate_soc(85.0), 82.0);
+    }
+
+    #[test]
+    fn calibrate_soc_clamps_into_range() {
+        let config = Config::default();
+        assert_eq!(config.calibrate_soc(101.0), 100.0);
+        assert_eq!(config.calibrate_soc(-2.0), 0.0);
+
+        let config = Config { soc_clamp: [5.0, 95.0], soc_calibration_offset_percent: 2.0, ..Config::default() };
+        assert_eq!(config.calibrate_soc(94.0), 95.0);
+        assert_eq!(config.calibrate_soc(1.0), 5.0);
+    }
+
+    #[test]
+    fn malformed_urls_are_rejected() {
+        let config   = Config {
+            p1_url:       "192.168.1.20/api/v1/data".to_string(),
+            indevolt_url: "http://".to_string(),
+            ..Config::default()
+        };
+        let problems = config.validate().unwrap_err();
+        assert_eq!(problems.len(), 2, "{:?}", problems);
+        assert!(problems[0].starts_with("p1_url '192.168.1.20/api/v1/data' is not a valid URL"));
+        assert!(problems[1].starts_with("indevolt_url 'http://' is not a valid URL"));
+        assert!(Config::default().validate().is_ok());
+    }
+
+    #[test]
+    fn api_v2_needs_a_way_to_trust_the_meter_certificate() {
+        let problems = Config { p1_api_version: 2, ..Config::default() }.validate().unwrap_err();
+        assert!(problems.iter().any(|p| p.contains("p1_ca_cert_path")), "{:?}", problems);
+
+        let ca     = Some("ca.pem".to_string());
+        let pinned = Config { p1_api_version: 2, p1_ca_cert_path: ca, ..Config::default() };
+        assert!(pinned.validate().is_ok());
+    }
+
+    #[test]
+    fn soc_target_rounds_per_mode() {
+        let round = Config { battery_max_soc_percent: 95.0, ..Config::default() };
+        assert_eq!(round.soc_target(89.6), 90);
+        assert_eq!(round.soc_target(89.4), 89);
+
+        let floor = Config { soc_target_rounding: SocRounding::Floor, ..Config::default() };
+        assert_eq!(floor.soc_target(89.6), 89);
+        let ceil  = Config { soc_target_rounding: SocRounding::Ceil, ..Config::default() };
+        assert_eq!(ceil.soc_target(89.2), 90);
+    }
+
+    #[test]
+    fn soc_target_stays_within_the_limits_after_rounding() {
+        let config = Config {
+            battery_min_soc_percent: 10.4,
+            battery_max_soc_percent: 89.6,
+            soc_target_rounding:     SocRounding::Round,
+            ..Config::default()
+        };
+        assert_eq!(config.soc_target(89.6), 89);
+        assert_eq!(config.soc_target(10.4), 11);
+        assert_eq!(config.soc_target(120.0), 89);
+    }
+
+    #[test]
+    fn env_variables_override_settings_of_every_type() {
+        let config = with_env(&[
+            ("EMS_POLL_INTERVAL_SECONDS", "10"),
+            ("EMS_BATTERY_MIN_SOC_PERCENT", "15"),
+            ("EMS_GRID_CHARGING_ENABLED", "true"),
+            ("EMS_P1_URL", "http://192.168.1.20/api/v1/data"),
+            ("EMS_ALLOWED_WORKING_MODES", r#"["ChargingFromSolar"]"#),
+        ])
+        .unwrap();
+        assert_eq!(config.poll_interval_seconds, 10);
+        assert_eq!(config.battery_min_soc_percent, 15.0);
+        assert!(config.grid_charging_enabled);
+        assert_eq!(config.p1_url, "http://192.168.1.20/api/v1/data");
+        assert_eq!(config.allowed_working_modes, vec!["ChargingFromSolar".to_string()]);
+        assert_eq!(config.env_overrides.len(), 5);
+    }
+
+    #[test]
+    fn an_env_value_that_does_not_fit_is_an_error() {
+        let error = with_env(&[("EMS_RECENT_CYCLES", "ten")]).unwrap_err();
+        assert!(matches!(&error, ConfigError::Env(var, _) if var == "EMS_RECENT_CYCLES"), "{}", error);
+    }
+
+    #[test]
+    fn without_env_variables_nothing_changes() {
+        let config = with_env(&[]).unwrap();
+        assert!(config.env_overrides.is_empty());
+        assert_eq!(config.poll_interval_seconds, Config::default().poll_interval_seconds);
+    }
+
+    #[test]
+    fn measured_efficiencies_set_the_break_even_spread() {
+        // 0.95 × 0.90 = 0.855: a stored kWh must sell for 1 / 0.855 = 1.1696× what it cost.
+        let measured = Config { charge_efficiency: Some(0.95), ..Config::default() };
+        let config   = Config { discharge_efficiency: Some(0.90), ..measured.clone() };
+        assert_eq!(config.efficiencies(), (0.95, 0.90));
+        assert!((config.round_trip_efficiency() - 0.855).abs() < 1e-12);
+        assert!((config.break_even_spread_percent() - 16.959).abs() < 1e-3);
+
+        // Only one of the pair: the round-trip figure split evenly, √0.81 = 0.9 each way.
+        let config = Config { battery_round_trip_efficiency: 0.81, ..measured };
+        assert_eq!(config.efficiencies(), (0.9, 0.9));
+        assert!((config.break_even_spread_percent() - 23.457).abs() < 1e-3);
+    }
 }
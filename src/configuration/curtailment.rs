@@ -0,0 +1,84 @@
+use chrono::NaiveTime;
+use serde::{Deserialize, Serialize};
+
+use super::config::Config;
+use super::floor_schedule::in_window;
+
+// --------------------------------------------------------------------------------------------------------------
+// Feed-in curtailment
+//
+// Some grid connections are asked not to export at certain times. During a `curtailment_windows` window the
+// battery absorbs every watt of solar surplus it can instead of letting it flow to the grid:
+//
+//   "curtailment_windows": [
+//       { "from": "11:00", "to": "16:00" }
+//   ],
+//   "curtailment_max_soc_percent": 100.0
+//
+// While curtailed, the surplus charge thresholds drop to 0 W and the charge ceiling is raised to
+// `curtailment_max_soc_percent`, so even a small surplus is stored. Outside the windows nothing changes.
+// The device's MaxSOC is reconciled with the configured `battery_max_soc_percent`, not with this ceiling, and
+// the ceiling itself never goes past the device's MaxSOC (see `soc_limits.rs`).
+// Windows are [from, to) in local time and may wrap midnight, like the discharge floor schedule.
+// --------------------------------------------------------------------------------------------------------------
+
+/// One local-time window during which feed-in is curtailed.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct CurtailmentWindow {
+    pub from: NaiveTime,
+    pub to:   NaiveTime,
+}
+
+impl Config {
+    /// Whether feed-in is curtailed at local `time`.
+    pub fn feed_in_curtailed_at(&self, time: NaiveTime) -> bool {
+        self.curtailment_windows.iter().any(|w| in_window(w.from, w.to, time))
+    }
+
+    /// Switch these settings to curtailment: charge from any surplus, up to the curtailment ceiling.
+    pub fn apply_curtailment(&mut self) {
+        self.uncurtailed_max_soc_percent.get_or_insert(self.battery_max_soc_percent);
+        self.feed_in_curtailed          = true;
+        self.surplus_charge_threshold_w = 0;
+        self.surplus_charge_exit_w      = 0;
+        self.battery_max_soc_percent    = self.battery_max_soc_percent.max(self.curtailment_max_soc_percent);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::optimiser::decision::Decision;
+    use crate::optimiser::self_consumption;
+    use crate::optimiser::testing::{battery, grid};
+
+    fn config() -> Config {
+        Config {
+            curtailment_windows:        vec![CurtailmentWindow {
+                from: NaiveTime::from_hms_opt(11, 0, 0).unwrap(),
+                to:   NaiveTime::from_hms_opt(16, 0, 0).unwrap(),
+            }],
+            battery_max_soc_percent:    90.0,
+            surplus_charge_threshold_w: 500,
+            ..Config::default()
+        }
+    }
+
+    #[test]
+    fn a_curtailment_window_stores_the_surplus_instead_of_exporting_it() {
+        let noon    = config().for_time(NaiveTime::from_hms_opt(12, 0, 0).unwrap());
+        let verdict = self_consumption::decide(&grid(-3000), &battery(92.0, 0), &noon);
+        assert_eq!(verdict.decision, Decision::ChargingFromSolar { watts: 2400, max_soc_percent: 100 });
+
+        let small = self_consumption::decide(&grid(-200), &battery(50.0, 0), &noon);
+        assert_eq!(small.decision, Decision::ChargingFromSolar { watts: 200, max_soc_percent: 100 });
+    }
+
+    #[test]
+    fn outside_the_window_nothing_changes() {
+        let evening = config().for_time(NaiveTime::from_hms_opt(17, 0, 0).unwrap());
+        assert!(!evening.feed_in_curtailed);
+        assert_eq!(self_consumption::decide(&grid(-3000), &battery(92.0, 0), &evening).decision, Decision::Idle);
+        assert_eq!(self_consumption::decide(&grid(-200), &battery(50.0, 0), &evening).decision, Decision::Idle);
+    }
+}
@@ -0,0 +1,114 @@
+use log::info;
+use serde_json::Value;
+
+use super::config::Config;
+
+// --------------------------------------------------------------------------------------------------------------
+// Effective configuration
+//
+// What is actually in force - the file with every default filled in - for the start-up log and the `config`
+// subcommand. Secrets are masked wherever they appear (including inside profiles): any non-empty string under
+// a key that names a token, password or secret becomes "***".
+// --------------------------------------------------------------------------------------------------------------
+
+const SECRET_MARKERS: &[&str] = &["token", "password", "secret"];
+
+fn is_secret(key: &str) -> bool {
+    SECRET_MARKERS.iter().any(|marker| key.contains(marker))
+}
+
+fn redact(value: &mut Value) {
+    match value {
+        Value::Object(map) => {
+            for (key, v) in map.iter_mut() {
+                if is_secret(key) && v.as_str().is_some_and(|s| !s.is_empty()) {
+                    *v = Value::from("***");
+                } else {
+                    redact(v);
+                }
+            }
+        }
+        Value::Array(items) => items.iter_mut().for_each(redact),
+        _ => {}
+    }
+}
+
+/// The configuration as JSON, secrets masked.
+pub fn redacted(config: &Config) -> Value {
+    let mut value = serde_json::to_value(config).unwrap_or_default();
+    redact(&mut value);
+    value
+}
+
+/// Log every effective setting at info level, one `key = value` line each, secrets masked.
+pub fn log_effective_config(config: &Config) {
+    let Value::Object(settings) = redacted(config) else {
+        return;
+    };
+    info!("[Config] Effective configuration ({} settings):", settings.len());
+    if !config.env_overrides.is_empty() {
+        info!("[Config]   overridden from the environment: {}", config.env_overrides.join(", "));
+    }
+    for (key, value) in &settings {
+        info!("[Config]   {:<40} = {}", key, value);
+    }
+}
+
+/// Log every setting that differs between `old` and `new` as `key: old -> new`, secrets masked. Returns how
+/// many changed.
+pub fn log_changed_settings(old: &Config, new: &Config) -> usize {
+    // Compared unmasked, so a changed secret still counts; shown masked.
+    let raw = (serde_json::to_value(old).unwrap_or_default(), serde_json::to_value(new).unwrap_or_default());
+    let (Value::Object(old_raw), Value::Object(new_raw)) = raw else {
+        return 0;
+    };
+    let (old_shown, new_shown) = (redacted(old), redacted(new));
+    let mut changed = 0;
+    for (key, value) in &new_raw {
+        if old_raw.get(key) == Some(value) {
+            continue;
+        }
+        info!("[Config]   {:<40} : {} -> {}", key, old_shown[key.as_str()], new_shown[key.as_str()]);
+        changed += 1;
+    }
+    changed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runtime::log_capture::capture;
+    use serde_json::json;
+
+    fn secret_config() -> Config {
+        Config {
+            p1_api_token:   "p1-secret-token".to_string(),
+            influxdb_token: "influx-secret-token".to_string(),
+            tibber_token:   "tibber-secret-token".to_string(),
+            ..Config::default()
+        }
+    }
+
+    #[test]
+    fn tokens_are_masked_and_empty_ones_left_alone() {
+        let shown = redacted(&secret_config());
+        assert_eq!(shown["p1_api_token"], "***");
+        assert_eq!(shown["influxdb_token"], "***");
+        assert_eq!(shown["entsoe_token"], "");
+        assert_eq!(shown["influxdb_url"], Value::from(Config::default().influxdb_url));
+    }
+
+    #[test]
+    fn passwords_are_masked_at_any_depth() {
+        let mut value = json!({"mqtt": {"password": "hunter2", "user": "ems"}, "list": [{"api_secret": "s3"}]});
+        redact(&mut value);
+        assert_eq!(value, json!({"mqtt": {"password": "***", "user": "ems"}, "list": [{"api_secret": "***"}]}));
+    }
+
+    #[test]
+    fn the_startup_log_never_shows_a_secret() {
+        let ((), lines) = capture(|| log_effective_config(&secret_config()));
+        assert!(lines.iter().any(|(_, line)| line.contains("p1_api_token") && line.contains("\"***\"")));
+        assert!(lines.iter().all(|(_, line)| !line.contains("secret-token")), "{lines:?}");
+    }
+}
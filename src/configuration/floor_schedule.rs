@@ -0,0 +1,107 @@
+use chrono::NaiveTime;
+use serde::{Deserialize, Serialize};
+
+use super::config::Config;
+
+// --------------------------------------------------------------------------------------------------------------
+// Time-of-day discharge floor
+//
+// `discharge_floor_schedule` replaces the single `peak_shaving_reserve_soc_percent` (the SOC the battery covers
+// household load down to) with a value per local-time window, e.g. a low floor overnight so the battery keeps
+// working, and a higher one in the evening so there is charge left for the morning:
+//
+//   "discharge_floor_schedule": [
+//       { "from": "22:00", "to": "06:00", "floor_percent": 15.0 },
+//       { "from": "17:00", "to": "22:00", "floor_percent": 40.0 }
+//   ]
+//
+// Windows are [from, to) and may wrap midnight; the first matching window wins, outside all windows the
+// configured reserve applies. `battery_min_soc_percent` stays the absolute backstop whatever the schedule says.
+// --------------------------------------------------------------------------------------------------------------
+
+/// One local-time window and the discharge floor that applies during it.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct FloorWindow {
+    pub from:          NaiveTime,
+    pub to:            NaiveTime,
+    pub floor_percent: f64,
+}
+
+impl FloorWindow {
+    pub fn contains(&self, time: NaiveTime) -> bool {
+        in_window(self.from, self.to, time)
+    }
+}
+
+/// Whether `time` falls in the local-time window [from, to), which wraps midnight when `to` < `from`.
+pub(crate) fn in_window(from: NaiveTime, to: NaiveTime, time: NaiveTime) -> bool {
+    if from <= to {
+        from <= time && time < to
+    } else {
+        time >= from || time < to
+    }
+}
+
+impl Config {
+    /// Discharge floor (SOC %) in force at local `time`, never below `battery_min_soc_percent`.
+    pub fn discharge_floor_at(&self, time: NaiveTime) -> f64 {
+        self.discharge_floor_schedule
+            .iter()
+            .find(|w| w.contains(time))
+            .map_or(self.peak_shaving_reserve_soc_percent, |w| w.floor_percent)
+            .max(self.battery_min_soc_percent)
+    }
+
+    /// Settings in force at local `time`: the reserve replaced by the scheduled discharge floor, and the
+    /// curtailment settings applied inside a curtailment window.
+    pub fn for_time(&self, time: NaiveTime) -> Config {
+        let mut effective = self.clone();
+        effective.peak_shaving_reserve_soc_percent = self.discharge_floor_at(time);
+        if self.feed_in_curtailed_at(time) {
+            effective.apply_curtailment();
+        }
+        effective
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(hour: u32, minute: u32) -> NaiveTime {
+        NaiveTime::from_hms_opt(hour, minute, 0).unwrap()
+    }
+
+    fn window(from: NaiveTime, to: NaiveTime, floor_percent: f64) -> FloorWindow {
+        FloorWindow { from, to, floor_percent }
+    }
+
+    fn config() -> Config {
+        Config {
+            discharge_floor_schedule: vec![
+                window(at(22, 0), at(6, 0), 15.0),
+                window(at(17, 0), at(22, 0), 40.0),
+            ],
+            ..Config::default()
+        }
+    }
+
+    #[test]
+    fn the_floor_follows_the_time_of_day() {
+        let config = config();
+        assert_eq!(config.discharge_floor_at(at(23, 30)), 15.0);
+        assert_eq!(config.discharge_floor_at(at(5, 59)), 15.0);
+        assert_eq!(config.discharge_floor_at(at(6, 0)), 20.0); // outside the windows: the reserve
+        assert_eq!(config.discharge_floor_at(at(17, 0)), 40.0);
+        assert_eq!(config.discharge_floor_at(at(22, 0)), 15.0);
+        assert_eq!(config.for_time(at(18, 0)).peak_shaving_reserve_soc_percent, 40.0);
+    }
+
+    #[test]
+    fn the_floor_never_drops_below_the_hard_minimum() {
+        let config = Config { battery_min_soc_percent: 25.0, ..config() };
+        assert_eq!(config.discharge_floor_at(at(2, 0)), 25.0);
+        assert_eq!(config.discharge_floor_at(at(12, 0)), 25.0);
+        assert_eq!(config.discharge_floor_at(at(19, 0)), 40.0);
+    }
+}
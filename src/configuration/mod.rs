@@ -1 +1,6 @@
 pub mod config;
+pub mod profiles;
+pub mod floor_schedule;
+pub mod curtailment;
+pub mod soc_limits;
+pub mod effective;
@@ -0,0 +1,215 @@
+use chrono::{Datelike, NaiveDate, Weekday};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+
+use crate::optimiser::pre_peak::PeakWindow;
+use super::config::Config;
+use super::curtailment::CurtailmentWindow;
+use super::floor_schedule::FloorWindow;
+
+// --------------------------------------------------------------------------------------------------------------
+// Calendar profiles
+//
+// A profile overrides a subset of the battery/optimiser settings on the days it selects. Selection is by day
+// type (weekday/weekend), by an inclusive month range, or both. Example:
+//
+//   "profiles": {
+//       "weekend": { "days": "weekend", "battery_min_soc_percent": 20.0, "grid_charging_enabled": false },
+//       "winter":  { "months": [11, 2], "battery_max_desired_grid_peak_w": 3000,
+//                    "pre_peak_window": { "from": "17:00", "to": "20:00" } }
+//   }
+//
+// Besides the limits, a profile can switch grid charging and replace the time windows (discharge floor
+// schedule, curtailment windows, pre-peak window) as a whole. Exactly one profile must match for it to be
+// applied. When none match, or several overlap, the top-level settings are used unchanged; the overlap is
+// reported when the selection changes, not every cycle. Each profile merged onto the top-level settings is
+// validated with them at load.
+// --------------------------------------------------------------------------------------------------------------
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum DaySelector {
+    /// Monday to Friday.
+    Weekday,
+    /// Saturday and Sunday.
+    Weekend,
+}
+
+/// One calendar profile: a selector plus the settings it overrides.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct Profile {
+    /// Day type this profile applies to.
+    pub days: Option<DaySelector>,
+    /// Inclusive month range [from, to] (1-12). May wrap the year end, e.g. [11, 2] = Nov-Feb.
+    pub months: Option<[u32; 2]>,
+
+    pub battery_min_soc_percent:          Option<f64>,
+    pub battery_max_soc_percent:          Option<f64>,
+    pub battery_max_charge_power_w:       Option<i32>,
+    pub battery_max_discharge_power_w:    Option<i32>,
+    pub battery_max_desired_grid_peak_w:  Option<i32>,
+    pub battery_min_price_spread_percent: Option<f64>,
+    pub peak_shaving_reserve_soc_percent: Option<f64>,
+    pub grid_charging_enabled:            Option<bool>,
+    pub discharge_floor_schedule:         Option<Vec<FloorWindow>>,
+    pub curtailment_windows:              Option<Vec<CurtailmentWindow>>,
+    pub pre_peak_window:                  Option<PeakWindow>,
+}
+
+impl Profile {
+    /// True when every selector that is set matches `date`. A profile without any selector never matches.
+    pub fn matches(&self, date: NaiveDate) -> bool {
+        if self.days.is_none() && self.months.is_none() {
+            return false;
+        }
+
+        let day_ok = match self.days {
+            None => true,
+            Some(DaySelector::Weekend) => matches!(date.weekday(), Weekday::Sat | Weekday::Sun),
+            Some(DaySelector::Weekday) => !matches!(date.weekday(), Weekday::Sat | Weekday::Sun),
+        };
+
+        let month_ok = match self.months {
+            None => true,
+            Some([from, to]) => {
+                let m = date.month();
+                if from <= to { (from..=to).contains(&m) } else { m >= from || m <= to }
+            }
+        };
+
+        day_ok && month_ok
+    }
+
+    /// Write this profile's overrides into `config`.
+    fn apply(&self, config: &mut Config) {
+        if let Some(v) = self.battery_min_soc_percent          { config.battery_min_soc_percent = v; }
+        if let Some(v) = self.battery_max_soc_percent          { config.battery_max_soc_percent = v; }
+        if let Some(v) = self.battery_max_charge_power_w       { config.battery_max_charge_power_w = v; }
+        if let Some(v) = self.battery_max_discharge_power_w    { config.battery_max_discharge_power_w = v; }
+        if let Some(v) = self.battery_max_desired_grid_peak_w  { config.battery_max_desired_grid_peak_w = v; }
+        if let Some(v) = self.battery_min_price_spread_percent { config.battery_min_price_spread_percent = v; }
+        if let Some(v) = self.peak_shaving_reserve_soc_percent { config.peak_shaving_reserve_soc_percent = v; }
+        if let Some(v) = self.grid_charging_enabled            { config.grid_charging_enabled = v; }
+        if let Some(v) = &self.discharge_floor_schedule        { config.discharge_floor_schedule = v.clone(); }
+        if let Some(v) = &self.curtailment_windows             { config.curtailment_windows = v.clone(); }
+        if let Some(v) = self.pre_peak_window                  { config.pre_peak_window = Some(v); }
+    }
+}
+
+/// Which settings are in force on a date.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum ActiveProfile {
+    /// No profile matches: the top-level settings.
+    #[default]
+    Default,
+    Named(String),
+    /// Several profiles match; the top-level settings are used.
+    Overlap(Vec<String>),
+}
+
+impl fmt::Display for ActiveProfile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ActiveProfile::Default       => write!(f, "default"),
+            ActiveProfile::Named(name)   => write!(f, "{}", name),
+            ActiveProfile::Overlap(many) => write!(f, "default (profiles {} overlap)", many.join(", ")),
+        }
+    }
+}
+
+/// The profile selection for `date`: the single matching profile, if exactly one does.
+pub fn select_profile(profiles: &BTreeMap<String, Profile>, date: NaiveDate) -> ActiveProfile {
+    let mut matching: Vec<String> = profiles
+        .iter()
+        .filter(|(_, p)| p.matches(date))
+        .map(|(name, _)| name.clone())
+        .collect();
+
+    match matching.len() {
+        0 => ActiveProfile::Default,
+        1 => ActiveProfile::Named(matching.remove(0)),
+        _ => ActiveProfile::Overlap(matching),
+    }
+}
+
+impl Config {
+    /// Settings in force on `date`: the top-level config with the matching profile applied, and which
+    /// profile that was.
+    pub fn for_date(&self, date: NaiveDate) -> (Config, ActiveProfile) {
+        let mut effective = self.clone();
+        let active = select_profile(&self.profiles, date);
+        if let ActiveProfile::Named(name) = &active {
+            self.profiles[name].apply(&mut effective);
+        }
+        (effective, active)
+    }
+
+    /// Problems of each profile merged onto these settings that the settings alone don't have.
+    pub(crate) fn profile_problems(&self, own: &[String]) -> Vec<String> {
+        let mut problems = Vec::new();
+        for (name, profile) in &self.profiles {
+            let mut merged = self.clone();
+            merged.profiles.clear();
+            profile.apply(&mut merged);
+            if let Err(merged_problems) = merged.validate() {
+                let new = merged_problems.into_iter().filter(|p| !own.contains(p));
+                problems.extend(new.map(|p| format!("profile '{}': {}", name, p)));
+            }
+        }
+        problems
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(profiles: &str) -> Config {
+        Config { profiles: serde_json::from_str(profiles).unwrap(), ..Config::default() }
+    }
+
+    const WEEK: &str = r#"{
+        "weekday": { "days": "weekday", "battery_min_soc_percent": 15.0 },
+        "weekend": { "days": "weekend", "battery_min_soc_percent": 30.0, "grid_charging_enabled": true }
+    }"#;
+
+    #[test]
+    fn saturday_selects_the_weekend_profile_and_tuesday_the_weekday_one() {
+        let config   = config(WEEK);
+        let saturday = NaiveDate::from_ymd_opt(2024, 6, 1).unwrap();
+        let tuesday  = NaiveDate::from_ymd_opt(2024, 6, 4).unwrap();
+
+        let (weekend, active) = config.for_date(saturday);
+        assert_eq!(active, ActiveProfile::Named("weekend".to_string()));
+        assert_eq!(weekend.battery_min_soc_percent, 30.0);
+        assert!(weekend.grid_charging_enabled);
+
+        let (weekday, active) = config.for_date(tuesday);
+        assert_eq!(active, ActiveProfile::Named("weekday".to_string()));
+        assert_eq!(weekday.battery_min_soc_percent, 15.0);
+        assert!(!weekday.grid_charging_enabled);
+    }
+
+    #[test]
+    fn overlapping_profiles_fall_back_to_the_defaults() {
+        let config = config(r#"{
+            "weekend": { "days": "weekend", "battery_min_soc_percent": 30.0 },
+            "summer":  { "months": [6, 8], "battery_min_soc_percent": 5.0 }
+        }"#);
+        let (effective, active) = config.for_date(NaiveDate::from_ymd_opt(2024, 6, 1).unwrap());
+
+        assert_eq!(active, ActiveProfile::Overlap(vec!["summer".to_string(), "weekend".to_string()]));
+        assert_eq!(effective.battery_min_soc_percent, 10.0);
+    }
+
+    #[test]
+    fn a_profile_that_breaks_the_settings_is_invalid() {
+        assert!(config(WEEK).validate().is_ok());
+
+        let invalid = config(r#"{ "weekend": { "days": "weekend", "battery_min_soc_percent": 100.0 } }"#);
+        let problems = invalid.validate().unwrap_err();
+        assert!(problems.iter().all(|p| p.starts_with("profile 'weekend': ")), "{:?}", problems);
+    }
+}
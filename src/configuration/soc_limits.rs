@@ -0,0 +1,142 @@
+use std::fmt;
+
+use super::config::Config;
+
+// --------------------------------------------------------------------------------------------------------------
+// Config vs device SOC limits
+//
+// The device has its own MinSOC / MaxSOC settings (set in the Indevolt app) and rejects commands past them, so a
+// config `battery_max_soc_percent` of 100 against a device MaxSOC of 95 would have every full charge refused.
+// When the firmware exposes the device limits (`device_min_soc_sensor_id` / `device_max_soc_sensor_id`), they
+// are reconciled with the config every cycle:
+//   - by default the more conservative value wins: the lower MaxSOC and the higher MinSOC;
+//   - with `trust_config_soc_limits` the config values stay in force.
+// Either way every disagreement is logged as a warning once. The last values the device reported are kept for
+// cycles in which they weren't read.
+// During feed-in curtailment the device's MaxSOC is compared with the configured maximum, not the curtailment
+// ceiling, so a curtailment window doesn't raise a disagreement of its own; the ceiling is then capped at the
+// device's MaxSOC, which the device would otherwise refuse.
+// --------------------------------------------------------------------------------------------------------------
+
+/// Values closer than this (%) are the same limit (the device reports whole percent).
+const SAME_LIMIT_PERCENT: f64 = 0.5;
+
+/// A SOC limit on which the config and the device disagree.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SocLimitMismatch {
+    /// "MinSOC" or "MaxSOC".
+    pub limit:   &'static str,
+    pub config:  f64,
+    pub device:  f64,
+    /// The value in force.
+    pub applied: f64,
+}
+
+impl fmt::Display for SocLimitMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}: config {:.0}% vs device {:.0}% - using {:.0}%",
+            self.limit, self.config, self.device, self.applied
+        )
+    }
+}
+
+impl Config {
+    /// These settings with the device's MinSOC / MaxSOC (%) reconciled into `battery_min_soc_percent` and
+    /// `battery_max_soc_percent`, plus the limits on which they disagree.
+    pub fn with_device_soc_limits(
+        &self,
+        device_min: Option<f64>,
+        device_max: Option<f64>,
+    ) -> (Config, Vec<SocLimitMismatch>) {
+        let mut effective  = self.clone();
+        let mut mismatches = Vec::new();
+        let differs = |device: &f64, config: f64| (device - config).abs() >= SAME_LIMIT_PERCENT;
+
+        if let Some(device) = device_min.filter(|d| differs(d, self.battery_min_soc_percent)) {
+            let config  = self.battery_min_soc_percent;
+            let applied = if self.trust_config_soc_limits { config } else { config.max(device) };
+            effective.battery_min_soc_percent = applied;
+            mismatches.push(SocLimitMismatch { limit: "MinSOC", config, device, applied });
+        }
+        let configured_max  = self.uncurtailed_max_soc_percent.unwrap_or(self.battery_max_soc_percent);
+        let mut applied_max = configured_max;
+        if let Some(device) = device_max.filter(|d| differs(d, configured_max)) {
+            let config  = configured_max;
+            applied_max = if self.trust_config_soc_limits { config } else { config.min(device) };
+            mismatches.push(SocLimitMismatch { limit: "MaxSOC", config, device, applied: applied_max });
+        }
+        effective.battery_max_soc_percent = if self.feed_in_curtailed {
+            let ceiling = self.curtailment_max_soc_percent;
+            applied_max.max(device_max.map_or(ceiling, |device| ceiling.min(device)))
+        } else {
+            applied_max
+        };
+        (effective, mismatches)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::optimiser::decision::Decision;
+    use crate::optimiser::self_consumption;
+    use crate::optimiser::testing::{battery, grid};
+
+    fn curtailed(max_soc: f64) -> Config {
+        let mut config = Config { battery_max_soc_percent: max_soc, ..Config::default() };
+        config.apply_curtailment();
+        config
+    }
+
+    #[test]
+    fn the_more_conservative_limit_wins() {
+        let config = Config::default();
+        let (effective, mismatches) = config.with_device_soc_limits(Some(15.0), Some(95.0));
+        assert_eq!((effective.battery_min_soc_percent, effective.battery_max_soc_percent), (15.0, 95.0));
+        assert_eq!(mismatches.len(), 2);
+
+        let trusted = Config { trust_config_soc_limits: true, ..Config::default() };
+        let (effective, mismatches) = trusted.with_device_soc_limits(Some(15.0), Some(95.0));
+        assert_eq!(effective.battery_max_soc_percent, 100.0);
+        assert_eq!(mismatches[1].applied, 100.0);
+    }
+
+    #[test]
+    fn a_device_max_soc_of_95_caps_a_charge_target_of_100() {
+        let surplus_charge = |config: &Config| {
+            let (effective, _) = config.with_device_soc_limits(None, Some(95.0));
+            self_consumption::decide(&grid(-1500), &battery(80.0, 0), &effective).decision
+        };
+        let expected = |max_soc_percent| Decision::ChargingFromSolar { watts: 1500, max_soc_percent };
+        assert_eq!(surplus_charge(&Config::default()), expected(95));
+        let trusted  = Config { trust_config_soc_limits: true, ..Config::default() };
+        assert_eq!(surplus_charge(&trusted), expected(100));
+    }
+
+    #[test]
+    fn matching_limits_are_not_a_disagreement() {
+        let (effective, mismatches) = Config::default().with_device_soc_limits(Some(10.0), Some(99.6));
+        assert!(mismatches.is_empty());
+        assert_eq!(effective.battery_max_soc_percent, 100.0);
+    }
+
+    #[test]
+    fn curtailment_is_not_a_disagreement_with_the_device() {
+        let (effective, mismatches) = curtailed(95.0).with_device_soc_limits(None, Some(95.0));
+        assert!(mismatches.is_empty(), "{:?}", mismatches);
+        assert_eq!(effective.battery_max_soc_percent, 95.0);
+    }
+
+    #[test]
+    fn the_curtailment_ceiling_stops_at_the_device_maximum() {
+        let (effective, mismatches) = curtailed(90.0).with_device_soc_limits(None, Some(98.0));
+        assert_eq!(effective.battery_max_soc_percent, 98.0);
+        let expected = SocLimitMismatch { limit: "MaxSOC", config: 90.0, device: 98.0, applied: 90.0 };
+        assert_eq!(mismatches, vec![expected]);
+
+        let (effective, _) = curtailed(90.0).with_device_soc_limits(None, None);
+        assert_eq!(effective.battery_max_soc_percent, 100.0);
+    }
+}
@@ -0,0 +1,173 @@
+use log::{debug, info, warn};
+use std::time::Duration;
+
+// --------------------------------------------------------------------------------------------------------------
+// External on/off signals
+//
+// Lets another system (home automation, a grid-signal listener, the EV charger) steer the EMS at runtime. Each
+// cycle the loop reads every configured signal from its URL (GET) or, when no URL is set, from its file. The
+// body or file content - "allow"/"deny", "true"/"false", "on"/"off" or "1"/"0", case-insensitive - is the
+// signal's value for that cycle. Two signals exist:
+//
+//   grid-charge gate     `grid_charge_gate_url` / `_file`   deny switches grid charging off; it can only take
+//                                                            away what the config allows
+//   EV charging signal   `ev_charging_url` / `_file`        on pauses battery discharge except for peak shaving
+//                                                            (see `optimiser/ev.rs`)
+//
+// When the grid-charge gate can't be read or says something else, `grid_charge_gate_on_error` decides: "deny"
+// (default, fail safe), "allow", or "last" to keep the last value read (deny if there is none yet). An
+// unreadable EV signal keeps its last value, off before the first read.
+// --------------------------------------------------------------------------------------------------------------
+
+/// Longest the loop waits for a signal URL.
+const GATE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Whether `text` means on (allow).
+pub fn parse_gate(text: &str) -> Result<bool, String> {
+    match text.trim().to_ascii_lowercase().as_str() {
+        "allow" | "true" | "on" | "1" => Ok(true),
+        "deny" | "false" | "off" | "0" => Ok(false),
+        other => Err(format!("unrecognised gate value '{}'", other)),
+    }
+}
+
+/// Read a signal from `url`, or else from `file`; `None` when neither is configured.
+pub async fn read_gate(client: &reqwest::Client, url: &str, file: &str) -> Option<Result<bool, String>> {
+    let text = if !url.is_empty() {
+        let response = client.get(url).timeout(GATE_TIMEOUT).send().await.and_then(|r| r.error_for_status());
+        match response {
+            Ok(response) => response.text().await.map_err(|e| format!("{}: {}", url, e)),
+            Err(e)       => Err(format!("{}: {}", url, e)),
+        }
+    } else if !file.is_empty() {
+        tokio::fs::read_to_string(file).await.map_err(|e| format!("{}: {}", file, e))
+    } else {
+        return None;
+    };
+    Some(text.and_then(|text| parse_gate(&text)))
+}
+
+/// The external signals the loop reads.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Signal {
+    GridChargeGate,
+    EvCharging,
+}
+
+impl Signal {
+    fn name(self) -> &'static str {
+        match self {
+            Signal::GridChargeGate => "grid-charge gate",
+            Signal::EvCharging     => "EV charging signal",
+        }
+    }
+
+    /// What `on` means for the EMS, for the log.
+    fn describe(self, on: bool) -> &'static str {
+        match (self, on) {
+            (Signal::GridChargeGate, true)  => "grid charging allowed",
+            (Signal::GridChargeGate, false) => "grid charging denied",
+            (Signal::EvCharging, true)      => "EV charging - battery discharge paused except for peak shaving",
+            (Signal::EvCharging, false)     => "EV not charging",
+        }
+    }
+}
+
+/// Last value read from one signal and whether reading it is currently failing.
+#[derive(Debug, Default)]
+pub struct GateState {
+    last:    Option<bool>,
+    failing: bool,
+}
+
+impl GateState {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The value of `signal` this cycle, given this cycle's read; `None` when the signal isn't configured.
+    /// On a failed read `fallback` is used, or the last value read when it is `None` (off before the first).
+    pub fn resolve(
+        &mut self,
+        signal: Signal,
+        read: Option<Result<bool, String>>,
+        fallback: Option<bool>,
+    ) -> Option<bool> {
+        match read? {
+            Ok(on) => {
+                if self.failing {
+                    info!("[Gate] {} readable again", signal.name());
+                    self.failing = false;
+                }
+                if self.last != Some(on) {
+                    info!("[Gate] {}: {}", signal.name(), signal.describe(on));
+                }
+                self.last = Some(on);
+                Some(on)
+            }
+            Err(e) => {
+                let on = fallback.or(self.last).unwrap_or(false);
+                if !self.failing {
+                    warn!("[Gate] Cannot read the {} ({}) - {}", signal.name(), e, signal.describe(on));
+                    self.failing = true;
+                } else {
+                    debug!("[Gate] {} still unreadable ({}) - {}", signal.name(), e, signal.describe(on));
+                }
+                Some(on)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::handlers::indevolt::testing::MockIndevolt;
+
+    #[test]
+    fn gate_values_parse_case_insensitively() {
+        for allow in ["allow", "TRUE", " on\n", "1"] {
+            assert_eq!(parse_gate(allow), Ok(true), "{allow}");
+        }
+        for deny in ["Deny", "false", "OFF", "0\n"] {
+            assert_eq!(parse_gate(deny), Ok(false), "{deny}");
+        }
+        assert!(parse_gate("maybe").is_err());
+    }
+
+    #[tokio::test]
+    async fn a_gate_url_saying_deny_or_allow_is_read() {
+        let client = reqwest::Client::new();
+        let deny   = MockIndevolt::start(|_| (200, "deny\n".to_string())).await;
+        let allow  = MockIndevolt::start(|_| (200, "allow".to_string())).await;
+        assert_eq!(read_gate(&client, &deny.base_url, "").await, Some(Ok(false)));
+        assert_eq!(read_gate(&client, &allow.base_url, "").await, Some(Ok(true)));
+        assert_eq!(read_gate(&client, "", "").await, None);
+
+        let broken = MockIndevolt::start(|_| (500, "allow".to_string())).await;
+        assert!(read_gate(&client, &broken.base_url, "").await.unwrap().is_err());
+    }
+
+    #[tokio::test]
+    async fn a_gate_file_is_read_when_no_url_is_set() {
+        let path = std::env::temp_dir().join(format!("ems-gate-{}", std::process::id()));
+        std::fs::write(&path, "off").unwrap();
+        let read = read_gate(&reqwest::Client::new(), "", path.to_str().unwrap()).await;
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(read, Some(Ok(false)));
+    }
+
+    #[test]
+    fn an_unreadable_gate_uses_the_fallback_or_the_last_value() {
+        let mut fail_safe = GateState::new();
+        assert_eq!(fail_safe.resolve(Signal::GridChargeGate, Some(Ok(true)), Some(false)), Some(true));
+        let error = Some(Err("timeout".to_string()));
+        assert_eq!(fail_safe.resolve(Signal::GridChargeGate, error.clone(), Some(false)), Some(false));
+
+        let mut last = GateState::new();
+        assert_eq!(last.resolve(Signal::GridChargeGate, error.clone(), None), Some(false));
+        last.resolve(Signal::GridChargeGate, Some(Ok(true)), None);
+        assert_eq!(last.resolve(Signal::GridChargeGate, error, None), Some(true));
+        assert_eq!(last.resolve(Signal::GridChargeGate, None, None), None);
+    }
+}
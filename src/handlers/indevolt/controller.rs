@@ -1,23 +1,16 @@
 use log::info;
 use reqwest::Client;
+use tracing::Instrument;
 
-use crate::models::indevolt_models::{SetDataConfig, WorkingMode};
+use crate::models::indevolt_models::{SetDataConfig, SetDataResponse, WorkingMode};
+use super::registers::{RegisterWrite, ACTION_STOP, REG_WORKING_MODE};
 
 // --------------------------------------------------------------------------------------------------------------
-// Register addresses
-const REG_WORKING_MODE: u32 = 47005; // set working mode (1=Self-consumed, 4=Realtime, 5=Schedule)
-const REG_CONTROL:      u32 = 47015; // real-time charge/discharge commands
-const FUNC_WRITE:       u32 = 16;    // Modbus function 16 (write multiple registers)
 
-// v[0] action codes for REG_CONTROL
-const ACTION_STOP:      i64 = 0;
-const ACTION_CHARGE:    i64 = 1;
-const ACTION_DISCHARGE: i64 = 2;
-
-// --------------------------------------------------------------------------------------------------------------
-
-/// Send a SetData command via GET /rpc/Indevolt.SetData?config=<json>.
-async fn send_command(client: &Client, base_url: &str, cfg: &SetDataConfig) -> Result<(), String> {
+/// Send a SetData command via GET /rpc/Indevolt.SetData?config=<json>. A 200 whose body says the write was
+/// not applied is an error too, and so is a body that can't be parsed: the write is then unconfirmed.
+async fn send_command(client: &Client, base_url: &str, write: RegisterWrite) -> Result<(), String> {
+    let cfg        = &SetDataConfig::from(write);
     let url        = format!("{}/rpc/Indevolt.SetData", base_url);
     let config_str = serde_json::to_string(cfg)
         .map_err(|e| format!("[Indevolt] Failed to serialise SetData config: {}", e))?;
@@ -29,69 +22,61 @@ async fn send_command(client: &Client, base_url: &str, cfg: &SetDataConfig) -> R
     let response: reqwest::Response = client
         .get(req_url)
         .send()
+        .instrument(tracing::info_span!("indevolt_set_data", register = cfg.t))
         .await
         .map_err(|e| format!("[Indevolt] HTTP error sending SetData {:?}: {}", cfg, e))?;
 
-    if response.status().is_success() {
-        info!("[Indevolt] SetData accepted: t={} v={:?}", cfg.t, cfg.v);
-        Ok(())
-    } else {
-        let status = response.status();
-        let body   = response.text().await.unwrap_or_default();
-        Err(format!("[Indevolt] SetData rejected (HTTP {}): {}", status, body))
+    let status = response.status();
+    let body   = response.text().await.unwrap_or_default();
+    if !status.is_success() {
+        return Err(format!("[Indevolt] SetData rejected (HTTP {}): {}", status, body));
     }
+    let Some(answer) = SetDataResponse::parse(&body) else {
+        return Err(format!(
+            "[Indevolt] SetData unconfirmed: t={} v={:?}, response '{}' not understood",
+            cfg.t, cfg.v, body.trim()
+        ));
+    };
+    answer
+        .applied()
+        .map_err(|e| format!("[Indevolt] SetData not applied: t={} v={:?}: {}", cfg.t, cfg.v, e))?;
+    let result = answer.result.map_or("none".to_string(), |result| result.to_string());
+    info!("[Indevolt] SetData applied: t={} v={:?} (result={})", cfg.t, cfg.v, result);
+    Ok(())
 }
 
 // --------------------------------------------------------------------------------------------------------------
 
-/// Set the working mode (register 47005).
+/// Set the working mode (`REG_WORKING_MODE`).
 /// Call with `RealtimeControl` before issuing charge/discharge commands.
 /// Call with `SelfConsumedPrioritized` to hand back control to the device.
 pub async fn set_working_mode(base_url: &str, mode: WorkingMode) -> Result<(), String> {
     let client = Client::new();
     let value  = mode.register_value();
-    let cfg    = SetDataConfig { f: FUNC_WRITE, t: REG_WORKING_MODE, v: vec![value] };
     info!("[Indevolt] Set working mode → {} (reg={} v={})", mode.as_str(), REG_WORKING_MODE, value);
-    send_command(&client, base_url, &cfg).await
-}
-
-/// Enable real-time control mode — convenience wrapper for
-/// `set_working_mode(RealtimeControl)`. Must be called before charge/discharge.
-pub async fn enable_realtime_mode(base_url: &str) -> Result<(), String> {
-    set_working_mode(base_url, WorkingMode::RealtimeControl).await
+    send_command(&client, base_url, RegisterWrite::working_mode(mode)).await
 }
 
 /// Charge the battery at the given power up to max_soc_percent.
 pub async fn charge(base_url: &str, watts: i32, max_soc_percent: u8) -> Result<(), String> {
     let client = Client::new();
-    let cfg = SetDataConfig {
-        f: FUNC_WRITE,
-        t: REG_CONTROL,
-        v: vec![ACTION_CHARGE, watts as i64, max_soc_percent as i64],
-    };
     info!("[Indevolt] Charge {} W up to {}% SOC", watts, max_soc_percent);
-    send_command(&client, base_url, &cfg).await
+    send_command(&client, base_url, RegisterWrite::charge(watts, max_soc_percent)).await
 }
 
 /// Discharge the battery at the given power down to min_soc_percent.
 pub async fn discharge(base_url: &str, watts: i32, min_soc_percent: u8) -> Result<(), String> {
     let client = Client::new();
-    let cfg = SetDataConfig {
-        f: FUNC_WRITE,
-        t: REG_CONTROL,
-        v: vec![ACTION_DISCHARGE, watts as i64, min_soc_percent as i64],
-    };
     info!("[Indevolt] Discharge {} W down to {}% SOC", watts, min_soc_percent);
-    send_command(&client, base_url, &cfg).await
+    send_command(&client, base_url, RegisterWrite::discharge(watts, min_soc_percent)).await
 }
 
 /// Stop real-time control (standby). The working mode stays at RealtimeControl;
 /// call `set_working_mode(SelfConsumedPrioritized)` to fully hand back control.
 pub async fn stop(base_url: &str) -> Result<(), String> {
     let client = Client::new();
-    let cfg = SetDataConfig { f: FUNC_WRITE, t: REG_CONTROL, v: vec![ACTION_STOP, 0, 0] };
     info!("[Indevolt] Stop (standby)");
-    send_command(&client, base_url, &cfg).await
+    send_command(&client, base_url, RegisterWrite::stop()).await
 }
 
 /// Restore autonomous self-consumption mode and stop any active command.
@@ -99,6 +84,71 @@ pub async fn restore_auto_mode(base_url: &str) -> Result<(), String> {
     set_working_mode(base_url, WorkingMode::SelfConsumedPrioritized).await
 }
 
+// --------------------------------------------------------------------------------------------------------------
+// Control register read-back
+//
+// SetData answers HTTP 200 as soon as the write is accepted; it says nothing about what the device then
+// executes. Firmware that exposes the active control values as GetData sensors can be read back to confirm a
+// command. The sensor IDs are not part of the public ID table and differ between firmware versions, so they
+// come from config (`control_readback_sensor_ids`: action, power, SOC limit).
+// --------------------------------------------------------------------------------------------------------------
+
+/// The command the device reports it is executing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ControlReadback {
+    pub action:      i64,
+    pub watts:       i64,
+    pub soc_percent: i64,
+}
+
+impl ControlReadback {
+    /// True when this read-back matches the values written with `v = [action, watts, soc]`.
+    /// For a stop only the action is compared.
+    pub fn matches(&self, action: i64, watts: i64, soc_percent: i64) -> bool {
+        if action == ACTION_STOP {
+            return self.action == ACTION_STOP;
+        }
+        self.action == action && self.watts == watts && self.soc_percent == soc_percent
+    }
+}
+
+/// Parse a GetData response `{"<id>": <value>, ...}` for the three read-back sensor IDs.
+pub fn parse_control_readback(json: &str, ids: [u32; 3]) -> Result<ControlReadback, String> {
+    let map: serde_json::Map<String, serde_json::Value> = serde_json::from_str(json)
+        .map_err(|e| format!("[Indevolt] Control read-back is not a JSON object: {}", e))?;
+    let value = |id: u32| -> Result<i64, String> {
+        map.get(&id.to_string())
+            .and_then(|v| v.as_f64())
+            .map(|f| f.round() as i64)
+            .ok_or_else(|| format!("[Indevolt] Control read-back lacks sensor {}", id))
+    };
+    Ok(ControlReadback { action: value(ids[0])?, watts: value(ids[1])?, soc_percent: value(ids[2])? })
+}
+
+/// Read the active control values via GET /rpc/Indevolt.GetData.
+pub async fn read_control_register(base_url: &str, ids: [u32; 3]) -> Result<ControlReadback, String> {
+    let url = format!("{}/rpc/Indevolt.GetData", base_url);
+    let mut req_url = reqwest::Url::parse(&url)
+        .map_err(|e| format!("[Indevolt] Invalid URL {}: {}", url, e))?;
+    req_url
+        .query_pairs_mut()
+        .append_pair("config", &format!("{{\"t\":[{},{},{}]}}", ids[0], ids[1], ids[2]));
+
+    let body = Client::new()
+        .get(req_url)
+        .send()
+        .instrument(tracing::info_span!("indevolt_control_readback"))
+        .await
+        .and_then(|r| r.error_for_status())
+        .map_err(|e| format!("[Indevolt] Control read-back failed: {}", e))?
+        .text()
+        .await
+        .map_err(|e| format!("[Indevolt] Control read-back body unreadable: {}", e))?;
+    parse_control_readback(&body, ids)
+}
+
+// --------------------------------------------------------------------------------------------------------------
+
 // Legacy stubs retained so existing call-sites (optimiser placeholder) still compile.
 #[allow(dead_code)]
 pub async fn set_charge_power(base_url: &str, watts: i32) -> Result<(), String> {
@@ -108,3 +158,61 @@ pub async fn set_charge_power(base_url: &str, watts: i32) -> Result<(), String>
 pub async fn set_discharge_power(base_url: &str, watts: i32) -> Result<(), String> {
     discharge(base_url, watts, 10).await
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::handlers::indevolt::testing::MockIndevolt;
+
+    /// The result of a stop sent to a device answering every SetData with `body`.
+    async fn stop_answered_with(body: &'static str) -> Result<(), String> {
+        let device = MockIndevolt::start(move |_| (200, body.to_string())).await;
+        stop(&device.base_url).await
+    }
+
+    #[tokio::test]
+    async fn an_applied_write_succeeds() {
+        assert_eq!(stop_answered_with(r#"{"result": true}"#).await, Ok(()));
+    }
+
+    #[tokio::test]
+    async fn a_write_the_device_did_not_apply_fails() {
+        let error = stop_answered_with(r#"{"result": false}"#).await.unwrap_err();
+        assert!(error.contains("not applied"), "{}", error);
+        let error = stop_answered_with(r#"{"error": "register locked"}"#).await.unwrap_err();
+        assert!(error.contains("register locked"), "{}", error);
+    }
+
+    #[tokio::test]
+    async fn an_unparseable_answer_leaves_the_write_unconfirmed() {
+        for body in ["", "OK"] {
+            let error = stop_answered_with(body).await.unwrap_err();
+            assert!(error.contains("unconfirmed"), "{}", error);
+        }
+    }
+
+    const READBACK_IDS: [u32; 3] = [47016, 47017, 47018];
+
+    #[test]
+    fn a_readback_parses_the_three_control_sensors() {
+        let body     = r#"{"47016": 1, "47017": 1200.0, "47018": 90}"#;
+        let readback = parse_control_readback(body, READBACK_IDS).unwrap();
+        assert_eq!(readback, ControlReadback { action: 1, watts: 1200, soc_percent: 90 });
+        assert!(readback.matches(1, 1200, 90));
+        assert!(!readback.matches(1, 800, 90));
+    }
+
+    #[test]
+    fn a_stop_readback_only_compares_the_action() {
+        let readback = parse_control_readback(r#"{"47016": 0, "47017": 35, "47018": 0}"#, READBACK_IDS).unwrap();
+        assert!(readback.matches(ACTION_STOP, 0, 0));
+    }
+
+    #[test]
+    fn an_incomplete_readback_is_an_error() {
+        let error = parse_control_readback(r#"{"47016": 1, "47017": 1200}"#, READBACK_IDS).unwrap_err();
+        assert!(error.contains("lacks sensor 47018"), "{}", error);
+        let error = parse_control_readback("[1, 1200, 90]", READBACK_IDS).unwrap_err();
+        assert!(error.contains("not a JSON object"), "{}", error);
+    }
+}
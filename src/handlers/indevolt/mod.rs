@@ -1,2 +1,6 @@
 pub mod reader;
 pub mod controller;
+pub mod registers;
+pub mod plausibility;
+#[cfg(test)]
+pub(crate) mod testing;
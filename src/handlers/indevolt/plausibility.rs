@@ -0,0 +1,142 @@
+use log::{info, warn};
+
+use crate::configuration::config::Config;
+use crate::models::indevolt_models::BatterySnapshot;
+use super::reader::{ID_BATTERY_POWER, ID_BATTERY_SOC};
+
+// --------------------------------------------------------------------------------------------------------------
+// Plausibility guard for glitchy battery readings
+//
+// A single bad SOC reading (80 % → 5 % for one cycle) would otherwise drive a decision on data that isn't
+// real. A reading that moves further than `max_soc_jump_per_cycle_percent` (or `max_battery_power_jump_w`)
+// from the last trusted value is rejected: the field keeps the last trusted value and is flagged not fresh,
+// so the optimiser sits the cycle out. A change that persists for `CONFIRM_AFTER` consecutive cycles is real
+// (e.g. a recalibration) and is accepted as the new trusted value. A limit of 0 disables that field's check.
+// --------------------------------------------------------------------------------------------------------------
+
+/// Consecutive implausible readings after which the new level is accepted.
+const CONFIRM_AFTER: u32 = 3;
+
+/// Last trusted value of one field and how many readings in a row were rejected.
+#[derive(Debug, Clone, Copy, Default)]
+struct Tracked {
+    trusted:  Option<f64>,
+    rejected: u32,
+}
+
+impl Tracked {
+    /// The value to use for this cycle, `None` when `value` is accepted as is.
+    fn check(&mut self, name: &str, value: f64, max_jump: f64) -> Option<f64> {
+        let Some(trusted) = self.trusted.filter(|_| max_jump > 0.0) else {
+            self.trusted = Some(value);
+            return None;
+        };
+        if (value - trusted).abs() <= max_jump {
+            self.trusted  = Some(value);
+            self.rejected = 0;
+            return None;
+        }
+
+        self.rejected += 1;
+        if self.rejected >= CONFIRM_AFTER {
+            info!("[Indevolt] {} {} confirmed over {} readings - accepted", name, value, self.rejected);
+            self.trusted  = Some(value);
+            self.rejected = 0;
+            return None;
+        }
+        warn!(
+            "[Indevolt] Implausible {} jump {} → {} (limit {}) - holding {}",
+            name, trusted, value, max_jump, trusted
+        );
+        Some(trusted)
+    }
+}
+
+/// Rejects implausible SOC and battery power jumps between consecutive snapshots.
+#[derive(Debug, Clone, Default)]
+pub struct PlausibilityGuard {
+    soc:   Tracked,
+    power: Tracked,
+}
+
+impl PlausibilityGuard {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Replace implausible values in `battery` with the last trusted ones and flag them not fresh.
+    pub fn apply(&mut self, battery: &mut BatterySnapshot, config: &Config) {
+        if battery.is_fresh(ID_BATTERY_SOC) {
+            let max_jump = config.max_soc_jump_per_cycle_percent;
+            if let Some(held) = self.soc.check("SOC", battery.battery_soc, max_jump) {
+                battery.battery_soc = held;
+                battery.not_fresh.insert(ID_BATTERY_SOC);
+            }
+        }
+        if battery.is_fresh(ID_BATTERY_POWER) {
+            let max_jump = config.max_battery_power_jump_w as f64;
+            if let Some(held) = self.power.check("battery power", battery.battery_power_w as f64, max_jump) {
+                battery.battery_power_w = held as i32;
+                battery.not_fresh.insert(ID_BATTERY_POWER);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::optimiser::testing::battery;
+
+    /// SOC of each snapshot after the guard, and whether it was held.
+    fn run(guard: &mut PlausibilityGuard, config: &Config, socs: &[f64]) -> Vec<(f64, bool)> {
+        socs.iter()
+            .map(|&soc| {
+                let mut snapshot = battery(soc, 0);
+                guard.apply(&mut snapshot, config);
+                (snapshot.battery_soc, !snapshot.is_fresh(ID_BATTERY_SOC))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn a_single_cycle_soc_spike_is_held() {
+        let outcome = run(&mut PlausibilityGuard::new(), &Config::default(), &[80.0, 5.0, 79.5]);
+        assert_eq!(outcome, vec![(80.0, false), (80.0, true), (79.5, false)]);
+    }
+
+    #[test]
+    fn a_gradual_change_passes() {
+        let outcome = run(&mut PlausibilityGuard::new(), &Config::default(), &[80.0, 72.0, 64.0, 56.0]);
+        assert!(outcome.iter().all(|&(_, held)| !held));
+        assert_eq!(outcome.last(), Some(&(56.0, false)));
+    }
+
+    #[test]
+    fn a_jump_that_persists_is_accepted() {
+        let outcome = run(&mut PlausibilityGuard::new(), &Config::default(), &[80.0, 50.0, 50.0, 50.0, 50.0]);
+        assert_eq!(outcome, vec![(80.0, false), (80.0, true), (80.0, true), (50.0, false), (50.0, false)]);
+    }
+
+    #[test]
+    fn battery_power_jumps_are_held_when_limited() {
+        let config    = Config { max_battery_power_jump_w: 2000, ..Config::default() };
+        let mut guard = PlausibilityGuard::new();
+        let powers: Vec<(i32, bool)> = [500, 4000, 900]
+            .iter()
+            .map(|&power_w| {
+                let mut snapshot = battery(60.0, power_w);
+                guard.apply(&mut snapshot, &config);
+                (snapshot.battery_power_w, !snapshot.is_fresh(ID_BATTERY_POWER))
+            })
+            .collect();
+        assert_eq!(powers, vec![(500, false), (500, true), (900, false)]);
+
+        // The default limit of 0 leaves battery power unchecked.
+        let mut snapshot = battery(60.0, 4000);
+        let mut guard    = PlausibilityGuard::new();
+        guard.apply(&mut battery(60.0, 500), &Config::default());
+        guard.apply(&mut snapshot, &Config::default());
+        assert_eq!(snapshot.battery_power_w, 4000);
+    }
+}
@@ -1,8 +1,11 @@
-use log::{debug, error};
+use chrono::Utc;
+use log::{debug, error, info, warn};
 use reqwest::Client;
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap};
+use std::sync::Mutex;
 
-use crate::models::indevolt_models::BatterySnapshot;
+use crate::configuration::config::Config;
+use crate::models::indevolt_models::{BatterySnapshot, WorkingMode};
 
 // --------------------------------------------------------------------------------------------------------------
 // Numeric sensor IDs for the Indevolt RPC bulk-read API.
@@ -30,23 +33,23 @@ use crate::models::indevolt_models::BatterySnapshot;
 //   11016 Meter Power (grid)        W  positive=import, negative=export
 // --------------------------------------------------------------------------------------------------------------
 
-const ID_WORKING_MODE:              u32 = 7101;  // 1=Self-consumed, 5=Schedule
-const ID_DC_INPUT1:                 u32 = 1664;  // W  PV string 1
-const ID_DC_INPUT2:                 u32 = 1665;  // W  PV string 2
-const ID_TOTAL_DC_OUTPUT:           u32 = 1501;  // W
-const ID_TOTAL_AC_OUTPUT:           u32 = 2108;  // W
-const ID_DAILY_PRODUCTION:          u32 = 1502;  // kWh
-const ID_CUMULATIVE_PRODUCTION:     u32 = 1505;  // raw ×0.001 = kWh
-const ID_TOTAL_AC_INPUT:            u32 = 2101;  // W
-const ID_TOTAL_AC_INPUT_ENERGY:     u32 = 2107;  // kWh
-const ID_BATTERY_POWER:             u32 = 6000;  // W
-const ID_BATTERY_STATE:             u32 = 6001;  // 1000=Static, 1001=Charging, 1002=Discharging
-const ID_BATTERY_SOC:               u32 = 6002;  // %
-const ID_DAILY_CHARGING:            u32 = 6004;  // kWh
-const ID_DAILY_DISCHARGING:         u32 = 6005;  // kWh
-const ID_TOTAL_CHARGING:            u32 = 6006;  // kWh
-const ID_TOTAL_DISCHARGING:         u32 = 6007;  // kWh
-const ID_METER_POWER:               u32 = 11016; // W  grid (positive=import)
+pub const ID_WORKING_MODE:              u32 = 7101;  // 1=Self-consumed, 5=Schedule
+pub const ID_DC_INPUT1:                 u32 = 1664;  // W  PV string 1
+pub const ID_DC_INPUT2:                 u32 = 1665;  // W  PV string 2
+pub const ID_TOTAL_DC_OUTPUT:           u32 = 1501;  // W
+pub const ID_TOTAL_AC_OUTPUT:           u32 = 2108;  // W
+pub const ID_DAILY_PRODUCTION:          u32 = 1502;  // kWh
+pub const ID_CUMULATIVE_PRODUCTION:     u32 = 1505;  // raw ×0.001 = kWh
+pub const ID_TOTAL_AC_INPUT:            u32 = 2101;  // W
+pub const ID_TOTAL_AC_INPUT_ENERGY:     u32 = 2107;  // kWh
+pub const ID_BATTERY_POWER:             u32 = 6000;  // W
+pub const ID_BATTERY_STATE:             u32 = 6001;  // 1000=Static, 1001=Charging, 1002=Discharging
+pub const ID_BATTERY_SOC:               u32 = 6002;  // %
+pub const ID_DAILY_CHARGING:            u32 = 6004;  // kWh
+pub const ID_DAILY_DISCHARGING:         u32 = 6005;  // kWh
+pub const ID_TOTAL_CHARGING:            u32 = 6006;  // kWh
+pub const ID_TOTAL_DISCHARGING:         u32 = 6007;  // kWh
+pub const ID_METER_POWER:               u32 = 11016; // W  grid (positive=import)
 
 /// All IDs requested in one shot (order mirrors the firmware table).
 const SNAPSHOT_IDS: &[u32] = &[
@@ -60,26 +63,114 @@ const SNAPSHOT_IDS: &[u32] = &[
 
 // --------------------------------------------------------------------------------------------------------------
 
+/// Optional sensor IDs the firmware answered with an error object; they are left out of later polls.
+static UNSUPPORTED_SENSORS: Mutex<BTreeSet<u32>> = Mutex::new(BTreeSet::new());
+
+/// Some firmware answers an unknown key with an error object instead of a value, still with HTTP 200 -
+/// either for the whole request (`{"error": "unknown key"}`) or per sensor (`{"6002": {"error": "..."}}`).
+/// Log those distinctly, drop them from `data`, and stop polling optional sensors that were rejected
+/// individually. The snapshot sensors (SOC, battery power, ...) are always polled again: the optimiser needs
+/// them, and a rejection may be a passing firmware hiccup.
+fn report_firmware_errors(data: &mut HashMap<String, serde_json::Value>) {
+    if let Some(err) = data.remove("error") {
+        error!(
+            "[Indevolt] GetData rejected by firmware: {} (check any configured temperature sensor IDs)",
+            err.as_str().map_or_else(|| err.to_string(), str::to_string)
+        );
+    }
+
+    let rejected: Vec<(String, String)> = data
+        .iter()
+        .filter_map(|(key, value)| {
+            let msg = value.get("error")?;
+            Some((key.clone(), msg.as_str().map_or_else(|| msg.to_string(), str::to_string)))
+        })
+        .collect();
+    if rejected.is_empty() {
+        return;
+    }
+
+    let mut unsupported = UNSUPPORTED_SENSORS.lock().unwrap_or_else(|e| e.into_inner());
+    for (key, msg) in rejected {
+        data.remove(&key);
+        match key.parse::<u32>() {
+            Ok(id) if SNAPSHOT_IDS.contains(&id) => {
+                error!("[Indevolt] core sensor '{}' rejected by firmware ({}) - still polled", key, msg);
+            }
+            Ok(id) => {
+                warn!("[Indevolt] sensor '{}' not supported by firmware ({}) - no longer polled", key, msg);
+                unsupported.insert(id);
+            }
+            Err(_) => warn!("[Indevolt] sensor '{}' not supported by firmware ({})", key, msg),
+        }
+    }
+}
+
+/// The unknown working mode last reported, so it is logged once rather than every cycle.
+static UNKNOWN_MODE: Mutex<Option<String>> = Mutex::new(None);
+
+/// Firmware may add working modes this build doesn't know. Warn once when the device reports one (the optimiser
+/// then leaves the device alone), and note when it is back in a known mode.
+fn report_unknown_mode(mode: Option<WorkingMode>, working_mode: &str) {
+    let mut last = UNKNOWN_MODE.lock().unwrap_or_else(|e| e.into_inner());
+    match mode {
+        None if last.as_deref() != Some(working_mode) => {
+            warn!("[Indevolt] unknown working mode '{}' - device treated as uncontrollable", working_mode);
+            *last = Some(working_mode.to_string());
+        }
+        Some(_) if last.is_some() => {
+            info!("[Indevolt] working mode '{}' recognised again - control resumes", working_mode);
+            *last = None;
+        }
+        _ => {}
+    }
+}
+
+// --------------------------------------------------------------------------------------------------------------
+
+/// Fixed snapshot IDs plus the optional, installation-specific temperature, grid state, SOC limit and grid
+/// quality sensors, minus the optional ones the firmware has already said it doesn't support.
+fn poll_ids(config: &Config, unsupported: &BTreeSet<u32>) -> Vec<u32> {
+    let optional = [
+        config.inverter_temperature_sensor_id,
+        config.battery_temperature_sensor_id,
+        config.grid_state_sensor_id,
+        config.device_min_soc_sensor_id,
+        config.device_max_soc_sensor_id,
+        config.grid_frequency_sensor_id,
+        config.grid_voltage_sensor_id,
+    ];
+    let optional = optional.into_iter().flatten().filter(|id| !unsupported.contains(id));
+    SNAPSHOT_IDS.iter().copied().chain(optional).collect()
+}
+
 /// Fetch all snapshot values in a single GET /rpc/Indevolt.GetData call.
-pub async fn read_battery_snapshot(base_url: &str, device_model: &str) -> BatterySnapshot {
-    let client = Client::new();
+/// The SOC is corrected with the configured calibration offset/clamp; the device value is kept in
+/// `battery_soc_raw` for logging.
+pub async fn read_battery_snapshot(config: &Config, device_model: &str) -> BatterySnapshot {
+    let client   = Client::new();
+    let base_url = config.indevolt_url.as_str();
+
+    let unsupported = UNSUPPORTED_SENSORS.lock().unwrap_or_else(|e| e.into_inner()).clone();
+    let ids = poll_ids(config, &unsupported);
 
     // Build the config query parameter: {"t":[id,...]}
     let ids_json = format!(
         "{{\"t\":[{}]}}",
-        SNAPSHOT_IDS.iter().map(|id| id.to_string()).collect::<Vec<_>>().join(",")
+        ids.iter().map(|id| id.to_string()).collect::<Vec<_>>().join(",")
     );
 
-    let mut req_url = reqwest::Url::parse(&format!("{}/rpc/Indevolt.GetData", base_url))
-        .expect("Invalid base_url");
-    req_url.query_pairs_mut().append_pair("config", &ids_json);
-
-    let result: Result<reqwest::Response, reqwest::Error> = client
-        .get(req_url)
-        .send()
-        .await;
+    // `validate` rejects a malformed `indevolt_url`; should one get here anyway, only this read fails.
+    let result = match reqwest::Url::parse(&format!("{}/rpc/Indevolt.GetData", base_url)) {
+        Ok(mut req_url) => {
+            req_url.query_pairs_mut().append_pair("config", &ids_json);
+            client.get(req_url).send().await.map_err(|e| format!("GetData request failed: {}", e))
+        }
+        Err(e) => Err(format!("invalid indevolt_url '{}': {}", base_url, e)),
+    };
 
-    let data: HashMap<String, serde_json::Value> = match result {
+    let read_at = Utc::now();
+    let mut data: HashMap<String, serde_json::Value> = match result {
         Ok(resp) if resp.status().is_success() => {
             match resp.json().await {
                 Ok(map) => map,
@@ -94,12 +185,24 @@ pub async fn read_battery_snapshot(base_url: &str, device_model: &str) -> Batter
             HashMap::new()
         }
         Err(e) => {
-            error!("[Indevolt] GetData request failed: {}", e);
+            error!("[Indevolt] {}", e);
             HashMap::new()
         }
     };
 
     debug!("[Indevolt] GetData raw: {:?}", data);
+    report_firmware_errors(&mut data);
+
+    // Anything requested but not answered with a number is flagged not fresh; its field gets a default.
+    let not_fresh: BTreeSet<u32> = ids
+        .iter()
+        .copied()
+        .filter(|id| data.get(&id.to_string()).and_then(|v| v.as_f64()).is_none())
+        .collect();
+    let keys_missing = not_fresh.len();
+    if keys_missing > 0 && !data.is_empty() {
+        warn!("[Indevolt] {} of {} sensor IDs missing from GetData response", keys_missing, ids.len());
+    }
 
     // Helpers to extract typed values by numeric ID.
     let f64_id = |id: u32| -> f64 {
@@ -107,6 +210,9 @@ pub async fn read_battery_snapshot(base_url: &str, device_model: &str) -> Batter
             .and_then(|v| v.as_f64())
             .unwrap_or(0.0)
     };
+    let opt_f64_id = |id: u32| -> Option<f64> {
+        data.get(&id.to_string()).and_then(|v| v.as_f64())
+    };
     let i32_id = |id: u32| -> i32 {
         data.get(&id.to_string())
             .and_then(|v| v.as_f64())
@@ -122,19 +228,28 @@ pub async fn read_battery_snapshot(base_url: &str, device_model: &str) -> Batter
         code => format!("Unknown({})", code),
     };
 
-    // Decode working mode integer to human-readable string.
-    let working_mode = match i32_id(ID_WORKING_MODE) {
-        1 => "Self-consumed Prioritized".to_string(),
-        4 => "Real-time Control".to_string(),
-        5 => "Schedule".to_string(),
-        code => format!("Mode({})", code),
-    };
+    // Decode working mode integer to human-readable string; a code this build doesn't know stays "Mode(N)".
+    let mode_read    = !not_fresh.contains(&ID_WORKING_MODE);
+    let mode_code    = i32_id(ID_WORKING_MODE) as i64;
+    let mode         = WorkingMode::from_register_value(mode_code).filter(|_| mode_read);
+    let working_mode = mode.map_or_else(|| format!("Mode({})", mode_code), |m| m.as_str().to_string());
+    if mode_read {
+        report_unknown_mode(mode, &working_mode);
+    }
+
+    let battery_soc_raw = f64_id(ID_BATTERY_SOC);
+    let battery_soc     = config.calibrate_soc(battery_soc_raw);
+    if battery_soc != battery_soc_raw {
+        debug!("[Indevolt] SOC calibrated {:.1}% → {:.1}%", battery_soc_raw, battery_soc);
+    }
 
     BatterySnapshot {
         device_model:              device_model.to_string(),
-        battery_soc:               f64_id(ID_BATTERY_SOC),
+        battery_soc,
+        battery_soc_raw,
         battery_state,
         working_mode,
+        mode,
         battery_power_w:           i32_id(ID_BATTERY_POWER),
         dc_input_power1_w:         i32_id(ID_DC_INPUT1),
         dc_input_power2_w:         i32_id(ID_DC_INPUT2),
@@ -149,5 +264,90 @@ pub async fn read_battery_snapshot(base_url: &str, device_model: &str) -> Batter
         total_charging_kwh:        f64_id(ID_TOTAL_CHARGING),
         total_discharging_kwh:     f64_id(ID_TOTAL_DISCHARGING),
         total_ac_input_energy_kwh: f64_id(ID_TOTAL_AC_INPUT_ENERGY),
+        inverter_temperature_c:    config.inverter_temperature_sensor_id.and_then(opt_f64_id),
+        battery_temperature_c:     config.battery_temperature_sensor_id.and_then(opt_f64_id),
+        device_min_soc_percent:    config.device_min_soc_sensor_id.and_then(opt_f64_id),
+        device_max_soc_percent:    config.device_max_soc_sensor_id.and_then(opt_f64_id),
+        off_grid:                  config.grid_state_sensor_id
+            .and_then(opt_f64_id)
+            .map(|v| config.off_grid_state_values.contains(&(v as i64))),
+        grid_frequency_hz:         config.grid_frequency_sensor_id.and_then(opt_f64_id),
+        grid_voltage_v:            config.grid_voltage_sensor_id.and_then(opt_f64_id),
+        keys_requested:            ids.len(),
+        keys_missing,
+        read_at,
+        not_fresh,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::handlers::indevolt::testing::MockIndevolt;
+
+    fn response(json: &str) -> HashMap<String, serde_json::Value> {
+        serde_json::from_str(json).unwrap()
+    }
+
+    #[test]
+    fn a_valid_reading_is_kept() {
+        let mut data = response(r#"{"6002": 55.0, "6000": -800}"#);
+        report_firmware_errors(&mut data);
+        assert_eq!(data.get("6002").and_then(|v| v.as_f64()), Some(55.0));
+        assert_eq!(data.len(), 2);
+    }
+
+    #[test]
+    fn a_rejected_optional_sensor_is_no_longer_polled() {
+        let config   = Config { battery_temperature_sensor_id: Some(9401), ..Config::default() };
+        let mut data = response(r#"{"6002": 55.0, "9401": {"error": "unknown key"}}"#);
+        report_firmware_errors(&mut data);
+
+        assert!(!data.contains_key("9401"));
+        let unsupported = UNSUPPORTED_SENSORS.lock().unwrap().clone();
+        assert!(!poll_ids(&config, &unsupported).contains(&9401));
+    }
+
+    #[test]
+    fn a_rejected_core_sensor_is_still_polled() {
+        let mut data = response(r#"{"6002": {"error": "unknown key"}, "6000": -800}"#);
+        report_firmware_errors(&mut data);
+
+        assert!(!data.contains_key("6002"));
+        let unsupported = UNSUPPORTED_SENSORS.lock().unwrap().clone();
+        assert!(!unsupported.contains(&ID_BATTERY_SOC));
+        assert!(poll_ids(&Config::default(), &BTreeSet::from([ID_BATTERY_SOC])).contains(&ID_BATTERY_SOC));
+    }
+
+    #[test]
+    fn a_whole_request_error_object_is_dropped() {
+        let mut data = response(r#"{"error": "unknown key"}"#);
+        report_firmware_errors(&mut data);
+        assert!(data.is_empty());
+    }
+
+    #[tokio::test]
+    async fn a_malformed_url_fails_the_read_instead_of_panicking() {
+        let config   = Config { indevolt_url: "192.168.1.30".to_string(), ..Config::default() };
+        let snapshot = read_battery_snapshot(&config, "BK1600").await;
+        assert!(!snapshot.is_fresh(ID_BATTERY_SOC));
+        assert_eq!(snapshot.keys_missing, snapshot.keys_requested);
+    }
+
+    #[tokio::test]
+    async fn only_sensors_answered_this_cycle_are_fresh() {
+        let body     = r#"{"6002": 55.0, "6001": 1000, "6000": null}"#;
+        let device   = MockIndevolt::start(move |_| (200, body.to_string())).await;
+        let config   = Config { indevolt_url: device.base_url.clone(), ..Config::default() };
+        let before   = Utc::now();
+        let snapshot = read_battery_snapshot(&config, "BK1600").await;
+
+        assert!(snapshot.is_fresh(ID_BATTERY_SOC) && snapshot.is_fresh(ID_BATTERY_STATE));
+        assert_eq!((snapshot.battery_soc, snapshot.battery_state.as_str()), (55.0, "Static"));
+        // Not answered with a number: defaulted, and flagged as such.
+        assert!(!snapshot.is_fresh(ID_BATTERY_POWER) && !snapshot.is_fresh(ID_METER_POWER));
+        assert_eq!(snapshot.battery_power_w, 0);
+        assert_eq!(snapshot.keys_missing, snapshot.keys_requested - 2);
+        assert!(snapshot.read_at >= before);
     }
 }
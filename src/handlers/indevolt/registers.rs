@@ -0,0 +1,97 @@
+use crate::models::indevolt_models::{SetDataConfig, WorkingMode};
+
+// --------------------------------------------------------------------------------------------------------------
+// Indevolt register map
+//
+// SetData is a thin wrapper around a Modbus "write multiple registers": `f` is the function code, `t` the
+// start register and `v` the values written from there on. Everything the EMS knows about the writable
+// registers lives here; the controller only sends the `RegisterWrite`s built below.
+//
+//   47005  working mode    v = [mode]                  1 self-consumption, 4 real-time control, 5 schedule
+//   47015  real-time cmd   v = [action, watts, soc]    action 0 stop, 1 charge, 2 discharge; watts ≥ 0;
+//                                                      soc = max SOC % for a charge, min SOC % for a discharge
+//
+// Real-time commands only take effect while the working mode is 4.
+// --------------------------------------------------------------------------------------------------------------
+
+/// Modbus function 16: write multiple registers. The only function SetData accepts.
+pub const FUNC_WRITE: u32 = 16;
+
+/// Working mode register, one value (`MODE_*`).
+pub const REG_WORKING_MODE: u32 = 47005;
+/// Real-time command register, three values: action (`ACTION_*`), power (W) and SOC limit (%).
+pub const REG_CONTROL:      u32 = 47015;
+
+// Values of REG_WORKING_MODE.
+pub const MODE_SELF_CONSUMED: i64 = 1;
+pub const MODE_REALTIME:      i64 = 4;
+pub const MODE_SCHEDULE:      i64 = 5;
+
+// v[0] action codes for REG_CONTROL.
+pub const ACTION_STOP:      i64 = 0;
+pub const ACTION_CHARGE:    i64 = 1;
+pub const ACTION_DISCHARGE: i64 = 2;
+
+/// One register write, as sent in a SetData request.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RegisterWrite {
+    pub function: u32,
+    pub address:  u32,
+    pub values:   Vec<i64>,
+}
+
+impl RegisterWrite {
+    /// Write `values` from register `address` on with function 16.
+    pub fn new(address: u32, values: Vec<i64>) -> Self {
+        Self { function: FUNC_WRITE, address, values }
+    }
+
+    /// Switch the working mode.
+    pub fn working_mode(mode: WorkingMode) -> Self {
+        Self::new(REG_WORKING_MODE, vec![mode.register_value()])
+    }
+
+    /// Charge at `watts` up to `max_soc_percent` (real-time mode only).
+    pub fn charge(watts: i32, max_soc_percent: u8) -> Self {
+        Self::new(REG_CONTROL, vec![ACTION_CHARGE, watts as i64, max_soc_percent as i64])
+    }
+
+    /// Discharge at `watts` down to `min_soc_percent` (real-time mode only).
+    pub fn discharge(watts: i32, min_soc_percent: u8) -> Self {
+        Self::new(REG_CONTROL, vec![ACTION_DISCHARGE, watts as i64, min_soc_percent as i64])
+    }
+
+    /// Stop the real-time command (standby); the working mode stays as it is.
+    pub fn stop() -> Self {
+        Self::new(REG_CONTROL, vec![ACTION_STOP, 0, 0])
+    }
+}
+
+impl From<RegisterWrite> for SetDataConfig {
+    fn from(write: RegisterWrite) -> Self {
+        SetDataConfig { f: write.function, t: write.address, v: write.values }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn json(write: RegisterWrite) -> String {
+        serde_json::to_string(&SetDataConfig::from(write)).unwrap()
+    }
+
+    #[test]
+    fn control_writes_serialise_to_the_exact_setdata_json() {
+        assert_eq!(json(RegisterWrite::charge(2000, 100)), r#"{"f":16,"t":47015,"v":[1,2000,100]}"#);
+        assert_eq!(json(RegisterWrite::discharge(1500, 10)), r#"{"f":16,"t":47015,"v":[2,1500,10]}"#);
+        assert_eq!(json(RegisterWrite::stop()), r#"{"f":16,"t":47015,"v":[0,0,0]}"#);
+    }
+
+    #[test]
+    fn mode_writes_serialise_to_the_exact_setdata_json() {
+        let (realtime, self_consumed) = (WorkingMode::RealtimeControl, WorkingMode::SelfConsumedPrioritized);
+        assert_eq!(json(RegisterWrite::working_mode(realtime)), r#"{"f":16,"t":47005,"v":[4]}"#);
+        assert_eq!(json(RegisterWrite::working_mode(self_consumed)), r#"{"f":16,"t":47005,"v":[1]}"#);
+    }
+}
@@ -0,0 +1,63 @@
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+
+// --------------------------------------------------------------------------------------------------------------
+// Mock Indevolt for the controller and dispatcher tests
+//
+// A local HTTP server standing in for the device. Each request is recorded as its path followed by the decoded
+// `config` parameter, e.g. `/rpc/Indevolt.SetData {"f":16,"t":47005,"v":[1]}`, and answered with whatever the
+// test's responder returns for it. The P1 reader tests use it as a meter too.
+// --------------------------------------------------------------------------------------------------------------
+
+pub struct MockIndevolt {
+    pub base_url: String,
+    requests:     Arc<Mutex<Vec<String>>>,
+}
+
+impl MockIndevolt {
+    /// Start the server; `respond` gives the HTTP status and body for each recorded request.
+    pub async fn start(respond: impl Fn(&str) -> (u16, String) + Send + Sync + 'static) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base_url = format!("http://{}", listener.local_addr().unwrap());
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let seen     = Arc::clone(&requests);
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let mut head  = Vec::new();
+                let mut chunk = [0u8; 1024];
+                while !head.windows(4).any(|w| w == b"\r\n\r\n") {
+                    match stream.read(&mut chunk).await {
+                        Ok(0) | Err(_) => break,
+                        Ok(n)          => head.extend_from_slice(&chunk[..n]),
+                    }
+                }
+                let head   = String::from_utf8_lossy(&head);
+                let target = head.split_whitespace().nth(1).unwrap_or("/");
+                let url    = reqwest::Url::parse(&format!("http://device{}", target)).unwrap();
+                let config = url.query_pairs().find(|(k, _)| k == "config").map(|(_, v)| v.into_owned());
+                let request = format!("{} {}", url.path(), config.unwrap_or_default());
+
+                let (status, body) = respond(&request);
+                seen.lock().unwrap().push(request);
+                let response = format!(
+                    "HTTP/1.1 {} Mock\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    status, body.len(), body
+                );
+                let _ = stream.write_all(response.as_bytes()).await;
+            }
+        });
+        Self { base_url, requests }
+    }
+
+    /// Requests received so far, oldest first.
+    pub fn requests(&self) -> Vec<String> {
+        self.requests.lock().unwrap().clone()
+    }
+
+    /// Whether a SetData write of `values` to `register` was received.
+    pub fn wrote(&self, register: u32, values: &str) -> bool {
+        let write = format!("\"t\":{},\"v\":{}", register, values);
+        self.requests().iter().any(|r| r.starts_with("/rpc/Indevolt.SetData") && r.contains(&write))
+    }
+}
@@ -1,2 +1,3 @@
 pub mod p1;
 pub mod indevolt;
+pub mod gate;
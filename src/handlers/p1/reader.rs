@@ -1,13 +1,28 @@
-use chrono::{DateTime, NaiveDateTime, TimeZone, Utc};
-use log::{debug, error, warn};
+use chrono::{DateTime, LocalResult, NaiveDateTime, Offset, TimeDelta, TimeZone, Utc};
+use log::{debug, error, info, warn};
+use serde::de::DeserializeOwned;
+use serde_json::{Map, Value};
+use serde_path_to_error::Segment;
+use std::collections::BTreeSet;
+use std::fmt;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
 
-use crate::models::p1_models::{fetch_p1_data, P1Data};
+use crate::configuration::config::{Config, DstGap};
+use crate::models::p1_models::{fetch_p1_data, fetch_p1_data_v2, P1Data, P1DataV2};
+use crate::runtime::clock::LOCAL_TZ;
 
 // --------------------------------------------------------------------------------------------------------------
 
+/// Last spring-forward timestamp whose shift was logged, so a gas stamp repeated every cycle is logged once.
+static LAST_GAP_SHIFT: Mutex<Option<String>> = Mutex::new(None);
+
 /// Parse the compact 12-character P1 timestamp (YYMMDDHHmmss, local time) into UTC.
-/// The HomeWizard firmware encodes the timestamp in local Belgian time without a zone indicator.
-fn parse_p1_timestamp(timestamp: &str) -> Result<DateTime<Utc>, String> {
+/// The HomeWizard firmware encodes the timestamp in local Belgian time without a zone indicator; it is read as
+/// `LOCAL_TZ` whatever the host's timezone. A local time inside the spring-forward gap (02:00-03:00 on the last
+/// Sunday of March) doesn't exist; with `DstGap::ShiftForward` it is read with the winter offset, which moves
+/// it forward by the gap - a meter clock a little late on the change, not bad data.
+fn parse_p1_timestamp(timestamp: &str, dst_gap: DstGap) -> Result<DateTime<Utc>, String> {
     if timestamp.len() != 12 {
         return Err(format!("Invalid P1 timestamp length (expected 12): '{}'", timestamp));
     }
@@ -24,18 +39,144 @@ fn parse_p1_timestamp(timestamp: &str) -> Result<DateTime<Utc>, String> {
 
     match NaiveDateTime::parse_from_str(&formatted, "%Y-%m-%d %H:%M:%S") {
         Ok(naive) => {
-            match chrono::Local.from_local_datetime(&naive).single() {
-                Some(local_dt) => {
+            match LOCAL_TZ.from_local_datetime(&naive) {
+                LocalResult::Single(local_dt) => {
                     debug!("Parsed P1 timestamp '{}' → {}", timestamp, local_dt.with_timezone(&Utc));
                     Ok(local_dt.with_timezone(&Utc))
                 }
-                None => Err(format!("Ambiguous local→UTC conversion for '{}'", timestamp)),
+                LocalResult::None if dst_gap == DstGap::ShiftForward => shift_out_of_gap(timestamp, naive),
+                LocalResult::None => Err(format!("'{}' falls in the spring-forward DST gap", timestamp)),
+                LocalResult::Ambiguous(..) => Err(format!("Ambiguous local→UTC conversion for '{}'", timestamp)),
             }
         }
         Err(e) => Err(format!("Cannot parse '{}': {}", timestamp, e)),
     }
 }
 
+/// Read a local time in the spring-forward gap with the offset in force just before it.
+fn shift_out_of_gap(timestamp: &str, naive: NaiveDateTime) -> Result<DateTime<Utc>, String> {
+    let before = LOCAL_TZ
+        .offset_from_local_datetime(&(naive - TimeDelta::hours(3)))
+        .earliest()
+        .ok_or_else(|| format!("No UTC offset before the DST gap for '{}'", timestamp))?;
+    let utc = (naive - TimeDelta::seconds(before.fix().local_minus_utc() as i64)).and_utc();
+
+    let mut last = LAST_GAP_SHIFT.lock().unwrap_or_else(|e| e.into_inner());
+    if last.as_deref() != Some(timestamp) {
+        info!(
+            "[P1] Timestamp '{}' falls in the spring-forward DST gap; read as {}",
+            timestamp, utc.with_timezone(&LOCAL_TZ).format("%Y-%m-%d %H:%M:%S %Z")
+        );
+        *last = Some(timestamp.to_string());
+    }
+    Ok(utc)
+}
+
+// --------------------------------------------------------------------------------------------------------------
+
+/// Build the HTTP client used for every P1 read. The HomeWizard dongle is a small embedded device that
+/// copes badly with a new TCP connection per request, so the client keeps a small pool of HTTP/1.1
+/// keep-alive connections and must be created once and reused for the lifetime of the loop.
+///
+/// API v2 serves HTTPS with a device certificate signed by the HomeWizard CA and issued to the device's serial,
+/// not its address. With `p1_ca_cert_path` only that CA is trusted and the host name isn't checked;
+/// `p1_accept_invalid_certs` is the explicit opt-in to accept any certificate.
+pub fn build_p1_client(config: &Config) -> Result<reqwest::Client, String> {
+    debug!(
+        "[P1] HTTP client: HTTP/1.1 keep-alive, pool_idle_timeout={}s pool_max_idle_per_host={}",
+        config.pool_idle_timeout_seconds, config.pool_max_idle_per_host,
+    );
+    let mut builder = reqwest::Client::builder()
+        .http1_only()
+        .pool_idle_timeout(Duration::from_secs(config.pool_idle_timeout_seconds))
+        .pool_max_idle_per_host(config.pool_max_idle_per_host)
+        .timeout(Duration::from_millis(config.p1_timeout_ms));
+    if let Some(path) = &config.p1_ca_cert_path {
+        let pem  = std::fs::read(path).map_err(|e| format!("Cannot read p1_ca_cert_path '{}': {}", path, e))?;
+        let cert = reqwest::Certificate::from_pem(&pem)
+            .map_err(|e| format!("Invalid certificate in p1_ca_cert_path '{}': {}", path, e))?;
+        builder = builder.tls_certs_only([cert]).tls_danger_accept_invalid_hostnames(true);
+    }
+    if config.p1_accept_invalid_certs {
+        warn!("[P1] p1_accept_invalid_certs is set - the meter's certificate is not verified");
+        builder = builder.tls_danger_accept_invalid_certs(true);
+    }
+    builder.build().map_err(|e| e.to_string())
+}
+
+// --------------------------------------------------------------------------------------------------------------
+
+/// External meter types already reported as unknown, so each one is only logged once per run.
+static REPORTED_UNKNOWN_EXTERNALS: Mutex<BTreeSet<String>> = Mutex::new(BTreeSet::new());
+
+/// Warn once per type about external meters this crate does not recognise. They are kept in the
+/// reading; this only makes sure they don't go unnoticed.
+fn report_unknown_externals(raw: &P1Data) {
+    let unknown = raw.unknown_externals();
+    if unknown.is_empty() {
+        return;
+    }
+    let mut reported = REPORTED_UNKNOWN_EXTERNALS.lock().unwrap_or_else(|e| e.into_inner());
+    for ext in unknown {
+        if reported.insert(ext.r#type.clone()) {
+            warn!(
+                "[P1] Unknown external meter type '{}' (unique_id={}, {} {}) - kept as-is",
+                ext.r#type, ext.unique_id, ext.value, ext.unit
+            );
+        }
+    }
+}
+
+// --------------------------------------------------------------------------------------------------------------
+// Critical and optional fields
+//
+// The optimiser only needs a few P1 fields; the rest (gas, voltages, currents, ...) differ between meters and
+// firmware versions. A reading is usable as long as every field in `p1_critical_fields` is present and parses.
+// Any other field the meter leaves out reads as zero or empty, and one that is malformed is dropped and read
+// the same way, with a warning the first time per field. Critical fields are given by their v1 names and
+// translated for API v2.
+// --------------------------------------------------------------------------------------------------------------
+
+/// Non-critical fields already reported as malformed, so each one is only logged once per run.
+static REPORTED_DROPPED_FIELDS: Mutex<BTreeSet<String>> = Mutex::new(BTreeSet::new());
+
+/// Parse a P1 response into `T`, dropping malformed fields that aren't in `critical`. `resolve` folds
+/// accepted aliases into the field names first. Returns the value and the names of the dropped fields.
+fn parse_lenient<T: DeserializeOwned>(
+    json: &str,
+    critical: &[&str],
+    resolve: fn(&mut Map<String, Value>),
+) -> Result<(T, Vec<String>), P1Error> {
+    let mut fields: Map<String, Value> = serde_json::from_str(json)
+        .map_err(|e| P1Error::Parse(".".to_string(), e.to_string()))?;
+    resolve(&mut fields);
+    if let Some(name) = critical.iter().find(|name| !fields.contains_key(**name)) {
+        return Err(P1Error::Parse(name.to_string(), "missing critical field".to_string()));
+    }
+
+    let mut dropped = Vec::new();
+    loop {
+        let err = match serde_path_to_error::deserialize(Value::Object(fields.clone())) {
+            Ok(data) => return Ok((data, dropped)),
+            Err(e) => e,
+        };
+        let field = match err.path().iter().next() {
+            Some(Segment::Map { key }) if !critical.contains(&key.as_str()) => key.clone(),
+            _ => return Err(P1Error::Parse(err.path().to_string(), err.inner().to_string())),
+        };
+        if fields.remove(&field).is_none() {
+            return Err(P1Error::Parse(err.path().to_string(), err.inner().to_string()));
+        }
+        let mut reported = REPORTED_DROPPED_FIELDS.lock().unwrap_or_else(|e| e.into_inner());
+        if reported.insert(field.clone()) {
+            warn!("[P1] Malformed '{}' ({}) - dropping non-critical field '{}'", err.path(), err.inner(), field);
+        } else {
+            debug!("[P1] Malformed field '{}' dropped", err.path());
+        }
+        dropped.push(field);
+    }
+}
+
 // --------------------------------------------------------------------------------------------------------------
 
 /// A fully resolved P1 reading with timestamps already converted to UTC.
@@ -44,48 +185,365 @@ pub struct P1Reading {
     pub raw: P1Data,
     pub monthly_power_peak_timestamp_utc: DateTime<Utc>,
     pub gas_timestamp_utc:                DateTime<Utc>,
+    /// How long import, export and power have shown these values (zero when they just changed).
+    pub unchanged_for:                    Duration,
+    /// Non-critical fields that were malformed and read as zero/empty.
+    pub dropped_fields:                   Vec<String>,
 }
 
-// --------------------------------------------------------------------------------------------------------------
+/// Gas readings reach the P1 port every 5 minutes from DSMR 5.0 on, hourly on older meters.
+const GAS_INTERVAL_SMR5: Duration = Duration::from_secs(300);
+const GAS_INTERVAL_OLDER: Duration = Duration::from_secs(3600);
 
-/// Fetch and parse one P1 reading from the HomeWizard API.
-/// Returns `None` on any HTTP or parse error so the caller can skip and retry next cycle.
-pub async fn read_p1(url: &str) -> Option<P1Reading> {
-    let json = match fetch_p1_data(url).await {
-        Ok(j)  => j,
-        Err(e) => {
-            error!("[P1] HTTP error fetching {}: {}", url, e);
-            return None;
+impl P1Reading {
+    /// The meter's own clock, as the time stamped on its latest gas reading, together with how far behind
+    /// the host that stamp may normally lag. `None` without a (valid) gas timestamp.
+    pub fn meter_clock(&self, dst_gap: DstGap) -> Option<(DateTime<Utc>, Duration)> {
+        let stamped = parse_p1_timestamp(&self.raw.gas_timestamp, dst_gap).ok()?;
+        let max_lag = if self.raw.smr_version >= 50 { GAS_INTERVAL_SMR5 } else { GAS_INTERVAL_OLDER };
+        Some((stamped, max_lag))
+    }
+}
+
+/// Why a P1 read produced no usable reading.
+#[derive(Debug, Clone, PartialEq)]
+pub enum P1Error {
+    /// Connection or HTTP-level failure (refused, reset, non-2xx status, ...).
+    Http(String),
+    /// The response body didn't match the expected shape: JSON path + message.
+    Parse(String, String),
+    /// The body isn't JSON at all (e.g. an HTML page from a proxy in front of the meter): body preview.
+    NotJson(String),
+    /// No response within `p1_timeout_ms`.
+    Timeout,
+    /// The meter values have not changed for this long - the dongle is serving a frozen reading.
+    Stale(Duration),
+}
+
+impl P1Error {
+    /// Short machine-readable kind, for the cycle outcome and metrics.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            P1Error::Http(_)     => "http",
+            P1Error::Parse(_, _) => "parse",
+            P1Error::NotJson(_)  => "not_json",
+            P1Error::Timeout     => "timeout",
+            P1Error::Stale(_)    => "stale",
         }
-    };
+    }
+
+    /// True for failures to get fresh data from the meter, as opposed to data that arrived but is malformed.
+    /// A non-JSON page means something in between answered instead of the meter, so it counts as connectivity.
+    pub fn is_connectivity(&self) -> bool {
+        !matches!(self, P1Error::Parse(_, _))
+    }
+}
 
-    let raw = match P1Data::from_json(&json) {
-        Ok(d)  => d,
-        Err(e) => {
-            error!("[P1] JSON parse error: {}", e);
-            return None;
+impl fmt::Display for P1Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            P1Error::Http(msg)        => write!(f, "HTTP error: {}", msg),
+            P1Error::Parse(path, msg) => write!(f, "JSON parse error at '{}': {}", path, msg),
+            P1Error::NotJson(preview) => write!(f, "non-JSON response (likely proxy/auth page): {}", preview),
+            P1Error::Timeout          => write!(f, "request timed out"),
+            P1Error::Stale(age)       => write!(f, "reading unchanged for {}s", age.as_secs()),
         }
-    };
+    }
+}
+
+impl std::error::Error for P1Error {}
 
-    let monthly_power_peak_timestamp_utc = match parse_p1_timestamp(&raw.montly_power_peak_timestamp) {
-        Ok(ts) => ts,
-        Err(e) => {
-            warn!("[P1] monthly_power_peak_timestamp parse failed ({}); using now()", e);
-            Utc::now()
+impl From<reqwest::Error> for P1Error {
+    fn from(e: reqwest::Error) -> Self {
+        if e.is_timeout() { P1Error::Timeout } else { P1Error::Http(e.to_string()) }
+    }
+}
+
+// --------------------------------------------------------------------------------------------------------------
+
+/// Characters of a non-JSON body quoted in the log.
+const BODY_PREVIEW_CHARS: usize = 120;
+
+/// Fail with `NotJson` when the body clearly isn't a JSON object. A reverse proxy or captive portal in front
+/// of the meter may answer with an HTML page and HTTP 200, which would otherwise surface as a cryptic parse
+/// error at line 1 column 1.
+fn reject_non_json(body: &str) -> Result<(), P1Error> {
+    if body.trim_start().starts_with('{') {
+        return Ok(());
+    }
+    let mut preview: String = body.split_whitespace().collect::<Vec<_>>().join(" ");
+    if let Some((cut, _)) = preview.char_indices().nth(BODY_PREVIEW_CHARS) {
+        preview.truncate(cut);
+        preview.push('…');
+    }
+    warn!("[P1] non-JSON response (likely proxy/auth page): {}", preview);
+    Err(P1Error::NotJson(preview))
+}
+
+// --------------------------------------------------------------------------------------------------------------
+
+/// Import kWh, export kWh and power W - the values that move on a live meter.
+type MeterValues = (f64, f64, f64);
+
+/// Last distinct meter values and when they first appeared.
+static LAST_CHANGE: Mutex<Option<(MeterValues, Instant)>> = Mutex::new(None);
+
+/// Fail with `Stale` when the meter values haven't moved for `stale_after` (zero disables the check).
+/// Active power changes practically every second on a live meter, so a frozen triple means frozen data.
+/// Returns how long the values have been unchanged.
+fn check_stale(raw: &P1Data, stale_after: Duration) -> Result<Duration, P1Error> {
+    let values: MeterValues = (raw.total_power_import_kwh, raw.total_power_export_kwh, raw.active_power_w);
+    let mut last = LAST_CHANGE.lock().unwrap_or_else(|e| e.into_inner());
+    match *last {
+        Some((seen, since)) if seen == values => {
+            if !stale_after.is_zero() && since.elapsed() >= stale_after {
+                return Err(P1Error::Stale(since.elapsed()));
+            }
+            Ok(since.elapsed())
         }
+        _ => {
+            *last = Some((values, Instant::now()));
+            Ok(Duration::ZERO)
+        }
+    }
+}
+
+// --------------------------------------------------------------------------------------------------------------
+
+/// Parse one of the reading's timestamps, falling back to now. A meter without the field (no gas meter, say)
+/// reports it empty, which isn't worth a warning every cycle.
+fn timestamp_or_now(timestamp: &str, name: &str, dst_gap: DstGap) -> DateTime<Utc> {
+    if timestamp.is_empty() {
+        return Utc::now();
+    }
+    parse_p1_timestamp(timestamp, dst_gap).unwrap_or_else(|e| {
+        warn!("[P1] {} timestamp parse failed ({}); using now()", name, e);
+        Utc::now()
+    })
+}
+
+/// Fetch and parse one P1 reading from the HomeWizard API.
+/// Every failure is returned as a `P1Error`; the caller skips (or falls back) and retries next cycle.
+/// `p1_api_version` selects the v1 (`/api/v1/data`) or the authenticated v2 (`/api/measurement`) API;
+/// both are returned in the v1 `P1Data` shape. Only the `p1_critical_fields` have to be present and valid.
+pub async fn read_p1(client: &reqwest::Client, config: &Config) -> Result<P1Reading, P1Error> {
+    let url = config.p1_url.as_str();
+    let json = match config.p1_api_version {
+        2 => fetch_p1_data_v2(client, url, &config.p1_api_token).await?,
+        _ => fetch_p1_data(client, url).await?,
     };
+    reject_non_json(&json)?;
 
-    let gas_timestamp_utc = match parse_p1_timestamp(&raw.gas_timestamp) {
-        Ok(ts) => ts,
-        Err(e) => {
-            warn!("[P1] gas_timestamp parse failed ({}); using now()", e);
-            Utc::now()
+    let critical: Vec<&str> = config.p1_critical_fields.iter().map(String::as_str).collect();
+    let parsed = match config.p1_api_version {
+        2 => {
+            let critical: Vec<&str> = critical.into_iter().map(P1DataV2::field_name).collect();
+            parse_lenient::<P1DataV2>(&json, &critical, |_| {})
+                .map(|(data, dropped)| (data.into_p1_data(), dropped))
+        }
+        _ => {
+            let critical: Vec<&str> = critical.into_iter().map(P1Data::field_name).collect();
+            parse_lenient::<P1Data>(&json, &critical, P1Data::resolve_aliases)
         }
     };
+    let (raw, dropped_fields) = parsed.inspect_err(|e| {
+        if let P1Error::Parse(path, message) = e {
+            error!("[P1] Response rejected: field '{}' - {}", path, message);
+        }
+    })?;
+
+    let unchanged_for = check_stale(&raw, Duration::from_secs(config.p1_stale_after_seconds))?;
+    report_unknown_externals(&raw);
 
-    Some(P1Reading {
+    let dst_gap = config.p1_dst_gap;
+    let monthly_power_peak_timestamp_utc =
+        timestamp_or_now(&raw.montly_power_peak_timestamp, "monthly_peak", dst_gap);
+    let gas_timestamp_utc                = timestamp_or_now(&raw.gas_timestamp, "gas", dst_gap);
+
+    Ok(P1Reading {
         raw,
         monthly_power_peak_timestamp_utc,
         gas_timestamp_utc,
+        unchanged_for,
+        dropped_fields,
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::handlers::indevolt::testing::MockIndevolt;
+    use crate::runtime::log_capture::capture;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    const READING: &str = r#"{"active_power_w": 512, "active_tariff": 2, "total_power_import_kwh": 1234.5,
+                              "total_power_export_kwh": 321.0}"#;
+
+    /// `read_p1` against a meter that answers every request with `status` and `body`.
+    async fn read_from(status: u16, body: &'static str, config: Config) -> Result<P1Reading, P1Error> {
+        let meter  = MockIndevolt::start(move |_| (status, body.to_string())).await;
+        let config = Config { p1_url: format!("{}/api/v1/data", meter.base_url), ..config };
+        read_p1(&build_p1_client(&config).unwrap(), &config).await
+    }
+
+    #[tokio::test]
+    async fn an_http_error_status_is_an_http_error() {
+        let error = read_from(503, READING, Config::default()).await.unwrap_err();
+        assert!(matches!(error, P1Error::Http(_)), "{:?}", error);
+        assert!(error.is_connectivity());
+    }
+
+    #[tokio::test]
+    async fn an_html_page_is_not_json() {
+        let error = read_from(200, "<html><body>Login</body></html>", Config::default()).await.unwrap_err();
+        assert!(matches!(error, P1Error::NotJson(ref preview) if preview.contains("Login")), "{:?}", error);
+    }
+
+    #[tokio::test]
+    async fn a_missing_critical_field_is_a_parse_error() {
+        let error = read_from(200, r#"{"active_power_w": 512}"#, Config::default()).await.unwrap_err();
+        assert!(matches!(error, P1Error::Parse(ref path, _) if path == "active_tariff"), "{:?}", error);
+        assert!(!error.is_connectivity());
+    }
+
+    #[tokio::test]
+    async fn a_meter_that_does_not_answer_times_out() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url      = format!("http://{}/api/v1/data", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            let mut held = Vec::new();
+            while let Ok((stream, _)) = listener.accept().await {
+                held.push(stream);
+            }
+        });
+        let config = Config { p1_url: url, p1_timeout_ms: 200, ..Config::default() };
+        let error  = read_p1(&build_p1_client(&config).unwrap(), &config).await.unwrap_err();
+        assert!(matches!(error, P1Error::Timeout), "{:?}", error);
+    }
+
+    #[tokio::test]
+    async fn a_frozen_reading_turns_stale() {
+        let frozen = r#"{"active_power_w": 777, "active_tariff": 1, "total_power_import_kwh": 9876.5,
+                         "total_power_export_kwh": 12.0}"#;
+        let config = Config { p1_stale_after_seconds: 1, ..Config::default() };
+        assert!(read_from(200, frozen, config.clone()).await.is_ok());
+        tokio::time::sleep(Duration::from_millis(1100)).await;
+
+        let error = read_from(200, frozen, config).await.unwrap_err();
+        assert!(matches!(error, P1Error::Stale(_)), "{:?}", error);
+    }
+
+    #[test]
+    fn a_missing_ca_certificate_is_reported() {
+        let path   = Some("/nonexistent/homewizard-ca.pem".to_string());
+        let config = Config { p1_ca_cert_path: path, ..Config::default() };
+        let error  = build_p1_client(&config).unwrap_err();
+        assert!(error.contains("/nonexistent/homewizard-ca.pem"), "{}", error);
+    }
+
+    #[test]
+    fn the_client_is_built_with_the_configured_pool() {
+        let config = Config { pool_idle_timeout_seconds: 30, pool_max_idle_per_host: 2, ..Config::default() };
+        let (_, lines) = capture(|| build_p1_client(&config).unwrap());
+        let pool = "pool_idle_timeout=30s pool_max_idle_per_host=2";
+        assert!(lines.iter().any(|(_, line)| line.contains(pool)), "{lines:?}");
+    }
+
+    #[tokio::test]
+    async fn consecutive_reads_reuse_one_connection() {
+        let listener    = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url         = format!("http://{}/api/v1/data", listener.local_addr().unwrap());
+        let response    = format!("HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n{}", READING.len(), READING);
+        let connections = Arc::new(AtomicUsize::new(0));
+        let accepted    = Arc::clone(&connections);
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                accepted.fetch_add(1, Ordering::SeqCst);
+                let response = response.clone();
+                tokio::spawn(async move {
+                    let mut chunk = [0u8; 1024];
+                    while matches!(stream.read(&mut chunk).await, Ok(n) if n > 0) {
+                        if stream.write_all(response.as_bytes()).await.is_err() {
+                            break;
+                        }
+                    }
+                });
+            }
+        });
+
+        let client = build_p1_client(&Config::default()).unwrap();
+        for _ in 0..3 {
+            let body = client.get(&url).send().await.unwrap().text().await.unwrap();
+            assert_eq!(body, READING);
+        }
+        assert_eq!(connections.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn invalid_certificates_are_only_accepted_on_request() {
+        assert!(build_p1_client(&Config::default()).is_ok());
+        assert!(build_p1_client(&Config { p1_accept_invalid_certs: true, ..Config::default() }).is_ok());
+    }
+
+    fn parse_v1(json: &str, critical: &[&str]) -> Result<(P1Data, Vec<String>), P1Error> {
+        let critical: Vec<&str> = critical.iter().map(|name| P1Data::field_name(name)).collect();
+        parse_lenient::<P1Data>(json, &critical, P1Data::resolve_aliases)
+    }
+
+    #[test]
+    fn critical_fields_accept_either_spelling_of_the_monthly_peak() {
+        let corrected = r#"{"active_power_w": 500, "monthly_power_peak_w": 3500.0}"#;
+        let (data, _) = parse_v1(corrected, &["active_power_w", "montly_power_peak_w"]).unwrap();
+        assert_eq!(data.montly_power_peak_w, 3500.0);
+
+        let typo = r#"{"active_power_w": 500, "montly_power_peak_w": 3500.0}"#;
+        assert!(parse_v1(typo, &["monthly_power_peak_w"]).is_ok());
+        assert!(parse_v1(r#"{"active_power_w": 500}"#, &["monthly_power_peak_w"]).is_err());
+    }
+
+    #[test]
+    fn both_spellings_in_one_response_parse() {
+        let json = r#"{"active_power_w": 500, "montly_power_peak_w": 3500.0, "monthly_power_peak_w": 4000.0}"#;
+        let (data, dropped) = parse_v1(json, &["active_power_w"]).unwrap();
+        assert_eq!(data.montly_power_peak_w, 3500.0);
+        assert!(dropped.is_empty());
+    }
+
+    #[test]
+    fn a_wrong_type_in_a_critical_field_names_that_field() {
+        let json = r#"{"active_power_w": 500, "active_tariff": "peak"}"#;
+        let err  = parse_v1(json, &["active_power_w", "active_tariff"]).unwrap_err();
+
+        assert!(matches!(&err, P1Error::Parse(path, _) if path == "active_tariff"), "{:?}", err);
+        assert!(err.to_string().contains("'active_tariff'"), "{}", err);
+    }
+
+    #[test]
+    fn a_wrong_type_in_another_field_is_dropped() {
+        let json = r#"{"active_power_w": 500, "active_voltage_l1_v": "n/a"}"#;
+        let (data, dropped) = parse_v1(json, &["active_power_w"]).unwrap();
+
+        assert_eq!(data.active_power_w, 500.0);
+        assert_eq!(dropped, vec!["active_voltage_l1_v".to_string()]);
+    }
+
+    #[test]
+    fn a_timestamp_in_the_spring_forward_gap_is_shifted_forward() {
+        // Brussels skips 02:00-03:00 on 29 March 2026; 02:30 is read with the winter offset, i.e. as 03:30 CEST.
+        let (parsed, lines) = capture(|| parse_p1_timestamp("260329023000", DstGap::ShiftForward));
+        assert_eq!(parsed.unwrap(), Utc.with_ymd_and_hms(2026, 3, 29, 1, 30, 0).unwrap());
+        assert!(lines.iter().any(|(_, line)| line.contains("spring-forward DST gap")), "{lines:?}");
+
+        assert!(parse_p1_timestamp("260329023000", DstGap::Reject).is_err());
+    }
+
+    #[test]
+    fn timestamps_around_the_gap_keep_their_own_offset() {
+        let before = parse_p1_timestamp("260329015959", DstGap::Reject).unwrap();
+        let after  = parse_p1_timestamp("260329030000", DstGap::Reject).unwrap();
+        assert_eq!(before, Utc.with_ymd_and_hms(2026, 3, 29, 0, 59, 59).unwrap());
+        assert_eq!(after, Utc.with_ymd_and_hms(2026, 3, 29, 1, 0, 0).unwrap());
+    }
+}
@@ -0,0 +1,9 @@
+pub mod configuration;
+pub mod models;
+pub mod handlers;
+pub mod runtime;
+pub mod analytics;
+pub mod sinks;
+pub mod optimiser;
+pub mod prices;
+pub mod reporting;
@@ -1,17 +1,79 @@
+use std::io::Write;
+use std::sync::Arc;
 use std::time::Instant;
+use chrono::Utc;
 use log::LevelFilter;
-use tokio::time::{sleep, Duration};
+use tracing::Instrument;
+use tokio::sync::watch;
+use tokio::time::Duration;
 
 // --------------------------------------------------------------------------------------------------------------
 
-mod configuration;
-use configuration::config::load_config;
-
-mod models;
-
-mod handlers;
-use handlers::p1::reader::read_p1;
-use handlers::indevolt::reader::read_battery_snapshot;
+use energy_management_system::analytics::data_quality::data_quality;
+use energy_management_system::analytics::idle_drift::IdleDriftDetector;
+use energy_management_system::analytics::pv_strings::PvStringMonitor;
+use energy_management_system::configuration::config::{
+    load_config, Config, MissedTicks, PriceSourceKind, Strategy,
+};
+use energy_management_system::configuration::effective::{log_effective_config, redacted};
+use energy_management_system::configuration::profiles::ActiveProfile;
+use energy_management_system::handlers::p1::reader::{build_p1_client, read_p1, P1Error, P1Reading};
+use energy_management_system::handlers::gate::{read_gate, Signal};
+use energy_management_system::handlers::indevolt::plausibility::PlausibilityGuard;
+use energy_management_system::handlers::indevolt::reader::{read_battery_snapshot, ID_BATTERY_SOC};
+use energy_management_system::models::indevolt_models::BatterySnapshot;
+use energy_management_system::optimiser;
+use energy_management_system::optimiser::allowed_modes::{mode_allowed, restrict_to_allowed};
+use energy_management_system::optimiser::confidence::apply_data_quality;
+use energy_management_system::optimiser::decision::{Decision, Direction};
+use energy_management_system::optimiser::dispatch::Dispatcher;
+use energy_management_system::optimiser::ev::pause_discharge_for_ev;
+use energy_management_system::optimiser::grid::best_grid_power_w;
+use energy_management_system::optimiser::interlock::guard_grid_charge;
+use energy_management_system::optimiser::islanding::{
+    backup_runtime, check_frequency, grid_outage, islanding_verdict,
+};
+use energy_management_system::optimiser::peak::{shaving_target_w, MonthlyPeak};
+use energy_management_system::optimiser::plan;
+use energy_management_system::optimiser::ramp::ramp_power;
+use energy_management_system::optimiser::settle::hold_during_settle;
+use energy_management_system::optimiser::shadow::{record_shadow, shadow_verdict};
+use energy_management_system::optimiser::spike::spike_verdict;
+use energy_management_system::optimiser::trace::{DecisionTrace, RuleTrace, TraceInputs};
+#[cfg(feature = "prices")]
+use energy_management_system::prices::csv::CsvSource;
+#[cfg(feature = "prices")]
+use energy_management_system::prices::entsoe::EntsoeSource;
+#[cfg(feature = "prices")]
+use energy_management_system::prices::refresh::price_worker;
+#[cfg(feature = "prices")]
+use energy_management_system::prices::tibber::TibberSource;
+#[cfg(feature = "prices")]
+use energy_management_system::prices::PriceSource;
+use energy_management_system::prices::PriceCurve;
+use energy_management_system::reporting::history::{DayHistory, SharedDayHistory};
+use energy_management_system::reporting::render::write_report;
+use energy_management_system::runtime::api::{api_server, ApiState};
+use energy_management_system::runtime::cadence::{cycle_ticker, reloaded_ticker};
+use energy_management_system::runtime::clock::{check_clock_skew, clock_skew_seconds, local_now};
+use energy_management_system::runtime::display::PowerFormat;
+use energy_management_system::runtime::loop_state::{current_cycle_id, LoopState};
+use energy_management_system::runtime::outcome::CycleOutcome;
+use energy_management_system::runtime::quiet::QuietIdle;
+use energy_management_system::runtime::recovery::recover_realtime_mode;
+use energy_management_system::runtime::recent::{RecentCycles, SharedRecentCycles};
+use energy_management_system::runtime::reload::reload_on_sighup;
+use energy_management_system::sinks::health::{SharedSinkHealth, SinkHealthTable};
+use energy_management_system::runtime::pipeline::{record_channel, CycleRecord, RecordSender};
+use energy_management_system::runtime::state_file::{load_state, save_state};
+use energy_management_system::runtime::shutdown::{hand_back, wait_for_signal, Shutdown, ShutdownSignal};
+use energy_management_system::runtime::supervisor::Supervisor;
+use energy_management_system::runtime::telemetry;
+use energy_management_system::runtime::watchdog::{self, watchdog};
+#[cfg(feature = "influxdb")]
+use energy_management_system::sinks::influxdb::{battery_line, device_day_line, p1_line, InfluxDbSink};
+#[cfg(feature = "influxdb")]
+use energy_management_system::sinks::sink::sink_worker;
 
 // --------------------------------------------------------------------------------------------------------------
 // Device model string - adjust if yours differs from the n8n logging.
@@ -21,72 +83,465 @@ const DEVICE_MODEL: &str = "PowerFlex2000";
 
 #[tokio::main]
 async fn main() {
-    let config = load_config();
+    // The logger isn't up yet: a config that can't be loaded is reported on stderr.
+    let config = match load_config() {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("Cannot start: {}", e);
+            std::process::exit(1);
+        }
+    };
 
     // Initialise logger.
+    // Every line carries the ID of the cycle it was emitted in, so interleaved logs can be correlated.
     if let Err(e) = env_logger::Builder::new()
         .filter_level(config.log_level.parse::<LevelFilter>().unwrap_or(LevelFilter::Info))
+        .format(|buf, record| {
+            writeln!(
+                buf,
+                "[{} {:<5} {} c{}] {}",
+                buf.timestamp(),
+                record.level(),
+                record.target(),
+                current_cycle_id(),
+                record.args()
+            )
+        })
         .try_init()
     {
         eprintln!("Failed to initialise logger: {}", e);
         panic!("Cannot start without logging");
     }
 
+    // Subcommand: `run` (default) starts the control loop; `read` and `config` are one-shot tools.
+    match std::env::args().nth(1).as_deref().unwrap_or("run") {
+        "run"    => {}
+        "read"   => return read_once(&config).await,
+        "config" => return print_config(&config),
+        other    => {
+            eprintln!("Unknown command '{}'. Usage: energy_management_system [run|read|config]", other);
+            std::process::exit(2);
+        }
+    }
+
     log::info!("=== Energy Management System starting ===");
-    log::info!("P1 URL:       {}", config.p1_url);
-    log::info!("Indevolt URL: {}", config.indevolt_url);
-    log::info!("Poll interval: {}s", config.poll_interval_seconds);
+    log_effective_config(&config);
+    let (charge_eff, discharge_eff) = config.efficiencies();
+    log::info!(
+        "Efficiency:   charge {:.1}% discharge {:.1}% (round trip {:.1}%, break-even spread {:.1}%)",
+        charge_eff * 100.0, discharge_eff * 100.0, config.round_trip_efficiency() * 100.0,
+        config.break_even_spread_percent()
+    );
 
-    let interval = Duration::from_secs(config.poll_interval_seconds);
+    // Span export (no-op unless built with the "otel" feature and `otel_endpoint` is set).
+    let _telemetry = telemetry::init(&config);
 
     // ----------------------------------------------------------------------------------------------------------
-    // Single control loop: read P1 → read battery → decide → act → sleep.
-    // Keeping this sequential means every battery decision is based on the
-    // freshest possible P1 reading from the same cycle.
+    // Every subsystem runs as a supervised task: a panic in one of them is logged and the task is restarted
+    // with backoff, without taking down the rest of the process. The control loop is the primary task.
+    // SIGINT/SIGTERM shut them down in order: loop, inverter hand-back, sink flush (see `runtime/shutdown.rs`).
+    let mut supervisor = Supervisor::new();
+    let shutdown = Shutdown::new();
+
+    // The loop publishes one record per cycle; every enabled sink consumes them in its own worker, without ever
+    // blocking the loop or each other. Each worker reports its writes in the shared health table.
+    let records = record_channel();
+    let sink_health = SinkHealthTable::shared();
+
+    spawn_influxdb_writer(&mut supervisor, &config, &records, &shutdown, &sink_health);
+
+    // Prices are refreshed in the background; the loop only reads the latest curve from the watch channel.
+    let (price_tx, prices) = watch::channel(Arc::new(PriceCurve::default()));
+    spawn_price_source(&mut supervisor, &config, price_tx);
+
+    // SIGHUP reloads config.json; the loop takes a valid new configuration between cycles.
+    let (config_tx, settings) = watch::channel(Arc::new(config.clone()));
+    supervisor.spawn("config-reload", move || reload_on_sighup(config_tx.clone()));
+
+    // Independent of the loop: restores self-consumption mode if the loop stops starting cycles.
+    if config.watchdog_timeout_seconds > 0 {
+        let watchdog_settings = settings.clone();
+        supervisor.spawn("watchdog", move || watchdog(watchdog_settings.clone()));
+    }
+
+    // The recent-cycles buffer and the day history outlive loop restarts; the API task only reads them.
+    let recent  = RecentCycles::shared(config.recent_cycles);
+    let history = DayHistory::shared();
+    if !config.api_listen.is_empty() {
+        let listen = config.api_listen.clone();
+        let state  = ApiState {
+            recent:   recent.clone(),
+            history:  history.clone(),
+            sinks:    sink_health.clone(),
+            settings: settings.clone(),
+        };
+        supervisor.spawn("api", move || api_server(listen.clone(), state.clone()));
+    }
+
+    let loop_shutdown = shutdown.signal();
+    supervisor.spawn_primary("control-loop", move || {
+        control_loop(
+            settings.clone(),
+            records.clone(),
+            prices.clone(),
+            recent.clone(),
+            history.clone(),
+            loop_shutdown.clone(),
+        )
+    });
+
+    let timeout = Duration::from_secs(config.shutdown_timeout_seconds);
+    supervisor.run(wait_for_signal(), shutdown, timeout).await;
+}
+
+#[cfg(feature = "influxdb")]
+fn spawn_influxdb_writer(
+    supervisor: &mut Supervisor,
+    config: &Config,
+    records: &RecordSender,
+    shutdown: &Shutdown,
+    health: &SharedSinkHealth,
+) {
+    if InfluxDbSink::from_config(config).is_none() {
+        return;
+    }
+    log::info!("InfluxDB:     {} (bucket {})", config.influxdb_url, config.influxdb_bucket);
+    let worker_config = config.clone();
+    let worker_records = records.clone();
+    let worker_shutdown = shutdown.signal();
+    let worker_health = health.clone();
+    supervisor.spawn_sink("influxdb-writer", move || {
+        let sink = InfluxDbSink::from_config(&worker_config);
+        let rx = worker_records.subscribe();
+        let (shutdown, health) = (worker_shutdown.clone(), worker_health.clone());
+        async move {
+            if let Some(sink) = sink {
+                sink_worker(sink, rx, shutdown, health).await;
+            }
+        }
+    });
+}
+
+#[cfg(not(feature = "influxdb"))]
+fn spawn_influxdb_writer(
+    _supervisor: &mut Supervisor,
+    config: &Config,
+    _records: &RecordSender,
+    _: &Shutdown,
+    _: &SharedSinkHealth,
+) {
+    if !config.influxdb_url.is_empty() {
+        log::warn!("[InfluxDB] influxdb_url ignored - built without the \"influxdb\" feature");
+    }
+}
+
+#[cfg(feature = "prices")]
+fn spawn_price_source(supervisor: &mut Supervisor, config: &Config, tx: watch::Sender<Arc<PriceCurve>>) {
+    match config.price_source {
+        PriceSourceKind::None   => {}
+        PriceSourceKind::Tibber => spawn_price_refresh(supervisor, TibberSource::from_config(config), tx),
+        PriceSourceKind::Entsoe => spawn_price_refresh(supervisor, EntsoeSource::from_config(config), tx),
+        PriceSourceKind::Csv    => spawn_price_refresh(supervisor, CsvSource::from_config(config), tx),
+    }
+}
+
+#[cfg(not(feature = "prices"))]
+fn spawn_price_source(_supervisor: &mut Supervisor, config: &Config, _tx: watch::Sender<Arc<PriceCurve>>) {
+    if config.price_source != PriceSourceKind::None {
+        log::warn!("[Prices] price_source ignored - built without the \"prices\" feature");
+    }
+}
+
+/// Run the price refresh task for `source`, or log why prices stay unavailable.
+#[cfg(feature = "prices")]
+fn spawn_price_refresh<S: PriceSource + Clone + 'static>(
+    supervisor: &mut Supervisor,
+    source: Result<S, String>,
+    tx: watch::Sender<Arc<PriceCurve>>,
+) {
+    match source {
+        Ok(source) => supervisor.spawn("price-refresh", move || price_worker(source.clone(), tx.clone())),
+        Err(e)     => log::error!("[Prices] {} - running without prices", e),
+    }
+}
+
+// --------------------------------------------------------------------------------------------------------------
+
+async fn control_loop(
+    mut settings: watch::Receiver<Arc<Config>>,
+    records: RecordSender,
+    prices: watch::Receiver<Arc<PriceCurve>>,
+    recent: SharedRecentCycles,
+    history: SharedDayHistory,
+    mut shutdown: ShutdownSignal,
+) {
+    let mut config = (**settings.borrow_and_update()).clone();
+
+    // One long-lived client so the P1 dongle sees a single reused keep-alive connection.
+    let p1_client = match build_p1_client(&config) {
+        Ok(client) => client,
+        Err(e) => {
+            log::error!("[P1] Failed to build HTTP client: {}", e);
+            return;
+        }
+    };
+    let gate_client = reqwest::Client::new();
+
+    let mut idle_drift = IdleDriftDetector::new(
+        config.idle_drift_warn_percent_per_hour,
+        config.idle_drift_min_window_minutes,
+    );
+    let mut plausibility = PlausibilityGuard::new();
+    let mut quiet = QuietIdle::new();
+    let mut pv_strings = PvStringMonitor::new(
+        config.pv_string_imbalance_ratio,
+        config.pv_string_min_power_w,
+        config.pv_string_imbalance_minutes,
+    );
+
+    let mut state = LoopState { recent, history, ..LoopState::new() };
+    if !config.state_file_path.is_empty() {
+        if let Some(saved) = load_state(&config.state_file_path, config.state_max_age_seconds) {
+            state.restore(saved);
+        }
+    }
+    let mut last_state_save = Instant::now();
+    let mut dispatcher = configured_dispatcher(Dispatcher::new(), &config);
+
+    // A device left in real-time mode by a crashed run is put back into self-consumption before the first cycle.
+    let startup = read_battery_snapshot(&config, DEVICE_MODEL).await;
+    recover_realtime_mode(&config.indevolt_url, startup.mode, state.last_decision.as_ref()).await;
+
+    // ----------------------------------------------------------------------------------------------------------
+    // Single control loop: wait for the tick → read P1 + battery → decide → act.
+    // The two reads run concurrently; everything after them stays sequential, so every battery decision is
+    // based on the freshest possible P1 reading from the same cycle.
+    let mut ticker = cycle_ticker(&config);
+    // Last cycle's battery snapshot and settings, while the load-spike fast path watches P1 until the next tick.
+    let mut spike_watch: Option<(BatterySnapshot, Config)> = None;
     loop {
+        let poll = Duration::from_millis(config.fast_discharge_poll_ms.max(100));
+        loop {
+            tokio::select! {
+                _ = ticker.tick() => break,
+                _ = shutdown.stopped() => return stop_control_loop(&mut state, &config).await,
+                Ok(()) = settings.changed() => {
+                    let reloaded = (**settings.borrow_and_update()).clone();
+                    if reloaded.poll_interval_seconds != config.poll_interval_seconds
+                        || reloaded.align_to_wallclock != config.align_to_wallclock
+                    {
+                        log::info!("[EMS] Poll interval now {} s", reloaded.poll_interval_seconds);
+                        ticker = reloaded_ticker(&reloaded);
+                    }
+                    // The dispatcher and the detectors keep their state and take the new settings.
+                    dispatcher = configured_dispatcher(std::mem::take(&mut dispatcher), &reloaded);
+                    idle_drift.set_thresholds(
+                        reloaded.idle_drift_warn_percent_per_hour,
+                        reloaded.idle_drift_min_window_minutes,
+                    );
+                    pv_strings.set_thresholds(
+                        reloaded.pv_string_imbalance_ratio,
+                        reloaded.pv_string_min_power_w,
+                        reloaded.pv_string_imbalance_minutes,
+                    );
+                    config = reloaded;
+                }
+                _ = tokio::time::sleep(poll), if spike_watch.is_some() => {
+                    if let Some((battery, config)) = &spike_watch {
+                        if react_to_load_spike(&p1_client, &mut dispatcher, &mut state, battery, config).await {
+                            spike_watch = None;
+                        }
+                    }
+                }
+            }
+        }
+        spike_watch = None;
+        watchdog::pet();
+        let interval    = Duration::from_secs(config.poll_interval_seconds);
         let cycle_start = Instant::now();
+        let cycle_id    = state.begin_cycle();
+        let cycle_span  = tracing::info_span!(
+            "cycle", cycle_id, soc = tracing::field::Empty, decision = tracing::field::Empty
+        );
 
-        // Step 1: read the smart meter.
-        let p1 = read_p1(&config.p1_url).await;
+        // Step 0: settings in force today and at this time of day. Shadows the startup config for the rest of
+        // the cycle, so everything below sees the calendar profile, discharge floor and curtailment applied.
+        let now_local = local_now();
+        let (config, profile) = config.for_date(now_local.date_naive());
+        if profile != state.active_profile {
+            if let ActiveProfile::Overlap(_) = profile {
+                log::warn!("[EMS] Active profile: {} - using the top-level settings", profile);
+            } else {
+                log::info!("[EMS] Active profile: {}", profile);
+            }
+            state.active_profile = profile;
+        }
+        let config = config.for_time(now_local.time());
+        if state.discharge_floor != Some(config.peak_shaving_reserve_soc_percent) {
+            log::info!("[EMS] Discharge floor: {:.1}%", config.peak_shaving_reserve_soc_percent);
+            state.discharge_floor = Some(config.peak_shaving_reserve_soc_percent);
+        }
+        if config.feed_in_curtailed != state.feed_in_curtailed {
+            if config.feed_in_curtailed {
+                log::info!("[EMS] Feed-in curtailment active - storing all solar surplus");
+            } else {
+                log::info!("[EMS] Feed-in curtailment lifted");
+            }
+            state.feed_in_curtailed = config.feed_in_curtailed;
+        }
+        let pf = PowerFormat::from_config(&config);
 
-        // Step 2: read the battery state.
-        let battery = read_battery_snapshot(&config.indevolt_url, DEVICE_MODEL).await;
+        // Steps 1+2: read the smart meter and the battery state concurrently. Both reads start at the same
+        // moment, so the decision below still sees two readings from the same instant. The external signals
+        // (grid-charge gate, EV charging), if any, are read alongside.
+        // Failed reads are retried while the cycle's retry budget lasts.
+        let reads_start = Instant::now();
+        state.retry_budget.start_cycle(Duration::from_millis(config.cycle_retry_budget_ms));
+        let budget = &state.retry_budget;
+        let ((p1_result, p1_elapsed), (mut battery, battery_elapsed), gate, ev_signal) = tokio::join!(
+            timed(
+                budget
+                    .run("P1 read", p1_retryable, || read_p1(&p1_client, &config))
+                    .instrument(tracing::info_span!(parent: &cycle_span, "p1_read"))
+            ),
+            timed(
+                budget
+                    .run("battery read", battery_unread, || read_battery_snapshot(&config, DEVICE_MODEL))
+                    .instrument(tracing::info_span!(parent: &cycle_span, "battery_read"))
+            ),
+            read_gate(&gate_client, &config.grid_charge_gate_url, &config.grid_charge_gate_file),
+            read_gate(&gate_client, &config.ev_charging_url, &config.ev_charging_file),
+        );
+        let reads_elapsed = reads_start.elapsed();
+        plausibility.apply(&mut battery, &config);
+        let p1 = p1_result.as_ref().ok();
+        cycle_span.record("soc", battery.battery_soc);
+        log::debug!(
+            "[EMS] Reads took {:?} (P1 {:?}, battery {:?}; {:?} saved vs sequential)",
+            reads_elapsed,
+            p1_elapsed,
+            battery_elapsed,
+            (p1_elapsed + battery_elapsed).saturating_sub(reads_elapsed),
+        );
+
+        // Step 2a: capacity-tariff target for this cycle (shadows config again, like the profile above).
+        state.monthly_peak = MonthlyPeak::observe(state.monthly_peak, p1, now_local.date_naive());
+        let mut config = config;
+        config.battery_max_desired_grid_peak_w = shaving_target_w(&config, state.monthly_peak.as_ref());
+        if state.peak_target_w != Some(config.battery_max_desired_grid_peak_w) {
+            log::info!(
+                "[Optimiser] Peak-shaving target {}W (month peak {})",
+                config.battery_max_desired_grid_peak_w,
+                state.monthly_peak.map_or("unknown".to_string(), |p| pf.power(p.peak_w)),
+            );
+            state.peak_target_w = Some(config.battery_max_desired_grid_peak_w);
+        }
+
+        // A "deny" from the external gate switches grid charging off for this cycle.
+        let fallback = config.grid_charge_gate_on_error.value();
+        if state.grid_charge_gate.resolve(Signal::GridChargeGate, gate, fallback) == Some(false) {
+            config.grid_charging_enabled = false;
+        }
+        state.ev_charging = state.ev_signal.resolve(Signal::EvCharging, ev_signal, None).unwrap_or(false);
+
+        // The device's own MinSOC / MaxSOC, when polled, are reconciled with the config (shadows config again).
+        let (device_min, device_max) = &mut state.device_soc_limits;
+        *device_min = battery.device_min_soc_percent.or(*device_min);
+        *device_max = battery.device_max_soc_percent.or(*device_max);
+        let (config, soc_mismatches) = config.with_device_soc_limits(*device_min, *device_max);
+        if soc_mismatches != state.soc_mismatches {
+            for mismatch in soc_mismatches.iter().filter(|m| !state.soc_mismatches.contains(m)) {
+                log::warn!("[Config] SOC limits disagree - {}", mismatch);
+            }
+            if soc_mismatches.is_empty() {
+                log::info!("[Config] Config and device SOC limits agree");
+            }
+            state.soc_mismatches = soc_mismatches;
+        }
+
+        // Step 2b: import price for this hour, when a price source is configured.
+        let price_now = prices.borrow().at(Utc::now()).copied();
+        if let Some(price) = price_now.filter(|p| state.price_slot != Some(p.starts_at)) {
+            log::info!(
+                "[Prices] Import price {:.4} €/kWh from {}",
+                price.price_eur_per_kwh,
+                price.starts_at.with_timezone(&now_local.timezone()).format("%H:%M"),
+            );
+            state.price_slot = Some(price.starts_at);
+        }
 
         // Step 3: log what we have.
-        match &p1 {
-            Some(reading) => {
+        match &p1_result {
+            Ok(reading) => {
                 let r = &reading.raw;
                 log::debug!(
-                    "[P1] tariff={} power={:+.0}W import={:.3}kWh export={:.3}kWh",
+                    "[P1] tariff={} power={} import={:.3}kWh export={:.3}kWh",
                     r.active_tariff,
-                    r.active_power_w,
+                    pf.signed(r.active_power_w),
                     r.total_power_import_kwh,
                     r.total_power_export_kwh,
                 );
                 log::debug!(
-                    "[P1] L1={:+.0}W L2={:+.0}W L3={:+.0}W | {:.1}V {:.1}V {:.1}V",
-                    r.active_power_l1_w, r.active_power_l2_w, r.active_power_l3_w,
+                    "[P1] L1={} L2={} L3={} | {:.1}V {:.1}V {:.1}V",
+                    pf.signed(r.active_power_l1_w), pf.signed(r.active_power_l2_w), pf.signed(r.active_power_l3_w),
                     r.active_voltage_l1_v, r.active_voltage_l2_v, r.active_voltage_l3_v,
                 );
+                for ext in &r.external {
+                    log::debug!(
+                        "[P1] external {} ({}) role={} value={} {}",
+                        ext.r#type,
+                        ext.unique_id,
+                        config.p1_external_roles.get(&ext.unique_id).map_or("-", String::as_str),
+                        ext.value,
+                        ext.unit,
+                    );
+                }
+                // Warn once per excursion; readings without a gas timestamp keep the last state.
+                let meter_clock =
+                    reading.meter_clock(config.p1_dst_gap).filter(|_| config.clock_skew_warn_seconds > 0);
+                if let Some((stamped, max_lag)) = meter_clock {
+                    let skew_s = clock_skew_seconds(stamped, max_lag, Utc::now());
+                    log::debug!("[P1] Meter clock skew {}s (gas stamp {})", skew_s, stamped);
+                    let warn_s = config.clock_skew_warn_seconds;
+                    if let Some(alarm) = check_clock_skew(skew_s, warn_s, &mut state.clock_skew_alarm) {
+                        state.report.alarm(Utc::now(), alarm);
+                    }
+                }
             }
-            None => log::warn!("[P1] No reading this cycle - skipping optimiser."),
+            Err(e) if config.fallback_to_indevolt_meter && e.is_connectivity() => {
+                log::warn!("[P1] {} - falling back to the Indevolt meter.", e)
+            }
+            Err(e) => log::warn!("[P1] {} - skipping optimiser.", e),
         }
 
         log::debug!(
-            "[Battery] SOC={:.1}% state={} mode={} power={:+}W meter={:+}W",
+            "[Battery] SOC={:.1}% (raw {:.1}%) state={} mode={} power={} meter={}",
             battery.battery_soc,
+            battery.battery_soc_raw,
             battery.battery_state,
             battery.working_mode,
-            battery.battery_power_w,
-            battery.meter_power_w,
+            pf.signed(battery.battery_power_w),
+            pf.signed(battery.meter_power_w),
         );
         log::debug!(
-            "[Battery] DC1={:+}W DC2={:+}W | AC_out={:+}W AC_in={:+}W",
-            battery.dc_input_power1_w,
-            battery.dc_input_power2_w,
-            battery.total_ac_output_power_w,
-            battery.total_ac_input_power_w,
+            "[Battery] DC1={} DC2={} | AC_out={} AC_in={}",
+            pf.signed(battery.dc_input_power1_w),
+            pf.signed(battery.dc_input_power2_w),
+            pf.signed(battery.total_ac_output_power_w),
+            pf.signed(battery.total_ac_input_power_w),
         );
+        if let Some(t) = battery.max_temperature_c() {
+            log::debug!(
+                "[Battery] temperature inverter={:?}°C battery={:?}°C (max {:.1}°C)",
+                battery.inverter_temperature_c, battery.battery_temperature_c, t
+            );
+        }
+        if let Some(hz) = battery.grid_frequency_hz {
+            log::debug!("[Battery] grid frequency={:.2}Hz voltage={:?}V", hz, battery.grid_voltage_v);
+        }
+        if let Some(alarm) = check_frequency(&battery, &config, &mut state.frequency_alarm) {
+            state.report.alarm(Utc::now(), alarm);
+        }
         log::debug!(
             "[Battery] daily prod={:.3}kWh chrg={:.3}kWh dischrg={:.3}kWh",
             battery.daily_production_kwh,
@@ -94,39 +549,424 @@ async fn main() {
             battery.daily_discharging_kwh,
         );
 
+        // Step 3a: analytics on the battery snapshot.
+        if let Some(rate) = idle_drift.observe(Utc::now(), &battery) {
+            log::debug!("[Analytics] Idle SOC drift {:.2}%/h", rate);
+        }
+        pv_strings.observe(Utc::now(), now_local.date_naive(), &battery);
+        let device_day = state.device_days.observe(Utc::now(), &battery);
+        if let Some(day) = &device_day {
+            log::info!(
+                "[Analytics] Device day {} closed by counter reset: production {:.2} kWh, charged {:.2} kWh, \
+                 discharged {:.2} kWh",
+                day.date, day.production_kwh, day.charging_kwh, day.discharging_kwh
+            );
+        }
+        if config.soh_tracking_enabled {
+            state.soh.observe(&battery, now_local.date_naive(), &config);
+        }
+
+        // Step 3c: daily report. The first cycle after local midnight closes the previous day.
+        let price = prices.borrow().at(Utc::now()).map(|p| p.price_eur_per_kwh);
+        let closed = state.report.observe(Utc::now(), p1, &battery, price, state.last_decision.as_ref());
+        if let Some(report) = closed.filter(|_| !config.report_dir.is_empty()) {
+            match write_report(&report, &config.report_dir, config.report_format) {
+                Ok(path) => log::info!("[Report] Report for {} written to {}", report.date, path.display()),
+                Err(e)   => log::warn!("{}", e),
+            }
+        }
+        *state.history.lock().unwrap_or_else(|e| e.into_inner()) = state.report.history();
+
         // Step 3b: reconciliation line — P1 vs Indevolt meter vs difference.
-        if let Some(ref reading) = p1 {
+        if let Some(reading) = p1 {
             let p1_w      = reading.raw.active_power_w as i32;
             let inv_w     = battery.meter_power_w;
             let diff_w    = p1_w - inv_w;
-            log::info!(
-                "[EMS] P1={:+}W  Indevolt={:+}W  diff={:+}W | SOC={:.1}% {} {} bat={:+}W",
-                p1_w, inv_w, diff_w,
+            log::log!(
+                quiet.level(),
+                "[EMS] P1={}  Indevolt={}  diff={} | SOC={:.1}% {} {} bat={}",
+                pf.signed(p1_w), pf.signed(inv_w), pf.signed(diff_w),
                 battery.battery_soc,
                 battery.battery_state,
                 battery.working_mode,
-                battery.battery_power_w,
+                pf.signed(battery.battery_power_w),
             );
         } else {
             log::warn!("[EMS] No P1 reading this cycle.");
         }
 
-        // Step 4: optimiser (placeholder - receives both readings together).
-        // if let Some(p1_reading) = p1 {
-        //     optimiser::run(&p1_reading, &battery, &config).await;
-        // }
+        // Step 3c: how far this cycle's readings can be trusted.
+        let quality = data_quality(p1_result.as_ref(), &battery, &config);
+        log::debug!(
+            "[EMS] Data quality {} (penalties p1={:.2} keys={:.2} reconciliation={:.2} consistency={:.2})",
+            quality.score, quality.p1, quality.battery_keys, quality.reconciliation, quality.consistency,
+        );
 
-        // Sleep for whatever time remains in the interval.
+        // Step 3d: grid-charged energy and battery throughput today, for the daily caps.
+        state.grid_charge_cap.observe(Utc::now(), &battery, state.last_decision.as_ref());
+        state.throughput_cap.observe(Utc::now(), &battery);
+
+        // Step 4: optimiser + dispatch (needs a grid measurement and the battery reading from this cycle).
+        // In a grid outage the islanding policy replaces the strategy.
+        let outage = grid_outage(p1_result.as_ref(), &battery, &config);
+        if outage != state.grid_outage {
+            if outage {
+                log::warn!("[EMS] Grid outage: P1 down, inverter on backup - islanding, control handed back");
+                state.report.alarm(Utc::now(), "grid outage - islanding");
+            } else {
+                log::info!("[EMS] Grid back - resuming normal operation");
+            }
+            state.grid_outage = outage;
+        }
+        let runtime = outage.then(|| backup_runtime(&battery, &config)).flatten();
+        if let Some(runtime) = runtime {
+            log::info!("[EMS] Backup runtime: {} (SOC {:.1}%)", runtime, battery.battery_soc);
+        }
+
+        // The price plan is kept up to date every cycle, so it is ready whichever way this cycle goes.
+        let plans = [Some(config.strategy), config.shadow_strategy].contains(&Some(Strategy::PriceArbitrage));
+        if plans && battery.is_fresh(ID_BATTERY_SOC) {
+            let curve  = prices.borrow().clone();
+            state.plan = plan::refresh(state.plan.take(), &curve, battery.battery_soc, Utc::now(), &config);
+        }
+        let plan_slot = state.plan.as_ref().and_then(|plan| plan.slot_at(Utc::now()));
+
+        let mut decision     = None;
+        let mut command_sent = false;
+        let mut shadow       = None;
+        let mut trace        = RuleTrace::default();
+        let mut grid_input   = None;
+        let learning         = state.learning.observing(Utc::now(), config.learning_period_hours);
+        let verdict = if outage {
+            trace.rule("islanding", true, "grid outage: P1 down, inverter on backup");
+            Some(islanding_verdict(&battery))
+        } else if let Some(grid) = best_grid_power_w(p1_result.as_ref(), &battery, &config) {
+            if grid.is_degraded() {
+                log::warn!(
+                    "[Optimiser] Degraded grid source {} ({}), deadband ±{}W",
+                    grid.source.as_str(), pf.signed(grid.power_w), grid.deadband_w
+                );
+            }
+            if let Some(diff_w) = grid.disagreement_w {
+                log::warn!(
+                    "[Optimiser] P1 and Indevolt meter disagree by {} (limit {}W) - using P1",
+                    pf.signed(diff_w), config.grid_meter_disagreement_w
+                );
+            }
+            grid_input = Some(grid);
+            state.pre_peak.observe(Utc::now(), &grid, &battery, &config);
+            shadow = shadow_verdict(&grid, &battery, &config, plan_slot);
+            tracing::info_span!(parent: &cycle_span, "optimiser")
+                .in_scope(|| optimiser::run_traced(&grid, &battery, &config, plan_slot, &mut trace))
+                .map(|verdict| {
+                    trace.stage("settle", verdict, |v| {
+                        hold_during_settle(
+                            v,
+                            dispatcher.last_sent(),
+                            dispatcher.since_last_command(),
+                            Duration::from_millis(config.post_command_settle_ms),
+                            &battery,
+                            &config,
+                        )
+                    })
+                })
+                .map(|verdict| {
+                    trace.stage("hold_band", verdict, |v| state.hold_band.apply(v, &battery, &config))
+                })
+                .map(|verdict| {
+                    trace.stage("maintenance", verdict, |v| {
+                        state.maintenance.apply(v, &battery, Instant::now(), &config)
+                    })
+                })
+                .map(|verdict| {
+                    trace.stage("pre_peak", verdict, |v| {
+                        state.pre_peak.apply(v, &grid, &battery, Utc::now(), &config)
+                    })
+                })
+                .map(|verdict| {
+                    trace.stage("grid_charge_interlock", verdict, |v| {
+                        guard_grid_charge(v, &grid, p1, &battery, Utc::now(), &config)
+                    })
+                })
+                .map(|verdict| {
+                    trace.stage("grid_charge_cap", verdict, |v| {
+                        state.grid_charge_cap.apply(v, &grid, &battery, &config)
+                    })
+                })
+                .map(|verdict| {
+                    trace.stage("throughput_cap", verdict, |v| {
+                        state.throughput_cap.apply(v, &grid, &battery, &config)
+                    })
+                })
+                .map(|verdict| {
+                    trace.stage("allowed_modes", verdict, |v| restrict_to_allowed(v, &grid, &battery, &config))
+                })
+                .map(|verdict| {
+                    if !state.ev_charging {
+                        return verdict;
+                    }
+                    trace.stage("ev_pause", verdict, |v| {
+                        pause_discharge_for_ev(v, grid.power_w - battery.battery_power_w, &config)
+                    })
+                })
+        } else {
+            trace.rule("grid_reading", true, "no grid measurement this cycle - no decision");
+            None
+        };
+        let verdict = verdict.map(|verdict| {
+            let verdict = trace.stage("data_quality", verdict, |v| {
+                apply_data_quality(v, quality.score, config.data_quality_min_score)
+            });
+            // Nothing is sent while learning: the direction budget and the ramp only follow sent commands.
+            let verdict = if learning {
+                verdict
+            } else {
+                trace.stage("direction_limit", verdict, |v| {
+                    state.direction_changes.limit(v, Instant::now(), config.max_direction_changes_per_hour)
+                })
+            };
+            // While the EV charges, hand-back would let the inverter discharge into it.
+            let handback_after = if state.ev_charging {
+                Duration::ZERO
+            } else {
+                Duration::from_secs(config.auto_handback_idle_seconds)
+            };
+            let verdict = trace.stage("idle_handback", verdict, |v| {
+                state.idle_handback.apply(v, Instant::now(), handback_after)
+            });
+            if learning {
+                verdict
+            } else {
+                trace.stage("ramp", verdict, |v| ramp_power(v, dispatcher.last_sent(), config.max_power_step_w))
+            }
+        });
+        let inputs = TraceInputs {
+            grid_power_w:      grid_input.map(|grid| grid.power_w),
+            grid_source:       grid_input.map(|grid| grid.source.as_str()),
+            soc_percent:       battery.battery_soc,
+            battery_power_w:   battery.battery_power_w,
+            price_eur_per_kwh: price_now.map(|p| p.price_eur_per_kwh),
+        };
+        let trace = DecisionTrace::new(cycle_id, Utc::now(), inputs, trace).with_verdict(verdict.as_ref());
+        state.recent.lock().unwrap_or_else(|e| e.into_inner()).set_trace(trace);
+        if let Some(verdict) = verdict {
+            let nominal = p1.is_some() && battery.keys_missing == 0;
+            quiet.observe(
+                config.quiet_when_idle && nominal && verdict.decision == Decision::Idle,
+                Instant::now(),
+                Duration::from_secs(config.idle_heartbeat_minutes * 60),
+                battery.battery_soc,
+            );
+            cycle_span.record("decision", verdict.decision.label());
+            if learning {
+                // Learning period: the decision is logged and recorded, the inverter keeps its own mode.
+                log::log!(
+                    quiet.level(),
+                    "[Optimiser] {} - {} (learning, not sent)", verdict.decision, verdict.reason
+                );
+                decision = Some(verdict.decision);
+            } else {
+                log::log!(quiet.level(), "[Optimiser] {} - {}", verdict.decision, verdict.reason);
+                let dispatch_span = tracing::info_span!(parent: &cycle_span, "dispatch");
+                match dispatcher
+                    .apply_decision(&config.indevolt_url, &verdict.decision, &battery)
+                    .instrument(dispatch_span)
+                    .await
+                {
+                    Ok(sent) => command_sent = sent,
+                    Err(e)   => {
+                        log::error!("[Dispatch] {}", e);
+                        state.report.alarm(Utc::now(), format!("dispatch error: {}", e));
+                    }
+                }
+                state.last_decision = Some(verdict.decision.clone());
+                decision = Some(verdict.decision);
+            }
+            if config.fast_discharge_trigger_w > 0 && !outage && !learning {
+                spike_watch = Some((battery.clone(), config.clone()));
+            }
+        } else {
+            quiet.observe(false, Instant::now(), Duration::ZERO, battery.battery_soc);
+        }
+        // Step 4a: the shadow strategy's decision, logged next to the live one and never dispatched.
+        let shadow = record_shadow(shadow, decision.as_ref(), quiet.level());
+
+        // Step 5: one structured record per cycle.
         let elapsed = cycle_start.elapsed();
+        let outcome = CycleOutcome::new(
+            cycle_id,
+            p1.is_some(),
+            battery.keys_missing,
+            battery.keys_requested,
+            elapsed.as_millis() as u64,
+            interval.as_millis() as u64,
+        )
+        .with_p1_error(p1_result.as_ref().err().map(P1Error::kind))
+        .with_data_quality(quality.score)
+        .with_decision(decision, command_sent)
+        .with_learning(learning)
+        .with_shadow_decision(shadow)
+        .with_backup_runtime(runtime.and_then(|r| r.minutes()))
+        .with_self_discharge(idle_drift.self_discharge_percent_per_hour());
+        log::log!(quiet.level(), "[EMS] outcome {}", outcome.to_log_line());
+        state.recent.lock().unwrap_or_else(|e| e.into_inner()).push(outcome.clone());
+
+        // Step 5a: hand the record to the sink workers. Never blocks; a lagging worker loses the oldest records.
+        if records.receiver_count() > 0 {
+            #[cfg(feature = "influxdb")]
+            let influx_lines = {
+                let now = Utc::now();
+                let mut lines = vec![battery_line(&battery, cycle_id, now)];
+                if let Some(reading) = p1 {
+                    lines.push(p1_line(reading, cycle_id, now));
+                }
+                lines.extend(device_day.as_ref().map(device_day_line));
+                lines
+            };
+            let _ = records.send(Arc::new(CycleRecord {
+                outcome,
+                #[cfg(feature = "influxdb")]
+                influx_lines,
+            }));
+        }
+
+        // Step 6: persist the loop state now and then.
+        if !config.state_file_path.is_empty()
+            && last_state_save.elapsed() >= Duration::from_secs(config.state_save_interval_seconds)
+        {
+            if let Err(e) = save_state(&config.state_file_path, &state.to_persisted()) {
+                log::warn!("{}", e);
+            }
+            last_state_save = Instant::now();
+        }
+
+        // The ticker paces the next cycle; here we only report how the work compared with the period.
         if elapsed < interval {
-            let remaining = interval - elapsed;
-            log::info!("[EMS] Cycle done in {:?}. Sleeping {:?}.", elapsed, remaining);
-            sleep(remaining).await;
+            log::log!(
+                quiet.level(),
+                "[EMS] Cycle done in {:?}. Next cycle in {:?}.", elapsed, interval - elapsed
+            );
         } else {
             log::warn!(
-                "[EMS] Cycle took {:?}, overran interval {:?} - skipping sleep.",
-                elapsed, interval
+                "[EMS] Cycle took {:?}, overran interval {:?} - {}.",
+                elapsed, interval,
+                match config.missed_tick_behavior {
+                    MissedTicks::Skip  => "skipping the missed tick(s)",
+                    MissedTicks::Delay => "starting the next cycle now",
+                }
             );
         }
     }
 }
+
+/// `dispatcher` with the control settings from `config`; at start-up and again after a reload.
+fn configured_dispatcher(dispatcher: Dispatcher, config: &Config) -> Dispatcher {
+    dispatcher
+        .with_readback(config.control_readback_sensor_ids)
+        .with_allowed_modes(config.allowed_working_modes.clone())
+        .with_failure_threshold(config.control_failure_threshold)
+}
+
+/// Shutdown, between cycles: hand the inverter back and save the state (the report day with it).
+async fn stop_control_loop(state: &mut LoopState, config: &Config) {
+    log::info!("[EMS] Control loop stopping - handing the inverter back to self-consumption");
+    if hand_back(&config.indevolt_url).await {
+        // The next start then finds nothing to recover.
+        state.last_decision = Some(Decision::SelfConsumption);
+    }
+    if !config.state_file_path.is_empty() {
+        match save_state(&config.state_file_path, &state.to_persisted()) {
+            Ok(())  => log::info!("[EMS] Shutdown: state saved to {}", config.state_file_path),
+            Err(e)  => log::warn!("{}", e),
+        }
+    }
+}
+
+/// Between cycles: re-read P1 and discharge at once when the import spiked (see `optimiser/spike.rs`).
+/// Returns true once a command was attempted, so only one goes out per cycle interval.
+async fn react_to_load_spike(
+    p1_client: &reqwest::Client,
+    dispatcher: &mut Dispatcher,
+    state: &mut LoopState,
+    battery: &BatterySnapshot,
+    config: &Config,
+) -> bool {
+    let Ok(reading) = read_p1(p1_client, config).await else {
+        return false;
+    };
+    let grid_w = reading.raw.active_power_w.round() as i32;
+    let Some(verdict) = spike_verdict(grid_w, battery, dispatcher.last_sent(), config) else {
+        return false;
+    };
+    let settle = Duration::from_millis(config.post_command_settle_ms);
+    if dispatcher.since_last_command().is_some_and(|since| since < settle) {
+        return false;
+    }
+    let mut verdict = optimiser::apply_limits(verdict, battery, config);
+    if state.ev_charging {
+        verdict = pause_discharge_for_ev(verdict, grid_w - battery.battery_power_w, config);
+    }
+    let verdict = state.direction_changes.limit(verdict, Instant::now(), config.max_direction_changes_per_hour);
+    if verdict.decision.direction() != Direction::Discharge
+        || !mode_allowed(&verdict.decision, &config.allowed_working_modes)
+    {
+        return false;
+    }
+
+    log::info!("[Optimiser] Fast path: {} - {}", verdict.decision, verdict.reason);
+    if let Err(e) = dispatcher.apply_decision(&config.indevolt_url, &verdict.decision, battery).await {
+        log::error!("[Dispatch] {}", e);
+        state.report.alarm(Utc::now(), format!("dispatch error: {}", e));
+    }
+    state.last_decision = Some(verdict.decision);
+    true
+}
+
+// --------------------------------------------------------------------------------------------------------------
+
+/// `read`: one P1 + battery read, printed as JSON on stdout. Exits with 1 when either device gave no data.
+async fn read_once(config: &Config) {
+    let p1_client = match build_p1_client(config) {
+        Ok(client) => client,
+        Err(e) => {
+            log::error!("[P1] Failed to build HTTP client: {}", e);
+            std::process::exit(1);
+        }
+    };
+    let (p1, battery) = tokio::join!(read_p1(&p1_client, config), read_battery_snapshot(config, DEVICE_MODEL));
+
+    let p1_json = match &p1 {
+        Ok(reading) => serde_json::to_value(&reading.raw).unwrap_or_default(),
+        Err(e)      => serde_json::json!({ "error": e.to_string() }),
+    };
+    let out = serde_json::json!({ "p1": p1_json, "battery": battery });
+    println!("{}", serde_json::to_string_pretty(&out).unwrap_or_default());
+
+    if p1.is_err() || battery.keys_missing >= battery.keys_requested {
+        std::process::exit(1);
+    }
+}
+
+/// `config`: the effective configuration (defaults filled in) as JSON on stdout, with secrets masked.
+fn print_config(config: &Config) {
+    println!("{}", serde_json::to_string_pretty(&redacted(config)).unwrap_or_default());
+}
+
+// --------------------------------------------------------------------------------------------------------------
+
+/// A P1 read worth retrying: the request failed or timed out. Stale or malformed data won't change in a moment.
+fn p1_retryable(result: &Result<P1Reading, P1Error>) -> bool {
+    matches!(result, Err(P1Error::Http(_) | P1Error::Timeout))
+}
+
+/// A battery read that got no value at all.
+fn battery_unread(battery: &BatterySnapshot) -> bool {
+    battery.keys_missing >= battery.keys_requested
+}
+
+/// Await `fut` and return its output together with how long it took.
+async fn timed<T>(fut: impl std::future::Future<Output = T>) -> (T, Duration) {
+    let start = Instant::now();
+    let out   = fut.await;
+    (out, start.elapsed())
+}
@@ -1,4 +1,8 @@
-use serde::Serialize;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+
+use crate::handlers::indevolt::registers::{MODE_REALTIME, MODE_SCHEDULE, MODE_SELF_CONSUMED};
 
 // --------------------------------------------------------------------------------------------------------------
 // Indevolt PowerFlex2000 local RPC API models
@@ -6,11 +10,12 @@ use serde::Serialize;
 // Read:  GET  /rpc/Indevolt.GetData?config={"t":[id,...]}
 //        Response: flat JSON object {"<id>": <numeric_value>, ...}
 //
-// Write: POST /rpc/Indevolt.SetData
-//        Body: {"id": <numeric_id>, "value": <value>}
+// Write: GET  /rpc/Indevolt.SetData?config={"f":16,"t":<reg>,"v":[...]}
+//        Response: {"result": true} once the write was applied, {"result": false} when it was accepted but not
+//        applied, or {"error": "..."}
 // --------------------------------------------------------------------------------------------------------------
 
-/// Config parameter for GET /rpc/Indevolt.SetData?config=<json>
+/// Config parameter for GET /rpc/Indevolt.SetData?config=<json>, built from a `RegisterWrite`.
 /// Example: {"f":16,"t":47015,"v":[1,2000,100]}
 #[derive(Serialize, Debug)]
 pub struct SetDataConfig {
@@ -19,16 +24,43 @@ pub struct SetDataConfig {
     pub v: Vec<i64>,  // Values to write
 }
 
+/// Body of a SetData answer. HTTP 200 only says the request was accepted; `result` says whether the register
+/// write was applied.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct SetDataResponse {
+    pub result: Option<bool>,
+    pub error:  Option<String>,
+}
+
+impl SetDataResponse {
+    /// Parse a SetData body; `None` when it isn't a JSON object, so whether the write was applied is unknown.
+    pub fn parse(body: &str) -> Option<Self> {
+        serde_json::from_str(body).ok()
+    }
+
+    /// `Err` when the device says the write was not applied.
+    pub fn applied(&self) -> Result<(), String> {
+        match (self.result, &self.error) {
+            (_, Some(error))    => Err(format!("device error '{}'", error)),
+            (Some(false), None) => Err("device reports the write as not applied".to_string()),
+            _                   => Ok(()),
+        }
+    }
+}
+
 // --------------------------------------------------------------------------------------------------------------
 
 /// A snapshot of all battery sensors polled in one cycle.
 /// Field names mirror the BatteryData table columns exactly so mapping is trivial.
-#[derive(Debug, Clone, Default)]
+#[derive(Serialize, Debug, Clone, Default)]
 pub struct BatterySnapshot {
     pub device_model:              String,
-    pub battery_soc:               f64,   // %
+    pub battery_soc:               f64,   // % after calibration (see Config::calibrate_soc)
+    pub battery_soc_raw:           f64,   // % as reported by the device
     pub battery_state:             String, // "Charging" | "Discharging" | "Static"
-    pub working_mode:              String, // e.g. "Self-consumed Prioritized"
+    pub working_mode:              String, // e.g. "Self-consumed Prioritized", "Mode(7)" when unknown
+    #[serde(skip)]
+    pub mode:                      Option<WorkingMode>, // `working_mode` mapped; None when unread or unknown
     pub battery_power_w:           i32,   // negative = discharging, positive = charging
     pub dc_input_power1_w:         i32,
     pub dc_input_power2_w:         i32,
@@ -43,6 +75,17 @@ pub struct BatterySnapshot {
     pub total_charging_kwh:        f64,
     pub total_discharging_kwh:     f64,
     pub total_ac_input_energy_kwh: f64,
+    pub inverter_temperature_c:    Option<f64>, // °C, only when a sensor ID is configured
+    pub battery_temperature_c:     Option<f64>, // °C, only when a sensor ID is configured
+    pub device_min_soc_percent:    Option<f64>, // MinSOC set on the device, only when a sensor ID is configured
+    pub device_max_soc_percent:    Option<f64>, // MaxSOC set on the device, only when a sensor ID is configured
+    pub off_grid:                  Option<bool>, // on backup power, only when a grid state sensor is configured
+    pub grid_frequency_hz:         Option<f64>, // AC side, only when a sensor ID is configured
+    pub grid_voltage_v:            Option<f64>, // AC side, only when a sensor ID is configured
+    pub keys_requested:            usize, // sensor IDs asked for in this poll
+    pub keys_missing:              usize, // of those, how many were absent or non-numeric (reported as 0)
+    pub read_at:                   DateTime<Utc>, // when GetData answered; all fresh fields are from this moment
+    pub not_fresh:                 BTreeSet<u32>, // IDs not answered this cycle - their fields are defaults
 }
 
 /// Battery static configuration read from the device (mirrors BatteryConfig table).
@@ -56,10 +99,25 @@ pub struct BatteryConfig {
     pub max_discharge_power_w: i32,
 }
 
+impl BatterySnapshot {
+    /// Whether the value of sensor `id` was read this cycle (rather than defaulted to 0 / `None`).
+    pub fn is_fresh(&self, id: u32) -> bool {
+        !self.not_fresh.contains(&id)
+    }
+
+    /// Hottest of the reported inverter/battery temperatures (°C), if any is polled.
+    pub fn max_temperature_c(&self) -> Option<f64> {
+        match (self.inverter_temperature_c, self.battery_temperature_c) {
+            (Some(a), Some(b)) => Some(a.max(b)),
+            (a, b)             => a.or(b),
+        }
+    }
+}
+
 // --------------------------------------------------------------------------------------------------------------
-// Working modes for register 47005
+// Working modes for `REG_WORKING_MODE` (see handlers/indevolt/registers.rs)
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum WorkingMode {
     /// Default: use solar first, battery as buffer (value = 1)
     SelfConsumedPrioritized,
@@ -72,17 +130,17 @@ pub enum WorkingMode {
 impl WorkingMode {
     pub fn register_value(&self) -> i64 {
         match self {
-            WorkingMode::SelfConsumedPrioritized => 1,
-            WorkingMode::RealtimeControl         => 4,
-            WorkingMode::Schedule                => 5,
+            WorkingMode::SelfConsumedPrioritized => MODE_SELF_CONSUMED,
+            WorkingMode::RealtimeControl         => MODE_REALTIME,
+            WorkingMode::Schedule                => MODE_SCHEDULE,
         }
     }
 
     pub fn from_register_value(v: i64) -> Option<Self> {
         match v {
-            1 => Some(WorkingMode::SelfConsumedPrioritized),
-            4 => Some(WorkingMode::RealtimeControl),
-            5 => Some(WorkingMode::Schedule),
+            MODE_SELF_CONSUMED => Some(WorkingMode::SelfConsumedPrioritized),
+            MODE_REALTIME      => Some(WorkingMode::RealtimeControl),
+            MODE_SCHEDULE      => Some(WorkingMode::Schedule),
             _ => None,
         }
     }
@@ -1,5 +1,6 @@
 use serde::{Deserialize, Serialize};
 use serde::de::{self, Deserializer};
+use std::collections::HashMap;
 use std::fmt;
 use reqwest::Error;
 
@@ -37,8 +38,80 @@ where
     deserializer.deserialize_any(StringOrNumber)
 }
 
+/// Same firmware quirk for small integer fields (`active_tariff`, `wifi_strength`, `smr_version`): accepts
+/// `1`, `1.0` and `"1"`. Values that aren't a whole number in 0..=255 are still rejected.
+fn deserialize_u8_flexible<'de, D>(deserializer: D) -> Result<u8, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    struct U8OrString;
+
+    impl<'de> de::Visitor<'de> for U8OrString {
+        type Value = u8;
+
+        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+            formatter.write_str("an integer 0-255 or a string holding one")
+        }
+
+        fn visit_str<E: de::Error>(self, v: &str) -> Result<Self::Value, E> {
+            v.trim().parse().map_err(|_| E::invalid_value(de::Unexpected::Str(v), &self))
+        }
+        fn visit_u64<E: de::Error>(self, v: u64) -> Result<Self::Value, E> {
+            u8::try_from(v).map_err(|_| E::invalid_value(de::Unexpected::Unsigned(v), &self))
+        }
+        fn visit_i64<E: de::Error>(self, v: i64) -> Result<Self::Value, E> {
+            u8::try_from(v).map_err(|_| E::invalid_value(de::Unexpected::Signed(v), &self))
+        }
+        fn visit_f64<E: de::Error>(self, v: f64) -> Result<Self::Value, E> {
+            if v.fract() == 0.0 && (0.0..=255.0).contains(&v) {
+                Ok(v as u8)
+            } else {
+                Err(E::invalid_value(de::Unexpected::Float(v), &self))
+            }
+        }
+    }
+
+    deserializer.deserialize_any(U8OrString)
+}
+
 // --------------------------------------------------------------------------------------------------------------
 
+/// Kind of external meter, from the `type` field of an `external` entry.
+/// Types this crate does not know are kept verbatim in `Unknown` rather than dropped.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ExternalType {
+    GasMeter,
+    HeatMeter,
+    WarmWaterMeter,
+    WaterMeter,
+    InletHeatMeter,
+    Unknown(String),
+}
+
+impl ExternalType {
+    pub fn from_api_str(s: &str) -> Self {
+        match s {
+            "gas_meter"        => ExternalType::GasMeter,
+            "heat_meter"       => ExternalType::HeatMeter,
+            "warm_water_meter" => ExternalType::WarmWaterMeter,
+            "water_meter"      => ExternalType::WaterMeter,
+            "inlet_heat_meter" => ExternalType::InletHeatMeter,
+            other              => ExternalType::Unknown(other.to_string()),
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        match self {
+            ExternalType::GasMeter       => "gas_meter",
+            ExternalType::HeatMeter      => "heat_meter",
+            ExternalType::WarmWaterMeter => "warm_water_meter",
+            ExternalType::WaterMeter     => "water_meter",
+            ExternalType::InletHeatMeter => "inlet_heat_meter",
+            ExternalType::Unknown(s)     => s,
+        }
+    }
+}
+
 /// An external (slave) meter attached to the P1 port.
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct ExternalMeasurement {
@@ -50,17 +123,28 @@ pub struct ExternalMeasurement {
     pub unit:      String,
 }
 
+impl ExternalMeasurement {
+    pub fn kind(&self) -> ExternalType {
+        ExternalType::from_api_str(&self.r#type)
+    }
+}
+
 // --------------------------------------------------------------------------------------------------------------
 
 /// Full response from GET /api/v1/data on a HomeWizard P1 dongle.
-/// Field names match the HomeWizard local API spec exactly.
-#[derive(Serialize, Deserialize, Debug, Clone)]
+/// Field names match the HomeWizard local API spec exactly. A field the meter doesn't report reads as zero
+/// or empty; the reader insists only on the fields listed in `p1_critical_fields`.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
 pub struct P1Data {
     pub wifi_ssid:               String,
+    #[serde(deserialize_with = "deserialize_u8_flexible")]
     pub wifi_strength:           u8,
+    #[serde(deserialize_with = "deserialize_u8_flexible")]
     pub smr_version:             u8,
     pub meter_model:             String,
     pub unique_id:               String,
+    #[serde(deserialize_with = "deserialize_u8_flexible")]
     pub active_tariff:           u8,
     pub total_power_import_kwh:  f64,
     pub total_power_import_t1_kwh: f64,
@@ -80,7 +164,9 @@ pub struct P1Data {
     pub active_current_l2_a:     f64,
     pub active_current_l3_a:     f64,
     pub active_power_average_w:  f64,
-    pub montly_power_peak_w:     f64,   // note: HomeWizard typo kept intentionally
+    // HomeWizard typo "montly" kept intentionally; `P1_ALIASES` accepts the corrected spelling in case
+    // a future firmware fixes it.
+    pub montly_power_peak_w:     f64,
     #[serde(deserialize_with = "deserialize_to_string")]
     pub montly_power_peak_timestamp: String,
     pub total_gas_m3:            f64,
@@ -90,22 +176,299 @@ pub struct P1Data {
     pub external:                Vec<ExternalMeasurement>,
 }
 
+/// Corrected spellings accepted for v1 fields, as (field, alias). A serde alias would reject a response that
+/// carries both spellings as a duplicate field; `resolve_aliases` takes the first one present instead.
+pub const P1_ALIASES: &[(&str, &str)] = &[
+    ("montly_power_peak_w",         "monthly_power_peak_w"),
+    ("montly_power_peak_timestamp", "monthly_power_peak_timestamp"),
+];
+
 impl P1Data {
-    pub fn from_json(json: &str) -> Result<Self, serde_json::Error> {
-        serde_json::from_str(json)
+    /// Parse a /api/v1/data response. On failure the error carries the JSON path of the offending
+    /// field (e.g. `active_tariff: invalid type: ...`), not just serde's line/column.
+    pub fn from_json(json: &str) -> Result<Self, serde_path_to_error::Error<serde_json::Error>> {
+        let de = &mut serde_json::Deserializer::from_str(json);
+        let mut fields: serde_json::Map<String, serde_json::Value> = serde_path_to_error::deserialize(de)?;
+        Self::resolve_aliases(&mut fields);
+        serde_path_to_error::deserialize(serde_json::Value::Object(fields))
+    }
+
+    /// Move the corrected spellings in `fields` to the field names, the HomeWizard spelling first: when a
+    /// response carries both, the alias is dropped.
+    pub fn resolve_aliases(fields: &mut serde_json::Map<String, serde_json::Value>) {
+        for (field, alias) in P1_ALIASES {
+            if let Some(value) = fields.remove(*alias) {
+                fields.entry(field.to_string()).or_insert(value);
+            }
+        }
+    }
+
+    /// The field name `name` stands for: the field itself, or the field an alias is accepted for.
+    pub fn field_name(name: &str) -> &str {
+        P1_ALIASES.iter().find(|(_, alias)| *alias == name).map_or(name, |(field, _)| field)
+    }
+
+    /// All external meters of the given type. An installation can have several of the same
+    /// type (e.g. two gas meters), so never assume the first one is the right one.
+    pub fn externals_of_type(&self, kind: &ExternalType) -> Vec<&ExternalMeasurement> {
+        self.external.iter().filter(|e| &e.kind() == kind).collect()
+    }
+
+    /// External meters whose type this crate does not recognise.
+    pub fn unknown_externals(&self) -> Vec<&ExternalMeasurement> {
+        self.external
+            .iter()
+            .filter(|e| matches!(e.kind(), ExternalType::Unknown(_)))
+            .collect()
+    }
+
+    /// The external meter assigned to a logical `role` via the `unique_id → role` map from the config.
+    pub fn external_for_role(
+        &self,
+        role: &str,
+        roles: &HashMap<String, String>,
+    ) -> Option<&ExternalMeasurement> {
+        self.external
+            .iter()
+            .find(|e| roles.get(&e.unique_id).is_some_and(|r| r == role))
+    }
+}
+
+// --------------------------------------------------------------------------------------------------------------
+// HomeWizard local API v2
+//
+// Newer firmware serves GET https://<ip>/api/measurement behind a bearer token and a device-specific TLS
+// certificate. The payload uses different field names and ISO-8601 local timestamps, and carries no Wi-Fi or
+// gas-specific fields (gas is one of the `external` entries). `P1DataV2::into_p1_data` maps it onto the v1
+// `P1Data` shape so the rest of the EMS is unaware of which API version is in use.
+// --------------------------------------------------------------------------------------------------------------
+
+/// An external meter as reported by the v2 API (timestamps are ISO-8601 local time).
+#[derive(Deserialize, Debug, Clone)]
+pub struct ExternalMeasurementV2 {
+    pub unique_id: String,
+    pub r#type:    String,
+    #[serde(default)]
+    pub timestamp: String,
+    #[serde(default)]
+    pub value:     f64,
+    #[serde(default)]
+    pub unit:      String,
+}
+
+/// Response from GET /api/measurement (HomeWizard API v2). Fields a meter does not report default to zero.
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct P1DataV2 {
+    pub protocol_version:             u8,
+    pub meter_model:                  String,
+    pub unique_id:                    String,
+    pub tariff:                       u8,
+    pub energy_import_kwh:            f64,
+    pub energy_import_t1_kwh:         f64,
+    pub energy_import_t2_kwh:         f64,
+    pub energy_export_kwh:            f64,
+    pub energy_export_t1_kwh:         f64,
+    pub energy_export_t2_kwh:         f64,
+    pub power_w:                      f64,
+    pub power_l1_w:                   f64,
+    pub power_l2_w:                   f64,
+    pub power_l3_w:                   f64,
+    pub voltage_l1_v:                 f64,
+    pub voltage_l2_v:                 f64,
+    pub voltage_l3_v:                 f64,
+    pub current_a:                    f64,
+    pub current_l1_a:                 f64,
+    pub current_l2_a:                 f64,
+    pub current_l3_a:                 f64,
+    pub average_power_15m_w:          f64,
+    pub monthly_power_peak_w:         f64,
+    pub monthly_power_peak_timestamp: String,
+    pub external:                     Vec<ExternalMeasurementV2>,
+}
+
+/// v1 field names whose v2 counterpart is named differently; the others are the same in both.
+const V2_FIELD_NAMES: &[(&str, &str)] = &[
+    ("smr_version",                 "protocol_version"),
+    ("active_tariff",               "tariff"),
+    ("total_power_import_kwh",      "energy_import_kwh"),
+    ("total_power_import_t1_kwh",   "energy_import_t1_kwh"),
+    ("total_power_import_t2_kwh",   "energy_import_t2_kwh"),
+    ("total_power_export_kwh",      "energy_export_kwh"),
+    ("total_power_export_t1_kwh",   "energy_export_t1_kwh"),
+    ("total_power_export_t2_kwh",   "energy_export_t2_kwh"),
+    ("active_power_w",              "power_w"),
+    ("active_power_l1_w",           "power_l1_w"),
+    ("active_power_l2_w",           "power_l2_w"),
+    ("active_power_l3_w",           "power_l3_w"),
+    ("active_voltage_l1_v",         "voltage_l1_v"),
+    ("active_voltage_l2_v",         "voltage_l2_v"),
+    ("active_voltage_l3_v",         "voltage_l3_v"),
+    ("active_current_a",            "current_a"),
+    ("active_current_l1_a",         "current_l1_a"),
+    ("active_current_l2_a",         "current_l2_a"),
+    ("active_current_l3_a",         "current_l3_a"),
+    ("active_power_average_w",      "average_power_15m_w"),
+    ("montly_power_peak_w",         "monthly_power_peak_w"),
+    ("montly_power_peak_timestamp", "monthly_power_peak_timestamp"),
+];
+
+/// Convert an ISO-8601 local timestamp ("2024-06-28T14:12:34") to the compact v1 form ("240628141234").
+/// Anything that doesn't look like ISO-8601 is passed through unchanged.
+fn iso_to_compact_timestamp(iso: &str) -> String {
+    let digits: String = iso.chars().filter(|c| c.is_ascii_digit()).collect();
+    if digits.len() >= 14 {
+        digits[2..14].to_string()
+    } else {
+        iso.to_string()
+    }
+}
+
+impl P1DataV2 {
+    pub fn from_json(json: &str) -> Result<Self, serde_path_to_error::Error<serde_json::Error>> {
+        let de = &mut serde_json::Deserializer::from_str(json);
+        serde_path_to_error::deserialize(de)
+    }
+
+    /// The v2 name of the v1 field `v1_name`.
+    pub fn field_name(v1_name: &str) -> &str {
+        V2_FIELD_NAMES.iter().find(|(v1, _)| *v1 == v1_name).map_or(v1_name, |(_, v2)| v2)
+    }
+
+    /// Map the v2 measurement onto the v1 `P1Data` shape. Wi-Fi fields are not part of the v2
+    /// measurement and are left empty; gas is taken from the first `gas_meter` external.
+    pub fn into_p1_data(self) -> P1Data {
+        let external: Vec<ExternalMeasurement> = self.external
+            .into_iter()
+            .map(|e| ExternalMeasurement {
+                unique_id: e.unique_id,
+                r#type:    e.r#type,
+                timestamp: iso_to_compact_timestamp(&e.timestamp),
+                value:     e.value,
+                unit:      e.unit,
+            })
+            .collect();
+
+        let gas = external.iter().find(|e| e.kind() == ExternalType::GasMeter);
+        let (total_gas_m3, gas_timestamp, gas_unique_id) = match gas {
+            Some(g) => (g.value, g.timestamp.clone(), g.unique_id.clone()),
+            None    => (0.0, String::new(), String::new()),
+        };
+
+        P1Data {
+            wifi_ssid:                   String::new(),
+            wifi_strength:               0,
+            smr_version:                 self.protocol_version,
+            meter_model:                 self.meter_model,
+            unique_id:                   self.unique_id,
+            active_tariff:               self.tariff,
+            total_power_import_kwh:      self.energy_import_kwh,
+            total_power_import_t1_kwh:   self.energy_import_t1_kwh,
+            total_power_import_t2_kwh:   self.energy_import_t2_kwh,
+            total_power_export_kwh:      self.energy_export_kwh,
+            total_power_export_t1_kwh:   self.energy_export_t1_kwh,
+            total_power_export_t2_kwh:   self.energy_export_t2_kwh,
+            active_power_w:              self.power_w,
+            active_power_l1_w:           self.power_l1_w,
+            active_power_l2_w:           self.power_l2_w,
+            active_power_l3_w:           self.power_l3_w,
+            active_voltage_l1_v:         self.voltage_l1_v,
+            active_voltage_l2_v:         self.voltage_l2_v,
+            active_voltage_l3_v:         self.voltage_l3_v,
+            active_current_a:            self.current_a,
+            active_current_l1_a:         self.current_l1_a,
+            active_current_l2_a:         self.current_l2_a,
+            active_current_l3_a:         self.current_l3_a,
+            active_power_average_w:      self.average_power_15m_w,
+            montly_power_peak_w:         self.monthly_power_peak_w,
+            montly_power_peak_timestamp: iso_to_compact_timestamp(&self.monthly_power_peak_timestamp),
+            total_gas_m3,
+            gas_timestamp,
+            gas_unique_id,
+            external,
+        }
     }
 }
 
 // --------------------------------------------------------------------------------------------------------------
 
 /// Fetch the raw JSON string from the P1 local API.
-pub async fn fetch_p1_data(url: &str) -> Result<String, Error> {
-    let client = reqwest::Client::new();
+/// Pass the long-lived client from `build_p1_client` so the TCP connection is reused between cycles.
+pub async fn fetch_p1_data(client: &reqwest::Client, url: &str) -> Result<String, Error> {
     let response = client
         .get(url)
         .send()
         .await?
+        .error_for_status()?
         .text()
         .await?;
     Ok(response)
 }
+
+/// Fetch the raw JSON string from the v2 measurement endpoint, authenticating with the device token.
+pub async fn fetch_p1_data_v2(client: &reqwest::Client, url: &str, token: &str) -> Result<String, Error> {
+    let response = client
+        .get(url)
+        .bearer_auth(token)
+        .header("X-Api-Version", "2")
+        .send()
+        .await?
+        .error_for_status()?
+        .text()
+        .await?;
+    Ok(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn both_spellings_of_the_monthly_peak_parse_into_the_same_field() {
+        let typo      = r#"{"montly_power_peak_w": 3500.0, "montly_power_peak_timestamp": 240601180000}"#;
+        let corrected = r#"{"monthly_power_peak_w": 3500.0, "monthly_power_peak_timestamp": 240601180000}"#;
+        for json in [typo, corrected] {
+            let data = P1Data::from_json(json).unwrap();
+            assert_eq!(data.montly_power_peak_w, 3500.0);
+            assert_eq!(data.montly_power_peak_timestamp, "240601180000");
+        }
+    }
+
+    #[test]
+    fn a_response_with_both_spellings_uses_the_homewizard_one() {
+        let json = r#"{"montly_power_peak_w": 3500.0, "monthly_power_peak_w": 4000.0}"#;
+        assert_eq!(P1Data::from_json(json).unwrap().montly_power_peak_w, 3500.0);
+    }
+
+    #[test]
+    fn small_integer_fields_accept_numbers_and_strings() {
+        let json = r#"{"active_tariff": "1", "wifi_strength": 78.0, "smr_version": 50}"#;
+        let data = P1Data::from_json(json).unwrap();
+        assert_eq!((data.active_tariff, data.wifi_strength, data.smr_version), (1, 78, 50));
+
+        assert!(P1Data::from_json(r#"{"active_tariff": "peak"}"#).is_err());
+        assert!(P1Data::from_json(r#"{"active_tariff": 256}"#).is_err());
+    }
+
+    #[test]
+    fn duplicate_and_unknown_externals_are_all_kept() {
+        let json = r#"{"external": [
+            {"unique_id": "G1", "type": "gas_meter", "timestamp": 240601180000, "value": 1234.5, "unit": "m3"},
+            {"unique_id": "W1", "type": "water_meter", "timestamp": 240601180000, "value": 87.2, "unit": "m3"},
+            {"unique_id": "G2", "type": "gas_meter", "timestamp": 240601180000, "value": 56.7, "unit": "m3"},
+            {"unique_id": "S1", "type": "steam_meter", "timestamp": 240601180000, "value": 1.0, "unit": "GJ"}
+        ]}"#;
+        let data = P1Data::from_json(json).unwrap();
+
+        let ids = |meters: Vec<&ExternalMeasurement>| -> Vec<String> {
+            meters.iter().map(|e| e.unique_id.clone()).collect()
+        };
+        assert_eq!(ids(data.externals_of_type(&ExternalType::GasMeter)), ["G1", "G2"]);
+        assert_eq!(ids(data.externals_of_type(&ExternalType::WaterMeter)), ["W1"]);
+        assert_eq!(ids(data.unknown_externals()), ["S1"]);
+
+        let roles = HashMap::from([("G2".to_string(), "workshop_gas".to_string())]);
+        assert_eq!(data.external_for_role("workshop_gas", &roles).unwrap().value, 56.7);
+        assert!(data.external_for_role("house_gas", &roles).is_none());
+    }
+}
@@ -0,0 +1,86 @@
+use crate::configuration::config::Config;
+use crate::models::indevolt_models::BatterySnapshot;
+
+use super::decision::{Decision, Verdict};
+use super::grid::GridReading;
+use super::{apply_limits, self_consumption};
+
+// --------------------------------------------------------------------------------------------------------------
+// Allowed working modes
+//
+// Site policy may rule out some of what the battery could do, e.g. no `DischargingToGrid` where exporting from
+// the battery isn't permitted. `allowed_working_modes` lists the charge and discharge decisions the EMS may
+// send (all four by default). `Idle` and `SelfConsumption` are always allowed: they are where every fallback,
+// the watchdog and the startup recovery end up. A disallowed strategy decision is replaced by what
+// self-consumption would do, or by `Idle` when that is not allowed either. The dispatcher checks the list once
+// more before any write, so no path (the load-spike fast path included) can send a disallowed command.
+// --------------------------------------------------------------------------------------------------------------
+
+/// Decisions `allowed_working_modes` may name.
+pub const RESTRICTABLE_MODES: [&str; 4] =
+    ["ChargingFromSolar", "ChargingFromGrid", "DischargingToLoad", "DischargingToGrid"];
+
+/// Whether `decision` may be sent under the allowlist `allowed`.
+pub fn mode_allowed(decision: &Decision, allowed: &[String]) -> bool {
+    matches!(decision, Decision::SelfConsumption | Decision::Idle) || allowed.iter().any(|m| m == decision.label())
+}
+
+/// Replace a decision that `allowed_working_modes` rules out with the nearest allowed one.
+pub fn restrict_to_allowed(
+    verdict: Verdict,
+    grid: &GridReading,
+    battery: &BatterySnapshot,
+    config: &Config,
+) -> Verdict {
+    let allowed = &config.allowed_working_modes;
+    if mode_allowed(&verdict.decision, allowed) {
+        return verdict;
+    }
+
+    let fallback = apply_limits(self_consumption::decide(grid, battery, config), battery, config);
+    let fallback = if mode_allowed(&fallback.decision, allowed) {
+        fallback
+    } else {
+        Verdict::new(Decision::Idle, format!("{} not allowed either", fallback.decision.label()))
+    };
+    Verdict::new(
+        fallback.decision,
+        format!("{} not allowed: {}", verdict.decision.label(), fallback.reason),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::optimiser::testing::{battery, grid};
+
+    fn no_export() -> Config {
+        let allowed = ["ChargingFromSolar", "ChargingFromGrid", "DischargingToLoad"];
+        Config { allowed_working_modes: allowed.map(String::from).to_vec(), ..Config::default() }
+    }
+
+    #[test]
+    fn a_disallowed_export_falls_back_to_self_consumption() {
+        let export  = Verdict::new(Decision::DischargingToGrid { watts: 2400, min_soc_percent: 20 }, "price");
+        let verdict = restrict_to_allowed(export, &grid(500), &battery(80.0, 0), &no_export());
+
+        assert_eq!(verdict.decision, Decision::DischargingToLoad { watts: 500, min_soc_percent: 20 });
+        assert!(verdict.reason.starts_with("DischargingToGrid not allowed: "), "{}", verdict.reason);
+    }
+
+    #[test]
+    fn idle_when_the_fallback_is_not_allowed_either() {
+        let config  = Config { allowed_working_modes: Vec::new(), ..Config::default() };
+        let charge  = Verdict::new(Decision::ChargingFromGrid { watts: 2400, max_soc_percent: 90 }, "cheap");
+        let verdict = restrict_to_allowed(charge, &grid(500), &battery(80.0, 0), &config);
+        assert_eq!(verdict.decision, Decision::Idle);
+    }
+
+    #[test]
+    fn idle_and_self_consumption_are_always_allowed() {
+        assert!(mode_allowed(&Decision::Idle, &[]));
+        assert!(mode_allowed(&Decision::SelfConsumption, &[]));
+        let export = Decision::DischargingToGrid { watts: 1, min_soc_percent: 20 };
+        assert!(!mode_allowed(&export, &no_export().allowed_working_modes));
+    }
+}
@@ -0,0 +1,77 @@
+use log::info;
+
+use crate::models::indevolt_models::BatterySnapshot;
+
+use super::decision::{Decision, Verdict};
+
+// --------------------------------------------------------------------------------------------------------------
+// SOC boundary guard
+//
+// A charge command while the battery already sits at its target SOC (or a discharge at its floor) is wasted at
+// best and rejected by the inverter at worst. Whatever the strategy decided, such a command is turned into
+// `Idle` here, with the boundary spelled out in the reason. The target is the one carried by the decision
+// itself, so a discharge bounded by the peak-shaving reserve stops at the reserve, not at the absolute minimum.
+// --------------------------------------------------------------------------------------------------------------
+
+/// Replace a charge at/above its `max_soc_percent` or a discharge at/below its `min_soc_percent` with `Idle`.
+pub fn guard_soc_boundaries(verdict: Verdict, battery: &BatterySnapshot) -> Verdict {
+    let soc = battery.battery_soc;
+    let reason = match verdict.decision {
+        Decision::ChargingFromSolar { max_soc_percent, .. }
+        | Decision::ChargingFromGrid { max_soc_percent, .. }
+            if soc >= max_soc_percent as f64 =>
+        {
+            format!("battery full: SOC {:.1}% ≥ {}%, charge not sent", soc, max_soc_percent)
+        }
+        Decision::DischargingToLoad { min_soc_percent, .. }
+        | Decision::DischargingToGrid { min_soc_percent, .. }
+            if soc <= min_soc_percent as f64 =>
+        {
+            format!("battery at floor: SOC {:.1}% ≤ {}%, discharge not sent", soc, min_soc_percent)
+        }
+        _ => return verdict,
+    };
+
+    info!("[Optimiser] {} suppressed - {}", verdict.decision, reason);
+    Verdict::new(Decision::Idle, format!("{} (was: {})", reason, verdict.reason))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::optimiser::testing::battery;
+
+    fn charge() -> Verdict {
+        Verdict::new(Decision::ChargingFromGrid { watts: 2000, max_soc_percent: 90 }, "cheap hour")
+    }
+
+    fn discharge() -> Verdict {
+        Verdict::new(Decision::DischargingToLoad { watts: 800, min_soc_percent: 20 }, "load")
+    }
+
+    #[test]
+    fn a_charge_stops_at_its_target_soc() {
+        let at_max = guard_soc_boundaries(charge(), &battery(90.0, 0));
+        assert_eq!(at_max.decision, Decision::Idle);
+        assert!(at_max.reason.starts_with("battery full: SOC 90.0% ≥ 90%"), "{}", at_max.reason);
+
+        assert_eq!(guard_soc_boundaries(charge(), &battery(90.5, 0)).decision, Decision::Idle);
+        assert_eq!(guard_soc_boundaries(charge(), &battery(89.9, 0)).decision, charge().decision);
+    }
+
+    #[test]
+    fn a_discharge_stops_at_its_floor() {
+        let at_floor = guard_soc_boundaries(discharge(), &battery(20.0, 0));
+        assert_eq!(at_floor.decision, Decision::Idle);
+        assert!(at_floor.reason.ends_with("(was: load)"), "{}", at_floor.reason);
+
+        assert_eq!(guard_soc_boundaries(discharge(), &battery(19.5, 0)).decision, Decision::Idle);
+        assert_eq!(guard_soc_boundaries(discharge(), &battery(20.1, 0)).decision, discharge().decision);
+    }
+
+    #[test]
+    fn idle_passes_at_any_soc() {
+        let idle = Verdict::new(Decision::Idle, "balanced");
+        assert_eq!(guard_soc_boundaries(idle, &battery(100.0, 0)).decision, Decision::Idle);
+    }
+}
@@ -0,0 +1,150 @@
+use chrono::{DateTime, NaiveDate, Utc};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+
+use crate::analytics::energy::{energy_delta_wh, wh_to_kwh};
+use crate::configuration::config::Config;
+use crate::handlers::indevolt::reader::ID_TOTAL_CHARGING;
+use crate::models::indevolt_models::BatterySnapshot;
+use crate::runtime::clock::LOCAL_TZ;
+
+use super::decision::{Decision, Verdict};
+use super::grid::GridReading;
+use super::{apply_limits, self_consumption};
+
+// --------------------------------------------------------------------------------------------------------------
+// Daily grid-charge cap
+//
+// A broken price feed or a strategy bug could keep the battery charging from the grid all day. With
+// `max_daily_grid_charge_kwh` set, the energy charged while `ChargingFromGrid` was in force is added up per
+// local day from the battery's cumulative charge counter. Once the total reaches the cap, further grid charge
+// decisions fall back to self-consumption until local midnight; charging from solar and discharging are not
+// affected. A grid charge also takes in any solar surplus at the same time, so the count errs on the high side.
+// The total is kept in the state file, so a restart doesn't reset the day.
+// --------------------------------------------------------------------------------------------------------------
+
+/// Energy charged from the grid today.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct GridChargeCap {
+    day:            Option<NaiveDate>,
+    charged_wh:     i64,
+    /// Last reading of the cumulative charge counter (kWh).
+    last_total_kwh: Option<f64>,
+    /// Whether the cap was reached today (logged once).
+    reached:        bool,
+}
+
+impl GridChargeCap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Grid-charged energy so far today (kWh).
+    pub fn charged_kwh(&self) -> f64 {
+        wh_to_kwh(self.charged_wh)
+    }
+
+    /// Feed one cycle. `previous` is the decision that was in force since the last cycle.
+    pub fn observe(&mut self, now: DateTime<Utc>, battery: &BatterySnapshot, previous: Option<&Decision>) {
+        let date = now.with_timezone(&LOCAL_TZ).date_naive();
+        if self.day != Some(date) {
+            if self.day.is_some() {
+                info!("[Optimiser] Daily grid-charge cap reset ({:.2} kWh the day before)", self.charged_kwh());
+            }
+            self.day        = Some(date);
+            self.charged_wh = 0;
+            self.reached    = false;
+        }
+
+        if !battery.is_fresh(ID_TOTAL_CHARGING) {
+            return;
+        }
+        let total = battery.total_charging_kwh;
+        if let Some(last) = self.last_total_kwh.replace(total) {
+            if matches!(previous, Some(Decision::ChargingFromGrid { .. })) {
+                self.charged_wh += energy_delta_wh(last, total);
+            }
+        }
+    }
+
+    /// Replace a `ChargingFromGrid` verdict with self-consumption once today's cap is reached.
+    pub fn apply(
+        &mut self,
+        verdict: Verdict,
+        grid: &GridReading,
+        battery: &BatterySnapshot,
+        config: &Config,
+    ) -> Verdict {
+        let cap_kwh = config.max_daily_grid_charge_kwh;
+        if cap_kwh <= 0.0 || !matches!(verdict.decision, Decision::ChargingFromGrid { .. }) {
+            return verdict;
+        }
+        let charged_kwh = self.charged_kwh();
+        if charged_kwh < cap_kwh {
+            return verdict;
+        }
+        if !self.reached {
+            warn!(
+                "[Optimiser] Daily grid-charge cap reached ({:.2} ≥ {:.2} kWh) - no grid charge until midnight",
+                charged_kwh, cap_kwh
+            );
+            self.reached = true;
+        }
+
+        let fallback = apply_limits(self_consumption::decide(grid, battery, config), battery, config);
+        Verdict::new(
+            fallback.decision,
+            format!("daily grid-charge cap {:.2} kWh reached: {}", cap_kwh, fallback.reason),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::optimiser::testing::{battery, grid};
+    use chrono::{Duration, TimeZone};
+
+    fn grid_charge() -> Decision {
+        Decision::ChargingFromGrid { watts: 2400, max_soc_percent: 90 }
+    }
+
+    fn counter(total_charging_kwh: f64) -> BatterySnapshot {
+        BatterySnapshot { total_charging_kwh, ..battery(50.0, 2400) }
+    }
+
+    #[test]
+    fn reaching_the_cap_suppresses_grid_charging_until_midnight() {
+        let config = Config { max_daily_grid_charge_kwh: 2.0, ..Config::default() };
+        let start  = Utc.with_ymd_and_hms(2026, 3, 10, 1, 0, 0).unwrap();
+        let mut cap = GridChargeCap::new();
+
+        // 1.2 kWh charged from the grid, then 0.5 kWh from solar that doesn't count, then 0.9 kWh more.
+        let solar = Decision::ChargingFromSolar { watts: 1000, max_soc_percent: 100 };
+        cap.observe(start, &counter(100.0), None);
+        cap.observe(start + Duration::minutes(30), &counter(101.2), Some(&grid_charge()));
+        cap.observe(start + Duration::minutes(60), &counter(101.7), Some(&solar));
+        let below = cap.apply(Verdict::new(grid_charge(), "cheap"), &grid(0), &battery(50.0, 0), &config);
+        assert_eq!(below.decision, grid_charge());
+
+        cap.observe(start + Duration::minutes(90), &counter(102.6), Some(&grid_charge()));
+        assert!((cap.charged_kwh() - 2.1).abs() < 1e-9, "{}", cap.charged_kwh());
+        let capped = cap.apply(Verdict::new(grid_charge(), "cheap"), &grid(0), &battery(50.0, 0), &config);
+        assert_eq!(capped.decision, Decision::Idle);
+        assert!(capped.reason.starts_with("daily grid-charge cap 2.00 kWh reached"), "{}", capped.reason);
+
+        // Solar charging is never capped, and the next local day starts from zero.
+        let solar_verdict = Verdict::new(solar.clone(), "surplus");
+        assert_eq!(cap.apply(solar_verdict, &grid(-1000), &battery(50.0, 0), &config).decision, solar);
+        cap.observe(Utc.with_ymd_and_hms(2026, 3, 10, 23, 30, 0).unwrap(), &counter(102.6), None);
+        assert_eq!(cap.charged_kwh(), 0.0);
+    }
+
+    #[test]
+    fn no_cap_configured_never_suppresses() {
+        let mut cap = GridChargeCap { charged_wh: 50_000, ..GridChargeCap::new() };
+        let config  = Config::default();
+        let verdict = cap.apply(Verdict::new(grid_charge(), "cheap"), &grid(0), &battery(50.0, 0), &config);
+        assert_eq!(verdict.decision, grid_charge());
+    }
+}
@@ -0,0 +1,36 @@
+use log::info;
+
+use super::decision::{Decision, Verdict};
+
+// --------------------------------------------------------------------------------------------------------------
+// Acting on the data quality score
+//
+// Below `data_quality_min_score` the optimiser is conservative: the commanded power is scaled by the score
+// (a score of 40 → 40 % of the intended power), and a decision that scales to nothing becomes `Idle`.
+// Decisions without a power setpoint pass unchanged.
+// --------------------------------------------------------------------------------------------------------------
+
+pub fn apply_data_quality(verdict: Verdict, score: u8, min_score: u8) -> Verdict {
+    if score >= min_score {
+        return verdict;
+    }
+    let Some(watts) = verdict.decision.watts() else {
+        return verdict;
+    };
+
+    let scaled = watts * score as i32 / 100;
+    info!(
+        "[Optimiser] Data quality {} < {} - power reduced {}W → {}W",
+        score, min_score, watts, scaled
+    );
+    if scaled <= 0 {
+        return Verdict::new(
+            Decision::Idle,
+            format!("data quality {} too low to act (was: {})", score, verdict.reason),
+        );
+    }
+    Verdict::new(
+        verdict.decision.with_watts(scaled),
+        format!("{}; scaled to {}W for data quality {}", verdict.reason, scaled, score),
+    )
+}
@@ -0,0 +1,114 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+// --------------------------------------------------------------------------------------------------------------
+
+/// What the optimiser wants the battery to do this cycle.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "action")]
+pub enum Decision {
+    /// Hand control back to the inverter's own self-consumption mode.
+    SelfConsumption,
+    /// Hold: real-time control with the battery stopped.
+    Idle,
+    /// Charge from solar surplus.
+    ChargingFromSolar { watts: i32, max_soc_percent: u8 },
+    /// Charge from the grid.
+    ChargingFromGrid { watts: i32, max_soc_percent: u8 },
+    /// Discharge to cover household load (no export).
+    DischargingToLoad { watts: i32, min_soc_percent: u8 },
+    /// Discharge beyond household load, exporting to the grid.
+    DischargingToGrid { watts: i32, min_soc_percent: u8 },
+}
+
+/// Direction of battery power a decision asks for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Charge,
+    Discharge,
+    Neutral,
+}
+
+impl Decision {
+    pub fn direction(&self) -> Direction {
+        match self {
+            Decision::ChargingFromSolar { .. } | Decision::ChargingFromGrid { .. }  => Direction::Charge,
+            Decision::DischargingToLoad { .. } | Decision::DischargingToGrid { .. } => Direction::Discharge,
+            Decision::SelfConsumption | Decision::Idle                             => Direction::Neutral,
+        }
+    }
+
+    /// True when going from `self` to `next` flips charge ↔ discharge.
+    pub fn reverses(&self, next: &Decision) -> bool {
+        matches!(
+            (self.direction(), next.direction()),
+            (Direction::Charge, Direction::Discharge) | (Direction::Discharge, Direction::Charge)
+        )
+    }
+
+    /// Commanded power in W, `None` for decisions without a power setpoint.
+    pub fn watts(&self) -> Option<i32> {
+        match self {
+            Decision::ChargingFromSolar { watts, .. }
+            | Decision::ChargingFromGrid { watts, .. }
+            | Decision::DischargingToLoad { watts, .. }
+            | Decision::DischargingToGrid { watts, .. } => Some(*watts),
+            Decision::SelfConsumption | Decision::Idle => None,
+        }
+    }
+
+    /// The same decision with a different power setpoint. Decisions without one are returned unchanged.
+    pub fn with_watts(&self, new_watts: i32) -> Decision {
+        let mut d = self.clone();
+        match &mut d {
+            Decision::ChargingFromSolar { watts, .. }
+            | Decision::ChargingFromGrid { watts, .. }
+            | Decision::DischargingToLoad { watts, .. }
+            | Decision::DischargingToGrid { watts, .. } => *watts = new_watts,
+            Decision::SelfConsumption | Decision::Idle => {}
+        }
+        d
+    }
+
+    /// Short label for logs and the cycle outcome.
+    pub fn label(&self) -> &'static str {
+        match self {
+            Decision::SelfConsumption          => "SelfConsumption",
+            Decision::Idle                     => "Idle",
+            Decision::ChargingFromSolar { .. } => "ChargingFromSolar",
+            Decision::ChargingFromGrid { .. }  => "ChargingFromGrid",
+            Decision::DischargingToLoad { .. } => "DischargingToLoad",
+            Decision::DischargingToGrid { .. } => "DischargingToGrid",
+        }
+    }
+}
+
+impl fmt::Display for Decision {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Decision::ChargingFromSolar { watts, max_soc_percent }
+            | Decision::ChargingFromGrid { watts, max_soc_percent } => {
+                write!(f, "{} {}W → {}%", self.label(), watts, max_soc_percent)
+            }
+            Decision::DischargingToLoad { watts, min_soc_percent }
+            | Decision::DischargingToGrid { watts, min_soc_percent } => {
+                write!(f, "{} {}W → {}%", self.label(), watts, min_soc_percent)
+            }
+            _ => write!(f, "{}", self.label()),
+        }
+    }
+}
+
+/// A decision plus the human-readable reason it was taken.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Verdict {
+    pub decision: Decision,
+    pub reason:   String,
+}
+
+impl Verdict {
+    pub fn new(decision: Decision, reason: impl Into<String>) -> Self {
+        Self { decision, reason: reason.into() }
+    }
+}
@@ -0,0 +1,127 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use log::warn;
+
+use super::decision::{Decision, Direction, Verdict};
+
+// --------------------------------------------------------------------------------------------------------------
+// Direction-change rate limit
+//
+// Every charge ↔ discharge change switches relays in the inverter. Volatile load or prices can make the
+// optimiser alternate faster than the hardware should, so at most `max_direction_changes_per_hour` changes are
+// allowed in any rolling hour. A change counts against the last charging or discharging direction actually
+// passed on, so charge → Idle → discharge is a change too. Over the limit the reversal is replaced by `Idle`;
+// it goes through again as soon as the oldest change in the window is more than an hour old.
+// --------------------------------------------------------------------------------------------------------------
+
+const WINDOW: Duration = Duration::from_secs(3600);
+
+/// Direction changes passed on during the last hour.
+#[derive(Debug, Default)]
+pub struct DirectionChanges {
+    last_direction: Option<Direction>,
+    changes:        VecDeque<Instant>,
+}
+
+impl DirectionChanges {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Number of changes within the hour before `now`.
+    pub fn in_last_hour(&mut self, now: Instant) -> usize {
+        while self.changes.front().is_some_and(|t| now.duration_since(*t) >= WINDOW) {
+            self.changes.pop_front();
+        }
+        self.changes.len()
+    }
+
+    /// Pass `verdict` on, or replace it with `Idle` when it would exceed `max_per_hour` direction changes.
+    /// `max_per_hour == 0` disables the limit.
+    pub fn limit(&mut self, verdict: Verdict, now: Instant, max_per_hour: u32) -> Verdict {
+        let direction = verdict.decision.direction();
+        if direction == Direction::Neutral {
+            return verdict;
+        }
+        let is_change = self.last_direction.is_some_and(|d| d != direction);
+
+        if is_change {
+            let count = self.in_last_hour(now);
+            if max_per_hour > 0 && count >= max_per_hour as usize {
+                warn!(
+                    "[Optimiser] Rate-limited: {} direction changes in the last hour (max {}) - not sending {}",
+                    count, max_per_hour, verdict.decision
+                );
+                return Verdict::new(
+                    Decision::Idle,
+                    format!(
+                        "direction changes rate-limited, not switching to {} ({})",
+                        verdict.decision.label(), verdict.reason
+                    ),
+                );
+            }
+            self.changes.push_back(now);
+        }
+
+        self.last_direction = Some(direction);
+        verdict
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn charge() -> Verdict {
+        Verdict::new(Decision::ChargingFromSolar { watts: 800, max_soc_percent: 100 }, "surplus")
+    }
+
+    fn discharge() -> Verdict {
+        Verdict::new(Decision::DischargingToLoad { watts: 800, min_soc_percent: 10 }, "load")
+    }
+
+    #[test]
+    fn an_alternating_stream_is_clamped_to_the_hourly_limit() {
+        let mut changes = DirectionChanges::new();
+        let start = Instant::now();
+        let decisions: Vec<_> = (0..8u64)
+            .map(|minute| {
+                let verdict = if minute % 2 == 0 { charge() } else { discharge() };
+                changes.limit(verdict, start + Duration::from_secs(minute * 60), 3).decision
+            })
+            .collect();
+
+        // The first charge is no change and three reversals go through. After that the battery stays on
+        // discharge: every charge is held at Idle, and a discharge is no change.
+        let (c, d) = (charge().decision, discharge().decision);
+        let idle = Decision::Idle;
+        assert_eq!(decisions, [c.clone(), d.clone(), c, d.clone(), idle.clone(), d.clone(), idle, d]);
+        assert_eq!(changes.in_last_hour(start + Duration::from_secs(420)), 3);
+    }
+
+    #[test]
+    fn a_change_counts_across_idle_and_goes_through_once_the_window_passes() {
+        let mut changes = DirectionChanges::new();
+        let start = Instant::now();
+        changes.limit(charge(), start, 1);
+        changes.limit(discharge(), start + Duration::from_secs(60), 1);
+        changes.limit(Verdict::new(Decision::Idle, "balanced"), start + Duration::from_secs(120), 1);
+
+        let blocked = changes.limit(charge(), start + Duration::from_secs(180), 1);
+        assert_eq!(blocked.decision, Decision::Idle);
+        let allowed = changes.limit(charge(), start + Duration::from_secs(3661), 1);
+        assert_eq!(allowed.decision, charge().decision);
+    }
+
+    #[test]
+    fn zero_disables_the_limit() {
+        let mut changes = DirectionChanges::new();
+        let start = Instant::now();
+        for second in 0..20u64 {
+            let verdict = if second % 2 == 0 { charge() } else { discharge() };
+            let expected = verdict.decision.clone();
+            assert_eq!(changes.limit(verdict, start + Duration::from_secs(second), 0).decision, expected);
+        }
+    }
+}
@@ -0,0 +1,323 @@
+use log::{debug, error, info, warn};
+use std::time::{Duration, Instant};
+
+use crate::handlers::indevolt::controller;
+use crate::handlers::indevolt::registers::RegisterWrite;
+use crate::models::indevolt_models::{BatterySnapshot, WorkingMode};
+
+use super::allowed_modes::mode_allowed;
+use super::decision::Decision;
+
+// --------------------------------------------------------------------------------------------------------------
+// Dispatcher
+//
+// Turns a `Decision` into controller calls:
+//   SelfConsumption       → working mode 1 (only if the device isn't already in it)
+//   Idle                  → working mode 4 + stop
+//   Charging*/Discharging* → working mode 4 + charge/discharge
+//
+// A decision identical to the last one sent, with the device still in the expected mode, is not re-sent.
+// If the mode switch succeeds but the charge/discharge/stop write that follows fails, the device is rolled back
+// to stop + self-consumption rather than left in real-time mode without a command.
+// With `control_readback_sensor_ids` configured, each control write is read back and re-sent once if the
+// device reports a different command; an unconfirmed command is reported as an error and retried next cycle.
+// A decision outside `allowed_working_modes` is refused before anything is written.
+// After `control_failure_threshold` cycles in a row whose command didn't get through, the device may still be
+// executing a stale one in real-time mode; self-consumption is restored (best effort, retried after as many
+// failures again) so the battery falls back to safe behaviour until control works again.
+// --------------------------------------------------------------------------------------------------------------
+
+#[derive(Debug, Default)]
+pub struct Dispatcher {
+    last_sent:         Option<Decision>,
+    last_command_at:   Option<Instant>,
+    readback_ids:      Option<[u32; 3]>,
+    allowed_modes:     Option<Vec<String>>,
+    /// Cycles in a row whose command failed, and the count that restores self-consumption (0: never).
+    failures:          u32,
+    failure_threshold: u32,
+}
+
+impl Dispatcher {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Confirm every control write by reading the active command back from these GetData sensor IDs
+    /// (action, power, SOC limit). `None` skips the confirmation.
+    pub fn with_readback(mut self, ids: Option<[u32; 3]>) -> Self {
+        self.readback_ids = ids;
+        self
+    }
+
+    /// Refuse charge/discharge decisions not in `allowed` (see `allowed_modes.rs`). Without it all are sent.
+    pub fn with_allowed_modes(mut self, allowed: Vec<String>) -> Self {
+        self.allowed_modes = Some(allowed);
+        self
+    }
+
+    /// Restore self-consumption after `threshold` failed cycles in a row. 0 (the default) never does.
+    pub fn with_failure_threshold(mut self, threshold: u32) -> Self {
+        self.failure_threshold = threshold;
+        self
+    }
+
+    /// The decision most recently applied successfully.
+    pub fn last_sent(&self) -> Option<&Decision> {
+        self.last_sent.as_ref()
+    }
+
+    /// Time since the last command was sent to the inverter.
+    pub fn since_last_command(&self) -> Option<Duration> {
+        self.last_command_at.map(|t| t.elapsed())
+    }
+
+    /// Apply `decision` to the inverter. Returns `Ok(true)` when at least one command was sent.
+    pub async fn apply_decision(
+        &mut self,
+        base_url: &str,
+        decision: &Decision,
+        battery:  &BatterySnapshot,
+    ) -> Result<bool, String> {
+        if let Some(allowed) = &self.allowed_modes {
+            if !mode_allowed(decision, allowed) {
+                return Err(format!("[Dispatch] {} refused: not in allowed_working_modes", decision));
+            }
+        }
+
+        let result = self.send(base_url, decision, battery).await;
+        if result.is_ok() {
+            if self.failures > 0 {
+                info!("[Dispatch] Control working again after {} failed cycle(s)", self.failures);
+            }
+            self.failures = 0;
+        } else {
+            self.failures += 1;
+            if self.failure_threshold > 0 && self.failures.is_multiple_of(self.failure_threshold) {
+                self.restore_safe_state(base_url).await;
+            }
+        }
+        result
+    }
+
+    /// Send `decision` unless it is already in force.
+    async fn send(
+        &mut self,
+        base_url: &str,
+        decision: &Decision,
+        battery:  &BatterySnapshot,
+    ) -> Result<bool, String> {
+        let device_mode = battery.mode;
+        let wanted_mode = match decision {
+            Decision::SelfConsumption => WorkingMode::SelfConsumedPrioritized,
+            _                         => WorkingMode::RealtimeControl,
+        };
+
+        if self.last_sent.as_ref() == Some(decision) && device_mode.as_ref() == Some(&wanted_mode) {
+            debug!("[Dispatch] {} unchanged - not re-sent", decision);
+            return Ok(false);
+        }
+
+        // Whatever happens below, the previous command is no longer known to be active.
+        self.last_sent = None;
+
+        self.last_command_at = Some(Instant::now());
+        let mode_switched = device_mode.as_ref() != Some(&wanted_mode);
+        if mode_switched {
+            controller::set_working_mode(base_url, wanted_mode).await?;
+        }
+
+        if let Err(e) = send_control(base_url, decision).await {
+            if mode_switched {
+                rollback(base_url).await;
+            }
+            return Err(e);
+        }
+
+        if let Some(ids) = self.readback_ids {
+            self.confirm(base_url, decision, ids).await?;
+        }
+
+        info!("[Dispatch] Applied {}", decision);
+        self.last_sent = Some(decision.clone());
+        Ok(true)
+    }
+
+    /// Control failed `failure_threshold` cycles in a row: hand the device back to self-consumption.
+    async fn restore_safe_state(&mut self, base_url: &str) {
+        error!(
+            "[Dispatch] {} control failures in a row - restoring self-consumption mode as a safe state",
+            self.failures
+        );
+        self.last_sent = None;
+        match controller::restore_auto_mode(base_url).await {
+            Ok(()) => warn!("[Dispatch] Device back in self-consumption mode until control works again"),
+            Err(e) => error!("[Dispatch] Restore of self-consumption mode failed: {}", e),
+        }
+    }
+
+    /// Read the control register back and compare it with `decision`; on a mismatch re-send once.
+    async fn confirm(&self, base_url: &str, decision: &Decision, ids: [u32; 3]) -> Result<(), String> {
+        let Some((action, watts, soc)) = expected_control(decision) else {
+            return Ok(());
+        };
+        for attempt in 1..=2 {
+            let readback = controller::read_control_register(base_url, ids).await?;
+            if readback.matches(action, watts, soc) {
+                debug!("[Dispatch] Read-back confirms {}", decision);
+                return Ok(());
+            }
+            warn!(
+                "[Dispatch] Read-back {:?} disagrees with {} (attempt {})",
+                readback, decision, attempt
+            );
+            if attempt == 1 {
+                send_control(base_url, decision).await?;
+            }
+        }
+        Err(format!("[Dispatch] {} not confirmed by the device after a retry", decision))
+    }
+}
+
+/// The control write for `decision` (none for `SelfConsumption`, which is only a mode).
+async fn send_control(base_url: &str, decision: &Decision) -> Result<(), String> {
+    match decision {
+        Decision::SelfConsumption => Ok(()),
+        Decision::Idle => controller::stop(base_url).await,
+        Decision::ChargingFromSolar { watts, max_soc_percent }
+        | Decision::ChargingFromGrid { watts, max_soc_percent } => {
+            controller::charge(base_url, *watts, *max_soc_percent).await
+        }
+        Decision::DischargingToLoad { watts, min_soc_percent }
+        | Decision::DischargingToGrid { watts, min_soc_percent } => {
+            controller::discharge(base_url, *watts, *min_soc_percent).await
+        }
+    }
+}
+
+/// `[action, watts, soc]` the control register should hold after `decision`.
+fn expected_control(decision: &Decision) -> Option<(i64, i64, i64)> {
+    let write = match decision {
+        Decision::SelfConsumption => return None,
+        Decision::Idle => RegisterWrite::stop(),
+        Decision::ChargingFromSolar { watts, max_soc_percent }
+        | Decision::ChargingFromGrid { watts, max_soc_percent } => {
+            RegisterWrite::charge(*watts, *max_soc_percent)
+        }
+        Decision::DischargingToLoad { watts, min_soc_percent }
+        | Decision::DischargingToGrid { watts, min_soc_percent } => {
+            RegisterWrite::discharge(*watts, *min_soc_percent)
+        }
+    };
+    match write.values[..] {
+        [action, watts, soc] => Some((action, watts, soc)),
+        _ => None,
+    }
+}
+
+/// Mode write succeeded but the control write didn't: the device would sit in real-time mode with no valid
+/// command. Put it back in a known-safe state - stopped and in its own self-consumption mode.
+async fn rollback(base_url: &str) {
+    warn!("[Dispatch] Control write failed after mode switch - rolling back to stop + self-consumption");
+    if let Err(e) = controller::stop(base_url).await {
+        error!("[Dispatch] Rollback stop failed: {}", e);
+    }
+    match controller::restore_auto_mode(base_url).await {
+        Ok(()) => info!("[Dispatch] Rollback complete - device back in self-consumption mode"),
+        Err(e) => error!("[Dispatch] Rollback to self-consumption failed: {}", e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::handlers::indevolt::registers::{REG_CONTROL, REG_WORKING_MODE};
+    use crate::handlers::indevolt::testing::MockIndevolt;
+    use crate::optimiser::testing::battery;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+
+    const CHARGE: Decision = Decision::ChargingFromGrid { watts: 2000, max_soc_percent: 90 };
+
+    #[tokio::test]
+    async fn a_failed_control_write_after_a_mode_switch_rolls_back() {
+        let device = MockIndevolt::start(|request| {
+            if request.contains("\"t\":47015,\"v\":[1,") {
+                (500, "busy".to_string())
+            } else {
+                (200, r#"{"result": true}"#.to_string())
+            }
+        })
+        .await;
+        let mut self_consuming = battery(50.0, 0);
+        self_consuming.mode    = Some(WorkingMode::SelfConsumedPrioritized);
+
+        let result = Dispatcher::new().apply_decision(&device.base_url, &CHARGE, &self_consuming).await;
+        assert!(result.is_err());
+        // Mode switch, the failed charge, then stop + self-consumption.
+        let write = |register, values| {
+            format!("/rpc/Indevolt.SetData {{\"f\":16,\"t\":{},\"v\":{}}}", register, values)
+        };
+        assert_eq!(
+            device.requests(),
+            vec![
+                write(REG_WORKING_MODE, "[4]"),
+                write(REG_CONTROL, "[1,2000,90]"),
+                write(REG_CONTROL, "[0,0,0]"),
+                write(REG_WORKING_MODE, "[1]"),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn repeated_control_failures_restore_self_consumption() {
+        let device = MockIndevolt::start(|_| (500, "busy".to_string())).await;
+        let mut dispatcher = Dispatcher::new().with_failure_threshold(3);
+
+        for _ in 0..2 {
+            assert!(dispatcher.apply_decision(&device.base_url, &CHARGE, &battery(50.0, 0)).await.is_err());
+        }
+        assert!(!device.wrote(REG_WORKING_MODE, "[1]"));
+
+        assert!(dispatcher.apply_decision(&device.base_url, &CHARGE, &battery(50.0, 0)).await.is_err());
+        assert!(device.wrote(REG_WORKING_MODE, "[1]"));
+    }
+
+    #[tokio::test]
+    async fn a_success_resets_the_failure_count() {
+        let fail    = Arc::new(AtomicBool::new(true));
+        let failing = Arc::clone(&fail);
+        let device  = MockIndevolt::start(move |_| {
+            if failing.load(Ordering::Relaxed) {
+                (500, "busy".to_string())
+            } else {
+                (200, r#"{"result": true}"#.to_string())
+            }
+        })
+        .await;
+        let mut dispatcher = Dispatcher::new().with_failure_threshold(2);
+
+        assert!(dispatcher.apply_decision(&device.base_url, &CHARGE, &battery(50.0, 0)).await.is_err());
+        fail.store(false, Ordering::Relaxed);
+        assert!(dispatcher.apply_decision(&device.base_url, &CHARGE, &battery(50.0, 0)).await.is_ok());
+        fail.store(true, Ordering::Relaxed);
+        assert!(dispatcher.apply_decision(&device.base_url, &Decision::Idle, &battery(50.0, 0)).await.is_err());
+
+        assert!(!device.wrote(REG_WORKING_MODE, "[1]"));
+    }
+
+    #[tokio::test]
+    async fn a_disallowed_decision_is_refused_before_any_write() {
+        let device  = MockIndevolt::start(|_| (200, r#"{"result": true}"#.to_string())).await;
+        let allowed = vec!["ChargingFromSolar".to_string(), "DischargingToLoad".to_string()];
+        let export  = Decision::DischargingToGrid { watts: 2400, min_soc_percent: 20 };
+
+        let result = Dispatcher::new()
+            .with_allowed_modes(allowed)
+            .apply_decision(&device.base_url, &export, &battery(80.0, 0))
+            .await;
+        assert!(result.unwrap_err().contains("not in allowed_working_modes"));
+        assert!(device.requests().is_empty());
+    }
+}
+
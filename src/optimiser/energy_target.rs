@@ -0,0 +1,93 @@
+use log::warn;
+
+use crate::configuration::config::Config;
+use crate::models::indevolt_models::BatterySnapshot;
+
+use super::decision::{Decision, Verdict};
+
+// --------------------------------------------------------------------------------------------------------------
+// Energy-based charge targets
+//
+// Price-driven buying is naturally expressed in energy ("buy 5 kWh while it's cheap"), while the inverter's
+// charge command takes a stop SOC. The conversion uses the rated capacity:
+//   stop_soc = soc + energy_kwh / battery_rated_capacity_kwh * 100
+// clamped to `battery_max_soc_percent`. Whatever doesn't fit in the remaining headroom is reported as a
+// shortfall. The price plan (`plan.rs`) charges this way: a planned charge buys the energy the battery still
+// needs to reach the hour's floor.
+// --------------------------------------------------------------------------------------------------------------
+
+/// Stop SOC for an energy target, and how much of the target the battery can't take.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EnergyTarget {
+    pub stop_soc_percent: f64,
+    pub shortfall_kwh:    f64,
+}
+
+/// Convert "add `energy_kwh` to the battery" into the SOC to stop charging at.
+pub fn soc_for_energy(current_soc: f64, energy_kwh: f64, config: &Config) -> EnergyTarget {
+    let capacity = config.battery_rated_capacity_kwh;
+    if capacity <= 0.0 {
+        return EnergyTarget { stop_soc_percent: current_soc, shortfall_kwh: energy_kwh.max(0.0) };
+    }
+
+    let wanted   = current_soc + energy_kwh.max(0.0) / capacity * 100.0;
+    let stop     = wanted.min(config.battery_max_soc_percent).max(current_soc);
+    let headroom = (stop - current_soc) / 100.0 * capacity;
+    EnergyTarget { stop_soc_percent: stop, shortfall_kwh: (energy_kwh - headroom).max(0.0) }
+}
+
+/// Grid-charge decision that adds `energy_kwh` at up to `watts`, capped at the configured charge power.
+pub fn charge_energy(energy_kwh: f64, watts: i32, battery: &BatterySnapshot, config: &Config) -> Verdict {
+    let target = soc_for_energy(battery.battery_soc, energy_kwh, config);
+    if target.shortfall_kwh > 0.0 {
+        warn!(
+            "[Optimiser] Energy target {:.2} kWh exceeds headroom - charging to {:.1}%, {:.2} kWh short",
+            energy_kwh, target.stop_soc_percent, target.shortfall_kwh
+        );
+    }
+    if target.stop_soc_percent <= battery.battery_soc {
+        return Verdict::new(Decision::Idle, format!("energy target {:.2} kWh: no headroom", energy_kwh));
+    }
+
+    Verdict::new(
+        Decision::ChargingFromGrid {
+            watts:           watts.min(config.battery_max_charge_power_w),
+            max_soc_percent: config.soc_target(target.stop_soc_percent),
+        },
+        format!(
+            "energy target {:.2} kWh → stop at {:.1}% SOC",
+            energy_kwh - target.shortfall_kwh, target.stop_soc_percent
+        ),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::optimiser::testing::battery;
+
+    #[test]
+    fn energy_converts_to_a_stop_soc_over_the_rated_capacity() {
+        // 3 kWh of 12 kWh is 25 points.
+        let target = soc_for_energy(40.0, 3.0, &Config::default());
+        assert_eq!(target, EnergyTarget { stop_soc_percent: 65.0, shortfall_kwh: 0.0 });
+    }
+
+    #[test]
+    fn a_target_beyond_the_headroom_stops_at_max_soc() {
+        let config = Config { battery_max_soc_percent: 95.0, ..Config::default() };
+        let target = soc_for_energy(80.0, 3.0, &config);
+        assert_eq!(target.stop_soc_percent, 95.0);
+        // 15 points of 12 kWh fit, 1.2 kWh doesn't.
+        assert!((target.shortfall_kwh - 1.2).abs() < 1e-9, "{:?}", target);
+    }
+
+    #[test]
+    fn charge_energy_charges_to_the_stop_soc_at_the_configured_power() {
+        let verdict = charge_energy(3.0, 3000, &battery(40.0, 0), &Config::default());
+        assert_eq!(verdict.decision, Decision::ChargingFromGrid { watts: 2400, max_soc_percent: 65 });
+
+        let full = charge_energy(3.0, 2000, &battery(100.0, 0), &Config::default());
+        assert_eq!(full.decision, Decision::Idle);
+    }
+}
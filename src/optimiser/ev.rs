@@ -0,0 +1,77 @@
+use crate::configuration::config::Config;
+
+use super::decision::{Decision, Direction, Verdict};
+
+// --------------------------------------------------------------------------------------------------------------
+// EV charging coordination
+//
+// An EV charging from the house connection would otherwise be fed by the home battery: the load shows up like
+// any other, and self-consumption discharges to cover it. Moving energy battery → EV only adds a round trip of
+// losses and battery wear, so while the EV charging signal is on (see `handlers/gate.rs`) the EV draws from
+// grid and solar instead:
+//   - a discharge is limited to the household demand above `battery_max_desired_grid_peak_w`, so peak shaving
+//     still holds the capacity-tariff target; below the target the battery pauses (`Idle`);
+//   - charging (solar surplus, planned grid charge) is left alone;
+//   - the idle hand-back is held off, as the inverter's own self-consumption mode would discharge into the EV.
+// The same limit applies to the load-spike fast path, which an EV switching on would otherwise trigger.
+// --------------------------------------------------------------------------------------------------------------
+
+/// Limit a discharge to the peak-shaving part of `house_net_w` while the EV charges.
+pub fn pause_discharge_for_ev(verdict: Verdict, house_net_w: i32, config: &Config) -> Verdict {
+    if verdict.decision.direction() != Direction::Discharge {
+        return verdict;
+    }
+    let peak_w   = config.battery_max_desired_grid_peak_w;
+    let excess_w = house_net_w - peak_w;
+    if excess_w <= 0 {
+        return Verdict::new(
+            Decision::Idle,
+            format!("EV charging: discharge paused, house load {}W within peak target {}W", house_net_w, peak_w),
+        );
+    }
+
+    let watts = verdict.decision.watts().unwrap_or(0);
+    if watts <= excess_w {
+        return verdict;
+    }
+    Verdict::new(
+        verdict.decision.with_watts(excess_w),
+        format!(
+            "EV charging: shaving only the {}W above peak target {}W ({})",
+            excess_w, peak_w, verdict.reason
+        ),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn discharge(watts: i32) -> Verdict {
+        Verdict::new(Decision::DischargingToLoad { watts, min_soc_percent: 20 }, "house load")
+    }
+
+    #[test]
+    fn ev_charging_pauses_a_discharge_below_the_peak_target() {
+        // 2500 W of house load with the EV on is within the 3381 W target, so the battery idles.
+        let verdict = pause_discharge_for_ev(discharge(2400), 2500, &Config::default());
+        assert_eq!(verdict.decision, Decision::Idle);
+        assert!(verdict.reason.contains("within peak target 3381W"), "{}", verdict.reason);
+    }
+
+    #[test]
+    fn ev_charging_still_shaves_the_peak() {
+        let verdict = pause_discharge_for_ev(discharge(2400), 4381, &Config::default());
+        assert_eq!(verdict.decision, Decision::DischargingToLoad { watts: 1000, min_soc_percent: 20 });
+
+        // A discharge already below the excess is left as it is.
+        let verdict = pause_discharge_for_ev(discharge(600), 4381, &Config::default());
+        assert_eq!(verdict.decision, discharge(600).decision);
+    }
+
+    #[test]
+    fn ev_charging_leaves_charging_alone() {
+        let charge = Verdict::new(Decision::ChargingFromSolar { watts: 1500, max_soc_percent: 100 }, "surplus");
+        assert_eq!(pause_discharge_for_ev(charge.clone(), -1500, &Config::default()).decision, charge.decision);
+    }
+}
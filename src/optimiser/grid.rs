@@ -0,0 +1,159 @@
+use crate::configuration::config::Config;
+use crate::handlers::indevolt::reader::ID_METER_POWER;
+use crate::handlers::p1::reader::{P1Error, P1Reading};
+use crate::models::indevolt_models::BatterySnapshot;
+
+// --------------------------------------------------------------------------------------------------------------
+// Grid measurement fed to the optimiser
+//
+// The P1 meter is the reference. When it can't deliver fresh data and `fallback_to_indevolt_meter` is set, the
+// Indevolt's own meter reading stands in for it. That reading is coarser and lags a little, so it comes with a
+// deadband (`indevolt_meter_deadband_w`) within which the optimiser does not act.
+//
+//   P1 read, meter read     → P1; a difference above `grid_meter_disagreement_w` is flagged
+//   P1 read, meter missing  → P1
+//   P1 down, meter read     → meter with deadband (with `fallback_to_indevolt_meter`)
+//   P1 down, meter missing  → no grid measurement, the optimiser sits the cycle out
+// --------------------------------------------------------------------------------------------------------------
+
+/// Where this cycle's grid power came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GridSource {
+    P1,
+    IndevoltMeter,
+}
+
+impl GridSource {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            GridSource::P1            => "p1",
+            GridSource::IndevoltMeter => "indevolt_meter",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct GridReading {
+    /// Net grid power in W (positive = import).
+    pub power_w:        i32,
+    pub source:         GridSource,
+    /// Net house demand within ±this many W is treated as balanced.
+    pub deadband_w:     i32,
+    /// P1 minus Indevolt meter (W) when both were read and differ by more than `grid_meter_disagreement_w`.
+    pub disagreement_w: Option<i32>,
+}
+
+impl GridReading {
+    pub fn from_p1(p1: &P1Reading) -> Self {
+        Self {
+            power_w:        p1.raw.active_power_w.round() as i32,
+            source:         GridSource::P1,
+            deadband_w:     0,
+            disagreement_w: None,
+        }
+    }
+
+    pub fn from_indevolt_meter(battery: &BatterySnapshot, config: &Config) -> Self {
+        Self {
+            power_w:        battery.meter_power_w,
+            source:         GridSource::IndevoltMeter,
+            deadband_w:     config.indevolt_meter_deadband_w,
+            disagreement_w: None,
+        }
+    }
+
+    pub fn is_degraded(&self) -> bool {
+        self.source != GridSource::P1
+    }
+}
+
+/// The grid power to trust this cycle: P1 if it was read, else the Indevolt meter when the fallback is enabled
+/// and P1 failed for lack of fresh data. A malformed P1 response is a bug to fix, not an outage to ride out, so
+/// it doesn't trigger the fallback; neither does a meter value that wasn't read this cycle.
+pub fn best_grid_power_w(
+    p1:      Result<&P1Reading, &P1Error>,
+    battery: &BatterySnapshot,
+    config:  &Config,
+) -> Option<GridReading> {
+    let meter_read = battery.is_fresh(ID_METER_POWER);
+    match p1 {
+        Ok(reading) => {
+            let mut grid = GridReading::from_p1(reading);
+            let diff_w   = grid.power_w - battery.meter_power_w;
+            let limit_w  = config.grid_meter_disagreement_w;
+            if meter_read && limit_w > 0 && diff_w.abs() > limit_w {
+                grid.disagreement_w = Some(diff_w);
+            }
+            Some(grid)
+        }
+        Err(e) if config.fallback_to_indevolt_meter && e.is_connectivity() && meter_read => {
+            Some(GridReading::from_indevolt_meter(battery, config))
+        }
+        Err(_) => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::optimiser::testing::{battery, p1_reading};
+
+    fn meter(power_w: i32) -> BatterySnapshot {
+        BatterySnapshot { meter_power_w: power_w, ..battery(60.0, 0) }
+    }
+
+    fn meter_not_read() -> BatterySnapshot {
+        let mut battery = meter(0);
+        battery.not_fresh.insert(ID_METER_POWER);
+        battery
+    }
+
+    #[test]
+    fn p1_is_the_reference_whenever_it_was_read() {
+        let p1     = p1_reading(1200.4, Duration::ZERO);
+        let config = Config::default();
+
+        let grid = best_grid_power_w(Ok(&p1), &meter(1100), &config).unwrap();
+        assert_eq!((grid.power_w, grid.source, grid.deadband_w), (1200, GridSource::P1, 0));
+        assert_eq!(grid.disagreement_w, None);
+
+        let grid = best_grid_power_w(Ok(&p1), &meter_not_read(), &config).unwrap();
+        assert_eq!((grid.power_w, grid.source, grid.disagreement_w), (1200, GridSource::P1, None));
+    }
+
+    #[test]
+    fn no_grid_measurement_without_p1_or_the_fallback() {
+        let config = Config::default();
+        assert!(best_grid_power_w(Err(&P1Error::Timeout), &meter_not_read(), &config).is_none());
+        assert!(best_grid_power_w(Err(&P1Error::Timeout), &meter(800), &config).is_none());
+    }
+
+    #[test]
+    fn a_meter_disagreeing_with_p1_is_flagged() {
+        let p1     = p1_reading(1200.0, Duration::ZERO);
+        let config = Config::default(); // 300 W
+
+        let grid = best_grid_power_w(Ok(&p1), &meter(800), &config).unwrap();
+        assert_eq!((grid.power_w, grid.disagreement_w), (1200, Some(400)));
+        assert_eq!(best_grid_power_w(Ok(&p1), &meter(1500), &config).unwrap().disagreement_w, None);
+
+        let unchecked = Config { grid_meter_disagreement_w: 0, ..Config::default() };
+        assert_eq!(best_grid_power_w(Ok(&p1), &meter(0), &unchecked).unwrap().disagreement_w, None);
+    }
+
+    #[test]
+    fn the_fallback_meter_stands_in_when_p1_is_down() {
+        let config = Config { fallback_to_indevolt_meter: true, ..Config::default() };
+
+        let grid = best_grid_power_w(Err(&P1Error::Timeout), &meter(800), &config).unwrap();
+        assert_eq!((grid.power_w, grid.source, grid.deadband_w), (800, GridSource::IndevoltMeter, 150));
+        assert!(grid.is_degraded());
+
+        // Not for a malformed response, nor a meter value that wasn't read.
+        let parse = P1Error::Parse("$.active_power_w".into(), "missing".into());
+        assert!(best_grid_power_w(Err(&parse), &meter(800), &config).is_none());
+        assert!(best_grid_power_w(Err(&P1Error::Timeout), &meter_not_read(), &config).is_none());
+    }
+}
@@ -0,0 +1,90 @@
+use std::time::{Duration, Instant};
+
+use log::info;
+
+use super::decision::{Decision, Verdict};
+
+// --------------------------------------------------------------------------------------------------------------
+// Warm standby and hand-back
+//
+// `Idle` keeps the inverter in real-time mode with the battery stopped, so the next charge/discharge is a
+// single control write instead of a mode switch followed by a write. That is the right trade-off for short
+// pauses. After `auto_handback_idle_seconds` of uninterrupted `Idle`, control is handed back to the inverter's
+// own self-consumption mode; the next non-idle decision takes it over again. 0 keeps real-time mode engaged
+// indefinitely.
+// --------------------------------------------------------------------------------------------------------------
+
+/// Tracks how long the optimiser has been idle.
+#[derive(Debug, Default)]
+pub struct IdleHandback {
+    idle_since:  Option<Instant>,
+    handed_back: bool,
+}
+
+impl IdleHandback {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Turn `Idle` into `SelfConsumption` once idle for `after` (zero disables the hand-back).
+    pub fn apply(&mut self, verdict: Verdict, now: Instant, after: Duration) -> Verdict {
+        if verdict.decision != Decision::Idle {
+            self.idle_since  = None;
+            self.handed_back = false;
+            return verdict;
+        }
+
+        let since = *self.idle_since.get_or_insert(now);
+        let idle  = now.duration_since(since);
+        if after.is_zero() || idle < after {
+            return verdict;
+        }
+
+        if !self.handed_back {
+            self.handed_back = true;
+            info!("[Optimiser] Idle for {}s - handing back to self-consumption", idle.as_secs());
+        }
+        Verdict::new(
+            Decision::SelfConsumption,
+            format!("idle for {}s, handed back ({})", idle.as_secs(), verdict.reason),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn idle() -> Verdict {
+        Verdict::new(Decision::Idle, "house load 20W within deadband")
+    }
+
+    #[test]
+    fn idle_cycles_keep_realtime_mode_until_the_timer_expires() {
+        let start        = Instant::now();
+        let after        = Duration::from_secs(300);
+        let mut handback = IdleHandback::new();
+
+        for n in 0..10 {
+            let verdict = handback.apply(idle(), start + Duration::from_secs(30) * n, after);
+            assert_eq!(verdict.decision, Decision::Idle, "cycle {n}");
+        }
+        let verdict = handback.apply(idle(), start + after, after);
+        assert_eq!(verdict.decision, Decision::SelfConsumption);
+        assert_eq!(verdict.reason, "idle for 300s, handed back (house load 20W within deadband)");
+
+        // The next real decision takes over again and restarts the timer.
+        let charge = Verdict::new(Decision::ChargingFromSolar { watts: 800, max_soc_percent: 100 }, "surplus");
+        assert_eq!(handback.apply(charge.clone(), start + after * 2, after), charge);
+        assert_eq!(handback.apply(idle(), start + after * 3, after).decision, Decision::Idle);
+    }
+
+    #[test]
+    fn a_zero_timeout_keeps_realtime_mode_indefinitely() {
+        let start        = Instant::now();
+        let mut handback = IdleHandback::new();
+        handback.apply(idle(), start, Duration::ZERO);
+        let verdict = handback.apply(idle(), start + Duration::from_secs(86_400), Duration::ZERO);
+        assert_eq!(verdict.decision, Decision::Idle);
+    }
+}
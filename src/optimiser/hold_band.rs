@@ -0,0 +1,157 @@
+use log::info;
+
+use crate::configuration::config::Config;
+use crate::models::indevolt_models::BatterySnapshot;
+
+use super::decision::{Decision, Verdict};
+
+// --------------------------------------------------------------------------------------------------------------
+// SOC hold band
+//
+// Once a charge has reached its target SOC, a little household load pulls the SOC just below it and the next
+// bit of surplus (or the plan) charges it back up: the battery cycles for nothing around its target. The hold
+// band adds hysteresis around the targets that were reached. After a charge reached its target T, further
+// charges are held as `Idle` until the SOC falls below T − `soc_hold_band_percent`; after a discharge reached
+// its floor F, further discharges are held until the SOC rises above F + band. The other direction is never
+// held, so the battery can always move out of the band. A charge to a target above T + band (or a discharge to
+// a floor below F − band) is a new target and goes through.
+//
+// The post-command settle time (just before this stage) and the direction-change limit (after it) still apply.
+// They limit reversals in time (the first few seconds after a command, the changes per hour) whatever the SOC;
+// the band limits them by SOC, so a battery resting at its target doesn't restart once the settle time is over.
+// 0 disables the band.
+// --------------------------------------------------------------------------------------------------------------
+
+/// The charge target and discharge floor last reached.
+#[derive(Debug, Default)]
+pub struct HoldBand {
+    charged_to:    Option<f64>,
+    discharged_to: Option<f64>,
+}
+
+impl HoldBand {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Hold a charge or discharge that would restart within the band of the target it last reached.
+    pub fn apply(&mut self, verdict: Verdict, battery: &BatterySnapshot, config: &Config) -> Verdict {
+        let band = config.soc_hold_band_percent;
+        if band <= 0.0 {
+            return verdict;
+        }
+        let soc = battery.battery_soc;
+        match verdict.decision {
+            Decision::ChargingFromSolar { max_soc_percent: target, .. }
+            | Decision::ChargingFromGrid { max_soc_percent: target, .. } => {
+                let target = target as f64;
+                if soc >= target {
+                    self.charged_to = Some(target);
+                    return verdict;
+                }
+                match self.charged_to {
+                    Some(reached) if soc >= reached - band && target <= reached + band => {
+                        hold(verdict, soc, "charge target", reached, band)
+                    }
+                    _ => {
+                        self.charged_to = None;
+                        verdict
+                    }
+                }
+            }
+            Decision::DischargingToLoad { min_soc_percent: floor, .. }
+            | Decision::DischargingToGrid { min_soc_percent: floor, .. } => {
+                let floor = floor as f64;
+                if soc <= floor {
+                    self.discharged_to = Some(floor);
+                    return verdict;
+                }
+                match self.discharged_to {
+                    Some(reached) if soc <= reached + band && floor >= reached - band => {
+                        hold(verdict, soc, "discharge floor", reached, band)
+                    }
+                    _ => {
+                        self.discharged_to = None;
+                        verdict
+                    }
+                }
+            }
+            Decision::SelfConsumption | Decision::Idle => verdict,
+        }
+    }
+}
+
+fn hold(verdict: Verdict, soc: f64, what: &str, reached: f64, band: f64) -> Verdict {
+    info!(
+        "[Optimiser] SOC {:.1}% within {:.1}% of the {} {:.0}% reached - holding instead of {}",
+        soc, band, what, reached, verdict.decision
+    );
+    Verdict::new(
+        Decision::Idle,
+        format!(
+            "SOC {:.1}% within {:.1}% of the {} {:.0}% reached (was: {})",
+            soc, band, what, reached, verdict.reason
+        ),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::optimiser::testing::battery;
+
+    fn config() -> Config {
+        Config { soc_hold_band_percent: 2.0, ..Config::default() }
+    }
+
+    fn charge() -> Verdict {
+        Verdict::new(Decision::ChargingFromSolar { watts: 500, max_soc_percent: 80 }, "solar surplus")
+    }
+
+    fn discharge() -> Verdict {
+        Verdict::new(Decision::DischargingToLoad { watts: 500, min_soc_percent: 20 }, "house load")
+    }
+
+    #[test]
+    fn no_recharge_while_the_soc_stays_within_the_band() {
+        let config   = config();
+        let mut band = HoldBand::new();
+        assert_eq!(band.apply(charge(), &battery(80.0, 0), &config), charge());
+
+        for soc in [79.5, 78.5, 79.0, 78.2] {
+            assert_eq!(band.apply(charge(), &battery(soc, 0), &config).decision, Decision::Idle);
+        }
+        assert_eq!(band.apply(charge(), &battery(77.5, 0), &config), charge());
+    }
+
+    #[test]
+    fn no_new_discharge_while_the_soc_stays_within_the_band() {
+        let config   = config();
+        let mut band = HoldBand::new();
+        assert_eq!(band.apply(discharge(), &battery(20.0, 0), &config), discharge());
+
+        for soc in [20.5, 21.5, 21.0] {
+            assert_eq!(band.apply(discharge(), &battery(soc, 0), &config).decision, Decision::Idle);
+        }
+        assert_eq!(band.apply(discharge(), &battery(22.5, 0), &config), discharge());
+    }
+
+    #[test]
+    fn the_other_direction_and_new_targets_go_through() {
+        let config   = config();
+        let mut band = HoldBand::new();
+        band.apply(charge(), &battery(80.0, 0), &config);
+
+        assert_eq!(band.apply(discharge(), &battery(79.0, 0), &config), discharge());
+        let higher = Verdict::new(Decision::ChargingFromGrid { watts: 2000, max_soc_percent: 95 }, "plan");
+        assert_eq!(band.apply(higher.clone(), &battery(79.0, 0), &config), higher);
+    }
+
+    #[test]
+    fn a_zero_band_holds_nothing() {
+        let config   = Config::default();
+        let mut band = HoldBand::new();
+        band.apply(charge(), &battery(80.0, 0), &config);
+        assert_eq!(band.apply(charge(), &battery(79.5, 0), &config), charge());
+    }
+}
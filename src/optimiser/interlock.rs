@@ -0,0 +1,128 @@
+use chrono::{DateTime, Utc};
+use std::time::Duration;
+
+use crate::configuration::config::Config;
+use crate::handlers::indevolt::reader::ID_BATTERY_SOC;
+use crate::handlers::p1::reader::P1Reading;
+use crate::models::indevolt_models::BatterySnapshot;
+
+use super::decision::{Decision, Verdict};
+use super::grid::{GridReading, GridSource};
+use super::{apply_limits, self_consumption};
+
+// --------------------------------------------------------------------------------------------------------------
+// Grid-charge interlock
+//
+// Charging from the grid is the one action that buys energy and adds to the import peak, so it gets the
+// strictest data requirements. Whichever stage asked for it (price plan, maintenance charge), it only goes
+// ahead when:
+//   - the grid reading comes from P1 (not the Indevolt-meter fallback) and its values changed within
+//     `grid_charge_max_p1_age_seconds`;
+//   - the SOC was read this cycle (not held back by the plausibility guard) and at most
+//     `grid_charge_max_soc_age_seconds` before the decision.
+// Otherwise the cycle falls back to self-consumption, which keeps the relaxed requirements of every other
+// decision: a P1 reading that isn't stale yet (`p1_stale_after_seconds`) or the meter fallback.
+// --------------------------------------------------------------------------------------------------------------
+
+/// Why grid charging isn't allowed on this cycle's data, `None` when it is.
+fn blocker(
+    grid: &GridReading,
+    p1: Option<&P1Reading>,
+    battery: &BatterySnapshot,
+    now: DateTime<Utc>,
+    config: &Config,
+) -> Option<String> {
+    let max_p1_age = Duration::from_secs(config.grid_charge_max_p1_age_seconds);
+    match p1 {
+        _ if grid.source != GridSource::P1 => return Some(format!("grid source {}", grid.source.as_str())),
+        None => return Some("no P1 reading".to_string()),
+        Some(p1) if !max_p1_age.is_zero() && p1.unchanged_for > max_p1_age => {
+            return Some(format!("P1 unchanged for {}s", p1.unchanged_for.as_secs()));
+        }
+        Some(_) => {}
+    }
+
+    if !battery.is_fresh(ID_BATTERY_SOC) {
+        return Some("SOC not read this cycle".to_string());
+    }
+    let soc_age = (now - battery.read_at).num_seconds();
+    if config.grid_charge_max_soc_age_seconds > 0 && soc_age > config.grid_charge_max_soc_age_seconds as i64 {
+        return Some(format!("SOC read {}s ago", soc_age));
+    }
+    None
+}
+
+/// Let a `ChargingFromGrid` verdict through only on fresh P1 and SOC data; otherwise replace it with the
+/// self-consumption decision. Every other verdict passes unchanged.
+pub fn guard_grid_charge(
+    verdict: Verdict,
+    grid: &GridReading,
+    p1: Option<&P1Reading>,
+    battery: &BatterySnapshot,
+    now: DateTime<Utc>,
+    config: &Config,
+) -> Verdict {
+    if !matches!(verdict.decision, Decision::ChargingFromGrid { .. }) {
+        return verdict;
+    }
+    let Some(reason) = blocker(grid, p1, battery, now, config) else {
+        return verdict;
+    };
+
+    let fallback = apply_limits(self_consumption::decide(grid, battery, config), battery, config);
+    Verdict::new(
+        fallback.decision,
+        format!("grid charge blocked ({}): {}", reason, fallback.reason),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::optimiser::testing::{battery, grid, p1_reading};
+
+    fn grid_charge() -> Verdict {
+        Verdict::new(Decision::ChargingFromGrid { watts: 2400, max_soc_percent: 80 }, "cheap hour")
+    }
+
+    fn fresh_battery(now: DateTime<Utc>) -> BatterySnapshot {
+        BatterySnapshot { read_at: now, ..battery(40.0, 0) }
+    }
+
+    #[test]
+    fn fresh_p1_and_soc_let_a_grid_charge_through() {
+        let now = Utc::now();
+        let p1  = p1_reading(500.0, Duration::from_secs(5));
+        let battery = fresh_battery(now);
+        let verdict = guard_grid_charge(grid_charge(), &grid(500), Some(&p1), &battery, now, &Config::default());
+        assert_eq!(verdict.decision, grid_charge().decision);
+    }
+
+    #[test]
+    fn a_stale_p1_reading_blocks_a_grid_charge() {
+        let now = Utc::now();
+        let p1  = p1_reading(500.0, Duration::from_secs(45));
+        let battery = fresh_battery(now);
+        let verdict = guard_grid_charge(grid_charge(), &grid(500), Some(&p1), &battery, now, &Config::default());
+
+        // Self-consumption instead: 500 W of house load comes from the battery.
+        assert_eq!(verdict.decision, Decision::DischargingToLoad { watts: 500, min_soc_percent: 20 });
+        assert!(verdict.reason.starts_with("grid charge blocked (P1 unchanged for 45s)"), "{}", verdict.reason);
+    }
+
+    #[test]
+    fn the_meter_fallback_or_an_old_soc_blocks_a_grid_charge() {
+        let now    = Utc::now();
+        let config = Config::default();
+        let p1     = p1_reading(500.0, Duration::ZERO);
+
+        let meter = GridReading { source: GridSource::IndevoltMeter, ..grid(500) };
+        let verdict = guard_grid_charge(grid_charge(), &meter, None, &fresh_battery(now), now, &config);
+        assert!(verdict.reason.contains("grid source indevolt_meter"), "{}", verdict.reason);
+
+        let old = BatterySnapshot { read_at: now - chrono::Duration::seconds(20), ..battery(40.0, 0) };
+        let verdict = guard_grid_charge(grid_charge(), &grid(500), Some(&p1), &old, now, &config);
+        assert!(verdict.reason.contains("SOC read 20s ago"), "{}", verdict.reason);
+    }
+}
+
@@ -0,0 +1,203 @@
+use std::fmt;
+use std::time::Duration;
+
+use crate::configuration::config::Config;
+use crate::handlers::indevolt::reader::{ID_BATTERY_POWER, ID_BATTERY_SOC};
+use crate::handlers::p1::reader::{P1Error, P1Reading};
+use crate::models::indevolt_models::BatterySnapshot;
+
+use super::decision::{Decision, Verdict};
+
+// --------------------------------------------------------------------------------------------------------------
+// Grid outage (islanding)
+//
+// The P1 dongle is powered from the grid side, so it goes dark in a power cut, while a backup-capable inverter
+// keeps running (and answering) off the battery. A P1 connectivity failure on its own is just a P1 fault; only
+// together with the inverter reporting an off-grid/backup state (`grid_state_sensor_id` /
+// `off_grid_state_values`) is it treated as an outage. Without a grid state sensor, an AC frequency outside
+// `grid_frequency_band_hz` (`grid_frequency_sensor_id`) counts as that second signal: the grid is failing or
+// gone. While P1 is fine, an out-of-band frequency is only grid stress - logged and noted in the daily report.
+//
+// During an outage the EMS stops steering: the inverter is handed back to its own mode, which runs the backup
+// loads itself. No real-time command - in particular no grid or maintenance charge - is sent, and no `Idle`,
+// which would stop the battery that is now powering the house. Normal operation resumes once P1 is back.
+//
+// Each outage cycle also estimates how long the battery can keep the house running: the energy above the
+// discharge floor (`battery_min_soc_percent` as reconciled with the device's MinSOC) divided by the current
+// discharge power. The floor is where the inverter stops, so the estimate runs to there, not to empty.
+// A battery that isn't discharging (solar covers the load, or it charges) has no runtime to report.
+// --------------------------------------------------------------------------------------------------------------
+
+/// The AC frequency (Hz) when it is polled and outside `grid_frequency_band_hz`, otherwise `None`.
+pub fn frequency_out_of_band(battery: &BatterySnapshot, config: &Config) -> Option<f64> {
+    let [min, max] = config.grid_frequency_band_hz;
+    battery.grid_frequency_hz.filter(|hz| !(min..=max).contains(hz))
+}
+
+/// Warns once per excursion outside `grid_frequency_band_hz`, tracking it in `alarm`; a cycle without a
+/// frequency reading keeps the last state. Returns the alarm text for the daily report when an excursion starts.
+pub fn check_frequency(battery: &BatterySnapshot, config: &Config, alarm: &mut bool) -> Option<String> {
+    let hz          = battery.grid_frequency_hz?;
+    let out_of_band = frequency_out_of_band(battery, config).is_some();
+    if out_of_band == *alarm {
+        return None;
+    }
+    *alarm = out_of_band;
+    let [min, max] = config.grid_frequency_band_hz;
+    if out_of_band {
+        log::warn!("[EMS] Grid frequency {:.2} Hz outside {:.2}-{:.2} Hz - grid under stress", hz, min, max);
+        Some(format!("grid frequency {:.2} Hz", hz))
+    } else {
+        log::info!("[EMS] Grid frequency back within {:.2}-{:.2} Hz ({:.2} Hz)", min, max, hz);
+        None
+    }
+}
+
+/// True when P1 can't be reached and the inverter reports it is running off-grid - or, without a grid state
+/// sensor, reports a grid frequency outside the nominal band.
+pub fn grid_outage(p1: Result<&P1Reading, &P1Error>, battery: &BatterySnapshot, config: &Config) -> bool {
+    let off_grid = match battery.off_grid {
+        Some(off_grid) => off_grid,
+        None           => frequency_out_of_band(battery, config).is_some(),
+    };
+    matches!(p1, Err(e) if e.is_connectivity()) && off_grid
+}
+
+/// The islanding policy's decision.
+pub fn islanding_verdict(battery: &BatterySnapshot) -> Verdict {
+    Verdict::new(
+        Decision::SelfConsumption,
+        format!("grid outage: inverter runs backup on its own (SOC {:.1}%)", battery.battery_soc),
+    )
+}
+
+/// Estimated backup runtime at the current load.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BackupRuntime {
+    /// Battery idle or charging: the load is covered without it.
+    NotDischarging,
+    /// Time until the discharge floor at `load_w`.
+    Remaining { runtime: Duration, load_w: i32, floor_percent: f64 },
+}
+
+impl BackupRuntime {
+    /// Whole minutes left, `None` when not discharging.
+    pub fn minutes(&self) -> Option<u64> {
+        match self {
+            BackupRuntime::NotDischarging            => None,
+            BackupRuntime::Remaining { runtime, .. } => Some(runtime.as_secs() / 60),
+        }
+    }
+}
+
+impl fmt::Display for BackupRuntime {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BackupRuntime::NotDischarging => write!(f, "not discharging"),
+            BackupRuntime::Remaining { runtime, load_w, floor_percent } => {
+                let (hours, minutes) = (runtime.as_secs() / 3600, runtime.as_secs() / 60 % 60);
+                write!(f, "~{}h{:02}m at {}W to the {:.0}% floor", hours, minutes, load_w, floor_percent)
+            }
+        }
+    }
+}
+
+/// How long the battery lasts at its current discharge power, down to the discharge floor. `None` when the
+/// SOC or battery power wasn't read this cycle.
+pub fn backup_runtime(battery: &BatterySnapshot, config: &Config) -> Option<BackupRuntime> {
+    if !battery.is_fresh(ID_BATTERY_SOC) || !battery.is_fresh(ID_BATTERY_POWER) {
+        return None;
+    }
+    let load_w = -battery.battery_power_w;
+    if load_w <= 0 {
+        return Some(BackupRuntime::NotDischarging);
+    }
+    let floor_percent = config.battery_min_soc_percent;
+    let above_floor   = (battery.battery_soc - floor_percent).max(0.0) / 100.0;
+    let energy_wh     = above_floor * config.battery_rated_capacity_kwh * 1000.0;
+    let runtime       = Duration::from_secs_f64(energy_wh / load_w as f64 * 3600.0);
+    Some(BackupRuntime::Remaining { runtime, load_w, floor_percent })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::optimiser::testing::battery;
+    use crate::runtime::log_capture::{capture, warnings};
+
+    fn at_frequency(hz: Option<f64>) -> BatterySnapshot {
+        BatterySnapshot { grid_frequency_hz: hz, ..battery(60.0, 0) }
+    }
+
+    #[test]
+    fn an_out_of_band_frequency_warns_once_per_excursion() {
+        let config    = Config::default(); // 49.8-50.2 Hz
+        let mut alarm = false;
+
+        let (report, lines) = capture(|| check_frequency(&at_frequency(Some(49.5)), &config, &mut alarm));
+        assert_eq!(report.as_deref(), Some("grid frequency 49.50 Hz"));
+        let expected = "[EMS] Grid frequency 49.50 Hz outside 49.80-50.20 Hz - grid under stress";
+        assert_eq!(warnings(&lines), [expected]);
+        assert!(alarm);
+
+        // Still outside, or not read this cycle: no repeat.
+        let (report, lines) = capture(|| check_frequency(&at_frequency(Some(49.6)), &config, &mut alarm));
+        assert!(report.is_none() && lines.is_empty(), "{lines:?}");
+        assert!(check_frequency(&at_frequency(None), &config, &mut alarm).is_none());
+        assert!(alarm);
+
+        let (report, lines) = capture(|| check_frequency(&at_frequency(Some(50.0)), &config, &mut alarm));
+        assert!(report.is_none() && warnings(&lines).is_empty());
+        assert_eq!(lines.len(), 1);
+        assert!(!alarm);
+    }
+
+    #[test]
+    fn an_outage_needs_p1_down_and_the_inverter_off_grid() {
+        let config   = Config::default();
+        let timeout  = P1Error::Timeout;
+        let off_grid = BatterySnapshot { off_grid: Some(true), ..battery(60.0, -800) };
+        let on_grid  = BatterySnapshot { off_grid: Some(false), ..battery(60.0, -800) };
+
+        assert!(grid_outage(Err(&timeout), &off_grid, &config));
+        assert!(!grid_outage(Err(&timeout), &on_grid, &config));
+        assert!(!grid_outage(Err(&P1Error::Parse("$".into(), "bad".into())), &off_grid, &config));
+
+        // Without a grid state sensor, an out-of-band frequency is the second signal.
+        assert!(grid_outage(Err(&timeout), &at_frequency(Some(48.0)), &config));
+        assert!(!grid_outage(Err(&timeout), &at_frequency(Some(50.0)), &config));
+        assert!(!grid_outage(Err(&timeout), &at_frequency(None), &config));
+    }
+
+    #[test]
+    fn islanding_hands_back_to_self_consumption() {
+        let verdict = islanding_verdict(&battery(64.0, -800));
+        assert_eq!(verdict.decision, Decision::SelfConsumption);
+        assert_eq!(verdict.reason, "grid outage: inverter runs backup on its own (SOC 64.0%)");
+    }
+
+    #[test]
+    fn backup_runtime_runs_to_the_discharge_floor() {
+        // 12 kWh, 10% floor: 50% above it is 6 kWh, 3 h at 2000 W.
+        let runtime = backup_runtime(&battery(60.0, -2000), &Config::default()).unwrap();
+        assert_eq!(runtime.minutes(), Some(180));
+        assert_eq!(runtime.to_string(), "~3h00m at 2000W to the 10% floor");
+
+        assert_eq!(backup_runtime(&battery(60.0, 500), &Config::default()), Some(BackupRuntime::NotDischarging));
+    }
+
+    #[test]
+    fn backup_runtime_math_for_a_partial_charge() {
+        // 25 % above a 10 % floor of 12 kWh is 3 kWh: 1 h 40 min at 1800 W.
+        let runtime = backup_runtime(&battery(35.0, -1800), &Config::default()).unwrap();
+        assert_eq!(runtime.to_string(), "~1h40m at 1800W to the 10% floor");
+
+        // At or below the floor there is nothing left to run on.
+        let runtime = backup_runtime(&battery(8.0, -500), &Config::default()).unwrap();
+        assert_eq!(runtime.minutes(), Some(0));
+
+        let mut unread = battery(35.0, -1800);
+        unread.not_fresh.insert(ID_BATTERY_POWER);
+        assert_eq!(backup_runtime(&unread, &Config::default()), None);
+    }
+}
@@ -0,0 +1,153 @@
+use std::time::{Duration, Instant};
+
+use log::{info, warn};
+
+use crate::configuration::config::Config;
+use crate::models::indevolt_models::BatterySnapshot;
+
+use super::decision::{Decision, Direction, Verdict};
+
+// --------------------------------------------------------------------------------------------------------------
+// Maintenance charge
+//
+// Cells left near empty for a long time age faster. When the SOC has stayed below `maintenance_soc_percent`
+// for `maintenance_interval_hours` without interruption, a maintenance charge starts and runs until the SOC
+// reaches `maintenance_target_soc_percent`:
+//   - a charge the strategy already decided (solar surplus) is kept;
+//   - otherwise the battery charges from the grid at `maintenance_charge_power_w` if `grid_charging_enabled`,
+//     or holds `Idle` (no further discharge) until solar surplus arrives.
+// A battery that cycles normally rises above the threshold regularly, which resets the timer, so it never
+// triggers. A `maintenance_interval_hours` of 0 disables the policy.
+// --------------------------------------------------------------------------------------------------------------
+
+/// Tracks how long the SOC has been low and whether a maintenance charge is running.
+#[derive(Debug, Default)]
+pub struct MaintenanceCharge {
+    low_since: Option<Instant>,
+    active:    bool,
+}
+
+impl MaintenanceCharge {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Whether a maintenance charge is in progress.
+    pub fn is_active(&self) -> bool {
+        self.active
+    }
+
+    /// Replace the strategy's verdict with a maintenance charge while one is due or running.
+    pub fn apply(
+        &mut self,
+        verdict: Verdict,
+        battery: &BatterySnapshot,
+        now: Instant,
+        config: &Config,
+    ) -> Verdict {
+        if config.maintenance_interval_hours == 0 {
+            return verdict;
+        }
+        let soc    = battery.battery_soc;
+        let target = config.maintenance_target_soc_percent;
+
+        if self.active && soc >= target {
+            self.active    = false;
+            self.low_since = None;
+            info!("[Optimiser] Maintenance charge complete: SOC {:.1}% ≥ {:.1}%", soc, target);
+            return verdict;
+        }
+
+        if !self.active {
+            if soc >= config.maintenance_soc_percent {
+                self.low_since = None;
+                return verdict;
+            }
+            let since = *self.low_since.get_or_insert(now);
+            let low   = now.duration_since(since);
+            if low < Duration::from_secs(config.maintenance_interval_hours * 3600) {
+                return verdict;
+            }
+            self.active = true;
+            warn!(
+                "[Optimiser] Maintenance charge: SOC below {:.1}% for {}h - charging to {:.1}%",
+                config.maintenance_soc_percent, low.as_secs() / 3600, target
+            );
+        }
+
+        if verdict.decision.direction() == Direction::Charge {
+            let reason = format!("maintenance charge to {:.1}% ({})", target, verdict.reason);
+            return Verdict::new(verdict.decision, reason);
+        }
+        if config.grid_charging_enabled {
+            let watts = config.maintenance_charge_power_w.min(config.battery_max_charge_power_w);
+            return Verdict::new(
+                Decision::ChargingFromGrid { watts, max_soc_percent: config.soc_target(target) },
+                format!("maintenance charge from grid: SOC {:.1}% → {:.1}%", soc, target),
+            );
+        }
+        Verdict::new(
+            Decision::Idle,
+            format!("maintenance charge: SOC {:.1}% held until solar surplus (was: {})", soc, verdict.reason),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::optimiser::testing::battery;
+
+    fn config() -> Config {
+        Config { maintenance_interval_hours: 24, grid_charging_enabled: true, ..Config::default() }
+    }
+
+    fn discharge() -> Verdict {
+        Verdict::new(Decision::DischargingToLoad { watts: 300, min_soc_percent: 10 }, "evening load")
+    }
+
+    #[test]
+    fn a_battery_left_low_gets_a_maintenance_charge_to_the_target() {
+        let start       = Instant::now();
+        let hour        = Duration::from_secs(3600);
+        let mut charger = MaintenanceCharge::new();
+
+        assert_eq!(charger.apply(discharge(), &battery(12.0, 0), start, &config()), discharge());
+        assert_eq!(charger.apply(discharge(), &battery(12.0, 0), start + hour * 23, &config()), discharge());
+
+        let verdict = charger.apply(discharge(), &battery(12.0, 0), start + hour * 24, &config());
+        assert_eq!(verdict.decision, Decision::ChargingFromGrid { watts: 1000, max_soc_percent: 30 });
+        assert!(charger.is_active());
+
+        // Running until the target, then the strategy is back in charge.
+        let verdict = charger.apply(discharge(), &battery(25.0, 1000), start + hour * 25, &config());
+        assert_eq!(verdict.decision.direction(), Direction::Charge);
+        assert_eq!(charger.apply(discharge(), &battery(30.0, 0), start + hour * 26, &config()), discharge());
+        assert!(!charger.is_active());
+    }
+
+    #[test]
+    fn normal_cycling_never_triggers_it() {
+        let start       = Instant::now();
+        let mut charger = MaintenanceCharge::new();
+        // Low every night, back above the threshold every day.
+        for day in 0..5 {
+            let night = start + Duration::from_secs(day * 86_400);
+            let noon  = night + Duration::from_secs(12 * 3600);
+            assert_eq!(charger.apply(discharge(), &battery(11.0, 0), night, &config()), discharge());
+            assert_eq!(charger.apply(discharge(), &battery(60.0, 0), noon, &config()), discharge());
+        }
+        assert!(!charger.is_active());
+    }
+
+    #[test]
+    fn without_grid_charging_the_battery_is_held_for_solar() {
+        let start       = Instant::now();
+        let config      = Config { grid_charging_enabled: false, ..config() };
+        let mut charger = MaintenanceCharge::new();
+        let day         = Duration::from_secs(86_400);
+        charger.apply(discharge(), &battery(12.0, 0), start, &config);
+        let verdict = charger.apply(discharge(), &battery(12.0, 0), start + day, &config);
+        assert_eq!(verdict.decision, Decision::Idle);
+    }
+}
@@ -0,0 +1,194 @@
+pub mod allowed_modes;
+pub mod boundary;
+pub mod charge_cap;
+pub mod confidence;
+pub mod decision;
+pub mod direction_limit;
+pub mod energy_target;
+pub mod ev;
+pub mod grid;
+pub mod handback;
+pub mod hold_band;
+pub mod interlock;
+pub mod islanding;
+pub mod maintenance;
+pub mod peak;
+pub mod plan;
+pub mod pre_peak;
+pub mod ramp;
+pub mod self_consumption;
+pub mod dispatch;
+pub mod settle;
+pub mod shadow;
+pub mod simulate;
+pub mod spike;
+#[cfg(test)]
+pub(crate) mod testing;
+pub mod thermal;
+pub mod throughput_cap;
+pub mod trace;
+
+use log::{debug, warn};
+
+use crate::configuration::config::{Config, Strategy};
+use crate::handlers::indevolt::reader::{ID_BATTERY_POWER, ID_BATTERY_SOC, ID_BATTERY_STATE, ID_WORKING_MODE};
+use crate::models::indevolt_models::BatterySnapshot;
+
+use decision::Verdict;
+use grid::GridReading;
+use plan::PlanSlot;
+use trace::RuleTrace;
+
+// --------------------------------------------------------------------------------------------------------------
+// Optimiser entry point
+//
+// `run` is a pure function of the cycle's readings and the config: no I/O, no hidden state. The control loop
+// hands the resulting `Verdict` to the dispatcher, which is the only place that talks to the inverter.
+// `run_traced` is the same decision with every rule it evaluated recorded (see `trace.rs`).
+// --------------------------------------------------------------------------------------------------------------
+
+/// Battery values no decision can be made without. A defaulted 0 % SOC would look like an empty battery.
+const REQUIRED_SENSORS: &[u32] = &[ID_BATTERY_SOC, ID_BATTERY_POWER, ID_BATTERY_STATE];
+
+/// Decide what the battery should do this cycle. Returns `None` when the configured strategy only monitors,
+/// when a required battery value wasn't read this cycle, or when the device is in a working mode this build
+/// doesn't know. `plan_slot` is the current hour of the price plan,
+/// used by the "price_arbitrage" strategy; without one it falls back to self-consumption.
+/// Hardware limits (SOC boundaries, thermal derating) are applied on top of the strategy's decision.
+pub fn run(
+    grid: &GridReading,
+    battery: &BatterySnapshot,
+    config: &Config,
+    plan_slot: Option<&PlanSlot>,
+) -> Option<Verdict> {
+    run_traced(grid, battery, config, plan_slot, &mut RuleTrace::default())
+}
+
+/// `run`, recording the rules it evaluates in `trace`.
+pub fn run_traced(
+    grid: &GridReading,
+    battery: &BatterySnapshot,
+    config: &Config,
+    plan_slot: Option<&PlanSlot>,
+    trace: &mut RuleTrace,
+) -> Option<Verdict> {
+    if let Some(id) = missing_sensor(battery) {
+        trace.rule("required_sensors", true, format!("sensor {} not read", id));
+        warn!("[Optimiser] Sensor {} not read this cycle - no decision", id);
+        return None;
+    }
+    trace.rule("required_sensors", false, "all read");
+    // A mode this build doesn't know (newer firmware) may not obey our commands the way we expect.
+    if trace.rule("unknown_mode", unknown_mode(battery), format!("working mode '{}'", battery.working_mode)) {
+        debug!("[Optimiser] Working mode '{}' unknown - no decision", battery.working_mode);
+        return None;
+    }
+    decide(config.strategy, grid, battery, config, plan_slot, trace)
+}
+
+/// First required battery value that wasn't read this cycle.
+fn missing_sensor(battery: &BatterySnapshot) -> Option<u32> {
+    REQUIRED_SENSORS.iter().copied().find(|id| !battery.is_fresh(*id))
+}
+
+fn unknown_mode(battery: &BatterySnapshot) -> bool {
+    battery.is_fresh(ID_WORKING_MODE) && battery.mode.is_none()
+}
+
+/// `strategy`'s decision on readings that passed the checks in `run`, with the hardware limits applied.
+fn decide(
+    strategy: Strategy,
+    grid: &GridReading,
+    battery: &BatterySnapshot,
+    config: &Config,
+    plan_slot: Option<&PlanSlot>,
+    trace: &mut RuleTrace,
+) -> Option<Verdict> {
+    let verdict = match strategy {
+        Strategy::Monitor         => {
+            trace.rule("monitor", true, "strategy \"monitor\" never decides");
+            return None;
+        }
+        Strategy::SelfConsumption => self_consumption::decide_traced(grid, battery, config, trace),
+        Strategy::PriceArbitrage  => match plan_slot {
+            Some(slot) => plan::follow_traced(slot, grid, battery, config, trace),
+            None       => {
+                trace.rule("price_plan", false, "no price plan - self-consumption");
+                let verdict = self_consumption::decide_traced(grid, battery, config, trace);
+                Verdict::new(verdict.decision, format!("no price plan: {}", verdict.reason))
+            }
+        },
+    };
+    let verdict = trace.stage("soc_boundary", verdict, |v| boundary::guard_soc_boundaries(v, battery));
+    Some(trace.stage("thermal", verdict, |v| thermal::apply_thermal_limit(v, battery, config)))
+}
+
+/// Hardware limits every strategy decision passes through: SOC boundaries, then thermal derating.
+pub fn apply_limits(verdict: Verdict, battery: &BatterySnapshot, config: &Config) -> Verdict {
+    let verdict = boundary::guard_soc_boundaries(verdict, battery);
+    thermal::apply_thermal_limit(verdict, battery, config)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::optimiser::decision::Decision;
+    use crate::optimiser::testing::{battery, battery_without_soc, grid};
+
+    #[test]
+    fn trace_lists_the_rules_up_to_the_one_that_fired() {
+        // 5 kW import at 15 % SOC: below the 20 % reserve, so only the part above the 3381 W peak is shaved.
+        let config  = Config::default();
+        let mut trace = RuleTrace::default();
+        let verdict = run_traced(&grid(5000), &battery(15.0, 0), &config, None, &mut trace).unwrap();
+
+        assert!(matches!(verdict.decision, Decision::DischargingToLoad { watts: 1619, .. }));
+        assert_eq!(trace.fired("required_sensors"), Some(false));
+        assert_eq!(trace.fired("deadband"), Some(false));
+        assert_eq!(trace.fired("solar_surplus"), None);
+        assert_eq!(trace.fired("house_load"), Some(false));
+        assert_eq!(trace.fired("peak_shaving"), Some(true));
+        assert_eq!(trace.fired("soc_boundary"), Some(false));
+    }
+
+    #[test]
+    fn trace_records_a_missing_sensor() {
+        let mut trace = RuleTrace::default();
+        let verdict = run_traced(&grid(500), &battery_without_soc(), &Config::default(), None, &mut trace);
+
+        assert!(verdict.is_none());
+        assert_eq!(trace.fired("required_sensors"), Some(true));
+        assert_eq!(trace.rules.len(), 1);
+    }
+
+    #[test]
+    fn run_and_run_traced_agree() {
+        let config = Config::default();
+        for (grid_w, soc) in [(-1500, 50.0), (800, 60.0), (800, 15.0), (0, 50.0)] {
+            let traced = run_traced(&grid(grid_w), &battery(soc, 0), &config, None, &mut RuleTrace::default());
+            assert_eq!(run(&grid(grid_w), &battery(soc, 0), &config, None), traced);
+        }
+    }
+
+    #[test]
+    fn an_unknown_working_mode_gets_no_decision() {
+        // Firmware reporting a mode this build doesn't know: nothing is sent, whatever the load.
+        let mut unknown = battery(60.0, 0);
+        unknown.working_mode = "9".to_string();
+        unknown.mode         = None;
+        let mut trace = RuleTrace::default();
+
+        assert_eq!(run_traced(&grid(5000), &unknown, &Config::default(), None, &mut trace), None);
+        assert_eq!(trace.fired("unknown_mode"), Some(true));
+        assert_eq!(trace.fired("peak_shaving"), None);
+    }
+
+    #[test]
+    fn a_working_mode_not_read_this_cycle_is_not_unknown() {
+        let mut unread = battery(60.0, 0);
+        unread.mode = None;
+        unread.not_fresh.insert(ID_WORKING_MODE);
+        assert!(run(&grid(5000), &unread, &Config::default(), None).is_some());
+    }
+}
+
@@ -0,0 +1,108 @@
+use chrono::{Datelike, NaiveDate};
+use serde::{Deserialize, Serialize};
+
+use crate::configuration::config::{Config, PeakTargetMode};
+use crate::handlers::p1::reader::P1Reading;
+use crate::runtime::clock::LOCAL_TZ;
+
+// --------------------------------------------------------------------------------------------------------------
+// Capacity-tariff peak target
+//
+// The Belgian capacity tariff bills the highest quarter-hour average import of the month. With
+// `peak_target_mode: "monthly_peak"` the shaving target follows the peak the meter has already recorded this
+// month (plus `monthly_peak_headroom_w`), so the battery prevents a new record instead of defending a fixed
+// value the bill may already have passed - or never come close to. `monthly_peak_min_target_w` keeps the
+// target from falling below the minimum the tariff bills anyway (2.5 kW), which also covers the first days of
+// a month when the meter's peak is still near zero.
+// --------------------------------------------------------------------------------------------------------------
+
+/// Highest monthly peak reported by the meter during a calendar month (local time).
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct MonthlyPeak {
+    pub year:   i32,
+    pub month:  u32,
+    pub peak_w: f64,
+}
+
+impl MonthlyPeak {
+    /// Fold this cycle's P1 reading into `previous`. A new month starts from scratch; a reading whose peak
+    /// timestamp still lies in an earlier month (meter not yet reset) is ignored.
+    pub fn observe(
+        previous: Option<MonthlyPeak>,
+        p1:       Option<&P1Reading>,
+        today:    NaiveDate,
+    ) -> Option<MonthlyPeak> {
+        let current = previous.filter(|p| p.year == today.year() && p.month == today.month());
+
+        let Some(reading) = p1 else {
+            return current;
+        };
+        let peak_date = reading.monthly_power_peak_timestamp_utc.with_timezone(&LOCAL_TZ).date_naive();
+        if peak_date.year() != today.year() || peak_date.month() != today.month() {
+            return current;
+        }
+
+        let peak_w = reading.raw.montly_power_peak_w.max(current.map_or(0.0, |p| p.peak_w));
+        Some(MonthlyPeak { year: today.year(), month: today.month(), peak_w })
+    }
+}
+
+/// Grid import (W) the optimiser should shave above this cycle.
+pub fn shaving_target_w(config: &Config, peak: Option<&MonthlyPeak>) -> i32 {
+    match config.peak_target_mode {
+        PeakTargetMode::Static      => config.battery_max_desired_grid_peak_w,
+        PeakTargetMode::MonthlyPeak => {
+            let learned = peak.map_or(0, |p| p.peak_w.round() as i32 + config.monthly_peak_headroom_w);
+            learned.max(config.monthly_peak_min_target_w)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+    use std::time::Duration;
+
+    use super::*;
+    use crate::optimiser::testing::p1_reading;
+
+    /// A P1 reading whose meter recorded `peak_w` as this month's peak on `day` October 2026.
+    fn reading(peak_w: f64, day: u32) -> P1Reading {
+        let mut reading = p1_reading(500.0, Duration::ZERO);
+        reading.raw.montly_power_peak_w              = peak_w;
+        reading.monthly_power_peak_timestamp_utc = Utc.with_ymd_and_hms(2026, 10, day, 18, 0, 0).unwrap();
+        reading
+    }
+
+    fn october(day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2026, 10, day).unwrap()
+    }
+
+    #[test]
+    fn a_2900_w_monthly_peak_sets_the_shaving_target() {
+        let peak   = MonthlyPeak::observe(None, Some(&reading(2900.0, 3)), october(15));
+        let config = Config { peak_target_mode: PeakTargetMode::MonthlyPeak, ..Config::default() };
+        assert_eq!(peak, Some(MonthlyPeak { year: 2026, month: 10, peak_w: 2900.0 }));
+        assert_eq!(shaving_target_w(&config, peak.as_ref()), 2900);
+
+        let headroom = Config { monthly_peak_headroom_w: 200, ..config.clone() };
+        assert_eq!(shaving_target_w(&headroom, peak.as_ref()), 3100);
+        assert_eq!(shaving_target_w(&Config::default(), peak.as_ref()), 3381);
+
+        // Early in a month the tariff's own minimum applies.
+        let low = MonthlyPeak::observe(None, Some(&reading(900.0, 1)), october(1));
+        assert_eq!(shaving_target_w(&config, low.as_ref()), 2500);
+    }
+
+    #[test]
+    fn the_peak_only_rises_within_a_month_and_resets_with_the_next() {
+        let peak = MonthlyPeak::observe(None, Some(&reading(2900.0, 3)), october(15));
+        let peak = MonthlyPeak::observe(peak, Some(&reading(2700.0, 3)), october(16));
+        assert_eq!(peak.map(|p| p.peak_w), Some(2900.0));
+        assert_eq!(MonthlyPeak::observe(peak, None, october(17)), peak);
+
+        // 1 November: the meter still reports October's peak until it resets.
+        let november = NaiveDate::from_ymd_opt(2026, 11, 1).unwrap();
+        assert_eq!(MonthlyPeak::observe(peak, Some(&reading(2900.0, 3)), november), None);
+    }
+}
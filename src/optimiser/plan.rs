@@ -0,0 +1,567 @@
+use chrono::{DateTime, Utc};
+use log::info;
+use serde::Deserialize;
+
+use crate::configuration::config::Config;
+use crate::models::indevolt_models::BatterySnapshot;
+use crate::prices::{HourlyPrice, PriceCurve, SLOT};
+use crate::runtime::clock::LOCAL_TZ;
+
+use super::decision::{Decision, Direction, Verdict};
+use super::energy_target;
+use super::grid::GridReading;
+use super::self_consumption;
+use super::trace::RuleTrace;
+
+// --------------------------------------------------------------------------------------------------------------
+// Price-curve planning (strategy "price_arbitrage")
+//
+// Instead of deciding greedily per cycle, `plan_day` looks at every known hour ahead (today and, once
+// published and with `plan_include_tomorrow`, tomorrow) and pairs cheap hours to charge from the grid with later
+// expensive hours to discharge:
+//   - candidate pairs (cheap hour i, later expensive hour j) are sorted by price difference, largest first;
+//   - a pair qualifies when j is at least max(`battery_min_price_spread_percent`, break-even spread) above i,
+//     both taken as net import prices: the curve price plus `import_adder_per_kwh` (taxes, grid fees). The
+//     energy is bought from the grid at i and, covering household load at j, saves an import there, so the
+//     fees count on both sides - and, through the round-trip losses, raise the spread a pair needs;
+//   - each pair moves as much energy as is left of hour i's charge power, hour j's discharge power, and the
+//     headroom below `battery_max_soc_percent` over the hours in between.
+// The result is an hour-by-hour schedule with, per hour, the SOC that must be kept to cover the planned
+// discharges still ahead (`floor_percent`). The per-cycle optimiser tracks that schedule:
+//   - in a charge hour it charges from the grid up to the hour's floor (only with `grid_charging_enabled`,
+//     and never above `battery_max_desired_grid_peak_w` of import) and only discharges for peak shaving:
+//     household load is cheapest from the grid then;
+//   - in every other hour it runs self-consumption, but never discharges below the hour's floor, so the
+//     energy bought cheaply is kept for the expensive hours. In a discharge hour the floor drops and the stored
+//     energy covers the household load;
+//   - in an hour priced below `cheap_price_threshold` household load comes from the grid as in a charge hour,
+//     and the battery only shaves the part of the load above `battery_max_desired_grid_peak_w`;
+//   - with `export_priority_bias_percent` set, an hour whose export price beats the dearest net import price
+//     still ahead in the plan by more than the bias discharges to the grid at full power, down to the hour's
+//     floor but never below the peak-shaving reserve, and with the export kept under `max_feed_in_w`. A kWh
+//     kept for later can at best save that import, so selling it now earns more.
+// The plan is rebuilt when new prices arrive, when it runs out, and when the SOC drifts more than
+// `plan_replan_soc_drift_percent` from the planned trajectory (solar, unexpected load).
+//
+// Looking past midnight matters most in the evening: a cheap hour tonight followed by an expensive morning
+// tomorrow makes tonight a charge hour, where a plan that ends at midnight would have discharged through it.
+// Until tomorrow's prices are published (early afternoon) the plan simply ends at midnight; their arrival
+// counts as new prices and rebuilds it.
+// --------------------------------------------------------------------------------------------------------------
+
+/// What the plan intends for one hour.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum PlanAction {
+    Charge,
+    Discharge,
+    Hold,
+}
+
+/// One hour of the plan.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PlanSlot {
+    pub starts_at:                DateTime<Utc>,
+    pub price_eur_per_kwh:        f64,
+    pub action:                   PlanAction,
+    /// Planned SOC at the start and end of the hour (%), assuming nothing but the plan moves the battery.
+    pub soc_start_percent:        f64,
+    pub soc_end_percent:          f64,
+    /// SOC (%) needed at the end of this hour to cover the planned discharges after it.
+    pub floor_percent:            f64,
+    /// Highest net import price (EUR/kWh) of the hours after this one; `None` in the last hour.
+    pub later_import_eur_per_kwh: Option<f64>,
+}
+
+/// Hour-by-hour schedule over the known prices.
+#[derive(Debug, Clone, Default)]
+pub struct DayPlan {
+    pub slots:               Vec<PlanSlot>,
+    /// `fetched_at` of the price curve the plan was made from.
+    pub based_on:            Option<DateTime<Utc>>,
+    /// Expected saving of the planned charge/discharge pairs (EUR), after efficiency losses.
+    pub expected_saving_eur: f64,
+}
+
+impl DayPlan {
+    /// The slot containing `at`.
+    pub fn slot_at(&self, at: DateTime<Utc>) -> Option<&PlanSlot> {
+        self.slots.iter().find(|s| s.starts_at <= at && at < s.starts_at + SLOT)
+    }
+
+    /// Local start hours of the slots with `action`, e.g. "02,03,04".
+    fn hours(&self, action: PlanAction) -> String {
+        let hours: Vec<String> = self
+            .slots
+            .iter()
+            .filter(|s| s.action == action)
+            .map(|s| s.starts_at.with_timezone(&LOCAL_TZ).format("%H").to_string())
+            .collect();
+        if hours.is_empty() { "-".to_string() } else { hours.join(",") }
+    }
+
+    /// Why the plan must be rebuilt at `now` with the battery at `soc`, `None` while it still holds.
+    pub fn replan_reason(
+        &self,
+        curve: &PriceCurve,
+        soc: f64,
+        now: DateTime<Utc>,
+        config: &Config,
+    ) -> Option<String> {
+        if self.based_on != curve.fetched_at {
+            return Some("new prices".to_string());
+        }
+        let Some(slot) = self.slot_at(now) else {
+            return Some("plan ran out".to_string());
+        };
+        let elapsed  = (now - slot.starts_at).num_seconds() as f64 / SLOT.num_seconds() as f64;
+        let expected = slot.soc_start_percent + (slot.soc_end_percent - slot.soc_start_percent) * elapsed;
+        if (soc - expected).abs() > config.plan_replan_soc_drift_percent {
+            return Some(format!("SOC {:.1}% drifted from planned {:.1}%", soc, expected));
+        }
+        None
+    }
+}
+
+// --------------------------------------------------------------------------------------------------------------
+
+/// Plan charge and discharge hours over `prices` (the hours from now on), starting at `current_soc`.
+pub fn plan_day(prices: &[HourlyPrice], config: &Config, current_soc: f64) -> DayPlan {
+    let capacity = config.battery_rated_capacity_kwh;
+    let mut prices = prices.to_vec();
+    prices.sort_by_key(|p| p.starts_at);
+    let n = prices.len();
+
+    let max_soc     = config.battery_max_soc_percent;
+    let reserve_soc = config.peak_shaving_reserve_soc_percent.max(config.battery_min_soc_percent);
+    let (charge_eff, discharge_eff) = config.efficiencies();
+    let min_spread  = config.battery_min_price_spread_percent.max(config.break_even_spread_percent());
+
+    // SOC points one hour at full power can add or remove. No grid charging → nothing to plan.
+    let to_percent    = |watts: i32| watts.max(0) as f64 / 1000.0 / capacity * 100.0;
+    let charge_step   = if config.grid_charging_enabled && capacity > 0.0 {
+        to_percent(config.battery_max_charge_power_w) * charge_eff
+    } else {
+        0.0
+    };
+    let discharge_step = if capacity > 0.0 { to_percent(config.battery_max_discharge_power_w) } else { 0.0 };
+
+    let mut charged    = vec![0.0; n];
+    let mut discharged = vec![0.0; n];
+    // Planned SOC at the end of each hour.
+    let mut soc_end    = vec![current_soc; n];
+    let mut saving     = 0.0;
+
+    let net: Vec<f64> = prices.iter().map(|p| config.import_price(p.price_eur_per_kwh)).collect();
+    let mut pairs: Vec<(usize, usize)> = (0..n)
+        .flat_map(|i| (i + 1..n).map(move |j| (i, j)))
+        .filter(|&(i, j)| {
+            let (buy, sell) = (net[i], net[j]);
+            sell * charge_eff * discharge_eff > buy && sell >= buy + buy.abs() * min_spread / 100.0
+        })
+        .collect();
+    pairs.sort_by(|a, b| {
+        let spread = |&(i, j): &(usize, usize)| prices[j].price_eur_per_kwh - prices[i].price_eur_per_kwh;
+        spread(b).total_cmp(&spread(a))
+    });
+
+    for (i, j) in pairs {
+        if discharged[i] > 0.0 || charged[j] > 0.0 {
+            continue;
+        }
+        let peak     = soc_end[i..j].iter().copied().fold(f64::MIN, f64::max);
+        let headroom = (max_soc - peak).max(0.0);
+        let amount   = (charge_step - charged[i]).min(discharge_step - discharged[j]).min(headroom);
+        if amount <= 0.01 {
+            continue;
+        }
+        charged[i]    += amount;
+        discharged[j] += amount;
+        for soc in &mut soc_end[i..j] {
+            *soc += amount;
+        }
+        let kwh = amount / 100.0 * capacity;
+        saving += kwh * discharge_eff * net[j] - kwh / charge_eff * net[i];
+    }
+
+    // Backwards: SOC needed at the end of each hour for the planned discharges after it.
+    let mut floor  = vec![reserve_soc; n];
+    let mut needed = reserve_soc;
+    for h in (0..n).rev() {
+        floor[h] = needed;
+        needed   = (needed + discharged[h] - charged[h]).max(reserve_soc);
+    }
+
+    // Dearest net import price after each hour.
+    let mut later = vec![None; n];
+    for h in (0..n.saturating_sub(1)).rev() {
+        later[h] = Some(later[h + 1].map_or(net[h + 1], |p: f64| p.max(net[h + 1])));
+    }
+
+    let slots = prices
+        .iter()
+        .enumerate()
+        .map(|(h, p)| PlanSlot {
+            starts_at:                p.starts_at,
+            price_eur_per_kwh:        p.price_eur_per_kwh,
+            action:                   if charged[h] > 0.0 {
+                PlanAction::Charge
+            } else if discharged[h] > 0.0 {
+                PlanAction::Discharge
+            } else {
+                PlanAction::Hold
+            },
+            soc_start_percent:        if h == 0 { current_soc } else { soc_end[h - 1] },
+            soc_end_percent:          soc_end[h],
+            floor_percent:            floor[h].min(max_soc),
+            later_import_eur_per_kwh: later[h],
+        })
+        .collect();
+
+    DayPlan { slots, based_on: None, expected_saving_eur: saving }
+}
+
+/// Rebuild `plan` from `curve` when it no longer holds. Returns the plan to use this cycle.
+pub fn refresh(
+    plan: Option<DayPlan>,
+    curve: &PriceCurve,
+    soc: f64,
+    now: DateTime<Utc>,
+    config: &Config,
+) -> Option<DayPlan> {
+    let reason = match &plan {
+        Some(current) => match current.replan_reason(curve, soc, now, config) {
+            Some(reason) => reason,
+            None         => return plan,
+        },
+        None => "no plan yet".to_string(),
+    };
+    let today    = now.with_timezone(&LOCAL_TZ).date_naive();
+    let tomorrow = today.succ_opt();
+    let in_horizon = |p: &HourlyPrice| {
+        let date = p.starts_at.with_timezone(&LOCAL_TZ).date_naive();
+        date == today || (config.plan_include_tomorrow && Some(date) == tomorrow)
+    };
+    let ahead: Vec<HourlyPrice> =
+        curve.prices.iter().copied().filter(|p| p.starts_at + SLOT > now && in_horizon(p)).collect();
+    if ahead.is_empty() {
+        return None;
+    }
+
+    let mut plan = plan_day(&ahead, config, soc);
+    plan.based_on = curve.fetched_at;
+    let horizon = match (config.plan_include_tomorrow, tomorrow) {
+        (false, _) => "today only",
+        (true, Some(tomorrow)) if curve.covers_date(tomorrow) => "through tomorrow",
+        (true, _) => "until midnight, tomorrow's prices not published yet",
+    };
+    info!(
+        "[Optimiser] Plan ({}): {} h ahead ({}), charge {} | discharge {} | expected saving €{:.2}",
+        reason, plan.slots.len(), horizon, plan.hours(PlanAction::Charge), plan.hours(PlanAction::Discharge),
+        plan.expected_saving_eur
+    );
+    Some(plan)
+}
+
+/// Decision for this cycle that follows `slot`.
+pub fn follow(slot: &PlanSlot, grid: &GridReading, battery: &BatterySnapshot, config: &Config) -> Verdict {
+    follow_traced(slot, grid, battery, config, &mut RuleTrace::default())
+}
+
+/// `follow`, recording the rules it evaluates in `trace`.
+pub fn follow_traced(
+    slot: &PlanSlot,
+    grid: &GridReading,
+    battery: &BatterySnapshot,
+    config: &Config,
+    trace: &mut RuleTrace,
+) -> Verdict {
+    let soc         = battery.battery_soc;
+    let floor       = slot.floor_percent;
+    let house_net_w = grid.power_w - battery.battery_power_w;
+    let peak_w      = config.battery_max_desired_grid_peak_w;
+
+    // Grid charging must not push the import above the capacity-tariff peak.
+    let charge_w = config.battery_max_charge_power_w.min(peak_w - house_net_w.max(0));
+    let charge   =
+        slot.action == PlanAction::Charge && config.grid_charging_enabled && soc < floor && charge_w > 0;
+    let detail   = format!(
+        "{:?} hour at {:.4} €/kWh, SOC {:.1}%, floor {:.1}%, grid charging {}, charge power {}W",
+        slot.action, slot.price_eur_per_kwh, soc, floor,
+        if config.grid_charging_enabled { "enabled" } else { "disabled" }, charge_w
+    );
+    if trace.rule("planned_charge", charge, detail) {
+        // The plan buys energy: what the battery still needs to reach the floor, as a kWh target.
+        let needed_kwh = (floor - soc) / 100.0 * config.battery_rated_capacity_kwh;
+        let verdict    = energy_target::charge_energy(needed_kwh, charge_w, battery, config);
+        return Verdict::new(
+            verdict.decision,
+            format!("planned charge at {:.4} €/kWh: {}", slot.price_eur_per_kwh, verdict.reason),
+        );
+    }
+
+    if let Some(bias) = config.export_priority_bias_percent {
+        let export_price = config.export_price(slot.price_eur_per_kwh);
+        let worth_above  = slot.later_import_eur_per_kwh.map(|p| p + p.abs() * bias / 100.0);
+        let reserve      = config.peak_shaving_reserve_soc_percent.max(config.battery_min_soc_percent);
+        let export_floor = floor.max(reserve);
+        // The battery covers the house first; what is left of its output is exported.
+        let export_w     = config
+            .battery_max_discharge_power_w
+            .min(house_net_w.saturating_add(config.max_feed_in_w.unwrap_or(i32::MAX)));
+        let export       = worth_above.is_some_and(|worth| export_price > worth)
+            && !config.feed_in_curtailed
+            && soc > export_floor
+            && export_w > 0;
+        let detail       = format!(
+            "export {:.4} €/kWh, later import {}, bias {:.0}%, SOC {:.1}%, floor {:.1}%, export power {}W{}",
+            export_price,
+            slot.later_import_eur_per_kwh.map_or("-".to_string(), |p| format!("{:.4} €/kWh", p)),
+            bias, soc, export_floor, export_w,
+            if config.feed_in_curtailed { ", feed-in curtailed" } else { "" }
+        );
+        if trace.rule("export_priority", export, detail) {
+            return Verdict::new(
+                Decision::DischargingToGrid {
+                    watts:           export_w,
+                    min_soc_percent: config.soc_target(export_floor),
+                },
+                format!(
+                    "export priority: {:.4} €/kWh now beats {:.4} €/kWh later",
+                    export_price, slot.later_import_eur_per_kwh.unwrap_or_default()
+                ),
+            );
+        }
+    }
+
+    let mut verdict = self_consumption::decide_traced(grid, battery, config, trace);
+    if verdict.decision.direction() != Direction::Discharge {
+        return verdict;
+    }
+    let charge_hour = slot.action == PlanAction::Charge;
+    if charge_hour {
+        let detail = format!("house load {}W, peak {}W", house_net_w, peak_w);
+        trace.rule("charge_hour_grid_load", house_net_w <= peak_w, detail);
+    }
+    if charge_hour && house_net_w <= peak_w {
+        return Verdict::new(
+            Decision::Idle,
+            format!(
+                "planned charge hour at {:.4} €/kWh: load from the grid ({})",
+                slot.price_eur_per_kwh, verdict.reason
+            ),
+        );
+    }
+    if let Some(threshold) = config.cheap_price_threshold.filter(|t| slot.price_eur_per_kwh < *t) {
+        let excess_w = house_net_w - peak_w;
+        let detail   = format!(
+            "{:.4} €/kWh below {:.4} €/kWh, house load {}W, peak {}W",
+            slot.price_eur_per_kwh, threshold, house_net_w, peak_w
+        );
+        trace.rule("cheap_hour_grid_load", true, detail);
+        if excess_w <= 0 {
+            return Verdict::new(
+                Decision::Idle,
+                format!(
+                    "cheap hour at {:.4} €/kWh: load from the grid ({})",
+                    slot.price_eur_per_kwh, verdict.reason
+                ),
+            );
+        }
+        if let Decision::DischargingToLoad { watts, .. } | Decision::DischargingToGrid { watts, .. } =
+            &mut verdict.decision
+        {
+            *watts = (*watts).min(excess_w);
+        }
+        verdict.reason = format!(
+            "cheap hour at {:.4} €/kWh: shaving only the {}W above peak {}W",
+            slot.price_eur_per_kwh, excess_w, peak_w
+        );
+    }
+    if trace.rule("plan_floor", soc <= floor, format!("SOC {:.1}%, plan floor {:.1}%", soc, floor)) {
+        return Verdict::new(
+            Decision::Idle,
+            format!(
+                "holding SOC {:.1}% (plan floor {:.1}%) for planned discharge ({})",
+                soc, floor, verdict.reason
+            ),
+        );
+    }
+
+    let planned_min = config.soc_target(floor);
+    let decision = match verdict.decision {
+        Decision::DischargingToLoad { watts, min_soc_percent } => {
+            Decision::DischargingToLoad { watts, min_soc_percent: min_soc_percent.max(planned_min) }
+        }
+        Decision::DischargingToGrid { watts, min_soc_percent } => {
+            Decision::DischargingToGrid { watts, min_soc_percent: min_soc_percent.max(planned_min) }
+        }
+        other => other,
+    };
+    let reason = if slot.action == PlanAction::Discharge {
+        format!("planned discharge at {:.4} €/kWh: {}", slot.price_eur_per_kwh, verdict.reason)
+    } else {
+        verdict.reason
+    };
+    Verdict::new(decision, reason)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::optimiser::testing::{battery, grid};
+    use chrono::TimeZone;
+
+    fn hold_hour(price_eur_per_kwh: f64) -> PlanSlot {
+        PlanSlot {
+            starts_at:                Utc.with_ymd_and_hms(2026, 10, 15, 10, 0, 0).unwrap(),
+            price_eur_per_kwh,
+            action:                   PlanAction::Hold,
+            soc_start_percent:        60.0,
+            soc_end_percent:          60.0,
+            floor_percent:            20.0,
+            later_import_eur_per_kwh: Some(0.25),
+        }
+    }
+
+    #[test]
+    fn a_planned_charge_buys_the_energy_up_to_the_floor() {
+        let config  = Config { grid_charging_enabled: true, ..Config::default() };
+        let slot    = PlanSlot { action: PlanAction::Charge, floor_percent: 65.0, ..hold_hour(0.10) };
+        let verdict = follow(&slot, &grid(800), &battery(40.0, 0), &config);
+        assert_eq!(verdict.decision, Decision::ChargingFromGrid { watts: 2400, max_soc_percent: 65 });
+        assert!(verdict.reason.contains("energy target 3.00 kWh"), "{}", verdict.reason);
+    }
+
+    #[test]
+    fn a_cheap_hour_takes_household_load_from_the_grid() {
+        let config = Config { cheap_price_threshold: Some(0.10), ..Config::default() };
+        let ordinary = follow(&hold_hour(0.05), &grid(800), &battery(60.0, 0), &config);
+        assert_eq!(ordinary.decision, Decision::Idle);
+
+        // 4381 W of load: the 1000 W above the 3381 W peak is still shaved.
+        let peak = follow(&hold_hour(0.05), &grid(4381), &battery(60.0, 0), &config);
+        assert_eq!(peak.decision, Decision::DischargingToLoad { watts: 1000, min_soc_percent: 20 });
+    }
+
+    #[test]
+    fn a_normal_hour_covers_household_load_from_the_battery() {
+        let config  = Config { cheap_price_threshold: Some(0.10), ..Config::default() };
+        let verdict = follow(&hold_hour(0.30), &grid(800), &battery(60.0, 0), &config);
+        assert_eq!(verdict.decision, Decision::DischargingToLoad { watts: 800, min_soc_percent: 20 });
+    }
+
+    #[test]
+    fn a_high_price_hour_exports_what_is_worth_more_now_than_later() {
+        // 0.40 €/kWh now against at most 0.25 €/kWh later, plus 10 %.
+        let config  = Config { export_priority_bias_percent: Some(10.0), ..Config::default() };
+        let verdict = follow(&hold_hour(0.40), &grid(500), &battery(80.0, 0), &config);
+        assert_eq!(verdict.decision, Decision::DischargingToGrid { watts: 2400, min_soc_percent: 20 });
+
+        // 1000 W of feed-in on top of the 500 W house load.
+        let capped  = Config { max_feed_in_w: Some(1000), ..config.clone() };
+        let verdict = follow(&hold_hour(0.40), &grid(500), &battery(80.0, 0), &capped);
+        assert_eq!(verdict.decision, Decision::DischargingToGrid { watts: 1500, min_soc_percent: 20 });
+    }
+
+    #[test]
+    fn a_normal_hour_keeps_the_energy_for_later() {
+        // 0.27 €/kWh is above the 0.25 €/kWh later, but not by the 10 % bias.
+        let config  = Config { export_priority_bias_percent: Some(10.0), ..Config::default() };
+        let verdict = follow(&hold_hour(0.27), &grid(500), &battery(80.0, 0), &config);
+        assert_eq!(verdict.decision, Decision::DischargingToLoad { watts: 500, min_soc_percent: 20 });
+
+        // At the reserve nothing is exported, however high the price.
+        let verdict = follow(&hold_hour(0.40), &grid(500), &battery(20.0, 0), &config);
+        assert_ne!(verdict.decision.direction(), Direction::Discharge);
+    }
+
+    /// Consecutive hours from 10:00 UTC at `prices`.
+    fn curve(prices: &[f64]) -> Vec<HourlyPrice> {
+        let start = Utc.with_ymd_and_hms(2026, 10, 15, 10, 0, 0).unwrap();
+        prices
+            .iter()
+            .enumerate()
+            .map(|(h, &price)| HourlyPrice { starts_at: start + SLOT * h as i32, price_eur_per_kwh: price })
+            .collect()
+    }
+
+    #[test]
+    fn plan_day_charges_in_the_cheap_hours_for_the_expensive_ones() {
+        use PlanAction::{Charge, Discharge, Hold};
+        let config  = Config { grid_charging_enabled: true, ..Config::default() };
+        let plan    = plan_day(&curve(&[0.30, 0.10, 0.12, 0.35, 0.40, 0.20]), &config, 50.0);
+        let actions: Vec<_> = plan.slots.iter().map(|s| s.action).collect();
+        assert_eq!(actions, vec![Hold, Charge, Charge, Discharge, Discharge, Hold]);
+        assert!(plan.expected_saving_eur > 0.0);
+
+        // The charged energy is kept until the discharge hours, then the floor drops back to the reserve.
+        let floors: Vec<_> = plan.slots.iter().map(|s| s.floor_percent.round()).collect();
+        assert!(floors[2] > floors[0] && floors[3] > 20.0);
+        assert_eq!(&floors[4..], &[20.0, 20.0]);
+    }
+
+    #[test]
+    fn plan_day_without_grid_charging_or_spread_plans_nothing() {
+        let plan = plan_day(&curve(&[0.30, 0.10, 0.40]), &Config::default(), 50.0);
+        assert!(plan.slots.iter().all(|s| s.action == PlanAction::Hold));
+
+        // 0.22 after 0.20 doesn't cover the round-trip losses.
+        let config = Config { grid_charging_enabled: true, ..Config::default() };
+        let plan   = plan_day(&curve(&[0.20, 0.22]), &config, 50.0);
+        assert!(plan.slots.iter().all(|s| s.action == PlanAction::Hold));
+        assert_eq!(plan.expected_saving_eur, 0.0);
+    }
+
+    #[test]
+    fn each_hour_knows_the_dearest_import_after_it() {
+        let plan  = plan_day(&curve(&[0.20, 0.35, 0.25]), &Config::default(), 50.0);
+        let later: Vec<_> = plan.slots.iter().map(|s| s.later_import_eur_per_kwh).collect();
+        assert_eq!(later, vec![Some(0.35), Some(0.25), None]);
+    }
+
+    #[test]
+    fn refresh_charges_tonight_for_tomorrows_expensive_morning() {
+        // From 21:00 local (19:00 UTC): a cheap hour at 23:00, then tomorrow's 07:00-09:00 peak.
+        let start  = Utc.with_ymd_and_hms(2026, 10, 15, 19, 0, 0).unwrap();
+        let mut prices = vec![0.25, 0.25, 0.08];
+        prices.extend([0.20; 7]);
+        prices.extend([0.45, 0.45, 0.25, 0.25]);
+        let prices = prices
+            .iter()
+            .enumerate()
+            .map(|(h, &price)| HourlyPrice { starts_at: start + SLOT * h as i32, price_eur_per_kwh: price })
+            .collect();
+        let curve  = PriceCurve::new(prices, start);
+        let config = Config { grid_charging_enabled: true, plan_include_tomorrow: true, ..Config::default() };
+
+        let plan = refresh(None, &curve, 30.0, start, &config).expect("a plan");
+        assert_eq!(plan.slots.len(), 14);
+        assert_eq!(plan.slots[2].action, PlanAction::Charge);
+        assert_eq!(plan.slots[10].action, PlanAction::Discharge);
+
+        // Planning today only, the cheap hour is the last one and has nothing to charge for.
+        let today_only = Config { plan_include_tomorrow: false, ..config };
+        let plan       = refresh(None, &curve, 30.0, start, &today_only).expect("a plan");
+        assert_eq!(plan.slots.len(), 3);
+        assert!(plan.slots.iter().all(|s| s.action == PlanAction::Hold));
+    }
+
+    #[test]
+    fn a_large_import_fee_makes_storing_solar_worth_more_than_exporting_it() {
+        // 0.40 €/kWh now, 0.30 €/kWh later: without fees exporting beats keeping the energy by more than 10 %.
+        let config = Config { export_priority_bias_percent: Some(10.0), ..Config::default() };
+        let plan   = plan_day(&curve(&[0.40, 0.30]), &config, 80.0);
+        let sunny  = follow(&plan.slots[0], &grid(-1500), &battery(80.0, 0), &config);
+        assert_eq!(sunny.decision.direction(), Direction::Discharge, "{}", sunny.reason);
+
+        // With 0.15 €/kWh of taxes and fees on every import, a stored kWh saves 0.45 €/kWh later.
+        let config = Config { import_adder_per_kwh: 0.15, ..config };
+        let plan   = plan_day(&curve(&[0.40, 0.30]), &config, 80.0);
+        let later  = plan.slots[0].later_import_eur_per_kwh.unwrap();
+        assert!((later - 0.45).abs() < 1e-9, "{}", later);
+        let sunny  = follow(&plan.slots[0], &grid(-1500), &battery(80.0, 0), &config);
+        assert_eq!(sunny.decision, Decision::ChargingFromSolar { watts: 1500, max_soc_percent: 100 });
+    }
+}
+
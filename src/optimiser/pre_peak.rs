@@ -0,0 +1,251 @@
+use chrono::{DateTime, NaiveDate, NaiveTime, TimeDelta, Utc};
+use log::info;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+
+use crate::configuration::config::Config;
+use crate::configuration::floor_schedule::in_window;
+use crate::models::indevolt_models::BatterySnapshot;
+use crate::runtime::clock::LOCAL_TZ;
+
+use super::decision::{Decision, Direction, Verdict};
+use super::grid::GridReading;
+
+// --------------------------------------------------------------------------------------------------------------
+// Pre-peak charge
+//
+// The capacity tariff bills the month's highest quarter-hour, and in most homes that quarter-hour falls in the
+// same evening stretch every day. With `pre_peak_window` set to that stretch, the battery is topped up during
+// the `pre_peak_lead_minutes` before it, so there is enough charge to shave the peak when it comes:
+//
+//   "pre_peak_window": { "from": "17:00", "to": "21:00" }
+//
+// How much is enough is learned from the previous days. Every cycle inside the window adds the household load
+// above the shaving target (`battery_max_desired_grid_peak_w`, as `peak::shaving_target_w` set it) to the
+// day's total: the energy peak shaving had to deliver, whether the battery delivered it or not. The largest
+// total of the last `pre_peak_history_days` days, but at least `pre_peak_min_energy_kwh`, is kept above the
+// SOC where peak shaving stops. Below that SOC during the lead time:
+//   - a charge the strategy already decided (solar surplus) is kept;
+//   - otherwise the battery charges from the grid at `pre_peak_charge_power_w` if `grid_charging_enabled`,
+//     or holds `Idle` (no discharge for ordinary load) until solar surplus arrives;
+//   - a discharge while the import is above the shaving target is kept - that peak comes first.
+// The day totals are kept in the state file.
+// --------------------------------------------------------------------------------------------------------------
+
+/// Window totals kept, one per day.
+const MAX_DAYS: usize = 31;
+
+/// Longest gap (s) between two cycles that is integrated; a longer one counts as this long.
+const MAX_GAP_SECONDS: i64 = 300;
+
+/// Local-time window [from, to) in which the daily peak is expected; wraps midnight when `to` < `from`.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct PeakWindow {
+    pub from: NaiveTime,
+    pub to:   NaiveTime,
+}
+
+impl PeakWindow {
+    pub fn contains(&self, time: NaiveTime) -> bool {
+        in_window(self.from, self.to, time)
+    }
+
+    /// Whether `time` falls in the `lead_minutes` before the window.
+    pub fn leads_up_to(&self, time: NaiveTime, lead_minutes: u32) -> bool {
+        lead_minutes > 0 && in_window(self.from - TimeDelta::minutes(lead_minutes.into()), self.from, time)
+    }
+}
+
+/// Energy peak shaving needed in the window, per day, and whether a top-up is running.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct PrePeakCharge {
+    day:       Option<NaiveDate>,
+    /// Load above the shaving target in today's window so far (Wh).
+    window_wh: f64,
+    in_window: bool,
+    last_at:   Option<DateTime<Utc>>,
+    /// Totals (kWh) of the previous days with a window, oldest first.
+    history:   VecDeque<f64>,
+    active:    bool,
+}
+
+impl PrePeakCharge {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Feed one cycle with a grid measurement.
+    pub fn observe(
+        &mut self,
+        now: DateTime<Utc>,
+        grid: &GridReading,
+        battery: &BatterySnapshot,
+        config: &Config,
+    ) {
+        let local = now.with_timezone(&LOCAL_TZ);
+        if self.day != Some(local.date_naive()) {
+            if self.in_window {
+                if self.history.len() >= MAX_DAYS {
+                    self.history.pop_front();
+                }
+                self.history.push_back(self.window_wh / 1000.0);
+            }
+            self.day       = Some(local.date_naive());
+            self.window_wh = 0.0;
+            self.in_window = false;
+        }
+
+        let last = self.last_at.replace(now);
+        if !config.pre_peak_window.is_some_and(|w| w.contains(local.time())) {
+            return;
+        }
+        self.in_window = true;
+        if let Some(last) = last {
+            let seconds  = (now - last).num_seconds().clamp(0, MAX_GAP_SECONDS);
+            let load_w   = grid.power_w - battery.battery_power_w;
+            let excess_w = (load_w - config.battery_max_desired_grid_peak_w).max(0);
+            self.window_wh += excess_w as f64 * seconds as f64 / 3600.0;
+        }
+    }
+
+    /// Energy (kWh) to keep for the coming window.
+    pub fn needed_kwh(&self, config: &Config) -> f64 {
+        self.history
+            .iter()
+            .rev()
+            .take(config.pre_peak_history_days)
+            .fold(config.pre_peak_min_energy_kwh, |needed, &kwh| needed.max(kwh))
+    }
+
+    /// SOC (%) that holds `needed_kwh` above the SOC where peak shaving stops.
+    pub fn required_soc_percent(&self, config: &Config) -> f64 {
+        let capacity_kwh = config.battery_rated_capacity_kwh * config.efficiencies().1;
+        if capacity_kwh <= 0.0 {
+            return config.battery_max_soc_percent;
+        }
+        let floor = config.battery_min_soc_percent + config.peak_shave_min_soc_headroom_percent;
+        (floor + self.needed_kwh(config) / capacity_kwh * 100.0).min(config.battery_max_soc_percent)
+    }
+
+    /// Replace the strategy's verdict with a top-up during the lead time while the SOC is short.
+    pub fn apply(
+        &mut self,
+        verdict: Verdict,
+        grid: &GridReading,
+        battery: &BatterySnapshot,
+        now: DateTime<Utc>,
+        config: &Config,
+    ) -> Verdict {
+        let time = now.with_timezone(&LOCAL_TZ).time();
+        let lead = config.pre_peak_window.is_some_and(|w| w.leads_up_to(time, config.pre_peak_lead_minutes));
+        let soc      = battery.battery_soc;
+        let required = self.required_soc_percent(config);
+        if !lead || soc >= required {
+            if self.active && lead {
+                info!("[Optimiser] Pre-peak charge complete: SOC {:.1}% ≥ {:.1}%", soc, required);
+            }
+            self.active = false;
+            return verdict;
+        }
+        if !self.active {
+            info!(
+                "[Optimiser] Pre-peak charge: SOC {:.1}% below the {:.1}% needed to shave {:.2} kWh",
+                soc, required, self.needed_kwh(config)
+            );
+            self.active = true;
+        }
+
+        match verdict.decision.direction() {
+            Direction::Charge => {
+                let reason = format!("pre-peak charge to {:.1}% ({})", required, verdict.reason);
+                return Verdict::new(verdict.decision, reason);
+            }
+            Direction::Discharge if grid.power_w > config.battery_max_desired_grid_peak_w => return verdict,
+            _ => {}
+        }
+        if config.grid_charging_enabled {
+            let watts = config.pre_peak_charge_power_w.min(config.battery_max_charge_power_w);
+            return Verdict::new(
+                Decision::ChargingFromGrid { watts, max_soc_percent: config.soc_target(required) },
+                format!("pre-peak charge from grid: SOC {:.1}% → {:.1}%", soc, required),
+            );
+        }
+        Verdict::new(
+            Decision::Idle,
+            format!("pre-peak charge: SOC {:.1}% held until solar surplus (was: {})", soc, verdict.reason),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::optimiser::testing::{battery, grid};
+    use chrono::TimeZone;
+
+    fn config(grid_charging_enabled: bool) -> Config {
+        Config {
+            pre_peak_window:         Some(PeakWindow {
+                from: NaiveTime::from_hms_opt(17, 0, 0).unwrap(),
+                to:   NaiveTime::from_hms_opt(21, 0, 0).unwrap(),
+            }),
+            pre_peak_min_energy_kwh: 3.0,
+            grid_charging_enabled,
+            ..Config::default()
+        }
+    }
+
+    /// 15:00 in Brussels (CEST), within three hours of the window.
+    fn lead_time() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 6, 3, 13, 0, 0).unwrap()
+    }
+
+    fn house_load() -> Verdict {
+        Verdict::new(Decision::DischargingToLoad { watts: 500, min_soc_percent: 20 }, "house load")
+    }
+
+    #[test]
+    fn low_soc_before_the_window_charges_from_the_grid() {
+        let config  = config(true);
+        let mut pre = PrePeakCharge::new();
+        // 3 kWh out of 12 kWh at √0.8 discharge efficiency, above the 10 % minimum: 38 %.
+        let verdict = pre.apply(house_load(), &grid(500), &battery(25.0, 0), lead_time(), &config);
+
+        assert_eq!(verdict.decision, Decision::ChargingFromGrid { watts: 1500, max_soc_percent: 38 });
+    }
+
+    #[test]
+    fn sufficient_soc_leaves_the_strategy_alone() {
+        let config  = config(true);
+        let mut pre = PrePeakCharge::new();
+        let verdict = pre.apply(house_load(), &grid(500), &battery(60.0, 0), lead_time(), &config);
+
+        assert_eq!(verdict, house_load());
+    }
+
+    #[test]
+    fn without_grid_charging_the_soc_is_held() {
+        let config  = config(false);
+        let mut pre = PrePeakCharge::new();
+        let verdict = pre.apply(house_load(), &grid(500), &battery(25.0, 0), lead_time(), &config);
+        assert_eq!(verdict.decision, Decision::Idle);
+
+        let outside = Utc.with_ymd_and_hms(2024, 6, 3, 8, 0, 0).unwrap();
+        assert_eq!(pre.apply(house_load(), &grid(500), &battery(25.0, 0), outside, &config), house_load());
+    }
+
+    #[test]
+    fn needed_energy_comes_from_the_load_above_the_target() {
+        let config  = Config { pre_peak_min_energy_kwh: 0.0, ..config(true) };
+        let mut pre = PrePeakCharge::new();
+        // 18:00-19:00 local: 1000 W above the 3381 W target, half of it covered by the battery.
+        for minute in 0..=60 {
+            let now = Utc.with_ymd_and_hms(2024, 6, 2, 16, 0, 0).unwrap() + TimeDelta::minutes(minute);
+            pre.observe(now, &grid(3881), &battery(50.0, -500), &config);
+        }
+        assert_eq!(pre.needed_kwh(&config), 0.0);
+
+        pre.observe(lead_time(), &grid(0), &battery(50.0, 0), &config);
+        assert!((pre.needed_kwh(&config) - 1.0).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,75 @@
+use super::decision::{Decision, Verdict};
+
+// --------------------------------------------------------------------------------------------------------------
+// Power ramp
+//
+// Jumping the commanded power from 0 to 2400 W in one step can cause transients on a weak grid connection. With
+// `max_power_step_w` set, a charge or discharge setpoint rises by at most that much per cycle, starting from the
+// power last sent in the same direction (0 after a stop, a reversal or a failed command). A 0 → 2400 W target
+// with a 600 W step is therefore reached in four cycles: 600, 1200, 1800, 2400. Lowering the power, stopping
+// and handing back are never delayed, as they only ever move towards the limits.
+// --------------------------------------------------------------------------------------------------------------
+
+/// Limit the rise of the commanded power to `max_step_w` above `previous` (the decision last sent).
+pub fn ramp_power(verdict: Verdict, previous: Option<&Decision>, max_step_w: i32) -> Verdict {
+    let Some(target_w) = verdict.decision.watts() else {
+        return verdict;
+    };
+    if max_step_w <= 0 {
+        return verdict;
+    }
+    let current_w = match previous {
+        Some(previous) if previous.direction() == verdict.decision.direction() => previous.watts().unwrap_or(0),
+        _ => 0,
+    };
+    if target_w - current_w <= max_step_w {
+        return verdict;
+    }
+
+    let watts = current_w + max_step_w;
+    Verdict::new(
+        verdict.decision.with_watts(watts),
+        format!("ramping to {}W in {}W steps: {}", target_w, max_step_w, verdict.reason),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn charge(watts: i32) -> Decision {
+        Decision::ChargingFromGrid { watts, max_soc_percent: 90 }
+    }
+
+    #[test]
+    fn a_2400_w_target_with_a_600_w_step_ramps_over_four_cycles() {
+        let mut sent: Option<Decision> = None;
+        let mut steps = Vec::new();
+        for _ in 0..5 {
+            let verdict = ramp_power(Verdict::new(charge(2400), "cheap hour"), sent.as_ref(), 600);
+            steps.push(verdict.decision.watts().unwrap());
+            sent = Some(verdict.decision);
+        }
+        assert_eq!(steps, vec![600, 1200, 1800, 2400, 2400]);
+    }
+
+    #[test]
+    fn lowering_stopping_and_reversing_are_not_delayed() {
+        let previous = charge(2400);
+        let lower    = ramp_power(Verdict::new(charge(500), "less"), Some(&previous), 600);
+        assert_eq!(lower.decision, charge(500));
+        let idle     = ramp_power(Verdict::new(Decision::Idle, "stop"), Some(&previous), 600);
+        assert_eq!(idle.decision, Decision::Idle);
+
+        // A reversal starts again from 0.
+        let discharge = Decision::DischargingToLoad { watts: 2000, min_soc_percent: 20 };
+        let reversed  = ramp_power(Verdict::new(discharge, "evening"), Some(&previous), 600);
+        assert_eq!(reversed.decision.watts(), Some(600));
+    }
+
+    #[test]
+    fn a_step_of_0_disables_the_ramp() {
+        let verdict = ramp_power(Verdict::new(charge(2400), "cheap hour"), None, 0);
+        assert_eq!(verdict.decision, charge(2400));
+    }
+}
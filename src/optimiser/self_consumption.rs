@@ -0,0 +1,201 @@
+use crate::configuration::config::Config;
+use crate::models::indevolt_models::BatterySnapshot;
+
+use super::decision::{Decision, Verdict};
+use super::grid::GridReading;
+use super::trace::RuleTrace;
+
+// --------------------------------------------------------------------------------------------------------------
+// Baseline strategy: self-consumption plus peak protection
+//
+// No price input at all - this is the strategy for households without a dynamic tariff:
+//   1. Solar surplus (export) charges the battery, up to `battery_max_soc_percent`. Charging starts once the
+//      surplus exceeds `surplus_charge_threshold_w` and continues until it falls to `surplus_charge_exit_w`.
+//   2. Household load is covered from the battery while SOC stays above `peak_shaving_reserve_soc_percent`.
+//      While feed-in is curtailed (see `curtailment_windows`) both thresholds are 0 W and the ceiling is
+//      `curtailment_max_soc_percent`, so any surplus is stored rather than exported.
+//   3. The SOC band between `battery_min_soc_percent` and the peak-shaving reserve is only used to keep grid
+//      import below `battery_max_desired_grid_peak_w` (Belgian capacity tariff). With
+//      `peak_shave_min_soc_headroom_percent`, peak shaving stops that much above the minimum SOC, and over the
+//      next band of the same width the shave power tapers linearly from full down to zero:
+//        protected = battery_min_soc_percent + headroom
+//        shave_w   = excess_w × min(1, (soc − protected) / headroom)
+//
+// The household's own net demand is the grid power with the battery's contribution removed:
+//   house_net_w = grid_w - battery_power_w    (battery_power_w > 0 = charging)
+// Within the grid reading's deadband (non-zero only for the degraded Indevolt-meter source) nothing is done.
+// --------------------------------------------------------------------------------------------------------------
+
+pub fn decide(grid: &GridReading, battery: &BatterySnapshot, config: &Config) -> Verdict {
+    decide_traced(grid, battery, config, &mut RuleTrace::default())
+}
+
+/// `decide`, recording the rules it evaluates in `trace`.
+pub fn decide_traced(
+    grid: &GridReading,
+    battery: &BatterySnapshot,
+    config: &Config,
+    trace: &mut RuleTrace,
+) -> Verdict {
+    let soc         = battery.battery_soc;
+    let grid_w      = grid.power_w;
+    let house_net_w = grid_w - battery.battery_power_w;
+
+    let max_soc     = config.soc_target(config.battery_max_soc_percent);
+    let reserve_soc = config.peak_shaving_reserve_soc_percent.max(config.battery_min_soc_percent);
+    let peak_w      = config.battery_max_desired_grid_peak_w;
+
+    let detail = format!("house load {}W, deadband ±{}W", house_net_w, grid.deadband_w);
+    if trace.rule("deadband", house_net_w.abs() <= grid.deadband_w, detail) {
+        return Verdict::new(Decision::Idle, format!("house load {}W within deadband", house_net_w));
+    }
+
+    // 1. Solar surplus → charge.
+    if house_net_w < 0 {
+        let surplus_w = -house_net_w;
+        if soc >= config.battery_max_soc_percent {
+            trace.rule(
+                "solar_surplus",
+                false,
+                format!("surplus {}W, SOC {:.1}% at max {:.1}%", surplus_w, soc, config.battery_max_soc_percent),
+            );
+            return Verdict::new(Decision::Idle, format!("surplus {}W but battery full ({:.1}%)", surplus_w, soc));
+        }
+        // Hysteresis: start only above the enter threshold, keep going until below the (lower) exit threshold.
+        let charging    = battery.battery_power_w > 0;
+        let threshold_w = if charging { config.surplus_charge_exit_w } else { config.surplus_charge_threshold_w };
+        let detail = format!("surplus {}W, threshold {}W, SOC {:.1}%", surplus_w, threshold_w, soc);
+        if !trace.rule("solar_surplus", surplus_w > threshold_w, detail) {
+            return Verdict::new(
+                Decision::Idle,
+                format!(
+                    "surplus {}W not above {} threshold {}W",
+                    surplus_w, if charging { "exit" } else { "enter" }, threshold_w
+                ),
+            );
+        }
+        let watts  = surplus_w.min(config.battery_max_charge_power_w);
+        let reason = if config.feed_in_curtailed {
+            format!("feed-in curtailed: absorbing solar surplus {}W", surplus_w)
+        } else {
+            format!("solar surplus {}W", surplus_w)
+        };
+        return Verdict::new(Decision::ChargingFromSolar { watts, max_soc_percent: max_soc }, reason);
+    }
+
+    if house_net_w == 0 {
+        trace.rule("house_load", false, "house load balanced");
+        return Verdict::new(Decision::Idle, "house load balanced");
+    }
+
+    // 2. Household load → discharge down to the peak-shaving reserve.
+    let detail = format!("house load {}W, SOC {:.1}%, reserve {:.1}%", house_net_w, soc, reserve_soc);
+    if trace.rule("house_load", soc > reserve_soc, detail) {
+        let watts = house_net_w.min(config.battery_max_discharge_power_w);
+        return Verdict::new(
+            Decision::DischargingToLoad { watts, min_soc_percent: config.soc_target(reserve_soc) },
+            format!("house load {}W", house_net_w),
+        );
+    }
+
+    // 3. Below the reserve: only shave the part of the load above the capacity-tariff peak.
+    let excess_w  = house_net_w - peak_w;
+    let headroom  = config.peak_shave_min_soc_headroom_percent.max(0.0);
+    let protected = config.battery_min_soc_percent + headroom;
+    let detail    = format!(
+        "house load {}W, peak {}W, SOC {:.1}%, protected SOC {:.1}%", house_net_w, peak_w, soc, protected
+    );
+    trace.rule("peak_shaving", excess_w > 0 && soc > protected, detail);
+    if excess_w > 0 {
+        if soc > protected {
+            let taper = if headroom > 0.0 { ((soc - protected) / headroom).min(1.0) } else { 1.0 };
+            let watts = ((excess_w as f64 * taper).round() as i32).min(config.battery_max_discharge_power_w);
+            let mut reason =
+                format!("peak shaving: load {}W exceeds peak {}W by {}W", house_net_w, peak_w, excess_w);
+            if taper < 1.0 {
+                reason += &format!(", tapered to {:.0}% above protected SOC {:.1}%", taper * 100.0, protected);
+            }
+            return Verdict::new(
+                Decision::DischargingToLoad { watts, min_soc_percent: config.soc_target(protected) },
+                reason,
+            );
+        }
+        return Verdict::new(
+            Decision::Idle,
+            format!(
+                "load {}W exceeds peak {}W but SOC {:.1}% is at the peak-shaving floor {:.1}%",
+                house_net_w, peak_w, soc, protected
+            ),
+        );
+    }
+
+    Verdict::new(
+        Decision::Idle,
+        format!("SOC {:.1}% at peak-shaving reserve {:.1}%, load {}W below peak", soc, reserve_soc, house_net_w),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::optimiser::testing::{battery, grid};
+
+    #[test]
+    fn solar_surplus_charges_the_battery() {
+        let verdict = decide(&grid(-1200), &battery(50.0, 0), &Config::default());
+        assert_eq!(verdict.decision, Decision::ChargingFromSolar { watts: 1200, max_soc_percent: 100 });
+    }
+
+    #[test]
+    fn evening_load_is_covered_down_to_the_reserve() {
+        let config  = Config::default();
+        let verdict = decide(&grid(800), &battery(60.0, 0), &config);
+        assert_eq!(verdict.decision, Decision::DischargingToLoad { watts: 800, min_soc_percent: 20 });
+
+        assert_eq!(decide(&grid(800), &battery(15.0, 0), &config).decision, Decision::Idle);
+    }
+
+    #[test]
+    fn load_above_the_peak_is_shaved_below_the_reserve() {
+        let config  = Config::default();
+        // 4381 W of load at 15 % SOC: only the 1000 W above the 3381 W peak comes from the battery.
+        let verdict = decide(&grid(4381), &battery(15.0, 0), &config);
+        assert_eq!(verdict.decision, Decision::DischargingToLoad { watts: 1000, min_soc_percent: 10 });
+
+        assert_eq!(decide(&grid(4381), &battery(10.0, 0), &config).decision, Decision::Idle);
+    }
+
+    #[test]
+    fn peak_shaving_tapers_over_the_headroom_and_stops_above_the_minimum() {
+        // Min SOC 10 % plus 5 % headroom: shaving stops at 15 % and tapers over 15-20 %.
+        let config = Config { peak_shave_min_soc_headroom_percent: 5.0, ..Config::default() };
+
+        let verdict = decide(&grid(4381), &battery(19.0, 0), &config);
+        assert_eq!(verdict.decision, Decision::DischargingToLoad { watts: 800, min_soc_percent: 15 });
+        assert!(verdict.reason.ends_with("tapered to 80% above protected SOC 15.0%"), "{}", verdict.reason);
+
+        let verdict = decide(&grid(4381), &battery(17.5, 0), &config);
+        assert_eq!(verdict.decision, Decision::DischargingToLoad { watts: 500, min_soc_percent: 15 });
+
+        let verdict = decide(&grid(4381), &battery(15.0, 0), &config);
+        assert_eq!(verdict.decision, Decision::Idle);
+        assert!(verdict.reason.ends_with("at the peak-shaving floor 15.0%"), "{}", verdict.reason);
+    }
+
+    #[test]
+    fn a_borderline_export_starts_and_stops_charging_at_separate_thresholds() {
+        let config = Config { surplus_charge_threshold_w: 200, surplus_charge_exit_w: 100, ..Config::default() };
+        let mut charging_w = 0;
+        let mut charged    = Vec::new();
+        for surplus_w in [150, 250, 150, 120, 80, 150, 210] {
+            // The grid sees the house's surplus less what the battery already takes.
+            let verdict = decide(&grid(charging_w - surplus_w), &battery(50.0, charging_w), &config);
+            charging_w  = match verdict.decision {
+                Decision::ChargingFromSolar { watts, .. } => watts,
+                _                                         => 0,
+            };
+            charged.push(charging_w);
+        }
+        assert_eq!(charged, [0, 250, 150, 120, 0, 0, 210]);
+    }
+}
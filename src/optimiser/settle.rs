@@ -0,0 +1,79 @@
+use std::time::Duration;
+
+use log::info;
+
+use crate::configuration::config::Config;
+use crate::models::indevolt_models::BatterySnapshot;
+
+use super::apply_limits;
+use super::decision::{Decision, Verdict};
+
+// --------------------------------------------------------------------------------------------------------------
+// Post-command settle window
+//
+// Right after a mode switch or a new command the inverter ramps, and for a few seconds `battery_power_w` can
+// still point the old way. Because the household load is derived from grid power minus battery power, such a
+// reading can make the optimiser flip charge ↔ discharge for no real reason. Within `post_command_settle_ms`
+// of the last command a reversal is therefore held back and the previous command is kept.
+//
+// This stage runs straight after the strategy, before every guard: the kept command passes the SOC limits
+// again here, and then the grid-charge interlock, the daily caps, `allowed_working_modes` and the EV pause like
+// a fresh decision, so holding it can never bring back a command one of them has since ruled out.
+// --------------------------------------------------------------------------------------------------------------
+
+/// Keep `previous` instead of `verdict` when `verdict` would reverse it within the settle window, within
+/// today's SOC limits.
+pub fn hold_during_settle(
+    verdict:       Verdict,
+    previous:      Option<&Decision>,
+    since_command: Option<Duration>,
+    settle:        Duration,
+    battery:       &BatterySnapshot,
+    config:        &Config,
+) -> Verdict {
+    let (Some(previous), Some(since)) = (previous, since_command) else {
+        return verdict;
+    };
+    if since >= settle || !previous.reverses(&verdict.decision) {
+        return verdict;
+    }
+
+    info!(
+        "[Optimiser] Settling {}ms after last command (< {}ms) - keeping {} instead of {}",
+        since.as_millis(), settle.as_millis(), previous, verdict.decision
+    );
+    let held = Verdict::new(
+        previous.clone(),
+        format!("settling after command, not reversing to {} ({})", verdict.decision.label(), verdict.reason),
+    );
+    apply_limits(held, battery, config)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::optimiser::testing::battery;
+
+    const SETTLE: Duration = Duration::from_millis(5000);
+    const CHARGE: Decision = Decision::ChargingFromSolar { watts: 1000, max_soc_percent: 90 };
+
+    fn discharge() -> Verdict {
+        Verdict::new(Decision::DischargingToLoad { watts: 400, min_soc_percent: 20 }, "house load")
+    }
+
+    fn settle(since_ms: u64, soc: f64) -> Verdict {
+        let since = Some(Duration::from_millis(since_ms));
+        hold_during_settle(discharge(), Some(&CHARGE), since, SETTLE, &battery(soc, -400), &Config::default())
+    }
+
+    #[test]
+    fn a_spurious_reversal_within_the_window_keeps_the_command() {
+        assert_eq!(settle(1500, 50.0).decision, CHARGE);
+        assert_eq!(settle(6000, 50.0), discharge());
+    }
+
+    #[test]
+    fn the_kept_command_still_respects_the_soc_limits() {
+        assert_eq!(settle(1500, 90.0).decision, Decision::Idle);
+    }
+}
@@ -0,0 +1,90 @@
+use log::{log, Level};
+
+use crate::configuration::config::Config;
+use crate::models::indevolt_models::BatterySnapshot;
+
+use super::decision::{Decision, Verdict};
+use super::grid::GridReading;
+use super::plan::PlanSlot;
+use super::trace::RuleTrace;
+use super::{decide, missing_sensor, unknown_mode};
+
+// --------------------------------------------------------------------------------------------------------------
+// Shadow strategy
+//
+// To try a strategy on live data without letting it steer the battery, set `shadow_strategy`. Every cycle in
+// which the live strategy runs, the shadow strategy decides on the same grid reading, battery snapshot and
+// price plan. Its decision is logged next to the live one ("[Shadow] ... agrees/differs") and recorded in the
+// cycle outcome as `shadow_decision`, but never dispatched. The shadow sees the strategy and the hardware
+// limits only; the stateful stages after them (maintenance charge, daily cap, settle time, direction limit,
+// hand-back) act on the live decision alone.
+// --------------------------------------------------------------------------------------------------------------
+
+/// What `shadow_strategy` would decide this cycle, `None` when it isn't set, only monitors, or the readings
+/// don't allow a decision (the live strategy already logs why).
+pub fn shadow_verdict(
+    grid: &GridReading,
+    battery: &BatterySnapshot,
+    config: &Config,
+    plan_slot: Option<&PlanSlot>,
+) -> Option<Verdict> {
+    let strategy = config.shadow_strategy?;
+    if missing_sensor(battery).is_some() || unknown_mode(battery) {
+        return None;
+    }
+    decide(strategy, grid, battery, config, plan_slot, &mut RuleTrace::default())
+}
+
+/// Log the shadow's verdict next to the `live` decision at `level` and return its decision for the cycle
+/// outcome. Nothing here reaches the dispatcher.
+pub fn record_shadow(shadow: Option<Verdict>, live: Option<&Decision>, level: Level) -> Option<Decision> {
+    shadow.map(|verdict| {
+        let agreement = if live == Some(&verdict.decision) { "agrees" } else { "differs" };
+        log!(level, "[Shadow] {} - {} ({})", verdict.decision, verdict.reason, agreement);
+        verdict.decision
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::configuration::config::Strategy;
+    use crate::optimiser::run;
+    use crate::optimiser::testing::{battery, grid};
+    use crate::runtime::log_capture::capture;
+
+    #[test]
+    fn the_shadow_decides_while_the_live_strategy_only_monitors() {
+        let config = Config {
+            strategy:        Strategy::Monitor,
+            shadow_strategy: Some(Strategy::SelfConsumption),
+            ..Config::default()
+        };
+        let (grid, battery) = (grid(800), battery(60.0, 0));
+
+        // Nothing for the dispatcher; the shadow's discharge only goes to the log and the outcome.
+        let live = run(&grid, &battery, &config, None);
+        assert_eq!(live, None);
+        let shadow = shadow_verdict(&grid, &battery, &config, None);
+        let (recorded, lines) = capture(|| record_shadow(shadow, None, Level::Info));
+
+        let discharge = Decision::DischargingToLoad { watts: 800, min_soc_percent: 20 };
+        assert_eq!(recorded, Some(discharge));
+        let logged = lines.iter().any(|(_, line)| line.starts_with("[Shadow]") && line.ends_with("(differs)"));
+        assert!(logged, "{lines:?}");
+    }
+
+    #[test]
+    fn no_shadow_strategy_no_shadow_decision() {
+        assert!(shadow_verdict(&grid(800), &battery(60.0, 0), &Config::default(), None).is_none());
+        assert_eq!(record_shadow(None, Some(&Decision::Idle), Level::Info), None);
+    }
+
+    #[test]
+    fn a_matching_shadow_agrees() {
+        let verdict = Verdict::new(Decision::Idle, "balanced");
+        let (_, lines) = capture(|| record_shadow(Some(verdict), Some(&Decision::Idle), Level::Info));
+        assert!(lines.iter().any(|(_, line)| line.ends_with("(agrees)")), "{lines:?}");
+    }
+}
+
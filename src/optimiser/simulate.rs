@@ -0,0 +1,158 @@
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use serde_json::{Map, Value};
+
+use crate::configuration::config::Config;
+use crate::models::indevolt_models::{BatterySnapshot, WorkingMode};
+use crate::runtime::clock::LOCAL_TZ;
+
+use super::grid::{GridReading, GridSource};
+use super::plan::{PlanAction, PlanSlot};
+use super::trace::{DecisionTrace, RuleTrace, TraceInputs};
+
+// --------------------------------------------------------------------------------------------------------------
+// What-if simulation
+//
+// `POST /simulate` runs the optimiser on hypothetical readings instead of live ones, to preview a decision
+// before changing the config. The request gives the grid power, the SOC and, optionally, the battery power, a
+// price plan hour and settings to change; the answer is the decision trace (see `trace.rs`) the optimiser
+// produces. Only the pure `optimiser::run` is involved: nothing is sent to the inverter and no loop state moves,
+// so the stateful stages after the strategy (maintenance charge, daily cap, settle time, direction limit, ramp)
+// are not part of the answer. The settings are the running ones with today's profile and this time of day's
+// discharge floor and curtailment applied, then the request's `config` changes on top.
+// --------------------------------------------------------------------------------------------------------------
+
+/// Body of `POST /simulate`.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct SimulateRequest {
+    /// Net grid power as P1 would read it (W, positive = import).
+    pub grid_power_w:                  i32,
+    pub soc_percent:                   f64,
+    /// Battery power (W, positive = charging).
+    #[serde(default)]
+    pub battery_power_w:               i32,
+    /// Price of the plan hour (EUR/kWh); only used with `plan_action`.
+    #[serde(default)]
+    pub price_eur_per_kwh:             Option<f64>,
+    /// What the price plan intends for this hour ("charge", "discharge", "hold"), for "price_arbitrage".
+    #[serde(default)]
+    pub plan_action:                   Option<PlanAction>,
+    /// SOC the plan keeps for later discharges (%); `battery_min_soc_percent` when not given.
+    #[serde(default)]
+    pub plan_floor_percent:            Option<f64>,
+    /// Highest net import price (EUR/kWh) later in the plan, for export priority.
+    #[serde(default)]
+    pub plan_later_import_eur_per_kwh: Option<f64>,
+    /// Settings to change for this simulation only, as in config.json.
+    #[serde(default)]
+    pub config:                        Map<String, Value>,
+}
+
+/// The decision the optimiser would make for `request` at `now`, starting from the running `config`.
+pub fn simulate(
+    request: &SimulateRequest,
+    config: &Config,
+    now: DateTime<Utc>,
+) -> Result<DecisionTrace, String> {
+    let local  = now.with_timezone(&LOCAL_TZ);
+    let config = with_changes(&config.for_date(local.date_naive()).0, &request.config)?.for_time(local.time());
+
+    let mode = WorkingMode::RealtimeControl;
+    let battery = BatterySnapshot {
+        battery_soc:     request.soc_percent,
+        battery_soc_raw: request.soc_percent,
+        battery_power_w: request.battery_power_w,
+        battery_state:   "Static".to_string(),
+        working_mode:    mode.as_str().to_string(),
+        mode:            Some(mode),
+        read_at:         now,
+        ..BatterySnapshot::default()
+    };
+    let grid = GridReading {
+        power_w:        request.grid_power_w,
+        source:         GridSource::P1,
+        deadband_w:     0,
+        disagreement_w: None,
+    };
+    let slot = request.plan_action.map(|action| PlanSlot {
+        starts_at:                now,
+        price_eur_per_kwh:        request.price_eur_per_kwh.unwrap_or_default(),
+        action,
+        soc_start_percent:        request.soc_percent,
+        soc_end_percent:          request.soc_percent,
+        floor_percent:            request.plan_floor_percent.unwrap_or(config.battery_min_soc_percent),
+        later_import_eur_per_kwh: request.plan_later_import_eur_per_kwh,
+    });
+
+    let mut rules = RuleTrace::default();
+    let verdict = super::run_traced(&grid, &battery, &config, slot.as_ref(), &mut rules);
+    let inputs = TraceInputs {
+        grid_power_w:      Some(grid.power_w),
+        grid_source:       Some(grid.source.as_str()),
+        soc_percent:       battery.battery_soc,
+        battery_power_w:   battery.battery_power_w,
+        price_eur_per_kwh: request.price_eur_per_kwh,
+    };
+    Ok(DecisionTrace::new(0, now, inputs, rules).with_verdict(verdict.as_ref()))
+}
+
+/// `config` with the settings in `changes` replaced, checked like a loaded config.
+fn with_changes(config: &Config, changes: &Map<String, Value>) -> Result<Config, String> {
+    if changes.is_empty() {
+        return Ok(config.clone());
+    }
+    let mut settings = match serde_json::to_value(config) {
+        Ok(Value::Object(settings)) => settings,
+        _ => return Err("cannot serialise the running config".to_string()),
+    };
+    for (key, value) in changes {
+        if !settings.contains_key(key) {
+            return Err(format!("config: unknown setting '{}'", key));
+        }
+        settings.insert(key.clone(), value.clone());
+    }
+    let changed: Config = serde_json::from_value(Value::Object(settings)).map_err(|e| format!("config: {}", e))?;
+    changed.validate().map_err(|problems| format!("config: {}", problems.join("; ")))?;
+    Ok(changed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::optimiser::decision::Decision;
+
+    fn request(json: &str) -> SimulateRequest {
+        serde_json::from_str(json).unwrap()
+    }
+
+    #[test]
+    fn high_import_at_low_soc_shaves_the_peak() {
+        let json  = r#"{"grid_power_w": 6000, "soc_percent": 15}"#;
+        let trace = simulate(&request(json), &Config::default(), Utc::now()).unwrap();
+
+        assert_eq!(trace.decision, Some(Decision::DischargingToLoad { watts: 2400, min_soc_percent: 10 }));
+        assert_eq!(trace.rules.fired("peak_shaving"), Some(true));
+    }
+
+    #[test]
+    fn config_changes_apply_to_the_simulation_only() {
+        let config = Config::default();
+        let json   = r#"{"grid_power_w": 6000, "soc_percent": 15,
+                        "config": {"battery_max_discharge_power_w": 1000}}"#;
+        let trace = simulate(&request(json), &config, Utc::now()).unwrap();
+
+        assert_eq!(trace.decision, Some(Decision::DischargingToLoad { watts: 1000, min_soc_percent: 10 }));
+        assert_eq!(config.battery_max_discharge_power_w, 2400);
+    }
+
+    #[test]
+    fn invalid_config_changes_are_rejected() {
+        let unknown = request(r#"{"grid_power_w": 0, "soc_percent": 50, "config": {"no_such_setting": 1}}"#);
+        assert!(simulate(&unknown, &Config::default(), Utc::now()).is_err());
+
+        let json    = r#"{"grid_power_w": 0, "soc_percent": 50, "config": {"battery_min_soc_percent": 120}}"#;
+        let invalid = request(json);
+        assert!(simulate(&invalid, &Config::default(), Utc::now()).is_err());
+    }
+}
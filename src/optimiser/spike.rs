@@ -0,0 +1,93 @@
+use crate::configuration::config::Config;
+use crate::handlers::indevolt::reader::ID_BATTERY_SOC;
+use crate::models::indevolt_models::{BatterySnapshot, WorkingMode};
+
+use super::decision::{Decision, Verdict};
+
+// --------------------------------------------------------------------------------------------------------------
+// Load-spike fast path
+//
+// A big load switching on (EV charger, heat pump) right after a cycle is otherwise imported from the grid for up
+// to a full poll interval. With `fast_discharge_trigger_w` set, the loop re-reads P1 every
+// `fast_discharge_poll_ms` while it waits for the next cycle. When the import exceeds the trigger and the SOC
+// is above the discharge floor, it discharges right away without waiting for the next cycle:
+//
+//   watts = discharge already commanded + import now   (capped at `battery_max_discharge_power_w`)
+//
+// Only one fast command is sent between two cycles; the next full cycle then decides as usual. Nothing is sent
+// within `post_command_settle_ms` of the previous command, and the direction-change limit applies as for any
+// other decision. In the device's own self-consumption mode the inverter already follows the load, so the
+// fast path stays out of it.
+// --------------------------------------------------------------------------------------------------------------
+
+/// Discharge decision for a load spike of `grid_w` import, or `None` when the fast path shouldn't act.
+/// `battery` is the snapshot of the last full cycle and `last_sent` the decision active on the device.
+pub fn spike_verdict(
+    grid_w: i32,
+    battery: &BatterySnapshot,
+    last_sent: Option<&Decision>,
+    config: &Config,
+) -> Option<Verdict> {
+    let trigger_w = config.fast_discharge_trigger_w;
+    if trigger_w <= 0 || grid_w <= trigger_w {
+        return None;
+    }
+    let floor = config.peak_shaving_reserve_soc_percent;
+    if !battery.is_fresh(ID_BATTERY_SOC) || battery.battery_soc <= floor {
+        return None;
+    }
+
+    let discharging_w = match last_sent {
+        Some(Decision::DischargingToLoad { watts, .. }) => *watts,
+        Some(Decision::SelfConsumption) | Some(Decision::DischargingToGrid { .. }) => return None,
+        None if battery.mode == Some(WorkingMode::SelfConsumedPrioritized) => return None,
+        _ => 0,
+    };
+    let watts = (discharging_w + grid_w).min(config.battery_max_discharge_power_w);
+    if watts <= discharging_w {
+        return None;
+    }
+
+    Some(Verdict::new(
+        Decision::DischargingToLoad { watts, min_soc_percent: config.soc_target(floor) },
+        format!("load spike: import {}W > {}W, SOC {:.1}%", grid_w, trigger_w, battery.battery_soc),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::optimiser::testing::battery;
+
+    fn config() -> Config {
+        Config { fast_discharge_trigger_w: 1500, ..Config::default() }
+    }
+
+    #[test]
+    fn a_spike_triggers_a_discharge_on_top_of_the_current_one() {
+        let running = Decision::DischargingToLoad { watts: 400, min_soc_percent: 20 };
+        let verdict = spike_verdict(1800, &battery(60.0, -400), Some(&running), &config()).unwrap();
+        assert_eq!(verdict.decision, Decision::DischargingToLoad { watts: 2200, min_soc_percent: 20 });
+        assert!(verdict.reason.starts_with("load spike: import 1800W > 1500W"), "{}", verdict.reason);
+
+        // Capped at the rated discharge power.
+        let verdict = spike_verdict(3000, &battery(60.0, 0), Some(&Decision::Idle), &config()).unwrap();
+        assert_eq!(verdict.decision, Decision::DischargingToLoad { watts: 2400, min_soc_percent: 20 });
+    }
+
+    #[test]
+    fn no_fast_discharge_below_the_trigger_or_the_reserve() {
+        assert!(spike_verdict(1500, &battery(60.0, 0), Some(&Decision::Idle), &config()).is_none());
+        assert!(spike_verdict(3000, &battery(20.0, 0), Some(&Decision::Idle), &config()).is_none());
+        assert!(spike_verdict(3000, &battery(60.0, 0), Some(&Decision::Idle), &Config::default()).is_none());
+    }
+
+    #[test]
+    fn no_fast_discharge_in_self_consumption_mode() {
+        assert!(spike_verdict(3000, &battery(60.0, 0), Some(&Decision::SelfConsumption), &config()).is_none());
+
+        let mut self_consuming = battery(60.0, 0);
+        self_consuming.mode    = Some(WorkingMode::SelfConsumedPrioritized);
+        assert!(spike_verdict(3000, &self_consuming, None, &config()).is_none());
+    }
+}
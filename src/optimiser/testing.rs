@@ -0,0 +1,50 @@
+use chrono::Utc;
+use std::time::Duration;
+
+use crate::handlers::indevolt::reader::ID_BATTERY_SOC;
+use crate::handlers::p1::reader::P1Reading;
+use crate::models::indevolt_models::{BatterySnapshot, WorkingMode};
+use crate::models::p1_models::P1Data;
+
+use super::grid::{GridReading, GridSource};
+
+// --------------------------------------------------------------------------------------------------------------
+// Readings for the optimiser tests
+// --------------------------------------------------------------------------------------------------------------
+
+/// A fully read battery at `soc` % moving `power_w` (positive = charging), in real-time mode.
+pub fn battery(soc: f64, power_w: i32) -> BatterySnapshot {
+    let mode = WorkingMode::RealtimeControl;
+    BatterySnapshot {
+        battery_soc:     soc,
+        battery_soc_raw: soc,
+        battery_power_w: power_w,
+        battery_state:   "Static".to_string(),
+        working_mode:    mode.as_str().to_string(),
+        mode:            Some(mode),
+        ..BatterySnapshot::default()
+    }
+}
+
+/// `battery` without a SOC reading this cycle.
+pub fn battery_without_soc() -> BatterySnapshot {
+    let mut battery = battery(0.0, 0);
+    battery.not_fresh.insert(ID_BATTERY_SOC);
+    battery
+}
+
+/// A P1 reading of `power_w` net import (negative = export).
+pub fn grid(power_w: i32) -> GridReading {
+    GridReading { power_w, source: GridSource::P1, deadband_w: 0, disagreement_w: None }
+}
+
+/// A P1 meter reading of `power_w` net import whose values changed `unchanged_for` ago.
+pub fn p1_reading(power_w: f64, unchanged_for: Duration) -> P1Reading {
+    P1Reading {
+        raw: P1Data { active_power_w: power_w, ..P1Data::default() },
+        monthly_power_peak_timestamp_utc: Utc::now(),
+        gas_timestamp_utc:                Utc::now(),
+        unchanged_for,
+        dropped_fields:                   Vec::new(),
+    }
+}
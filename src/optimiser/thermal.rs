@@ -0,0 +1,112 @@
+use log::warn;
+
+use crate::configuration::config::Config;
+use crate::models::indevolt_models::BatterySnapshot;
+
+use super::decision::{Decision, Direction, Verdict};
+
+// --------------------------------------------------------------------------------------------------------------
+// Thermal derating
+//
+// Above `thermal_derate_start_c` the allowed charge/discharge power falls linearly, reaching zero at
+// `thermal_cutoff_c`. The hottest of the polled inverter/battery temperatures counts. Applied to whatever the
+// strategy decided, so no strategy can push the hardware harder than its temperature allows.
+// --------------------------------------------------------------------------------------------------------------
+
+/// Fraction (0.0-1.0) of the rated power allowed at `temperature_c`.
+pub fn derate_factor(temperature_c: f64, start_c: f64, cutoff_c: f64) -> f64 {
+    if temperature_c <= start_c {
+        1.0
+    } else if temperature_c >= cutoff_c || cutoff_c <= start_c {
+        0.0
+    } else {
+        (cutoff_c - temperature_c) / (cutoff_c - start_c)
+    }
+}
+
+/// Cap the commanded power of `verdict` according to the snapshot's temperature.
+pub fn apply_thermal_limit(verdict: Verdict, battery: &BatterySnapshot, config: &Config) -> Verdict {
+    let Some(temperature_c) = battery.max_temperature_c() else {
+        return verdict;
+    };
+    let factor = derate_factor(temperature_c, config.thermal_derate_start_c, config.thermal_cutoff_c);
+    if factor >= 1.0 {
+        return verdict;
+    }
+
+    let Some(watts) = verdict.decision.watts() else {
+        return verdict;
+    };
+    let rated = match verdict.decision.direction() {
+        Direction::Charge => config.battery_max_charge_power_w,
+        _                 => config.battery_max_discharge_power_w,
+    };
+    let cap = (rated as f64 * factor).floor() as i32;
+    if watts <= cap {
+        return verdict;
+    }
+
+    warn!(
+        "[Optimiser] Thermal derating at {:.1}°C: power capped to {:.0}% ({}W → {}W)",
+        temperature_c, factor * 100.0, watts, cap
+    );
+    if cap <= 0 {
+        return Verdict::new(
+            Decision::Idle,
+            format!("thermal cutoff at {:.1}°C (was: {})", temperature_c, verdict.reason),
+        );
+    }
+    Verdict::new(
+        verdict.decision.with_watts(cap),
+        format!("{}; thermally derated to {}W at {:.1}°C", verdict.reason, cap, temperature_c),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::optimiser::testing::battery;
+
+    fn hot(inverter_c: f64, battery_c: f64) -> BatterySnapshot {
+        BatterySnapshot {
+            inverter_temperature_c: Some(inverter_c),
+            battery_temperature_c:  Some(battery_c),
+            ..battery(60.0, 0)
+        }
+    }
+
+    fn discharge() -> Verdict {
+        Verdict::new(Decision::DischargingToLoad { watts: 2400, min_soc_percent: 10 }, "load")
+    }
+
+    #[test]
+    fn a_high_temperature_caps_the_power() {
+        // 45-55 °C derating, the hottest sensor counts: at 50 °C half of the 2400 W rating is left.
+        let capped = apply_thermal_limit(discharge(), &hot(30.0, 50.0), &Config::default());
+        assert_eq!(capped.decision, Decision::DischargingToLoad { watts: 1200, min_soc_percent: 10 });
+        assert!(capped.reason.ends_with("thermally derated to 1200W at 50.0°C"), "{}", capped.reason);
+    }
+
+    #[test]
+    fn the_cutoff_stops_the_battery() {
+        let stopped = apply_thermal_limit(discharge(), &hot(55.0, 20.0), &Config::default());
+        assert_eq!(stopped.decision, Decision::Idle);
+    }
+
+    #[test]
+    fn a_cool_battery_or_a_low_command_is_untouched() {
+        let config = Config::default();
+        assert_eq!(apply_thermal_limit(discharge(), &hot(30.0, 44.0), &config).decision, discharge().decision);
+        assert_eq!(apply_thermal_limit(discharge(), &battery(60.0, 0), &config).decision, discharge().decision);
+
+        let low = Verdict::new(Decision::DischargingToLoad { watts: 600, min_soc_percent: 10 }, "load");
+        assert_eq!(apply_thermal_limit(low.clone(), &hot(30.0, 50.0), &config).decision, low.decision);
+    }
+
+    #[test]
+    fn the_derate_factor_is_linear_between_start_and_cutoff() {
+        assert_eq!(derate_factor(45.0, 45.0, 55.0), 1.0);
+        assert_eq!(derate_factor(47.5, 45.0, 55.0), 0.75);
+        assert_eq!(derate_factor(60.0, 45.0, 55.0), 0.0);
+    }
+}
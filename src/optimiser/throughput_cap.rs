@@ -0,0 +1,168 @@
+use chrono::{DateTime, NaiveDate, Utc};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+
+use crate::analytics::energy::{energy_delta_wh, wh_to_kwh};
+use crate::configuration::config::Config;
+use crate::handlers::indevolt::reader::{ID_TOTAL_CHARGING, ID_TOTAL_DISCHARGING};
+use crate::models::indevolt_models::BatterySnapshot;
+use crate::runtime::clock::LOCAL_TZ;
+
+use super::decision::{Decision, Verdict};
+use super::grid::GridReading;
+use super::{apply_limits, self_consumption};
+
+// --------------------------------------------------------------------------------------------------------------
+// Daily throughput cap
+//
+// Every kWh through the battery wears it a little. With `max_daily_throughput_kwh` set, the energy charged plus
+// the energy discharged is added up per local day from the battery's cumulative counters, whatever caused it.
+// Once the total reaches the cap, no new arbitrage is started until local midnight: grid charges and exports
+// (`ChargingFromGrid`, `DischargingToGrid`) fall back to self-consumption. Self-consumption and peak shaving
+// carry on, so the cap limits what trading adds on top of them, not what the household needs. The total is kept
+// in the state file, so a restart doesn't reset the day.
+// --------------------------------------------------------------------------------------------------------------
+
+/// Energy through the battery today.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct ThroughputCap {
+    day:                 Option<NaiveDate>,
+    throughput_wh:       i64,
+    /// Last readings of the cumulative charge and discharge counters (kWh).
+    last_charged_kwh:    Option<f64>,
+    last_discharged_kwh: Option<f64>,
+    /// Whether the cap was reached today (logged once).
+    reached:             bool,
+}
+
+impl ThroughputCap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Charged plus discharged energy so far today (kWh).
+    pub fn throughput_kwh(&self) -> f64 {
+        wh_to_kwh(self.throughput_wh)
+    }
+
+    /// Feed one cycle's counters.
+    pub fn observe(&mut self, now: DateTime<Utc>, battery: &BatterySnapshot) {
+        let date = now.with_timezone(&LOCAL_TZ).date_naive();
+        if self.day != Some(date) {
+            if self.day.is_some() {
+                let kwh = self.throughput_kwh();
+                info!("[Optimiser] Daily throughput cap reset ({:.2} kWh the day before)", kwh);
+            }
+            self.day           = Some(date);
+            self.throughput_wh = 0;
+            self.reached       = false;
+        }
+
+        if battery.is_fresh(ID_TOTAL_CHARGING) {
+            if let Some(last) = self.last_charged_kwh.replace(battery.total_charging_kwh) {
+                self.throughput_wh += energy_delta_wh(last, battery.total_charging_kwh);
+            }
+        }
+        if battery.is_fresh(ID_TOTAL_DISCHARGING) {
+            if let Some(last) = self.last_discharged_kwh.replace(battery.total_discharging_kwh) {
+                self.throughput_wh += energy_delta_wh(last, battery.total_discharging_kwh);
+            }
+        }
+    }
+
+    /// Replace a grid charge or export with self-consumption once today's cap is reached.
+    pub fn apply(
+        &mut self,
+        verdict: Verdict,
+        grid: &GridReading,
+        battery: &BatterySnapshot,
+        config: &Config,
+    ) -> Verdict {
+        let cap_kwh   = config.max_daily_throughput_kwh;
+        let arbitrage = matches!(
+            verdict.decision,
+            Decision::ChargingFromGrid { .. } | Decision::DischargingToGrid { .. }
+        );
+        if cap_kwh <= 0.0 || !arbitrage {
+            return verdict;
+        }
+        let throughput_kwh = self.throughput_kwh();
+        if throughput_kwh < cap_kwh {
+            return verdict;
+        }
+        if !self.reached {
+            warn!(
+                "[Optimiser] Daily throughput cap reached ({:.2} ≥ {:.2} kWh) - no arbitrage until midnight",
+                throughput_kwh, cap_kwh
+            );
+            self.reached = true;
+        }
+
+        let fallback = apply_limits(self_consumption::decide(grid, battery, config), battery, config);
+        Verdict::new(
+            fallback.decision,
+            format!("daily throughput cap {:.2} kWh reached: {}", cap_kwh, fallback.reason),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::optimiser::testing::{battery, grid};
+    use chrono::TimeZone;
+
+    fn counters(charged_kwh: f64, discharged_kwh: f64) -> BatterySnapshot {
+        BatterySnapshot {
+            total_charging_kwh:    charged_kwh,
+            total_discharging_kwh: discharged_kwh,
+            ..battery(50.0, 0)
+        }
+    }
+
+    fn grid_charge() -> Verdict {
+        Verdict::new(Decision::ChargingFromGrid { watts: 2000, max_soc_percent: 90 }, "planned charge")
+    }
+
+    #[test]
+    fn reaching_the_cap_mid_day_stops_grid_charging() {
+        let config = Config { max_daily_throughput_kwh: 10.0, ..Config::default() };
+        let noon   = Utc.with_ymd_and_hms(2024, 6, 1, 10, 0, 0).unwrap();
+        let mut cap = ThroughputCap::new();
+
+        cap.observe(noon, &counters(100.0, 100.0));
+        cap.observe(noon, &counters(104.0, 105.0));
+        let verdict = cap.apply(grid_charge(), &grid(0), &counters(104.0, 105.0), &config);
+        assert!(matches!(verdict.decision, Decision::ChargingFromGrid { .. }));
+
+        cap.observe(noon, &counters(105.0, 105.5));
+        assert_eq!(cap.throughput_kwh(), 10.5);
+        let verdict = cap.apply(grid_charge(), &grid(0), &counters(105.0, 105.5), &config);
+        assert_eq!(verdict.decision, Decision::Idle);
+        assert!(verdict.reason.starts_with("daily throughput cap"));
+    }
+
+    #[test]
+    fn self_consumption_continues_past_the_cap() {
+        let config = Config { max_daily_throughput_kwh: 1.0, ..Config::default() };
+        let now    = Utc.with_ymd_and_hms(2024, 6, 1, 10, 0, 0).unwrap();
+        let mut cap = ThroughputCap::new();
+        cap.observe(now, &counters(0.0, 0.0));
+        cap.observe(now, &counters(2.0, 0.0));
+
+        let discharge = Verdict::new(Decision::DischargingToLoad { watts: 800, min_soc_percent: 20 }, "load");
+        assert_eq!(cap.apply(discharge.clone(), &grid(800), &battery(50.0, 0), &config), discharge);
+    }
+
+    #[test]
+    fn the_count_restarts_at_local_midnight() {
+        let mut cap = ThroughputCap::new();
+        cap.observe(Utc.with_ymd_and_hms(2024, 6, 1, 21, 0, 0).unwrap(), &counters(0.0, 0.0));
+        cap.observe(Utc.with_ymd_and_hms(2024, 6, 1, 21, 30, 0).unwrap(), &counters(3.0, 0.0));
+        assert_eq!(cap.throughput_kwh(), 3.0);
+
+        // 22:00 UTC is midnight in Brussels (CEST).
+        cap.observe(Utc.with_ymd_and_hms(2024, 6, 1, 22, 0, 0).unwrap(), &counters(3.5, 0.0));
+        assert_eq!(cap.throughput_kwh(), 0.5);
+    }
+}
@@ -0,0 +1,118 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+use super::decision::{Decision, Verdict};
+
+// --------------------------------------------------------------------------------------------------------------
+// Decision trace
+//
+// While the optimiser decides, each rule it evaluates is appended to a `RuleTrace`: its name, whether it fired
+// (settled or changed the decision) and the inputs and thresholds it looked at. Rules that were never reached
+// because an earlier one settled the decision are not listed. The stages the control loop applies after the
+// strategy (interlock, caps, settle, ramp, ...) are traced the same way through `RuleTrace::stage`, which marks
+// a stage as fired when it changed the verdict. The control loop keeps the trace of its latest cycle, served as
+// `GET /decision`; `POST /simulate` returns the trace of a hypothetical decision.
+// --------------------------------------------------------------------------------------------------------------
+
+/// One rule the decision passed through.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct RuleEval {
+    pub rule:   &'static str,
+    /// The rule settled or changed the decision.
+    pub fired:  bool,
+    /// Inputs and thresholds involved, or the resulting reason when it fired.
+    pub detail: String,
+}
+
+/// The rules evaluated for one decision, in order.
+#[derive(Serialize, Debug, Clone, Default, PartialEq)]
+#[serde(transparent)]
+pub struct RuleTrace {
+    pub rules: Vec<RuleEval>,
+}
+
+impl RuleTrace {
+    /// Record that `rule` was evaluated. Returns `fired`, so a rule can be recorded where it is tested.
+    pub fn rule(&mut self, rule: &'static str, fired: bool, detail: impl Into<String>) -> bool {
+        self.rules.push(RuleEval { rule, fired, detail: detail.into() });
+        fired
+    }
+
+    /// Run the stage `rule` on `verdict`, recording whether it changed it.
+    pub fn stage(
+        &mut self,
+        rule: &'static str,
+        verdict: Verdict,
+        apply: impl FnOnce(Verdict) -> Verdict,
+    ) -> Verdict {
+        let before = verdict.clone();
+        let after  = apply(verdict);
+        if after == before {
+            self.rule(rule, false, format!("kept {}", before.decision));
+        } else {
+            self.rule(rule, true, format!("{} - {}", after.decision, after.reason));
+        }
+        after
+    }
+
+    /// Whether `rule` fired, `None` when it wasn't evaluated. The last evaluation counts.
+    pub fn fired(&self, rule: &str) -> Option<bool> {
+        self.rules.iter().rev().find(|eval| eval.rule == rule).map(|eval| eval.fired)
+    }
+}
+
+/// The readings a decision was made from.
+#[derive(Serialize, Debug, Clone, Default, PartialEq)]
+pub struct TraceInputs {
+    /// Net grid power (W, positive = import); `None` without a grid measurement.
+    pub grid_power_w:      Option<i32>,
+    pub grid_source:       Option<&'static str>,
+    pub soc_percent:       f64,
+    /// Battery power (W, positive = charging).
+    pub battery_power_w:   i32,
+    pub price_eur_per_kwh: Option<f64>,
+}
+
+/// How one decision came about, from the readings to the final decision.
+#[derive(Serialize, Debug, Clone, Default, PartialEq)]
+pub struct DecisionTrace {
+    /// Cycle the decision was made in; 0 for a simulation.
+    pub cycle_id: u64,
+    pub at:       Option<DateTime<Utc>>,
+    pub inputs:   TraceInputs,
+    pub rules:    RuleTrace,
+    /// Final decision; `None` when no decision could be made.
+    pub decision: Option<Decision>,
+    pub reason:   Option<String>,
+}
+
+impl DecisionTrace {
+    pub fn new(cycle_id: u64, at: DateTime<Utc>, inputs: TraceInputs, rules: RuleTrace) -> Self {
+        Self { cycle_id, at: Some(at), inputs, rules, decision: None, reason: None }
+    }
+
+    /// The trace ending in `verdict`.
+    pub fn with_verdict(mut self, verdict: Option<&Verdict>) -> Self {
+        self.decision = verdict.map(|v| v.decision.clone());
+        self.reason   = verdict.map(|v| v.reason.clone());
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stage_fires_only_when_it_changes_the_verdict() {
+        let mut trace = RuleTrace::default();
+        let verdict = Verdict::new(Decision::Idle, "balanced");
+        let verdict = trace.stage("noop", verdict, |v| v);
+        let verdict = trace.stage("hand_back", verdict, |_| Verdict::new(Decision::SelfConsumption, "idle"));
+
+        assert_eq!(verdict.decision, Decision::SelfConsumption);
+        assert_eq!(trace.fired("noop"), Some(false));
+        assert_eq!(trace.fired("hand_back"), Some(true));
+        assert_eq!(trace.fired("ramp"), None);
+    }
+}
@@ -0,0 +1,62 @@
+use chrono::{DateTime, NaiveDate, Utc};
+
+use crate::configuration::config::Config;
+use super::{prices_for_day, HourlyPrice, PriceError, PriceSource};
+
+// --------------------------------------------------------------------------------------------------------------
+// CSV file price source
+//
+// For fixed time-of-use contracts, or prices exported from elsewhere. One price per line:
+//
+//     # starts_at,price_eur_per_kwh
+//     2026-10-15T00:00:00+02:00,0.2512
+//     2026-10-15T01:00:00+02:00,0.2431
+//
+// Times are RFC 3339 with their offset. Blank lines, lines starting with '#' and a header line that doesn't
+// start with a digit are skipped. The file is re-read on every fetch, so it can be updated while running.
+// --------------------------------------------------------------------------------------------------------------
+
+/// Parse the CSV contents. Errors name the offending line.
+pub fn parse_csv(contents: &str) -> Result<Vec<HourlyPrice>, PriceError> {
+    let mut prices = Vec::new();
+    for (index, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') || !line.starts_with(|c: char| c.is_ascii_digit()) {
+            continue;
+        }
+        let bad = |msg: String| PriceError::Response(format!("line {}: {}", index + 1, msg));
+        let (starts_at, price) = line.split_once(',').ok_or_else(|| bad("expected starts_at,price".into()))?;
+        let starts_at = DateTime::parse_from_rfc3339(starts_at.trim()).map_err(|e| bad(e.to_string()))?;
+        let price: f64 = price.trim().parse().map_err(|e| bad(format!("bad price: {}", e)))?;
+        prices.push(HourlyPrice { starts_at: starts_at.with_timezone(&Utc), price_eur_per_kwh: price });
+    }
+    Ok(prices)
+}
+
+/// Prices from a local CSV file.
+#[derive(Debug, Clone)]
+pub struct CsvSource {
+    path: String,
+}
+
+impl CsvSource {
+    pub fn from_config(config: &Config) -> Result<Self, String> {
+        if config.price_csv_path.is_empty() {
+            return Err("price_source \"csv\" needs price_csv_path".to_string());
+        }
+        Ok(Self { path: config.price_csv_path.clone() })
+    }
+}
+
+impl PriceSource for CsvSource {
+    fn name(&self) -> &'static str {
+        "CSV"
+    }
+
+    async fn fetch(&self, day: NaiveDate) -> Result<Vec<HourlyPrice>, PriceError> {
+        let contents = tokio::fs::read_to_string(&self.path)
+            .await
+            .map_err(|e| PriceError::Request(format!("{}: {}", self.path, e)))?;
+        prices_for_day(parse_csv(&contents)?, day)
+    }
+}
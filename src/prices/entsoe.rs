@@ -0,0 +1,196 @@
+use chrono::{DateTime, Duration as ChronoDuration, NaiveDate, NaiveDateTime, TimeZone, Timelike, Utc};
+use reqwest::{Client, StatusCode, Url};
+use std::collections::BTreeMap;
+use tokio::time::Duration;
+
+use crate::configuration::config::Config;
+use crate::runtime::clock::LOCAL_TZ;
+use super::{prices_for_day, HourlyPrice, PriceError, PriceSource};
+
+// --------------------------------------------------------------------------------------------------------------
+// ENTSO-E Transparency Platform price source (day-ahead market)
+//
+// API:  GET <entsoe_api_url>?securityToken=<entsoe_token>&documentType=A44
+//           &in_Domain=<entsoe_area>&out_Domain=<entsoe_area>&periodStart=yyyyMMddHHmm&periodEnd=yyyyMMddHHmm
+// Resp: <Publication_MarketDocument> ... <TimeSeries><Period>
+//           <timeInterval><start>2026-10-14T22:00Z</start><end>2026-10-15T22:00Z</end></timeInterval>
+//           <resolution>PT15M</resolution>
+//           <Point><position>1</position><price.amount>85.30</price.amount></Point> ...
+//       or <Acknowledgement_MarketDocument> ... <Reason><code>999</code><text>No matching data found</text>
+//
+// Prices are wholesale EUR/MWh, without taxes or supplier fees; they are converted to EUR/kWh. Positions with
+// the same price as the one before may be left out (curve type A03) and are filled forward. Quarter-hour
+// periods are averaged per hour. The period is requested in UTC, covering the local day.
+// --------------------------------------------------------------------------------------------------------------
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// "No matching data found": the day isn't published (yet).
+const NO_DATA_REASON: &str = "999";
+
+/// Inner text of every `<tag>…</tag>` element in `xml`. Only for elements that don't nest in themselves.
+fn elements<'a>(xml: &'a str, tag: &str) -> Vec<&'a str> {
+    let open  = format!("<{}", tag);
+    let close = format!("</{}>", tag);
+    let mut found = Vec::new();
+    let mut rest  = xml;
+    while let Some(at) = rest.find(&open) {
+        let after = &rest[at + open.len()..];
+        // `<start>` must not match `<startDate>`.
+        if !after.starts_with('>') && !after.starts_with(' ') {
+            rest = after;
+            continue;
+        }
+        let Some(body_start) = after.find('>') else { break };
+        let body = &after[body_start + 1..];
+        let Some(end) = body.find(&close) else { break };
+        found.push(&body[..end]);
+        rest = &body[end + close.len()..];
+    }
+    found
+}
+
+fn first<'a>(xml: &'a str, tag: &str) -> Result<&'a str, PriceError> {
+    elements(xml, tag)
+        .into_iter()
+        .next()
+        .map(str::trim)
+        .ok_or_else(|| PriceError::Response(format!("missing <{}>", tag)))
+}
+
+fn parse_time(value: &str) -> Result<DateTime<Utc>, PriceError> {
+    NaiveDateTime::parse_from_str(value, "%Y-%m-%dT%H:%MZ")
+        .map(|t| t.and_utc())
+        .map_err(|e| PriceError::Response(format!("bad time '{}': {}", value, e)))
+}
+
+fn parse_resolution(value: &str) -> Result<i64, PriceError> {
+    value
+        .strip_prefix("PT")
+        .and_then(|v| v.strip_suffix('M'))
+        .and_then(|v| v.parse::<i64>().ok())
+        .filter(|minutes| *minutes > 0)
+        .ok_or_else(|| PriceError::Response(format!("unsupported resolution '{}'", value)))
+}
+
+/// Map an ENTSO-E A44 document to hourly prices. An acknowledgement without data is `NotPublished(day)`.
+pub fn parse_day_ahead(xml: &str, day: NaiveDate) -> Result<Vec<HourlyPrice>, PriceError> {
+    if xml.contains("<Acknowledgement_MarketDocument") {
+        let code = first(xml, "code").unwrap_or_default();
+        if code == NO_DATA_REASON {
+            return Err(PriceError::NotPublished(day));
+        }
+        let text = first(xml, "text").unwrap_or("no reason given");
+        return Err(PriceError::Response(format!("ENTSO-E reason {}: {}", code, text)));
+    }
+
+    // Slot prices (EUR/MWh) per hour start, averaged below.
+    let mut hours: BTreeMap<DateTime<Utc>, Vec<f64>> = BTreeMap::new();
+    for period in elements(xml, "Period") {
+        let start   = parse_time(first(period, "start")?)?;
+        let end     = parse_time(first(period, "end")?)?;
+        let minutes = parse_resolution(first(period, "resolution")?)?;
+        let slots   = (end - start).num_minutes() / minutes;
+
+        let mut points = BTreeMap::new();
+        for point in elements(period, "Point") {
+            let position: i64 = first(point, "position")?
+                .parse()
+                .map_err(|e| PriceError::Response(format!("bad position: {}", e)))?;
+            let price: f64 = first(point, "price.amount")?
+                .parse()
+                .map_err(|e| PriceError::Response(format!("bad price: {}", e)))?;
+            points.insert(position, price);
+        }
+
+        let mut current = None;
+        for position in 1..=slots {
+            current = points.get(&position).copied().or(current);
+            let Some(price) = current else { continue };
+            let slot_start = start + ChronoDuration::minutes((position - 1) * minutes);
+            let hour_start = slot_start - ChronoDuration::minutes(slot_start.minute() as i64);
+            hours.entry(hour_start).or_default().push(price);
+        }
+    }
+
+    let prices = hours
+        .into_iter()
+        .map(|(starts_at, slot_prices)| HourlyPrice {
+            starts_at,
+            price_eur_per_kwh: slot_prices.iter().sum::<f64>() / slot_prices.len() as f64 / 1000.0,
+        })
+        .collect();
+    prices_for_day(prices, day)
+}
+
+/// Day-ahead prices from the ENTSO-E Transparency Platform.
+#[derive(Debug, Clone)]
+pub struct EntsoeSource {
+    client: Client,
+    url:    String,
+    token:  String,
+    area:   String,
+}
+
+impl EntsoeSource {
+    pub fn from_config(config: &Config) -> Result<Self, String> {
+        if config.entsoe_token.is_empty() {
+            return Err("price_source \"entsoe\" needs entsoe_token".to_string());
+        }
+        let client = Client::builder()
+            .timeout(REQUEST_TIMEOUT)
+            .build()
+            .map_err(|e| format!("failed to build HTTP client: {}", e))?;
+        Ok(Self {
+            client,
+            url:   config.entsoe_api_url.clone(),
+            token: config.entsoe_token.clone(),
+            area:  config.entsoe_area.clone(),
+        })
+    }
+}
+
+/// UTC start of local date `day`.
+fn local_day_start(day: NaiveDate) -> DateTime<Utc> {
+    let midnight = day.and_hms_opt(0, 0, 0).unwrap_or_default();
+    LOCAL_TZ
+        .from_local_datetime(&midnight)
+        .earliest()
+        .map_or_else(|| midnight.and_utc(), |t| t.with_timezone(&Utc))
+}
+
+impl PriceSource for EntsoeSource {
+    fn name(&self) -> &'static str {
+        "ENTSO-E"
+    }
+
+    async fn fetch(&self, day: NaiveDate) -> Result<Vec<HourlyPrice>, PriceError> {
+        let start = local_day_start(day);
+        let end   = local_day_start(day.succ_opt().unwrap_or(day));
+        let url   = Url::parse_with_params(&self.url, &[
+            ("securityToken", self.token.as_str()),
+            ("documentType",  "A44"),
+            ("in_Domain",     self.area.as_str()),
+            ("out_Domain",    self.area.as_str()),
+            ("periodStart",   &start.format("%Y%m%d%H%M").to_string()),
+            ("periodEnd",     &end.format("%Y%m%d%H%M").to_string()),
+        ])
+        .map_err(|e| PriceError::Request(format!("bad entsoe_api_url: {}", e)))?;
+        let resp = self
+            .client
+            .get(url)
+            .send()
+            .await?;
+
+        let status = resp.status();
+        if status == StatusCode::UNAUTHORIZED || status == StatusCode::FORBIDDEN {
+            return Err(PriceError::Unauthorized(format!("HTTP {} - check entsoe_token", status)));
+        }
+        // "No data" comes back as an acknowledgement document with HTTP 200 or 400; let the parser decide.
+        let body = resp.text().await?;
+        if !status.is_success() && !body.contains("<Acknowledgement_MarketDocument") {
+            return Err(PriceError::Request(format!("HTTP {}", status)));
+        }
+        parse_day_ahead(&body, day)
+    }
+}
@@ -0,0 +1,184 @@
+#[cfg(feature = "prices")]
+pub mod csv;
+#[cfg(feature = "prices")]
+pub mod entsoe;
+#[cfg(feature = "prices")]
+pub mod refresh;
+#[cfg(feature = "prices")]
+pub mod tibber;
+
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::future::Future;
+
+use crate::runtime::clock::LOCAL_TZ;
+
+// --------------------------------------------------------------------------------------------------------------
+// Dynamic tariff prices
+//
+// Every provider implements `PriceSource`: the prices of one local day, or a `PriceError` saying why not. The
+// provider is picked with `price_source` in config.json; a background task (`refresh.rs`) asks it for today
+// and tomorrow and publishes the merged `PriceCurve` on a watch channel, so the control loop only ever reads
+// the current value, never waits on a provider and never knows which one is in use. Adding a provider means
+// one new module with a `PriceSource` impl and one arm in `main`. The providers and the refresh task are only
+// built with the "prices" feature; the curve types are core, as the optimiser plans over them.
+// --------------------------------------------------------------------------------------------------------------
+
+/// Length of one price slot.
+pub const SLOT: Duration = Duration::hours(1);
+
+/// Price for one hour.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct HourlyPrice {
+    pub starts_at:         DateTime<Utc>,
+    /// All-in import price (energy, taxes and fees as the provider reports them), EUR/kWh.
+    pub price_eur_per_kwh: f64,
+}
+
+/// The known prices, ordered by start time.
+#[derive(Debug, Clone, Default)]
+pub struct PriceCurve {
+    pub prices:     Vec<HourlyPrice>,
+    pub fetched_at: Option<DateTime<Utc>>,
+}
+
+impl PriceCurve {
+    pub fn new(mut prices: Vec<HourlyPrice>, fetched_at: DateTime<Utc>) -> Self {
+        prices.sort_by_key(|p| p.starts_at);
+        Self { prices, fetched_at: Some(fetched_at) }
+    }
+
+    /// The price of the slot containing `at`.
+    pub fn at(&self, at: DateTime<Utc>) -> Option<&HourlyPrice> {
+        self.prices.iter().find(|p| p.starts_at <= at && at < p.starts_at + SLOT)
+    }
+
+    /// Whether any price falls on local date `date`.
+    pub fn covers_date(&self, date: NaiveDate) -> bool {
+        self.prices.iter().any(|p| p.starts_at.with_timezone(&LOCAL_TZ).date_naive() == date)
+    }
+
+    /// Replace the prices of local date `day` with `prices` and drop the days before `keep_from`.
+    pub fn merge(&self, day: NaiveDate, prices: Vec<HourlyPrice>, keep_from: NaiveDate) -> Self {
+        let local_date = |p: &HourlyPrice| p.starts_at.with_timezone(&LOCAL_TZ).date_naive();
+        let kept = self.prices.iter().copied().filter(|p| local_date(p) != day && local_date(p) >= keep_from);
+        Self::new(kept.chain(prices).collect(), Utc::now())
+    }
+}
+
+// --------------------------------------------------------------------------------------------------------------
+
+/// Why a source could not deliver the prices of a day.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PriceError {
+    /// Credentials missing or rejected by the provider.
+    Unauthorized(String),
+    /// Connection or HTTP-level failure (refused, timeout, non-2xx status, ...).
+    Request(String),
+    /// The response arrived but could not be interpreted.
+    Response(String),
+    /// The provider has no prices for this day (yet): tomorrow's prices before they are published.
+    NotPublished(NaiveDate),
+}
+
+impl fmt::Display for PriceError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PriceError::Unauthorized(msg) => write!(f, "credentials rejected: {}", msg),
+            PriceError::Request(msg)      => write!(f, "request failed: {}", msg),
+            PriceError::Response(msg)     => write!(f, "unexpected response: {}", msg),
+            PriceError::NotPublished(day) => write!(f, "no prices published for {} yet", day),
+        }
+    }
+}
+
+impl std::error::Error for PriceError {}
+
+impl From<reqwest::Error> for PriceError {
+    /// Without the URL: it may carry an API token as a query parameter.
+    fn from(e: reqwest::Error) -> Self {
+        PriceError::Request(e.without_url().to_string())
+    }
+}
+
+/// A provider of hourly import prices.
+pub trait PriceSource: Send + Sync {
+    /// Short name for log lines.
+    fn name(&self) -> &'static str;
+
+    /// The prices of local date `day`, ordered or not. An empty day is `NotPublished`, never `Ok(vec![])`.
+    fn fetch(&self, day: NaiveDate) -> impl Future<Output = Result<Vec<HourlyPrice>, PriceError>> + Send;
+}
+
+/// Keep only the prices that start on local date `day`; `NotPublished` when none do.
+pub fn prices_for_day(prices: Vec<HourlyPrice>, day: NaiveDate) -> Result<Vec<HourlyPrice>, PriceError> {
+    let day_prices: Vec<HourlyPrice> = prices
+        .into_iter()
+        .filter(|p| p.starts_at.with_timezone(&LOCAL_TZ).date_naive() == day)
+        .collect();
+    if day_prices.is_empty() {
+        return Err(PriceError::NotPublished(day));
+    }
+    Ok(day_prices)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Timelike};
+
+    use super::*;
+    use crate::configuration::config::Config;
+    use crate::optimiser::plan::{self, PlanAction};
+
+    /// A source with fixed prices for one day: cheap at night, dear in the evening.
+    struct StubSource {
+        day: NaiveDate,
+    }
+
+    impl PriceSource for StubSource {
+        fn name(&self) -> &'static str {
+            "stub"
+        }
+
+        async fn fetch(&self, day: NaiveDate) -> Result<Vec<HourlyPrice>, PriceError> {
+            if day != self.day {
+                return Err(PriceError::NotPublished(day));
+            }
+            let midnight = LOCAL_TZ.from_local_datetime(&day.and_hms_opt(0, 0, 0).unwrap()).unwrap();
+            Ok((0..24)
+                .map(|h| {
+                    let price = match h {
+                        2..=4   => 0.10,
+                        18..=20 => 0.40,
+                        _       => 0.22,
+                    };
+                    let starts_at = (midnight + SLOT * h).with_timezone(&Utc);
+                    HourlyPrice { starts_at, price_eur_per_kwh: price }
+                })
+                .collect())
+        }
+    }
+
+    #[tokio::test]
+    async fn a_stub_source_feeds_its_curve_into_the_plan() {
+        let day      = NaiveDate::from_ymd_opt(2026, 10, 15).unwrap();
+        let tomorrow = day.succ_opt().unwrap();
+        let source   = StubSource { day };
+        assert_eq!(source.fetch(tomorrow).await, Err(PriceError::NotPublished(tomorrow)));
+
+        let curve  = PriceCurve::default().merge(day, source.fetch(day).await.unwrap(), day);
+        let now    = curve.prices[0].starts_at;
+        let config = Config { grid_charging_enabled: true, ..Config::default() };
+        let plan   = plan::refresh(None, &curve, 30.0, now, &config).expect("a plan");
+
+        let action_at = |hour: u32| {
+            let slot = plan.slots.iter().find(|s| s.starts_at.with_timezone(&LOCAL_TZ).hour() == hour).unwrap();
+            slot.action
+        };
+        assert_eq!(plan.slots.len(), 24);
+        assert_eq!(action_at(3), PlanAction::Charge);
+        assert_eq!(action_at(19), PlanAction::Discharge);
+        assert_eq!(action_at(12), PlanAction::Hold);
+    }
+}
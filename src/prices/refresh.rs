@@ -0,0 +1,75 @@
+use chrono::{NaiveDate, Timelike, Utc};
+use log::{debug, error, info, warn};
+use std::sync::Arc;
+use tokio::sync::watch;
+use tokio::time::{sleep, Duration, Instant};
+
+use crate::runtime::clock::local_now;
+use super::{PriceCurve, PriceError, PriceSource};
+
+// --------------------------------------------------------------------------------------------------------------
+// Price refresh task
+//
+// Keeps the published curve covering today and, once they are out, tomorrow:
+//   - today is fetched when the curve doesn't cover the current hour or is older than `MAX_AGE` (providers
+//     occasionally correct prices);
+//   - tomorrow is fetched from `TOMORROW_PUBLISHED_HOUR` local time, every `TOMORROW_RETRY` until it arrives.
+// Days before today are dropped at every merge. A failed fetch keeps the previous curve.
+// --------------------------------------------------------------------------------------------------------------
+
+const CHECK_EVERY:    Duration = Duration::from_secs(5 * 60);
+const TOMORROW_RETRY: Duration = Duration::from_secs(15 * 60);
+const MAX_AGE:        Duration = Duration::from_secs(6 * 3600);
+
+/// Local hour from which tomorrow's day-ahead prices are expected.
+const TOMORROW_PUBLISHED_HOUR: u32 = 13;
+
+/// Keep the price curve fresh from `source` and publish it on `tx`.
+pub async fn price_worker<S: PriceSource>(source: S, tx: watch::Sender<Arc<PriceCurve>>) {
+    info!("[Prices] Price source: {}", source.name());
+    let mut tomorrow_attempt: Option<Instant> = None;
+
+    loop {
+        let now   = local_now();
+        let today = now.date_naive();
+        let curve = tx.borrow().clone();
+        let age   = curve.fetched_at.and_then(|at| (Utc::now() - at).to_std().ok());
+
+        if curve.at(Utc::now()).is_none() || age.is_none_or(|age| age >= MAX_AGE) {
+            refresh_day(&source, &tx, today, today).await;
+        }
+
+        if let Some(tomorrow) = today.succ_opt() {
+            let due = now.hour() >= TOMORROW_PUBLISHED_HOUR
+                && !tx.borrow().covers_date(tomorrow)
+                && tomorrow_attempt.is_none_or(|at| at.elapsed() >= TOMORROW_RETRY);
+            if due {
+                tomorrow_attempt = Some(Instant::now());
+                refresh_day(&source, &tx, tomorrow, today).await;
+            }
+        }
+        sleep(CHECK_EVERY).await;
+    }
+}
+
+/// Fetch `day` and merge it into the published curve. Failures are logged; the curve is left as it was.
+async fn refresh_day<S: PriceSource>(
+    source: &S,
+    tx: &watch::Sender<Arc<PriceCurve>>,
+    day: NaiveDate,
+    today: NaiveDate,
+) {
+    match source.fetch(day).await {
+        Ok(prices) => {
+            let count  = prices.len();
+            let merged = tx.borrow().merge(day, prices, today);
+            info!("[Prices] {} prices for {} from {}", count, day, source.name());
+            tx.send_replace(Arc::new(merged));
+        }
+        Err(PriceError::NotPublished(day)) if day > today => {
+            debug!("[Prices] {}: prices for {} not published yet", source.name(), day)
+        }
+        Err(e @ PriceError::Unauthorized(_)) => error!("[Prices] {}: {}", source.name(), e),
+        Err(e) => warn!("[Prices] {}: {} - keeping the previous prices", source.name(), e),
+    }
+}
@@ -0,0 +1,197 @@
+use chrono::{DateTime, FixedOffset, NaiveDate, Utc};
+use reqwest::{Client, StatusCode};
+use serde::Deserialize;
+use tokio::time::Duration;
+
+use crate::configuration::config::Config;
+use super::{prices_for_day, HourlyPrice, PriceError, PriceSource};
+
+// --------------------------------------------------------------------------------------------------------------
+// Tibber price source
+//
+// API:  POST <tibber_api_url>   (GraphQL)
+//       Header: Authorization: Bearer <tibber_token>
+//       Body:   {"query": "{ viewer { homes { currentSubscription { priceInfo {
+//                            today { total startsAt } tomorrow { total startsAt } } } } } }"}
+// Resp: {"data": {"viewer": {"homes": [{"currentSubscription": {"priceInfo": {
+//           "today":    [{"total": 0.2512, "startsAt": "2026-10-15T00:00:00.000+02:00"}, ...],
+//           "tomorrow": [...]}}}]}}}
+//
+// `total` includes energy tax and VAT. The first home with a subscription is used. Tibber only serves today
+// and tomorrow, so any other day is `NotPublished`; tomorrow is an empty list until it is published around
+// 13:00 local time. A rejected token comes back either as HTTP 401 or as a GraphQL `errors` entry.
+// --------------------------------------------------------------------------------------------------------------
+
+const QUERY: &str = "{ viewer { homes { currentSubscription { priceInfo { \
+                     today { total startsAt } tomorrow { total startsAt } } } } } }";
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(15);
+
+#[derive(Deserialize, Debug)]
+struct GraphQlResponse {
+    data:   Option<Data>,
+    #[serde(default)]
+    errors: Vec<GraphQlError>,
+}
+
+#[derive(Deserialize, Debug)]
+struct GraphQlError {
+    message:    String,
+    #[serde(default)]
+    extensions: Option<GraphQlErrorExtensions>,
+}
+
+#[derive(Deserialize, Debug)]
+struct GraphQlErrorExtensions {
+    code: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+struct Data {
+    viewer: Viewer,
+}
+
+#[derive(Deserialize, Debug)]
+struct Viewer {
+    homes: Vec<Home>,
+}
+
+#[derive(Deserialize, Debug)]
+struct Home {
+    #[serde(rename = "currentSubscription")]
+    current_subscription: Option<Subscription>,
+}
+
+#[derive(Deserialize, Debug)]
+struct Subscription {
+    #[serde(rename = "priceInfo")]
+    price_info: PriceInfo,
+}
+
+#[derive(Deserialize, Debug)]
+struct PriceInfo {
+    #[serde(default)]
+    today:    Vec<TibberPrice>,
+    #[serde(default)]
+    tomorrow: Vec<TibberPrice>,
+}
+
+#[derive(Deserialize, Debug)]
+struct TibberPrice {
+    total:     f64,
+    #[serde(rename = "startsAt")]
+    starts_at: DateTime<FixedOffset>,
+}
+
+// --------------------------------------------------------------------------------------------------------------
+
+/// Map a Tibber GraphQL response body to hourly prices (today, then tomorrow when published).
+pub fn parse_price_info(body: &str) -> Result<Vec<HourlyPrice>, PriceError> {
+    let response: GraphQlResponse =
+        serde_json::from_str(body).map_err(|e| PriceError::Response(e.to_string()))?;
+
+    if let Some(err) = response.errors.first() {
+        let code = err.extensions.as_ref().and_then(|x| x.code.as_deref());
+        if code == Some("UNAUTHENTICATED") {
+            return Err(PriceError::Unauthorized(err.message.clone()));
+        }
+        return Err(PriceError::Response(format!("GraphQL error: {}", err.message)));
+    }
+    let info = response
+        .data
+        .and_then(|d| d.viewer.homes.into_iter().find_map(|h| h.current_subscription))
+        .map(|s| s.price_info)
+        .ok_or_else(|| PriceError::Response("no Tibber home with an active subscription".to_string()))?;
+
+    Ok(info
+        .today
+        .into_iter()
+        .chain(info.tomorrow)
+        .map(|p| HourlyPrice { starts_at: p.starts_at.with_timezone(&Utc), price_eur_per_kwh: p.total })
+        .collect())
+}
+
+/// Prices from the Tibber API.
+#[derive(Debug, Clone)]
+pub struct TibberSource {
+    client: Client,
+    url:    String,
+    token:  String,
+}
+
+impl TibberSource {
+    pub fn from_config(config: &Config) -> Result<Self, String> {
+        if config.tibber_token.is_empty() {
+            return Err("price_source \"tibber\" needs tibber_token".to_string());
+        }
+        let client = Client::builder()
+            .timeout(REQUEST_TIMEOUT)
+            .build()
+            .map_err(|e| format!("failed to build HTTP client: {}", e))?;
+        Ok(Self { client, url: config.tibber_api_url.clone(), token: config.tibber_token.clone() })
+    }
+}
+
+impl PriceSource for TibberSource {
+    fn name(&self) -> &'static str {
+        "Tibber"
+    }
+
+    async fn fetch(&self, day: NaiveDate) -> Result<Vec<HourlyPrice>, PriceError> {
+        let resp = self
+            .client
+            .post(&self.url)
+            .bearer_auth(&self.token)
+            .json(&serde_json::json!({ "query": QUERY }))
+            .send()
+            .await?;
+
+        let status = resp.status();
+        if status == StatusCode::UNAUTHORIZED || status == StatusCode::FORBIDDEN {
+            return Err(PriceError::Unauthorized(format!("HTTP {} - check tibber_token", status)));
+        }
+        let body = resp.text().await?;
+        if !status.is_success() {
+            let preview: String = body.chars().take(200).collect();
+            return Err(PriceError::Request(format!("HTTP {}: {}", status, preview)));
+        }
+        prices_for_day(parse_price_info(&body)?, day)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    #[test]
+    fn a_tibber_response_maps_to_hourly_prices() {
+        let body = r#"{"data": {"viewer": {"homes": [
+            {"currentSubscription": null},
+            {"currentSubscription": {"priceInfo": {
+                "today":    [{"total": 0.2512, "startsAt": "2026-10-15T00:00:00.000+02:00"},
+                             {"total": 0.2398, "startsAt": "2026-10-15T01:00:00.000+02:00"}],
+                "tomorrow": [{"total": 0.3120, "startsAt": "2026-10-16T00:00:00.000+02:00"}]}}}]}}}"#;
+
+        let prices = parse_price_info(body).unwrap();
+        let hour   = |d, h| Utc.with_ymd_and_hms(2026, 10, d, h, 0, 0).unwrap();
+        assert_eq!(prices, [
+            HourlyPrice { starts_at: hour(14, 22), price_eur_per_kwh: 0.2512 },
+            HourlyPrice { starts_at: hour(14, 23), price_eur_per_kwh: 0.2398 },
+            HourlyPrice { starts_at: hour(15, 22), price_eur_per_kwh: 0.3120 },
+        ]);
+
+        let tomorrow = NaiveDate::from_ymd_opt(2026, 10, 16).unwrap();
+        assert_eq!(prices_for_day(prices, tomorrow).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn a_graphql_error_is_not_a_price_list() {
+        let body = r#"{"errors": [{"message": "invalid token", "extensions": {"code": "UNAUTHENTICATED"}}]}"#;
+        assert_eq!(parse_price_info(body), Err(PriceError::Unauthorized("invalid token".to_string())));
+
+        let body = r#"{"data": {"viewer": {"homes": []}}}"#;
+        assert!(matches!(parse_price_info(body), Err(PriceError::Response(_))));
+    }
+}
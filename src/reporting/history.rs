@@ -0,0 +1,252 @@
+use chrono::{Datelike, NaiveDate};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+
+use super::DailyReport;
+
+// --------------------------------------------------------------------------------------------------------------
+// Day history
+//
+// Each day the `DailyReporter` closes leaves a small summary behind: its peak import, the energy charged and
+// discharged, and the import and consumption that self-sufficiency is computed from. The last
+// `MAX_HISTORY_DAYS` are kept in the state file, and `GET /history?metric=...&period=...` aggregates them, with
+// the day in progress as the last entry:
+//
+//   peak              highest quarter-hour average import (W) of the period
+//   charged           energy charged into the battery (kWh), summed over the period
+//   discharged        energy discharged from the battery (kWh), summed over the period
+//   self_sufficiency  (Σ consumption − Σ import) / Σ consumption (%) over the days where both are known
+//
+// The period is a local day, an ISO week (Monday to Sunday) or a calendar month. A period whose days never
+// measured the metric has a `null` value.
+// --------------------------------------------------------------------------------------------------------------
+
+/// Closed days kept for `/history`: a little over a year.
+const MAX_HISTORY_DAYS: usize = 400;
+
+/// What one day contributes to the history.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct DaySummary {
+    pub date:            NaiveDate,
+    pub peak_import_w:   Option<f64>,
+    pub charged_kwh:     Option<f64>,
+    pub discharged_kwh:  Option<f64>,
+    pub imported_kwh:    Option<f64>,
+    pub consumption_kwh: Option<f64>,
+}
+
+impl From<&DailyReport> for DaySummary {
+    fn from(report: &DailyReport) -> Self {
+        Self {
+            date:            report.date,
+            peak_import_w:   report.peak_import_w,
+            charged_kwh:     report.charged_kwh,
+            discharged_kwh:  report.discharged_kwh,
+            imported_kwh:    report.imported_kwh,
+            consumption_kwh: report.consumption_kwh,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Metric {
+    Peak,
+    Charged,
+    Discharged,
+    SelfSufficiency,
+}
+
+impl Metric {
+    pub fn unit(self) -> &'static str {
+        match self {
+            Metric::Peak                         => "W",
+            Metric::Charged | Metric::Discharged => "kWh",
+            Metric::SelfSufficiency              => "%",
+        }
+    }
+
+    fn aggregate(self, days: &[&DaySummary]) -> Option<f64> {
+        let values = |field: fn(&DaySummary) -> Option<f64>| days.iter().filter_map(move |day| field(day));
+        match self {
+            Metric::Peak       => values(|day| day.peak_import_w).reduce(f64::max),
+            Metric::Charged    => values(|day| day.charged_kwh).reduce(|a, b| a + b),
+            Metric::Discharged => values(|day| day.discharged_kwh).reduce(|a, b| a + b),
+            Metric::SelfSufficiency => {
+                let (consumption, import) = days
+                    .iter()
+                    .filter_map(|day| day.consumption_kwh.zip(day.imported_kwh))
+                    .fold((0.0, 0.0), |(c, i), (consumption, import)| (c + consumption, i + import));
+                (consumption > 0.0).then(|| ((consumption - import) / consumption * 100.0).clamp(0.0, 100.0))
+            }
+        }
+    }
+}
+
+impl FromStr for Metric {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "peak"             => Ok(Metric::Peak),
+            "charged"          => Ok(Metric::Charged),
+            "discharged"       => Ok(Metric::Discharged),
+            "self_sufficiency" => Ok(Metric::SelfSufficiency),
+            _ => Err(format!("unknown metric '{}' (peak, charged, discharged, self_sufficiency)", s)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Period {
+    Day,
+    Week,
+    Month,
+}
+
+impl Period {
+    /// Label of the period `date` falls in: "2026-10-15", "2026-W42" or "2026-10".
+    fn label(self, date: NaiveDate) -> String {
+        match self {
+            Period::Day   => date.to_string(),
+            Period::Week  => format!("{}-W{:02}", date.iso_week().year(), date.iso_week().week()),
+            Period::Month => date.format("%Y-%m").to_string(),
+        }
+    }
+}
+
+impl FromStr for Period {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "day"   => Ok(Period::Day),
+            "week"  => Ok(Period::Week),
+            "month" => Ok(Period::Month),
+            _ => Err(format!("unknown period '{}' (day, week, month)", s)),
+        }
+    }
+}
+
+/// One period's aggregate: the days of it that are in the history and the value over them.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct HistoryBucket {
+    pub period: String,
+    pub from:   NaiveDate,
+    pub to:     NaiveDate,
+    pub days:   usize,
+    pub value:  Option<f64>,
+}
+
+/// Day summaries, oldest first.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct DayHistory {
+    days: VecDeque<DaySummary>,
+}
+
+/// The history as shared between the control loop, which publishes it every cycle, and the API task.
+pub type SharedDayHistory = Arc<Mutex<DayHistory>>;
+
+impl DayHistory {
+    pub fn shared() -> SharedDayHistory {
+        Arc::new(Mutex::new(Self::default()))
+    }
+
+    pub fn push(&mut self, day: DaySummary) {
+        if self.days.len() >= MAX_HISTORY_DAYS {
+            self.days.pop_front();
+        }
+        self.days.push_back(day);
+    }
+
+    /// `metric` per `period`, oldest period first.
+    pub fn query(&self, metric: Metric, period: Period) -> Vec<HistoryBucket> {
+        let mut buckets = Vec::new();
+        let mut group: Vec<&DaySummary> = Vec::new();
+        let mut close = |group: &mut Vec<&DaySummary>| {
+            if let (Some(first), Some(last)) = (group.first(), group.last()) {
+                buckets.push(HistoryBucket {
+                    period: period.label(first.date),
+                    from:   first.date,
+                    to:     last.date,
+                    days:   group.len(),
+                    value:  metric.aggregate(group),
+                });
+            }
+            group.clear();
+        };
+        for day in &self.days {
+            if group.first().is_some_and(|first| period.label(first.date) != period.label(day.date)) {
+                close(&mut group);
+            }
+            group.push(day);
+        }
+        close(&mut group);
+        buckets
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn day(date: &str, peak_w: f64, charged: f64, imported: f64, consumption: f64) -> DaySummary {
+        DaySummary {
+            date:            date.parse().unwrap(),
+            peak_import_w:   Some(peak_w),
+            charged_kwh:     Some(charged),
+            discharged_kwh:  None,
+            imported_kwh:    Some(imported),
+            consumption_kwh: Some(consumption),
+        }
+    }
+
+    /// Sunday 2026-09-27 to Tuesday 2026-09-29, then Thursday 2026-10-01.
+    fn history() -> DayHistory {
+        let mut history = DayHistory::default();
+        history.push(day("2026-09-27", 2800.0, 4.0, 6.0, 12.0));
+        history.push(day("2026-09-28", 4100.0, 3.0, 2.0, 8.0));
+        history.push(day("2026-09-29", 3200.0, 5.0, 4.0, 10.0));
+        history.push(day("2026-10-01", 3600.0, 2.5, 8.0, 10.0));
+        history
+    }
+
+    #[test]
+    fn peak_is_the_highest_day_of_each_month() {
+        let buckets = history().query(Metric::Peak, Period::Month);
+        let values: Vec<_> = buckets.iter().map(|b| (b.period.as_str(), b.days, b.value)).collect();
+        assert_eq!(values, vec![("2026-09", 3, Some(4100.0)), ("2026-10", 1, Some(3600.0))]);
+    }
+
+    #[test]
+    fn charged_energy_is_summed_per_iso_week() {
+        let buckets = history().query(Metric::Charged, Period::Week);
+        let values: Vec<_> = buckets.iter().map(|b| (b.period.as_str(), b.value)).collect();
+        assert_eq!(values, vec![("2026-W39", Some(4.0)), ("2026-W40", Some(10.5))]);
+        assert_eq!(buckets[1].from, "2026-09-28".parse::<NaiveDate>().unwrap());
+        assert_eq!(buckets[1].to, "2026-10-01".parse::<NaiveDate>().unwrap());
+    }
+
+    #[test]
+    fn self_sufficiency_weighs_the_days_by_consumption() {
+        // September: consumption 30 kWh, import 12 kWh → 60 %.
+        let buckets = history().query(Metric::SelfSufficiency, Period::Month);
+        assert_eq!(buckets[0].value, Some(60.0));
+        assert_eq!(buckets[1].value, Some(20.0));
+    }
+
+    #[test]
+    fn an_unmeasured_metric_is_null() {
+        let buckets = history().query(Metric::Discharged, Period::Day);
+        assert_eq!(buckets.len(), 4);
+        assert!(buckets.iter().all(|b| b.value.is_none()));
+    }
+
+    #[test]
+    fn unknown_names_are_rejected() {
+        assert_eq!("self_sufficiency".parse(), Ok(Metric::SelfSufficiency));
+        assert!("energy".parse::<Metric>().is_err());
+        assert!("year".parse::<Period>().is_err());
+    }
+}
@@ -0,0 +1,466 @@
+pub mod history;
+pub mod render;
+
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::analytics::energy::{energy_delta_wh, wh_to_kwh};
+use crate::analytics::integrator::PowerIntegrators;
+use crate::handlers::indevolt::reader::{
+    ID_BATTERY_SOC, ID_CUMULATIVE_PRODUCTION, ID_TOTAL_CHARGING, ID_TOTAL_DISCHARGING,
+};
+use crate::handlers::p1::reader::P1Reading;
+use crate::models::indevolt_models::BatterySnapshot;
+use crate::optimiser::decision::Decision;
+use crate::runtime::clock::LOCAL_TZ;
+
+use history::{DayHistory, DaySummary};
+
+// --------------------------------------------------------------------------------------------------------------
+// Daily report
+//
+// The loop feeds every cycle to a `DailyReporter`, which adds up the day (local time) from the cumulative
+// counters: grid import/export from P1, battery charge/discharge and solar production from the Indevolt. Each
+// total is the sum of per-cycle deltas (`energy_delta_wh`), so a gap in the readings - a P1 outage, a restart
+// with the state file - simply lands in the next delta. The first cycle after local midnight closes the day
+// into a `DailyReport`, which `render` writes to `report_dir`.
+//
+//   consumption       = import − export + production + discharged − charged
+//   self-sufficiency  = (consumption − import) / consumption
+//   cost              = Σ import delta × import price of that hour
+//   estimated saving  = Σ discharged delta × price − Σ grid-charged delta × price
+//
+// Energy charged while the previous decision was `ChargingFromGrid` counts as grid-charged. Days with partial
+// data still get a report: totals that could not be measured at all are "n/a", and a day whose readings
+// cover less than `FULL_DAY_HOURS` is marked partial.
+//
+// Grid and battery power are also integrated over the day (`PowerIntegrators`). A grid or battery total whose
+// counter never delivered two readings that day is taken from that estimate instead, and listed in
+// `estimated`. Cost and saving stay counter-based.
+//
+// Every closed day also leaves a summary in the day history behind `GET /history` (see `history.rs`).
+// --------------------------------------------------------------------------------------------------------------
+
+/// Coverage (hours between the first and last cycle) below which a day is marked partial.
+const FULL_DAY_HOURS: f64 = 23.0;
+
+/// Alarms kept per day; later ones are only counted.
+const MAX_ALARMS: usize = 50;
+
+/// Something worth a look in the report (grid outage, dispatch error). Repeats of the same text in a row are
+/// folded into one entry.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Alarm {
+    pub at:      DateTime<Utc>,
+    pub text:    String,
+    pub repeats: u32,
+}
+
+/// Last value of each cumulative counter, to difference the next reading against.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default)]
+struct Counters {
+    import_kwh:     Option<f64>,
+    export_kwh:     Option<f64>,
+    charged_kwh:    Option<f64>,
+    discharged_kwh: Option<f64>,
+    produced_kwh:   Option<f64>,
+}
+
+/// Running totals of the current day. A total stays `None` until its counter delivered two readings.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct DayAccumulator {
+    pub date:               NaiveDate,
+    pub first_sample:       DateTime<Utc>,
+    pub last_sample:        DateTime<Utc>,
+    pub cycles:             u64,
+    pub p1_cycles:          u64,
+    pub import_wh:          Option<i64>,
+    pub export_wh:          Option<i64>,
+    pub charged_wh:         Option<i64>,
+    pub discharged_wh:      Option<i64>,
+    pub produced_wh:        Option<i64>,
+    pub grid_charged_wh:    i64,
+    /// Highest quarter-hour average import (W) and when it was seen.
+    pub peak_import_w:      Option<f64>,
+    pub peak_import_at:     Option<DateTime<Utc>>,
+    pub soc_min_percent:    Option<f64>,
+    pub soc_max_percent:    Option<f64>,
+    pub cost_eur:           Option<f64>,
+    /// Import during hours without a known price, left out of `cost_eur`.
+    pub unpriced_import_wh: i64,
+    pub saving_eur:         Option<f64>,
+    pub alarms:             Vec<Alarm>,
+    pub alarms_dropped:     u32,
+    /// Grid and battery energy integrated from power, for totals without a counter.
+    #[serde(default)]
+    pub integrated:         PowerIntegrators,
+}
+
+/// Add `delta` to an optional total, starting it at zero.
+fn add<T: std::ops::AddAssign + Default>(total: &mut Option<T>, delta: T) {
+    *total.get_or_insert_with(T::default) += delta;
+}
+
+/// Energy (Wh) since the previous reading of a counter, `None` on its first reading.
+fn delta(last: &mut Option<f64>, current: f64) -> Option<i64> {
+    let delta = last.map(|prev| energy_delta_wh(prev, current));
+    *last = Some(current);
+    delta
+}
+
+impl DayAccumulator {
+    fn new(date: NaiveDate, now: DateTime<Utc>, integrated: PowerIntegrators) -> Self {
+        Self {
+            date,
+            first_sample:       now,
+            last_sample:        now,
+            cycles:             0,
+            p1_cycles:          0,
+            import_wh:          None,
+            export_wh:          None,
+            charged_wh:         None,
+            discharged_wh:      None,
+            produced_wh:        None,
+            grid_charged_wh:    0,
+            peak_import_w:      None,
+            peak_import_at:     None,
+            soc_min_percent:    None,
+            soc_max_percent:    None,
+            cost_eur:           None,
+            unpriced_import_wh: 0,
+            saving_eur:         None,
+            alarms:             Vec::new(),
+            alarms_dropped:     0,
+            integrated,
+        }
+    }
+
+    /// Close the day.
+    pub fn finish(&self) -> DailyReport {
+        let kwh = |wh: Option<i64>| wh.map(wh_to_kwh);
+        let mut estimated = Vec::new();
+        let mut or_integrated = |counter: Option<i64>, name: &'static str, covered: f64, wh: f64| {
+            if counter.is_some() || covered <= 0.0 {
+                return counter;
+            }
+            estimated.push(name);
+            Some(wh.round() as i64)
+        };
+        let (grid, battery) = (&self.integrated.grid, &self.integrated.battery);
+        let import_wh     =
+            or_integrated(self.import_wh, "imported", grid.covered_seconds(), grid.positive_wh());
+        let export_wh     =
+            or_integrated(self.export_wh, "exported", grid.covered_seconds(), grid.negative_wh());
+        let charged_wh    =
+            or_integrated(self.charged_wh, "charged", battery.covered_seconds(), battery.positive_wh());
+        let discharged_wh =
+            or_integrated(self.discharged_wh, "discharged", battery.covered_seconds(), battery.negative_wh());
+
+        let consumption_wh = match (import_wh, export_wh) {
+            (Some(import), Some(export)) => Some(
+                import - export + self.produced_wh.unwrap_or(0) + discharged_wh.unwrap_or(0)
+                    - charged_wh.unwrap_or(0),
+            ),
+            _ => None,
+        };
+        let self_sufficiency_percent = match (consumption_wh, import_wh) {
+            (Some(consumption), Some(import)) if consumption > 0 => {
+                Some(((consumption - import) as f64 / consumption as f64 * 100.0).clamp(0.0, 100.0))
+            }
+            _ => None,
+        };
+        let coverage_hours = (self.last_sample - self.first_sample).num_minutes() as f64 / 60.0;
+
+        DailyReport {
+            date:                     self.date,
+            first_sample:             self.first_sample,
+            last_sample:              self.last_sample,
+            coverage_hours,
+            partial:                  coverage_hours < FULL_DAY_HOURS,
+            cycles:                   self.cycles,
+            p1_cycles:                self.p1_cycles,
+            imported_kwh:             kwh(import_wh),
+            exported_kwh:             kwh(export_wh),
+            charged_kwh:              kwh(charged_wh),
+            discharged_kwh:           kwh(discharged_wh),
+            grid_charged_kwh:         wh_to_kwh(self.grid_charged_wh),
+            produced_kwh:             kwh(self.produced_wh),
+            consumption_kwh:          kwh(consumption_wh),
+            self_sufficiency_percent,
+            peak_import_w:            self.peak_import_w,
+            peak_import_at:           self.peak_import_at,
+            soc_min_percent:          self.soc_min_percent,
+            soc_max_percent:          self.soc_max_percent,
+            cost_eur:                 self.cost_eur,
+            unpriced_import_kwh:      wh_to_kwh(self.unpriced_import_wh),
+            saving_eur:               self.saving_eur,
+            alarms:                   self.alarms.clone(),
+            alarms_dropped:           self.alarms_dropped,
+            estimated,
+        }
+    }
+}
+
+/// Summary of one local day.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct DailyReport {
+    pub date:                     NaiveDate,
+    pub first_sample:             DateTime<Utc>,
+    pub last_sample:              DateTime<Utc>,
+    pub coverage_hours:           f64,
+    pub partial:                  bool,
+    pub cycles:                   u64,
+    pub p1_cycles:                u64,
+    pub imported_kwh:             Option<f64>,
+    pub exported_kwh:             Option<f64>,
+    pub charged_kwh:              Option<f64>,
+    pub discharged_kwh:           Option<f64>,
+    pub grid_charged_kwh:         f64,
+    pub produced_kwh:             Option<f64>,
+    pub consumption_kwh:          Option<f64>,
+    pub self_sufficiency_percent: Option<f64>,
+    pub peak_import_w:            Option<f64>,
+    pub peak_import_at:           Option<DateTime<Utc>>,
+    pub soc_min_percent:          Option<f64>,
+    pub soc_max_percent:          Option<f64>,
+    pub cost_eur:                 Option<f64>,
+    pub unpriced_import_kwh:      f64,
+    pub saving_eur:               Option<f64>,
+    pub alarms:                   Vec<Alarm>,
+    pub alarms_dropped:           u32,
+    /// Totals integrated from power because their counter was missing ("imported", "charged", ...).
+    pub estimated:                Vec<&'static str>,
+}
+
+/// Day totals in progress plus the counter baselines and the closed days' summaries. Kept in the state file,
+/// so a restart continues the day.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct DailyReporter {
+    day:     Option<DayAccumulator>,
+    last:    Counters,
+    #[serde(default)]
+    history: DayHistory,
+}
+
+impl DailyReporter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Feed one cycle. `price` is the import price (EUR/kWh) of this hour, `previous` the decision that was in
+    /// force since the last cycle. Returns the report of the previous day on the first cycle of a new one.
+    pub fn observe(
+        &mut self,
+        now: DateTime<Utc>,
+        p1: Option<&P1Reading>,
+        battery: &BatterySnapshot,
+        price: Option<f64>,
+        previous: Option<&Decision>,
+    ) -> Option<DailyReport> {
+        let date     = now.with_timezone(&LOCAL_TZ).date_naive();
+        let mut integrated = PowerIntegrators::new();
+        let finished = match &self.day {
+            Some(day) if day.date != date => self.day.take().map(|day| {
+                integrated = day.integrated.carry_over();
+                let report = day.finish();
+                self.history.push(DaySummary::from(&report));
+                report
+            }),
+            _ => None,
+        };
+        let day  = self.day.get_or_insert_with(|| DayAccumulator::new(date, now, integrated));
+        let last = &mut self.last;
+        day.cycles     += 1;
+        day.last_sample = now;
+        day.integrated.observe(now, p1, battery);
+
+        if let Some(p1) = p1 {
+            day.p1_cycles += 1;
+            if let Some(wh) = delta(&mut last.import_kwh, p1.raw.total_power_import_kwh) {
+                add(&mut day.import_wh, wh);
+                match price {
+                    Some(price) => add(&mut day.cost_eur, wh_to_kwh(wh) * price),
+                    None        => day.unpriced_import_wh += wh,
+                }
+            }
+            if let Some(wh) = delta(&mut last.export_kwh, p1.raw.total_power_export_kwh) {
+                add(&mut day.export_wh, wh);
+            }
+            let average_w = p1.raw.active_power_average_w;
+            if day.peak_import_w.is_none_or(|peak| average_w > peak) {
+                day.peak_import_w  = Some(average_w);
+                day.peak_import_at = Some(now);
+            }
+        }
+
+        if battery.is_fresh(ID_TOTAL_CHARGING) {
+            if let Some(wh) = delta(&mut last.charged_kwh, battery.total_charging_kwh) {
+                add(&mut day.charged_wh, wh);
+                if matches!(previous, Some(Decision::ChargingFromGrid { .. })) {
+                    day.grid_charged_wh += wh;
+                    if let Some(price) = price {
+                        add(&mut day.saving_eur, -wh_to_kwh(wh) * price);
+                    }
+                }
+            }
+        }
+        if battery.is_fresh(ID_TOTAL_DISCHARGING) {
+            if let Some(wh) = delta(&mut last.discharged_kwh, battery.total_discharging_kwh) {
+                add(&mut day.discharged_wh, wh);
+                if let Some(price) = price {
+                    add(&mut day.saving_eur, wh_to_kwh(wh) * price);
+                }
+            }
+        }
+        if battery.is_fresh(ID_CUMULATIVE_PRODUCTION) {
+            if let Some(wh) = delta(&mut last.produced_kwh, battery.cumulative_production_kwh) {
+                add(&mut day.produced_wh, wh);
+            }
+        }
+        if battery.is_fresh(ID_BATTERY_SOC) {
+            let soc = battery.battery_soc;
+            day.soc_min_percent = Some(day.soc_min_percent.map_or(soc, |min| min.min(soc)));
+            day.soc_max_percent = Some(day.soc_max_percent.map_or(soc, |max| max.max(soc)));
+        }
+        finished
+    }
+
+    /// The closed days with today so far as the last entry.
+    pub fn history(&self) -> DayHistory {
+        let mut history = self.history.clone();
+        if let Some(day) = &self.day {
+            history.push(DaySummary::from(&day.finish()));
+        }
+        history
+    }
+
+    /// Note an alarm for today's report. Ignored until the first cycle was observed.
+    pub fn alarm(&mut self, now: DateTime<Utc>, text: impl Into<String>) {
+        let Some(day) = self.day.as_mut() else { return };
+        let text = text.into();
+        let full = day.alarms.len() >= MAX_ALARMS;
+        match day.alarms.last_mut() {
+            Some(last) if last.text == text => last.repeats += 1,
+            _ if full => day.alarms_dropped += 1,
+            _ => day.alarms.push(Alarm { at: now, text, repeats: 0 }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::optimiser::testing::{battery, p1_reading};
+    use chrono::TimeZone;
+    use std::time::Duration;
+
+    /// One seeded cycle: local hour, P1 import/export counters and quarter-hour average, battery
+    /// charged/discharged/produced counters and SOC, the hour's price and the decision in force.
+    struct Row {
+        hour:     f64,
+        import:   f64,
+        export:   f64,
+        average:  f64,
+        charged:  f64,
+        discharged:  f64,
+        produced: f64,
+        soc:      f64,
+        price:    f64,
+        previous: Option<Decision>,
+    }
+
+    fn observe(reporter: &mut DailyReporter, row: &Row) -> Option<DailyReport> {
+        // 14 October 2026 00:00 in Brussels.
+        let midnight = Utc.with_ymd_and_hms(2026, 10, 13, 22, 0, 0).unwrap();
+        let now      = midnight + chrono::Duration::minutes((row.hour * 60.0) as i64);
+        let mut p1   = p1_reading(row.average, Duration::ZERO);
+        p1.raw.total_power_import_kwh = row.import;
+        p1.raw.total_power_export_kwh = row.export;
+        p1.raw.active_power_average_w = row.average;
+        let snapshot = BatterySnapshot {
+            total_charging_kwh:        row.charged,
+            total_discharging_kwh:     row.discharged,
+            cumulative_production_kwh: row.produced,
+            ..battery(row.soc, 0)
+        };
+        reporter.observe(now, Some(&p1), &snapshot, Some(row.price), row.previous.as_ref())
+    }
+
+    #[test]
+    fn closed_days_stay_in_the_history() {
+        let mut reporter = DailyReporter::new();
+        let mut snapshot = battery(50.0, 0);
+        // 23:00 UTC is 01:00 the next day in Brussels, which closes 14 October.
+        for (hour, charged_kwh) in [(6, 100.0), (12, 104.5), (23, 105.0)] {
+            snapshot.total_charging_kwh = charged_kwh;
+            let now = Utc.with_ymd_and_hms(2026, 10, 14, hour, 0, 0).unwrap();
+            reporter.observe(now, None, &snapshot, None, None);
+        }
+        assert_eq!(reporter.history().query(history::Metric::Charged, history::Period::Day)[0].value, Some(4.5));
+
+        let saved: DailyReporter = serde_json::from_str(&serde_json::to_string(&reporter).unwrap()).unwrap();
+        let buckets = saved.history().query(history::Metric::Charged, history::Period::Day);
+        let days: Vec<_> = buckets.iter().map(|b| (b.period.as_str(), b.value)).collect();
+        assert_eq!(days, vec![("2026-10-14", Some(4.5)), ("2026-10-15", Some(0.5))]);
+    }
+
+    #[test]
+    fn a_seeded_day_aggregates_into_its_report() {
+        let grid_charge = Some(Decision::ChargingFromGrid { watts: 2400, max_soc_percent: 80 });
+        let rows = [
+            Row { hour: 0.0, import: 1000.0, export: 500.0, average: 800.0, charged: 200.0,
+                  discharged: 180.0, produced: 3000.0, soc: 40.0, price: 0.20, previous: None },
+            Row { hour: 6.0, import: 1003.0, export: 500.0, average: 2900.0, charged: 203.0,
+                  discharged: 180.0, produced: 3000.0, soc: 70.0, price: 0.10, previous: grid_charge },
+            Row { hour: 12.0, import: 1003.0, export: 502.0, average: -1500.0, charged: 205.0,
+                  discharged: 180.0, produced: 3006.0, soc: 95.0, price: 0.25, previous: None },
+            Row { hour: 18.0, import: 1004.0, export: 502.0, average: 1200.0, charged: 205.0,
+                  discharged: 184.0, produced: 3006.5, soc: 45.0, price: 0.35, previous: None },
+            Row { hour: 23.5, import: 1005.0, export: 502.0, average: 600.0, charged: 205.0,
+                  discharged: 184.0, produced: 3006.5, soc: 30.0, price: 0.20, previous: None },
+        ];
+        let mut reporter = DailyReporter::new();
+        for row in &rows {
+            assert_eq!(observe(&mut reporter, row), None);
+        }
+        let six = Utc.with_ymd_and_hms(2026, 10, 14, 4, 0, 0).unwrap();
+        reporter.alarm(six, "grid frequency 49.70 Hz");
+
+        let next_day = Row { hour: 24.0, ..rows.into_iter().last().unwrap() };
+        let report   = observe(&mut reporter, &next_day).expect("the closed day");
+        assert_eq!(report.date, NaiveDate::from_ymd_opt(2026, 10, 14).unwrap());
+        assert_eq!((report.cycles, report.p1_cycles, report.partial), (5, 5, false));
+        assert_eq!((report.imported_kwh, report.exported_kwh), (Some(5.0), Some(2.0)));
+        assert_eq!((report.charged_kwh, report.discharged_kwh), (Some(5.0), Some(4.0)));
+        assert_eq!((report.grid_charged_kwh, report.produced_kwh), (3.0, Some(6.5)));
+        // 5 − 2 + 6.5 + 4 − 5 = 8.5 kWh used, of which 3.5 kWh not imported.
+        assert_eq!(report.consumption_kwh, Some(8.5));
+        assert!((report.self_sufficiency_percent.unwrap() - 3.5 / 8.5 * 100.0).abs() < 1e-9);
+        assert_eq!((report.peak_import_w, report.peak_import_at), (Some(2900.0), Some(six)));
+        assert_eq!((report.soc_min_percent, report.soc_max_percent), (Some(30.0), Some(95.0)));
+        // 3 kWh at 0.10 + 1 kWh at 0.35 + 1 kWh at 0.20; 4 kWh discharged at 0.35 less 3 kWh bought at 0.10.
+        assert!((report.cost_eur.unwrap() - 0.85).abs() < 1e-9);
+        assert!((report.saving_eur.unwrap() - 1.10).abs() < 1e-9);
+        assert_eq!(report.alarms.len(), 1);
+        assert!(report.estimated.is_empty());
+    }
+
+    #[test]
+    fn a_day_without_p1_is_partial_with_the_grid_totals_unknown() {
+        let mut reporter = DailyReporter::new();
+        let mut snapshot = battery(50.0, 0);
+        for (hour, charged_kwh) in [(8, 100.0), (12, 102.0), (23, 102.0)] {
+            snapshot.total_charging_kwh = charged_kwh;
+            let now = Utc.with_ymd_and_hms(2026, 10, 14, hour, 0, 0).unwrap();
+            let report = reporter.observe(now, None, &snapshot, None, None);
+            if hour < 23 {
+                assert_eq!(report, None);
+                continue;
+            }
+            let report = report.expect("the closed day");
+            assert!(report.partial);
+            assert_eq!((report.imported_kwh, report.consumption_kwh, report.cost_eur), (None, None, None));
+            assert_eq!(report.charged_kwh, Some(2.0));
+        }
+    }
+}
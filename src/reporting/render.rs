@@ -0,0 +1,133 @@
+use chrono::{DateTime, Utc};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::configuration::config::ReportFormat;
+use crate::runtime::clock::LOCAL_TZ;
+use super::DailyReport;
+
+// --------------------------------------------------------------------------------------------------------------
+// Report rendering
+//
+// Text and Markdown show the same rows (a plain list vs a table), JSON is the `DailyReport` itself. Each day is
+// written to `<report_dir>/report-YYYY-MM-DD.<txt|md|json>`; the directory is created when missing.
+// --------------------------------------------------------------------------------------------------------------
+
+fn local_hhmm(at: DateTime<Utc>) -> String {
+    at.with_timezone(&LOCAL_TZ).format("%H:%M").to_string()
+}
+
+fn or_na(value: Option<f64>, format: impl Fn(f64) -> String) -> String {
+    value.map_or_else(|| "n/a".to_string(), format)
+}
+
+/// (label, value) rows shared by the text and Markdown renderings.
+fn rows(report: &DailyReport) -> Vec<(&'static str, String)> {
+    let kwh = |value: Option<f64>| or_na(value, |v| format!("{:.2} kWh", v));
+    let flow = |value: Option<f64>, name: &str| {
+        let estimate = if report.estimated.contains(&name) { " (from power)" } else { "" };
+        format!("{}{}", kwh(value), estimate)
+    };
+    let coverage = format!(
+        "{}-{} ({:.1} h{}), {} cycles, P1 read in {}",
+        local_hhmm(report.first_sample),
+        local_hhmm(report.last_sample),
+        report.coverage_hours,
+        if report.partial { ", partial day" } else { "" },
+        report.cycles,
+        report.p1_cycles,
+    );
+    let peak = match (report.peak_import_w, report.peak_import_at) {
+        (Some(w), Some(at)) => format!("{:.0} W at {}", w, local_hhmm(at)),
+        _                   => "n/a".to_string(),
+    };
+    let cost = or_na(report.cost_eur, |eur| {
+        if report.unpriced_import_kwh > 0.0 {
+            format!("€{:.2} ({:.2} kWh without a price)", eur, report.unpriced_import_kwh)
+        } else {
+            format!("€{:.2}", eur)
+        }
+    });
+
+    vec![
+        ("Coverage",             coverage),
+        ("Imported",             flow(report.imported_kwh, "imported")),
+        ("Exported",             flow(report.exported_kwh, "exported")),
+        ("Solar production",     kwh(report.produced_kwh)),
+        ("Battery charged",      format!("{} ({:.2} kWh from the grid)", flow(report.charged_kwh, "charged"),
+                                         report.grid_charged_kwh)),
+        ("Battery discharged",   flow(report.discharged_kwh, "discharged")),
+        ("House consumption",    kwh(report.consumption_kwh)),
+        ("Self-sufficiency",     or_na(report.self_sufficiency_percent, |p| format!("{:.0}%", p))),
+        ("Peak import (15 min)", peak),
+        ("SOC range",            match (report.soc_min_percent, report.soc_max_percent) {
+            (Some(min), Some(max)) => format!("{:.0}-{:.0}%", min, max),
+            _                      => "n/a".to_string(),
+        }),
+        ("Import cost",          cost),
+        ("Estimated saving",     or_na(report.saving_eur, |eur| format!("€{:.2}", eur))),
+    ]
+}
+
+/// One line per alarm: "HH:MM text (×N)".
+fn alarm_lines(report: &DailyReport) -> Vec<String> {
+    let mut lines: Vec<String> = report
+        .alarms
+        .iter()
+        .map(|a| {
+            let repeats = if a.repeats > 0 { format!(" (×{})", a.repeats + 1) } else { String::new() };
+            format!("{} {}{}", local_hhmm(a.at), a.text, repeats)
+        })
+        .collect();
+    if report.alarms_dropped > 0 {
+        lines.push(format!("... {} more", report.alarms_dropped));
+    }
+    lines
+}
+
+pub fn render_text(report: &DailyReport) -> String {
+    let mut out = format!("EMS daily report {}\n\n", report.date);
+    for (label, value) in rows(report) {
+        out.push_str(&format!("{:<22}{}\n", format!("{}:", label), value));
+    }
+    let alarms = alarm_lines(report);
+    out.push_str(if alarms.is_empty() { "\nAlarms: none\n" } else { "\nAlarms:\n" });
+    for line in alarms {
+        out.push_str(&format!("  {}\n", line));
+    }
+    out
+}
+
+pub fn render_markdown(report: &DailyReport) -> String {
+    let mut out = format!("# EMS daily report {}\n\n| | |\n|---|---|\n", report.date);
+    for (label, value) in rows(report) {
+        out.push_str(&format!("| {} | {} |\n", label, value));
+    }
+    out.push_str("\n## Alarms\n\n");
+    let alarms = alarm_lines(report);
+    if alarms.is_empty() {
+        out.push_str("None.\n");
+    }
+    for line in alarms {
+        out.push_str(&format!("- {}\n", line));
+    }
+    out
+}
+
+pub fn render(report: &DailyReport, format: ReportFormat) -> Result<String, String> {
+    match format {
+        ReportFormat::Text     => Ok(render_text(report)),
+        ReportFormat::Markdown => Ok(render_markdown(report)),
+        ReportFormat::Json     => serde_json::to_string_pretty(report)
+            .map_err(|e| format!("[Report] Failed to serialise report: {}", e)),
+    }
+}
+
+/// Render `report` and write it into `dir`. Returns the file written.
+pub fn write_report(report: &DailyReport, dir: &str, format: ReportFormat) -> Result<PathBuf, String> {
+    let contents = render(report, format)?;
+    fs::create_dir_all(dir).map_err(|e| format!("[Report] Failed to create {}: {}", dir, e))?;
+    let path = Path::new(dir).join(format!("report-{}.{}", report.date, format.extension()));
+    fs::write(&path, contents).map_err(|e| format!("[Report] Failed to write {}: {}", path.display(), e))?;
+    Ok(path)
+}
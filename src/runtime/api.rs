@@ -0,0 +1,367 @@
+use chrono::Utc;
+use log::{debug, error, info};
+use std::sync::Arc;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::tcp::OwnedWriteHalf;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::watch;
+use tokio::time::{timeout, Duration};
+
+use super::recent::SharedRecentCycles;
+use crate::configuration::config::Config;
+use crate::optimiser::simulate::{simulate, SimulateRequest};
+use crate::reporting::history::{Metric, Period, SharedDayHistory};
+use crate::sinks::health::SharedSinkHealth;
+
+// --------------------------------------------------------------------------------------------------------------
+// HTTP API
+//
+// A minimal HTTP/1.1 server, started when `api_listen` is set. One request per connection:
+//
+//   GET  /recent?n=20   the last n cycle outcomes (default 20) as a JSON array, oldest first
+//   GET  /sinks         health of each enabled sink: last success, consecutive failures, last error
+//   GET  /decision      the latest cycle's decision trace: inputs, every rule evaluated and whether it fired,
+//                       and the final decision (see `optimiser/trace.rs`)
+//   POST /simulate      the decision trace for hypothetical readings (see `optimiser/simulate.rs`)
+//   GET  /history?metric=peak&period=month
+//                       a metric (peak, charged, discharged, self_sufficiency) per day, week or month over the
+//                       kept day summaries (see `reporting/history.rs`)
+//   GET  /              with `dashboard_enabled`, a single HTML page (`dashboard.html`, compiled in) that shows
+//                       the SOC, the power flows and the decision from `/decision`, and a chart from `/history`
+//
+// Nothing here changes the running system: `/simulate` only runs the pure optimiser. An `n` larger than the
+// buffer returns what is there. The server runs as its own supervised task, so a failure here never touches the
+// control loop; if the address can't be bound the task ends and is retried with backoff.
+// --------------------------------------------------------------------------------------------------------------
+
+/// Outcomes returned by `/recent` when the request has no `n`.
+const DEFAULT_RECENT: usize = 20;
+/// A client has this long to send its request line, headers and body.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+/// Largest request body accepted.
+const MAX_BODY_BYTES: usize = 64 * 1024;
+/// The dashboard page served at `/`.
+const DASHBOARD_HTML: &str = include_str!("dashboard.html");
+
+/// What the API serves from: the buffers the control loop and the sinks fill, and the running settings.
+#[derive(Clone)]
+pub struct ApiState {
+    pub recent:   SharedRecentCycles,
+    pub history:  SharedDayHistory,
+    pub sinks:    SharedSinkHealth,
+    pub settings: watch::Receiver<Arc<Config>>,
+}
+
+/// One parsed request.
+struct Request {
+    method: String,
+    target: String,
+    body:   String,
+}
+
+type Response = (&'static str, String);
+
+/// Serve the API on `listen` until the listener fails.
+pub async fn api_server(listen: String, state: ApiState) {
+    let listener = match TcpListener::bind(&listen).await {
+        Ok(listener) => listener,
+        Err(e) => {
+            error!("[API] Cannot listen on {}: {}", listen, e);
+            return;
+        }
+    };
+    info!("[API] Listening on http://{}", listen);
+    loop {
+        match listener.accept().await {
+            Ok((stream, _)) => {
+                tokio::spawn(serve_connection(stream, state.clone()));
+            }
+            Err(e) => {
+                error!("[API] Accept failed: {}", e);
+                return;
+            }
+        }
+    }
+}
+
+async fn serve_connection(stream: TcpStream, state: ApiState) {
+    let (read_half, mut write_half) = stream.into_split();
+    let mut reader = BufReader::new(read_half);
+
+    let (status, body) = match timeout(REQUEST_TIMEOUT, read_request(&mut reader)).await {
+        Ok(Ok(request)) => {
+            if let Some(page) = dashboard(&state, &request) {
+                debug!("[API] {} {} -> dashboard", request.method, request.target);
+                return send(&mut write_half, "200 OK", "text/html; charset=utf-8", page).await;
+            }
+            let response = route(&state, &request);
+            debug!("[API] {} {} -> {}", request.method, request.target, response.0);
+            response
+        }
+        Ok(Err(response)) => response,
+        Err(_)            => return,
+    };
+
+    send(&mut write_half, status, "application/json", &body).await;
+}
+
+async fn send(write_half: &mut OwnedWriteHalf, status: &str, content_type: &str, body: &str) {
+    let reply = format!(
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        content_type,
+        body.len(),
+        body
+    );
+    let _ = write_half.write_all(reply.as_bytes()).await;
+}
+
+/// The dashboard page for `GET /`, when `dashboard_enabled` is set.
+fn dashboard(state: &ApiState, request: &Request) -> Option<&'static str> {
+    let page = request.method == "GET" && request.target.split('?').next() == Some("/");
+    (page && state.settings.borrow().dashboard_enabled).then_some(DASHBOARD_HTML)
+}
+
+/// Read the request line, the headers and, when there is a `Content-Length`, the body.
+async fn read_request(reader: &mut BufReader<tokio::net::tcp::OwnedReadHalf>) -> Result<Request, Response> {
+    let closed = || ("400 Bad Request", r#"{"error":"incomplete request"}"#.to_string());
+    let mut request_line = String::new();
+    if reader.read_line(&mut request_line).await.unwrap_or(0) == 0 {
+        return Err(closed());
+    }
+    let mut parts = request_line.split_whitespace();
+    let (Some(method), Some(target)) = (parts.next(), parts.next()) else {
+        return Err(("400 Bad Request", r#"{"error":"bad request line"}"#.to_string()));
+    };
+
+    let mut content_length = 0;
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header).await.unwrap_or(0) == 0 {
+            return Err(closed());
+        }
+        if header == "\r\n" || header == "\n" {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.trim().eq_ignore_ascii_case("content-length") {
+                content_length = value.trim().parse().unwrap_or(0);
+            }
+        }
+    }
+    if content_length > MAX_BODY_BYTES {
+        return Err(("413 Payload Too Large", r#"{"error":"request body too large"}"#.to_string()));
+    }
+    let mut body = vec![0; content_length];
+    if reader.read_exact(&mut body).await.is_err() {
+        return Err(closed());
+    }
+
+    Ok(Request {
+        method: method.to_string(),
+        target: target.to_string(),
+        body:   String::from_utf8_lossy(&body).into_owned(),
+    })
+}
+
+fn route(state: &ApiState, request: &Request) -> Response {
+    let url = match reqwest::Url::parse(&format!("http://ems{}", request.target)) {
+        Ok(url) => url,
+        Err(_) => return ("400 Bad Request", r#"{"error":"bad request target"}"#.to_string()),
+    };
+    match (request.method.as_str(), url.path()) {
+        ("GET", "/recent") => {
+            let n = match url.query_pairs().find(|(k, _)| k == "n") {
+                None => DEFAULT_RECENT,
+                Some((_, v)) => match v.parse::<usize>() {
+                    Ok(n) => n,
+                    Err(_) => return ("400 Bad Request", r#"{"error":"n must be a whole number"}"#.to_string()),
+                },
+            };
+            let outcomes = state.recent.lock().unwrap_or_else(|e| e.into_inner()).latest(n);
+            ("200 OK", serde_json::to_string(&outcomes).unwrap_or_else(|_| "[]".to_string()))
+        }
+        ("GET", "/decision") => match state.recent.lock().unwrap_or_else(|e| e.into_inner()).trace() {
+            Some(trace) => ("200 OK", serde_json::to_string(trace).unwrap_or_else(|_| "{}".to_string())),
+            None        => ("404 Not Found", r#"{"error":"no decision yet"}"#.to_string()),
+        },
+        ("GET", "/sinks") => {
+            let health = serde_json::to_string(&*state.sinks.lock().unwrap_or_else(|e| e.into_inner()));
+            ("200 OK", health.unwrap_or_else(|_| "{}".to_string()))
+        }
+        ("POST", "/simulate") => {
+            let simulation = serde_json::from_str::<SimulateRequest>(&request.body)
+                .map_err(|e| e.to_string())
+                .and_then(|inputs| simulate(&inputs, &state.settings.borrow(), Utc::now()));
+            match simulation {
+                Ok(trace) => ("200 OK", serde_json::to_string(&trace).unwrap_or_else(|_| "{}".to_string())),
+                Err(e)    => ("400 Bad Request", serde_json::json!({ "error": e }).to_string()),
+            }
+        }
+        ("GET", "/history") => {
+            let param = |name: &str| {
+                url.query_pairs().find(|(k, _)| k == name).map(|(_, v)| v.into_owned()).unwrap_or_default()
+            };
+            let query = param("metric").parse::<Metric>().and_then(|metric| {
+                param("period").parse::<Period>().map(|period| (metric, period))
+            });
+            let (metric, period) = match query {
+                Ok(query) => query,
+                Err(e)    => return ("400 Bad Request", serde_json::json!({ "error": e }).to_string()),
+            };
+            let buckets = state.history.lock().unwrap_or_else(|e| e.into_inner()).query(metric, period);
+            let body = serde_json::json!({
+                "metric":  param("metric"),
+                "period":  param("period"),
+                "unit":    metric.unit(),
+                "buckets": buckets,
+            });
+            ("200 OK", body.to_string())
+        }
+        (_, "/recent" | "/decision" | "/sinks" | "/simulate" | "/history") => {
+            ("405 Method Not Allowed", r#"{"error":"method not allowed"}"#.to_string())
+        }
+        _ => ("404 Not Found", r#"{"error":"not found"}"#.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::optimiser::decision::{Decision, Verdict};
+    use crate::optimiser::trace::{DecisionTrace, RuleTrace, TraceInputs};
+    use crate::reporting::history::{DayHistory, DaySummary};
+    use crate::runtime::outcome::CycleOutcome;
+    use crate::runtime::recent::RecentCycles;
+    use crate::sinks::health::SinkHealthTable;
+    use serde_json::Value;
+
+    fn state(recent: &SharedRecentCycles) -> ApiState {
+        ApiState {
+            recent:   recent.clone(),
+            history:  history(),
+            sinks:    SinkHealthTable::shared(),
+            settings: watch::channel(Arc::new(Config::default())).1,
+        }
+    }
+
+    /// Two September days and one October day.
+    fn history() -> SharedDayHistory {
+        let history = DayHistory::shared();
+        for (date, peak_import_w) in [("2026-09-29", 4100.0), ("2026-09-30", 3200.0), ("2026-10-01", 3600.0)] {
+            history.lock().unwrap().push(DaySummary {
+                date:            date.parse().unwrap(),
+                peak_import_w:   Some(peak_import_w),
+                charged_kwh:     Some(4.0),
+                discharged_kwh:  Some(3.0),
+                imported_kwh:    Some(5.0),
+                consumption_kwh: Some(10.0),
+            });
+        }
+        history
+    }
+
+    fn request(recent: &SharedRecentCycles, method: &str, target: &str, body: &str) -> (&'static str, Value) {
+        let request = Request { method: method.to_string(), target: target.to_string(), body: body.to_string() };
+        let (status, body) = route(&state(recent), &request);
+        (status, serde_json::from_str(&body).unwrap())
+    }
+
+    fn get(recent: &SharedRecentCycles, target: &str) -> (&'static str, Value) {
+        request(recent, "GET", target, "")
+    }
+
+    #[test]
+    fn recent_returns_the_last_n_outcomes() {
+        let recent = RecentCycles::shared(10);
+        for cycle_id in 1..=5 {
+            let outcome = CycleOutcome::new(cycle_id, true, 0, 10, 100, 30_000);
+            recent.lock().unwrap().push(outcome);
+        }
+        let (status, body) = get(&recent, "/recent?n=2");
+        assert_eq!(status, "200 OK");
+        let ids: Vec<u64> = body.as_array().unwrap().iter().map(|o| o["cycle_id"].as_u64().unwrap()).collect();
+        assert_eq!(ids, vec![4, 5]);
+
+        assert_eq!(get(&recent, "/recent?n=x").0, "400 Bad Request");
+    }
+
+    #[test]
+    fn decision_serves_the_latest_trace() {
+        let recent = RecentCycles::shared(10);
+        assert_eq!(get(&recent, "/decision").0, "404 Not Found");
+
+        let mut rules = RuleTrace::default();
+        rules.rule("house_load", true, "house load 800W, SOC 60.0%, reserve 20.0%");
+        let decision = Decision::DischargingToLoad { watts: 800, min_soc_percent: 20 };
+        let verdict  = Verdict::new(decision, "house load");
+        let trace    = DecisionTrace::new(7, Utc::now(), TraceInputs::default(), rules);
+        recent.lock().unwrap().set_trace(trace.with_verdict(Some(&verdict)));
+
+        let (status, body) = get(&recent, "/decision");
+        assert_eq!(status, "200 OK");
+        assert_eq!(body["cycle_id"], 7);
+        assert_eq!(body["rules"][0]["rule"], "house_load");
+        assert_eq!(body["rules"][0]["fired"], true);
+        assert_eq!(body["decision"]["action"], "DischargingToLoad");
+    }
+
+    #[test]
+    fn simulate_returns_the_decision_for_the_posted_readings() {
+        let recent = RecentCycles::shared(1);
+        let json   = r#"{"grid_power_w": 6000, "soc_percent": 15}"#;
+        let (status, body) = request(&recent, "POST", "/simulate", json);
+        assert_eq!(status, "200 OK");
+        assert_eq!(body["decision"]["action"], "DischargingToLoad");
+        assert_eq!(body["decision"]["watts"], 2400);
+        assert!(recent.lock().unwrap().trace().is_none());
+
+        assert_eq!(request(&recent, "POST", "/simulate", r#"{"soc_percent": 15}"#).0, "400 Bad Request");
+        assert_eq!(get(&recent, "/simulate").0, "405 Method Not Allowed");
+    }
+
+    #[test]
+    fn history_aggregates_a_metric_per_period() {
+        let recent = RecentCycles::shared(1);
+        let (status, body) = get(&recent, "/history?metric=peak&period=month");
+        assert_eq!(status, "200 OK");
+        assert_eq!(body["unit"], "W");
+        assert_eq!(body["buckets"][0]["period"], "2026-09");
+        assert_eq!(body["buckets"][0]["value"], 4100.0);
+        assert_eq!(body["buckets"][1]["value"], 3600.0);
+
+        let (_, body) = get(&recent, "/history?metric=self_sufficiency&period=day");
+        assert_eq!(body["buckets"].as_array().unwrap().len(), 3);
+        assert_eq!(body["buckets"][2]["value"], 50.0);
+    }
+
+    #[test]
+    fn history_rejects_unknown_parameters() {
+        let recent = RecentCycles::shared(1);
+        assert_eq!(get(&recent, "/history?metric=energy&period=month").0, "400 Bad Request");
+        assert_eq!(get(&recent, "/history?metric=peak&period=year").0, "400 Bad Request");
+        assert_eq!(get(&recent, "/history?metric=peak").0, "400 Bad Request");
+        assert_eq!(request(&recent, "POST", "/history?metric=peak&period=day", "").0, "405 Method Not Allowed");
+    }
+
+    #[test]
+    fn the_dashboard_is_served_only_when_enabled() {
+        let page = Request { method: "GET".to_string(), target: "/".to_string(), body: String::new() };
+        let mut state = state(&RecentCycles::shared(1));
+        assert_eq!(dashboard(&state, &page), None);
+        assert_eq!(route(&state, &page).0, "404 Not Found");
+
+        let config = Config { dashboard_enabled: true, ..Config::default() };
+        state.settings = watch::channel(Arc::new(config)).1;
+        let html = dashboard(&state, &page).unwrap();
+        assert!(html.starts_with("<!DOCTYPE html>"));
+        assert!(html.contains("/decision") && html.contains("/history"));
+        let post = Request { method: "POST".to_string(), ..page };
+        assert_eq!(dashboard(&state, &post), None);
+    }
+
+    #[test]
+    fn unknown_path_is_not_found() {
+        assert_eq!(get(&RecentCycles::shared(1), "/nope").0, "404 Not Found");
+    }
+}
@@ -0,0 +1,127 @@
+use chrono::{DateTime, Utc};
+use tokio::time::{interval_at, Duration, Instant, Interval, MissedTickBehavior};
+
+use crate::configuration::config::{Config, MissedTicks};
+
+// --------------------------------------------------------------------------------------------------------------
+// Cycle cadence
+//
+// Cycles are paced by a `tokio::time::Interval`, which schedules ticks at fixed multiples of the period from
+// its start instead of re-computing a sleep from the cycle's own duration, so the cadence doesn't drift.
+// What happens after an overrun is explicit and configurable (`missed_tick_behavior`):
+//   - skip:  the missed ticks are dropped and the next cycle starts on the next tick of the original grid;
+//   - delay: the next cycle starts immediately and the grid shifts to start from there.
+//
+// With `align_to_wallclock` enabled the first tick lands on a multiple of the poll interval since the Unix
+// epoch (e.g. :00 and :30 of every minute for a 30 s interval); with "skip" every later tick stays aligned.
+// --------------------------------------------------------------------------------------------------------------
+
+/// Time to sleep from `now` until the next multiple of `interval` on the wall clock.
+/// When `now` sits exactly on a boundary the full interval is returned, so a cycle that overran simply
+/// lands on the next boundary after the one it missed.
+pub fn sleep_until_next_boundary(now: DateTime<Utc>, interval: Duration) -> Duration {
+    let period_ms = interval.as_millis() as i64;
+    if period_ms <= 0 {
+        return Duration::ZERO;
+    }
+    let into_period_ms = now.timestamp_millis().rem_euclid(period_ms);
+    Duration::from_millis((period_ms - into_period_ms) as u64)
+}
+
+/// The interval the control loop waits on before each cycle. The first tick fires immediately, or on the
+/// next wall-clock boundary when aligned.
+pub fn cycle_ticker(config: &Config) -> Interval {
+    ticker_from(config, Instant::now())
+}
+
+/// The interval after a config reload changed the cadence: the next cycle is one new period from now, or on
+/// the next wall-clock boundary when aligned.
+pub fn reloaded_ticker(config: &Config) -> Interval {
+    ticker_from(config, Instant::now() + period(config))
+}
+
+fn period(config: &Config) -> Duration {
+    // A zero period would make `interval_at` panic; 1 ms keeps a misconfigured loop spinning as before.
+    Duration::from_secs(config.poll_interval_seconds).max(Duration::from_millis(1))
+}
+
+fn ticker_from(config: &Config, unaligned_start: Instant) -> Interval {
+    let period = period(config);
+    let start  = if config.align_to_wallclock {
+        Instant::now() + sleep_until_next_boundary(Utc::now(), period)
+    } else {
+        unaligned_start
+    };
+
+    let mut ticker = interval_at(start, period);
+    ticker.set_missed_tick_behavior(match config.missed_tick_behavior {
+        MissedTicks::Skip  => MissedTickBehavior::Skip,
+        MissedTicks::Delay => MissedTickBehavior::Delay,
+    });
+    ticker
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn at(h: u32, m: u32, s: u32, ms: i64) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 6, 1, h, m, s).unwrap() + chrono::TimeDelta::milliseconds(ms)
+    }
+
+    #[test]
+    fn sleeps_to_the_next_boundary() {
+        let period = Duration::from_secs(30);
+        assert_eq!(sleep_until_next_boundary(at(12, 0, 10, 0), period), Duration::from_secs(20));
+        assert_eq!(sleep_until_next_boundary(at(12, 0, 29, 750), period), Duration::from_millis(250));
+        assert_eq!(sleep_until_next_boundary(at(12, 0, 45, 0), period), Duration::from_secs(15));
+    }
+
+    #[test]
+    fn on_a_boundary_waits_a_full_period() {
+        let period = Duration::from_secs(30);
+        assert_eq!(sleep_until_next_boundary(at(12, 1, 0, 0), period), period);
+        assert_eq!(sleep_until_next_boundary(at(12, 1, 30, 0), period), period);
+    }
+
+    #[test]
+    fn overrun_lands_on_the_boundary_after_the_missed_one() {
+        // A cycle started at 12:00:00 that ran 40 s skips 12:00:30 and waits for 12:01:00.
+        let period = Duration::from_secs(30);
+        assert_eq!(sleep_until_next_boundary(at(12, 0, 40, 0), period), Duration::from_secs(20));
+    }
+
+    #[test]
+    fn zero_interval_does_not_sleep() {
+        assert_eq!(sleep_until_next_boundary(at(12, 0, 10, 0), Duration::ZERO), Duration::ZERO);
+    }
+
+    /// When the two ticks after a 75 s cycle on a 30 s period fire, counted from the first tick.
+    async fn ticks_after_an_overrun(missed_tick_behavior: MissedTicks) -> (Duration, Duration) {
+        let config = Config { poll_interval_seconds: 30, missed_tick_behavior, ..Config::default() };
+        let start  = Instant::now();
+        let mut ticker = cycle_ticker(&config);
+        ticker.tick().await;
+        tokio::time::advance(Duration::from_secs(75)).await;
+
+        ticker.tick().await;
+        let late = start.elapsed();
+        ticker.tick().await;
+        (late, start.elapsed())
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn skip_keeps_the_original_grid_after_an_overrun() {
+        let (late, next) = ticks_after_an_overrun(MissedTicks::Skip).await;
+        assert_eq!(late, Duration::from_secs(75));
+        assert_eq!(next, Duration::from_secs(90));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn delay_restarts_the_grid_after_an_overrun() {
+        let (late, next) = ticks_after_an_overrun(MissedTicks::Delay).await;
+        assert_eq!(late, Duration::from_secs(75));
+        assert_eq!(next, Duration::from_secs(105));
+    }
+}
@@ -0,0 +1,94 @@
+use chrono::{DateTime, Utc};
+use chrono_tz::Tz;
+use std::time::Duration;
+
+// --------------------------------------------------------------------------------------------------------------
+// Local time
+//
+// Tariffs, daily counters and calendar-based settings all follow Belgian local time, independent of the host's
+// own timezone setting.
+//
+// The host clock is compared with the P1 meter's once per reading. The meter stamps each gas reading with its
+// own time, and a stamp is normally somewhat behind the host: up to the gas update interval. A stamp ahead
+// of the host, or further behind than that interval, is clock skew, and is warned about once it exceeds
+// `clock_skew_warn_seconds`. Skew points at a host without NTP or with the wrong timezone, which would shift
+// day boundaries and time windows. Staleness and settle times are measured on the host's monotonic clock and
+// are not affected.
+// --------------------------------------------------------------------------------------------------------------
+
+/// Timezone of the installation.
+pub const LOCAL_TZ: Tz = chrono_tz::Europe::Brussels;
+
+/// Current time in the installation's timezone.
+pub fn local_now() -> DateTime<Tz> {
+    Utc::now().with_timezone(&LOCAL_TZ)
+}
+
+/// Seconds the meter clock is ahead (positive) or behind (negative) the host, given the time `stamped` on its
+/// latest reading, which normally lags `now` by up to `max_lag`. 0 within that window.
+pub fn clock_skew_seconds(stamped: DateTime<Utc>, max_lag: Duration, now: DateTime<Utc>) -> i64 {
+    let offset = (stamped - now).num_seconds();
+    if offset > 0 {
+        offset
+    } else {
+        (offset + max_lag.as_secs() as i64).min(0)
+    }
+}
+
+/// Warns once per stretch of `skew_s` beyond `warn_seconds`, tracking it in `alarm`. Returns the alarm text for
+/// the daily report when the skew first exceeds the limit.
+pub fn check_clock_skew(skew_s: i64, warn_seconds: u64, alarm: &mut bool) -> Option<String> {
+    let skewed = skew_s.unsigned_abs() > warn_seconds;
+    if skewed == *alarm {
+        return None;
+    }
+    *alarm = skewed;
+    if skewed {
+        let side = if skew_s > 0 { "ahead of" } else { "behind" };
+        log::warn!(
+            "[EMS] P1 meter clock {}s {} the host (limit {}s) - check NTP and the timezone",
+            skew_s.unsigned_abs(), side, warn_seconds
+        );
+        Some(format!("clock skew {}s", skew_s))
+    } else {
+        log::info!("[EMS] P1 meter and host clocks agree again ({}s)", skew_s);
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+    use crate::runtime::log_capture::{capture, warnings};
+
+    #[test]
+    fn a_gas_stamp_within_the_update_interval_is_no_skew() {
+        let now     = Utc.with_ymd_and_hms(2026, 10, 15, 12, 0, 0).unwrap();
+        let max_lag = Duration::from_secs(300);
+        assert_eq!(clock_skew_seconds(now - chrono::Duration::seconds(240), max_lag, now), 0);
+        assert_eq!(clock_skew_seconds(now + chrono::Duration::seconds(90), max_lag, now), 90);
+        assert_eq!(clock_skew_seconds(now - chrono::Duration::seconds(720), max_lag, now), -420);
+    }
+
+    #[test]
+    fn a_meter_clock_minutes_off_warns_once() {
+        let now       = Utc.with_ymd_and_hms(2026, 10, 15, 12, 0, 0).unwrap();
+        let stamped   = now + chrono::Duration::minutes(6);
+        let skew_s    = clock_skew_seconds(stamped, Duration::from_secs(300), now);
+        let mut alarm = false;
+
+        let (report, lines) = capture(|| check_clock_skew(skew_s, 120, &mut alarm));
+        assert_eq!(report.as_deref(), Some("clock skew 360s"));
+        let expected = "[EMS] P1 meter clock 360s ahead of the host (limit 120s) - check NTP and the timezone";
+        assert_eq!(warnings(&lines), [expected]);
+
+        let (report, lines) = capture(|| check_clock_skew(skew_s, 120, &mut alarm));
+        assert!(report.is_none() && lines.is_empty());
+
+        let (_, lines) = capture(|| check_clock_skew(0, 120, &mut alarm));
+        assert_eq!(lines[0].1, "[EMS] P1 meter and host clocks agree again (0s)");
+        assert!(!alarm);
+    }
+}
@@ -0,0 +1,62 @@
+use crate::configuration::config::{Config, PowerUnit};
+
+// --------------------------------------------------------------------------------------------------------------
+// Operator-facing number formatting
+//
+// Only the human-readable log lines go through this; the cycle outcome, the state file and the InfluxDB export
+// keep base units (W) so downstream tooling never has to guess.
+// --------------------------------------------------------------------------------------------------------------
+
+#[derive(Debug, Clone, Copy)]
+pub struct PowerFormat {
+    unit:     PowerUnit,
+    decimals: usize,
+}
+
+impl PowerFormat {
+    pub fn from_config(config: &Config) -> Self {
+        let unit = config.power_display_unit;
+        let decimals = config.power_decimals.unwrap_or(match unit {
+            PowerUnit::W  => 0,
+            PowerUnit::KW => 2,
+        });
+        Self { unit, decimals }
+    }
+
+    /// `watts` in the configured unit, e.g. "2400W" or "2.40 kW".
+    pub fn power(&self, watts: impl Into<f64>) -> String {
+        let (value, suffix) = self.scale(watts.into());
+        format!("{:.*}{}", self.decimals, value, suffix)
+    }
+
+    /// Like `power`, always with a sign: "+2400W", "-0.35 kW".
+    pub fn signed(&self, watts: impl Into<f64>) -> String {
+        let (value, suffix) = self.scale(watts.into());
+        format!("{:+.*}{}", self.decimals, value, suffix)
+    }
+
+    fn scale(&self, watts: f64) -> (f64, &'static str) {
+        match self.unit {
+            PowerUnit::W  => (watts, "W"),
+            PowerUnit::KW => (watts / 1000.0, " kW"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn format(unit: PowerUnit, decimals: Option<usize>) -> PowerFormat {
+        let config = Config { power_display_unit: unit, power_decimals: decimals, ..Config::default() };
+        PowerFormat::from_config(&config)
+    }
+
+    #[test]
+    fn power_renders_in_the_configured_unit() {
+        assert_eq!(format(PowerUnit::KW, None).power(2400), "2.40 kW");
+        assert_eq!(format(PowerUnit::KW, Some(1)).signed(-350), "-0.3 kW");
+        assert_eq!(format(PowerUnit::W, None).power(2400), "2400W");
+        assert_eq!(format(PowerUnit::W, None).signed(2400), "+2400W");
+    }
+}
@@ -0,0 +1,90 @@
+use chrono::{DateTime, TimeDelta, Utc};
+use log::info;
+use serde::{Deserialize, Serialize};
+
+use super::clock::LOCAL_TZ;
+
+// --------------------------------------------------------------------------------------------------------------
+// Learning period
+//
+// With `learning_period_hours` set, a new installation first only watches. For that long after its first start
+// the loop runs as it would live - readings, strategy, the stages after it - and logs and records each decision,
+// but sends no command and leaves the inverter in its own mode (as the `monitor` strategy does, only with real
+// decisions to compare against what the inverter does by itself). The direction-change limit and the power ramp
+// are skipped: their state follows the commands sent, and would otherwise start live control with a used-up
+// budget and a ramp from a power never sent. When the period is over the loop logs the switch and takes control
+// from the next cycle on.
+//
+// The start is kept in the state file, so a restart continues the period instead of beginning a new one. Without
+// a state file, or with one older than `state_max_age_seconds`, every start begins a new period.
+// --------------------------------------------------------------------------------------------------------------
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default)]
+pub struct LearningPeriod {
+    /// First cycle of the period; `None` until a period has started.
+    started_at: Option<DateTime<Utc>>,
+    /// Whether the previous cycle was observing, to log the switch to live control once.
+    #[serde(skip)]
+    observing:  bool,
+}
+
+impl LearningPeriod {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Whether the cycle at `now` only observes. The first call with a period configured starts it.
+    pub fn observing(&mut self, now: DateTime<Utc>, hours: f64) -> bool {
+        if hours <= 0.0 {
+            return false;
+        }
+        let started_at = *self.started_at.get_or_insert(now);
+        let ends_at    = started_at + TimeDelta::milliseconds((hours * 3_600_000.0) as i64);
+        let observing  = now < ends_at;
+        if observing && !self.observing {
+            let ends = ends_at.with_timezone(&LOCAL_TZ).format("%Y-%m-%d %H:%M");
+            if started_at == now {
+                info!("[EMS] Learning period: observing only, no commands until {}", ends);
+            } else {
+                info!("[EMS] Learning period continues: observing only, no commands until {}", ends);
+            }
+        } else if !observing && self.observing {
+            info!("[EMS] Learning period over after {} h - switching to live control", hours);
+        }
+        self.observing = observing;
+        observing
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn control_is_suppressed_during_the_period_and_enabled_after() {
+        let start      = Utc.with_ymd_and_hms(2026, 10, 15, 8, 0, 0).unwrap();
+        let mut period = LearningPeriod::new();
+        assert!(period.observing(start, 24.0));
+        assert!(period.observing(start + TimeDelta::hours(23), 24.0));
+        assert!(!period.observing(start + TimeDelta::hours(24), 24.0));
+        assert!(!period.observing(start + TimeDelta::hours(30), 24.0));
+    }
+
+    #[test]
+    fn a_restart_continues_the_period() {
+        let start      = Utc.with_ymd_and_hms(2026, 10, 15, 8, 0, 0).unwrap();
+        let mut period = LearningPeriod::new();
+        period.observing(start, 24.0);
+        let saved        = serde_json::to_string(&period).unwrap();
+        let mut restored = serde_json::from_str::<LearningPeriod>(&saved).unwrap();
+        assert!(restored.observing(start + TimeDelta::hours(12), 24.0));
+        assert!(!restored.observing(start + TimeDelta::hours(25), 24.0));
+    }
+
+    #[test]
+    fn without_a_period_control_is_live_at_once() {
+        let start = Utc.with_ymd_and_hms(2026, 10, 15, 8, 0, 0).unwrap();
+        assert!(!LearningPeriod::new().observing(start, 0.0));
+    }
+}
@@ -0,0 +1,47 @@
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use std::cell::RefCell;
+use std::sync::Once;
+
+// --------------------------------------------------------------------------------------------------------------
+// Log capture for the tests
+//
+// Some behaviour is only visible in the log (a drift warning, a clock-skew warning). `capture` runs a closure
+// and returns the lines it logged, per thread, so tests running in parallel don't see each other's lines.
+// --------------------------------------------------------------------------------------------------------------
+
+thread_local! {
+    static LINES: RefCell<Vec<(Level, String)>> = const { RefCell::new(Vec::new()) };
+}
+
+struct Capture;
+
+impl Log for Capture {
+    fn enabled(&self, _: &Metadata) -> bool {
+        true
+    }
+
+    fn log(&self, record: &Record) {
+        LINES.with(|lines| lines.borrow_mut().push((record.level(), record.args().to_string())));
+    }
+
+    fn flush(&self) {}
+}
+
+static CAPTURE: Capture = Capture;
+static INSTALL: Once = Once::new();
+
+/// Run `f` and return its result with the lines it logged on this thread.
+pub fn capture<T>(f: impl FnOnce() -> T) -> (T, Vec<(Level, String)>) {
+    INSTALL.call_once(|| {
+        let _ = log::set_logger(&CAPTURE);
+        log::set_max_level(LevelFilter::Trace);
+    });
+    LINES.with(|lines| lines.borrow_mut().clear());
+    let result = f();
+    (result, LINES.with(|lines| lines.take()))
+}
+
+/// The warnings among `lines`.
+pub fn warnings(lines: &[(Level, String)]) -> Vec<&str> {
+    lines.iter().filter(|(level, _)| *level == Level::Warn).map(|(_, line)| line.as_str()).collect()
+}
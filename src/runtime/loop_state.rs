@@ -0,0 +1,177 @@
+use chrono::{DateTime, Utc};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::analytics::device_days::DeviceDayTracker;
+use crate::analytics::soh::SohTracker;
+use crate::configuration::profiles::ActiveProfile;
+use crate::configuration::soc_limits::SocLimitMismatch;
+use crate::handlers::gate::GateState;
+use crate::optimiser::charge_cap::GridChargeCap;
+use crate::optimiser::decision::Decision;
+use crate::optimiser::direction_limit::DirectionChanges;
+use crate::optimiser::handback::IdleHandback;
+use crate::optimiser::hold_band::HoldBand;
+use crate::optimiser::maintenance::MaintenanceCharge;
+use crate::optimiser::peak::MonthlyPeak;
+use crate::optimiser::plan::DayPlan;
+use crate::optimiser::pre_peak::PrePeakCharge;
+use crate::optimiser::throughput_cap::ThroughputCap;
+use crate::reporting::history::SharedDayHistory;
+use crate::reporting::DailyReporter;
+use super::learning::LearningPeriod;
+use super::recent::SharedRecentCycles;
+use super::retry_budget::RetryBudget;
+use super::state_file::PersistedState;
+
+// --------------------------------------------------------------------------------------------------------------
+// Control-loop state carried from one cycle to the next
+//
+// The current cycle ID is also published in a process-wide atomic so the log formatter can prefix every line
+// emitted during a cycle - from the loop itself or from any handler it calls - with the same `c<id>` tag.
+// Because the counter lives outside `LoopState`, IDs stay monotonic when the supervisor restarts the loop.
+// --------------------------------------------------------------------------------------------------------------
+
+static CURRENT_CYCLE: AtomicU64 = AtomicU64::new(0);
+
+/// ID of the cycle currently running (0 before the first cycle).
+pub fn current_cycle_id() -> u64 {
+    CURRENT_CYCLE.load(Ordering::Relaxed)
+}
+
+#[derive(Debug, Default)]
+pub struct LoopState {
+    /// Monotonically increasing ID of the current cycle.
+    pub cycle_id:          u64,
+    /// Name of the calendar profile in force, `None` for the default settings.
+    pub active_profile:    ActiveProfile,
+    /// Decision taken in the most recent cycle that ran the optimiser.
+    pub last_decision:     Option<Decision>,
+    /// Highest peak the meter has reported this month.
+    pub monthly_peak:      Option<MonthlyPeak>,
+    /// Capacity-tariff shaving target in force (W).
+    pub peak_target_w:     Option<i32>,
+    /// Discharge floor (SOC %) in force, from the schedule or the reserve.
+    pub discharge_floor:   Option<f64>,
+    /// Last MinSOC / MaxSOC (%) the device reported, kept for cycles in which they weren't read.
+    pub device_soc_limits: (Option<f64>, Option<f64>),
+    /// Config vs device SOC limit disagreements last logged, so each one is warned about once.
+    pub soc_mismatches:    Vec<SocLimitMismatch>,
+    /// Whether the previous cycle saw a grid outage (islanding).
+    pub grid_outage:       bool,
+    /// Whether the last grid frequency read was outside `grid_frequency_band_hz`.
+    pub frequency_alarm:   bool,
+    /// Whether the P1 meter clock was last seen more than `clock_skew_warn_seconds` off the host clock.
+    pub clock_skew_alarm:  bool,
+    /// Whether the previous cycle ran inside a feed-in curtailment window.
+    pub feed_in_curtailed: bool,
+    /// Start of the price slot last logged, so the import price is logged once per hour.
+    pub price_slot:        Option<DateTime<Utc>>,
+    /// Charge ↔ discharge changes within the last hour, for the relay-protection limit.
+    pub direction_changes: DirectionChanges,
+    /// How long the optimiser has been idle, for the hand-back to self-consumption.
+    pub idle_handback:     IdleHandback,
+    /// Charge target and discharge floor last reached, for the SOC hold band.
+    pub hold_band:         HoldBand,
+    /// Last value of the external grid-charge gate.
+    pub grid_charge_gate:  GateState,
+    /// Last value of the EV charging signal.
+    pub ev_signal:         GateState,
+    /// Whether the EV is charging this cycle; discharge is then limited to peak shaving.
+    pub ev_charging:       bool,
+    /// Energy charged from the grid today, for the daily cap.
+    pub grid_charge_cap:   GridChargeCap,
+    /// Energy charged plus discharged today, for the daily throughput cap.
+    pub throughput_cap:    ThroughputCap,
+    /// Low-SOC timer and progress of a maintenance charge.
+    pub maintenance:       MaintenanceCharge,
+    /// Peak-window energy of the previous days and whether a pre-peak top-up is running.
+    pub pre_peak:          PrePeakCharge,
+    /// Charge/discharge schedule over the price curve ("price_arbitrage" strategy).
+    pub plan:              Option<DayPlan>,
+    /// Capacity samples and month bookkeeping for the state-of-health trend.
+    pub soh:               SohTracker,
+    /// Totals of the current day for the daily report.
+    pub report:            DailyReporter,
+    /// Device daily counters: peaks of the running device day and the days closed by a reset.
+    pub device_days:       DeviceDayTracker,
+    /// Start of the observe-only learning period.
+    pub learning:          LearningPeriod,
+    /// Outcomes of the last cycles for `GET /recent`, shared with the API task (not persisted).
+    pub recent:            SharedRecentCycles,
+    /// Day summaries for `GET /history`, published from `report` every cycle (not persisted).
+    pub history:           SharedDayHistory,
+    /// Retry time left for this cycle's reads.
+    pub retry_budget:      RetryBudget,
+}
+
+impl LoopState {
+    pub fn new() -> Self {
+        Self { cycle_id: current_cycle_id(), ..Self::default() }
+    }
+
+    /// Continue from a state saved by a previous run.
+    pub fn restore(&mut self, saved: PersistedState) {
+        CURRENT_CYCLE.fetch_max(saved.cycle_id, Ordering::Relaxed);
+        self.cycle_id        = current_cycle_id();
+        self.last_decision   = saved.last_decision;
+        self.monthly_peak    = saved.monthly_peak;
+        self.soh             = saved.soh;
+        self.report          = saved.report;
+        self.grid_charge_cap = saved.grid_charge_cap;
+        self.throughput_cap  = saved.throughput_cap;
+        self.pre_peak        = saved.pre_peak;
+        self.device_days     = saved.device_days;
+        self.learning        = saved.learning;
+    }
+
+    /// The part of the state that survives a restart.
+    pub fn to_persisted(&self) -> PersistedState {
+        PersistedState {
+            saved_at:        Utc::now(),
+            cycle_id:        self.cycle_id,
+            last_decision:   self.last_decision.clone(),
+            monthly_peak:    self.monthly_peak,
+            soh:             self.soh.clone(),
+            report:          self.report.clone(),
+            grid_charge_cap: self.grid_charge_cap.clone(),
+            throughput_cap:  self.throughput_cap.clone(),
+            pre_peak:        self.pre_peak.clone(),
+            device_days:     self.device_days.clone(),
+            learning:        self.learning,
+        }
+    }
+
+    /// Start a new cycle and return its ID.
+    pub fn begin_cycle(&mut self) -> u64 {
+        self.cycle_id = CURRENT_CYCLE.fetch_add(1, Ordering::Relaxed) + 1;
+        self.cycle_id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The counter is process-wide and other tests may start cycles concurrently, so only ordering is asserted.
+    #[test]
+    fn every_cycle_gets_a_higher_id() {
+        let mut state = LoopState::new();
+        let first     = state.begin_cycle();
+        let second    = state.begin_cycle();
+        assert!(second > first);
+        assert_eq!(state.cycle_id, second);
+        assert!(current_cycle_id() >= second);
+    }
+
+    #[test]
+    fn a_restored_state_continues_after_the_saved_id() {
+        let mut saved = LoopState::new().to_persisted();
+        saved.cycle_id = current_cycle_id() + 1000;
+        let resume_after = saved.cycle_id;
+
+        let mut state = LoopState::new();
+        state.restore(saved);
+        assert!(state.cycle_id >= resume_after);
+        assert!(state.begin_cycle() > resume_after);
+    }
+}
@@ -0,0 +1,20 @@
+pub mod supervisor;
+pub mod cadence;
+pub mod outcome;
+pub mod clock;
+pub mod loop_state;
+pub mod state_file;
+pub mod display;
+pub mod telemetry;
+pub mod pipeline;
+pub mod watchdog;
+pub mod quiet;
+pub mod recovery;
+pub mod recent;
+pub mod api;
+pub mod retry_budget;
+pub mod shutdown;
+pub mod learning;
+pub mod reload;
+#[cfg(test)]
+pub(crate) mod log_capture;
@@ -0,0 +1,199 @@
+use serde::Serialize;
+
+use crate::optimiser::decision::Decision;
+
+// --------------------------------------------------------------------------------------------------------------
+// Per-cycle outcome
+//
+// One machine-readable record per control-loop cycle. It is logged as a single JSON line so operators can build
+// dashboards and alerts on it instead of parsing the free-text log lines.
+// --------------------------------------------------------------------------------------------------------------
+
+/// Terse status code summarising a cycle, most severe condition first.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum CycleStatus {
+    /// Every reading arrived and the cycle finished within the interval.
+    Ok,
+    /// The battery snapshot came back completely empty.
+    BatteryDown,
+    /// No P1 reading this cycle.
+    P1Down,
+    /// Some battery sensors were missing from the snapshot.
+    BatteryPartial,
+    /// All data arrived but the cycle took longer than the poll interval.
+    Overrun,
+}
+
+impl CycleStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CycleStatus::Ok             => "OK",
+            CycleStatus::BatteryDown    => "BATTERY_DOWN",
+            CycleStatus::P1Down         => "P1_DOWN",
+            CycleStatus::BatteryPartial => "BATTERY_PARTIAL",
+            CycleStatus::Overrun        => "OVERRUN",
+        }
+    }
+}
+
+/// Structured summary of one cycle.
+#[derive(Serialize, Debug, Clone)]
+pub struct CycleOutcome {
+    pub cycle_id:                u64,
+    pub status:                  CycleStatus,
+    pub p1_ok:                   bool,
+    /// Kind of P1 failure ("http", "parse", "not_json", "timeout", "stale") when `p1_ok` is false.
+    pub p1_error:                Option<&'static str>,
+    pub battery_keys_missing:    usize,
+    pub battery_keys_total:      usize,
+    /// 0-100 confidence in this cycle's readings (see `analytics/data_quality.rs`).
+    pub data_quality:            Option<u8>,
+    pub decision:                Option<Decision>,
+    pub command_sent:            bool,
+    /// The cycle fell in the learning period: `decision` was only recorded, not sent.
+    pub learning:                bool,
+    /// What `shadow_strategy` would have decided; never dispatched.
+    pub shadow_decision:         Option<Decision>,
+    /// Minutes the battery lasts at the current load; only during a grid outage, while discharging.
+    pub backup_runtime_min:      Option<u64>,
+    /// Self-discharge estimate (%/h, positive = SOC falling) from the last long enough idle stretch.
+    pub self_discharge_per_hour: Option<f64>,
+    pub duration_ms:             u64,
+    pub overran:                 bool,
+}
+
+impl CycleOutcome {
+    pub fn new(
+        cycle_id:             u64,
+        p1_ok:                bool,
+        battery_keys_missing: usize,
+        battery_keys_total:   usize,
+        duration_ms:          u64,
+        interval_ms:          u64,
+    ) -> Self {
+        let overran = duration_ms >= interval_ms;
+        let status = if battery_keys_missing >= battery_keys_total {
+            CycleStatus::BatteryDown
+        } else if !p1_ok {
+            CycleStatus::P1Down
+        } else if battery_keys_missing > 0 {
+            CycleStatus::BatteryPartial
+        } else if overran {
+            CycleStatus::Overrun
+        } else {
+            CycleStatus::Ok
+        };
+
+        Self {
+            cycle_id,
+            status,
+            p1_ok,
+            p1_error: None,
+            battery_keys_missing,
+            battery_keys_total,
+            data_quality: None,
+            decision: None,
+            command_sent: false,
+            learning: false,
+            shadow_decision: None,
+            backup_runtime_min: None,
+            self_discharge_per_hour: None,
+            duration_ms,
+            overran,
+        }
+    }
+
+    /// Record why the P1 read failed.
+    pub fn with_p1_error(mut self, kind: Option<&'static str>) -> Self {
+        self.p1_error = kind;
+        self
+    }
+
+    /// Record the cycle's data quality score.
+    pub fn with_data_quality(mut self, score: u8) -> Self {
+        self.data_quality = Some(score);
+        self
+    }
+
+    /// Record what the optimiser decided and whether a command went out to the inverter.
+    pub fn with_decision(mut self, decision: Option<Decision>, command_sent: bool) -> Self {
+        self.decision     = decision;
+        self.command_sent = command_sent;
+        self
+    }
+
+    /// Record that the cycle fell in the learning period.
+    pub fn with_learning(mut self, learning: bool) -> Self {
+        self.learning = learning;
+        self
+    }
+
+    /// Record the shadow strategy's decision.
+    pub fn with_shadow_decision(mut self, decision: Option<Decision>) -> Self {
+        self.shadow_decision = decision;
+        self
+    }
+
+    /// Record the estimated backup runtime during a grid outage.
+    pub fn with_backup_runtime(mut self, minutes: Option<u64>) -> Self {
+        self.backup_runtime_min = minutes;
+        self
+    }
+
+    /// Record the idle self-discharge estimate.
+    pub fn with_self_discharge(mut self, percent_per_hour: Option<f64>) -> Self {
+        self.self_discharge_per_hour = percent_per_hour;
+        self
+    }
+
+    /// Single-line JSON form for the per-cycle log record.
+    pub fn to_log_line(&self) -> String {
+        serde_json::to_string(self).unwrap_or_else(|_| format!("{{\"status\":\"{}\"}}", self.status.as_str()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_clean_cycle_is_ok_and_records_its_decision() {
+        let decision = Decision::DischargingToLoad { watts: 1200, min_soc_percent: 20 };
+        let outcome  = CycleOutcome::new(7, true, 0, 10, 850, 30_000)
+            .with_data_quality(100)
+            .with_decision(Some(decision.clone()), true);
+
+        assert_eq!(outcome.cycle_id, 7);
+        assert_eq!(outcome.status, CycleStatus::Ok);
+        assert!(outcome.p1_ok);
+        assert_eq!(outcome.battery_keys_missing, 0);
+        assert_eq!(outcome.data_quality, Some(100));
+        assert_eq!(outcome.decision, Some(decision));
+        assert!(outcome.command_sent);
+        assert_eq!(outcome.duration_ms, 850);
+        assert!(!outcome.overran);
+    }
+
+    #[test]
+    fn the_status_code_names_the_worst_problem() {
+        let status = |p1_ok, missing, duration_ms| {
+            CycleOutcome::new(1, p1_ok, missing, 10, duration_ms, 30_000).status
+        };
+        assert_eq!(status(false, 10, 100), CycleStatus::BatteryDown);
+        assert_eq!(status(false, 3, 100), CycleStatus::P1Down);
+        assert_eq!(status(true, 3, 100), CycleStatus::BatteryPartial);
+        assert_eq!(status(true, 0, 30_000), CycleStatus::Overrun);
+    }
+
+    #[test]
+    fn the_log_line_is_one_json_record() {
+        let line = CycleOutcome::new(3, false, 0, 10, 100, 30_000).with_p1_error(Some("timeout")).to_log_line();
+        assert!(!line.contains('\n'));
+
+        let json: serde_json::Value = serde_json::from_str(&line).unwrap();
+        assert_eq!(json["cycle_id"], 3);
+        assert_eq!(json["status"], "P1_DOWN");
+        assert_eq!(json["p1_error"], "timeout");
+    }
+}
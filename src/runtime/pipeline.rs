@@ -0,0 +1,106 @@
+use log::warn;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use tokio::sync::broadcast;
+
+use super::outcome::CycleOutcome;
+
+// --------------------------------------------------------------------------------------------------------------
+// Cycle record pipeline
+//
+// The control loop must never wait on a sink. Each cycle it publishes one `CycleRecord` on a bounded channel
+// and moves on; sink workers (InfluxDB today) consume records in their own supervised tasks. A
+// `tokio::sync::broadcast` channel is used rather than `mpsc` because it has exactly the overflow behaviour
+// wanted here: the sender never blocks, and a worker that falls more than `RECORD_CHANNEL_CAPACITY` records
+// behind loses the oldest ones and is told how many. Those are counted in `dropped_records()`.
+// --------------------------------------------------------------------------------------------------------------
+
+/// Records a worker may fall behind before the oldest are dropped (~30 min at a 30 s interval).
+pub const RECORD_CHANNEL_CAPACITY: usize = 64;
+
+/// Everything the sinks need from one cycle.
+#[derive(Debug, Clone)]
+pub struct CycleRecord {
+    pub outcome:      CycleOutcome,
+    /// InfluxDB line-protocol lines for this cycle's measurements.
+    #[cfg(feature = "influxdb")]
+    pub influx_lines: Vec<String>,
+}
+
+pub type RecordSender = broadcast::Sender<Arc<CycleRecord>>;
+
+static DROPPED_RECORDS: AtomicU64 = AtomicU64::new(0);
+
+/// Total records dropped by slow workers since start-up.
+pub fn dropped_records() -> u64 {
+    DROPPED_RECORDS.load(Ordering::Relaxed)
+}
+
+pub fn record_channel() -> RecordSender {
+    broadcast::channel(RECORD_CHANNEL_CAPACITY).0
+}
+
+/// Count and log `n` records `worker` missed.
+fn lagged(worker: &str, n: u64) {
+    let total = DROPPED_RECORDS.fetch_add(n, Ordering::Relaxed) + n;
+    warn!("[Pipeline] {} fell behind - dropped {} oldest record(s) ({} total)", worker, n, total);
+}
+
+/// Next record for a worker, counting and logging any it missed. `None` once the channel is closed.
+pub async fn next_record(
+    rx: &mut broadcast::Receiver<Arc<CycleRecord>>,
+    worker: &str,
+) -> Option<Arc<CycleRecord>> {
+    loop {
+        match rx.recv().await {
+            Ok(record) => return Some(record),
+            Err(broadcast::error::RecvError::Lagged(n)) => lagged(worker, n),
+            Err(broadcast::error::RecvError::Closed)    => return None,
+        }
+    }
+}
+
+/// Every record still waiting for a worker, without waiting for new ones. Used for the last flush at shutdown.
+pub fn drain_records(rx: &mut broadcast::Receiver<Arc<CycleRecord>>, worker: &str) -> Vec<Arc<CycleRecord>> {
+    let mut records = Vec::new();
+    loop {
+        match rx.try_recv() {
+            Ok(record) => records.push(record),
+            Err(broadcast::error::TryRecvError::Lagged(n)) => lagged(worker, n),
+            Err(_) => return records,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(cycle_id: u64) -> Arc<CycleRecord> {
+        Arc::new(CycleRecord {
+            outcome:      CycleOutcome::new(cycle_id, true, 0, 10, 100, 30_000),
+            #[cfg(feature = "influxdb")]
+            influx_lines: Vec::new(),
+        })
+    }
+
+    #[tokio::test]
+    async fn a_slow_worker_loses_the_oldest_records_and_they_are_counted() {
+        let tx     = record_channel();
+        let mut rx = tx.subscribe();
+        let before = dropped_records();
+
+        // The loop never waits: 10 more records than the channel holds go out while the worker is busy.
+        for cycle_id in 0..RECORD_CHANNEL_CAPACITY as u64 + 10 {
+            tx.send(record(cycle_id)).unwrap();
+        }
+        let next = next_record(&mut rx, "slow worker").await.unwrap();
+        assert_eq!(next.outcome.cycle_id, 10);
+        assert_eq!(dropped_records() - before, 10);
+
+        let rest = drain_records(&mut rx, "slow worker");
+        assert_eq!(rest.len(), RECORD_CHANNEL_CAPACITY - 1);
+        drop(tx);
+        assert!(next_record(&mut rx, "slow worker").await.is_none());
+    }
+}
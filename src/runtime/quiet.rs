@@ -0,0 +1,94 @@
+use log::{info, Level};
+use std::time::{Duration, Instant};
+
+// --------------------------------------------------------------------------------------------------------------
+// Quiet logging while idle
+//
+// Overnight a 30 s loop logs the same four info lines every cycle with nothing happening. With
+// `quiet_when_idle`, a cycle whose decision is `Idle` and whose readings are complete (P1 read, no battery
+// sensor missing) logs its per-cycle lines (reconciliation, decision, outcome, cycle timing) at debug level
+// instead. An info heartbeat every `idle_heartbeat_minutes` shows the loop is alive; the start and end of a
+// quiet stretch are logged too. Any other decision, a missing reading, warnings and errors log as usual.
+//
+// The reconciliation line is logged before the decision is known, so it follows the previous cycle: the first
+// cycle of a quiet stretch still logs it at info, the first cycle after one at debug.
+// --------------------------------------------------------------------------------------------------------------
+
+/// Tracks the current stretch of quiet idle cycles.
+#[derive(Debug, Default)]
+pub struct QuietIdle {
+    since:          Option<Instant>,
+    last_heartbeat: Option<Instant>,
+    cycles:         u64,
+}
+
+impl QuietIdle {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Level for per-cycle summary lines: debug inside a quiet stretch, info otherwise.
+    pub fn level(&self) -> Level {
+        if self.since.is_some() { Level::Debug } else { Level::Info }
+    }
+
+    /// Record whether this cycle qualifies as quiet (idle with nominal readings, and quiet mode on). Logs the
+    /// start and end of a stretch and the heartbeat.
+    pub fn observe(&mut self, quiet: bool, now: Instant, heartbeat: Duration, soc: f64) {
+        let Some(since) = self.since else {
+            if quiet {
+                info!(
+                    "[EMS] Idle with nominal readings - per-cycle lines at debug level, heartbeat every {} min",
+                    heartbeat.as_secs() / 60
+                );
+                self.since          = Some(now);
+                self.last_heartbeat = Some(now);
+                self.cycles         = 1;
+            }
+            return;
+        };
+
+        let minutes = now.duration_since(since).as_secs() / 60;
+        if !quiet {
+            info!("[EMS] Idle stretch ended after {} min ({} cycles)", minutes, self.cycles);
+            *self = Self::default();
+            return;
+        }
+
+        self.cycles += 1;
+        if self.last_heartbeat.is_none_or(|at| now.duration_since(at) >= heartbeat) {
+            info!("[EMS] Still idle for {} min ({} cycles), SOC {:.1}%", minutes, self.cycles, soc);
+            self.last_heartbeat = Some(now);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runtime::log_capture::capture;
+
+    #[test]
+    fn a_run_of_idle_cycles_logs_a_heartbeat_at_the_configured_cadence() {
+        let start     = Instant::now();
+        let cycle     = Duration::from_secs(30);
+        let heartbeat = Duration::from_secs(5 * 60);
+        let mut quiet = QuietIdle::new();
+
+        // 20 minutes of idle cycles: the stretch start, then one heartbeat every 5 minutes.
+        let ((), lines) = capture(|| {
+            for n in 0..=40 {
+                quiet.observe(true, start + cycle * n, heartbeat, 55.0);
+            }
+        });
+        let heartbeats: Vec<_> = lines.iter().filter(|(_, line)| line.starts_with("[EMS] Still idle")).collect();
+        assert_eq!(lines.len(), 1 + heartbeats.len());
+        assert_eq!(heartbeats.len(), 4);
+        assert_eq!(heartbeats[3].1, "[EMS] Still idle for 20 min (41 cycles), SOC 55.0%");
+        assert_eq!(quiet.level(), Level::Debug);
+
+        let ((), lines) = capture(|| quiet.observe(false, start + cycle * 41, heartbeat, 55.0));
+        assert_eq!(lines[0].1, "[EMS] Idle stretch ended after 20 min (41 cycles)");
+        assert_eq!(quiet.level(), Level::Info);
+    }
+}
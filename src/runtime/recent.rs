@@ -0,0 +1,105 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+use super::outcome::CycleOutcome;
+use crate::optimiser::trace::DecisionTrace;
+
+// --------------------------------------------------------------------------------------------------------------
+// Recent cycles
+//
+// For quick debugging without a database, the last `recent_cycles` cycle outcomes are kept in memory and served
+// by `GET /recent` (see `api.rs`), together with the decision trace of the latest cycle (`GET /decision`). The
+// buffer is created once at start-up and shared between the control loop,
+// which pushes one outcome per cycle, and the API task, which reads it; a supervised restart of the loop keeps
+// the history. The lock is only held to push or copy outcomes, never across an await.
+// --------------------------------------------------------------------------------------------------------------
+
+/// Fixed-size buffer of the most recent cycle outcomes, oldest first.
+#[derive(Debug, Default)]
+pub struct RecentCycles {
+    capacity: usize,
+    outcomes: VecDeque<CycleOutcome>,
+    trace:    Option<DecisionTrace>,
+}
+
+/// The buffer as shared between the control loop and the API task.
+pub type SharedRecentCycles = Arc<Mutex<RecentCycles>>;
+
+impl RecentCycles {
+    pub fn new(capacity: usize) -> Self {
+        Self { capacity, outcomes: VecDeque::with_capacity(capacity), trace: None }
+    }
+
+    pub fn shared(capacity: usize) -> SharedRecentCycles {
+        Arc::new(Mutex::new(Self::new(capacity)))
+    }
+
+    pub fn len(&self) -> usize {
+        self.outcomes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.outcomes.is_empty()
+    }
+
+    /// Add the outcome of the cycle that just finished, evicting the oldest once full.
+    pub fn push(&mut self, outcome: CycleOutcome) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.outcomes.len() >= self.capacity {
+            self.outcomes.pop_front();
+        }
+        self.outcomes.push_back(outcome);
+    }
+
+    /// The last `n` outcomes in cycle order, or all of them when fewer are kept.
+    pub fn latest(&self, n: usize) -> Vec<CycleOutcome> {
+        let skip = self.outcomes.len().saturating_sub(n);
+        self.outcomes.iter().skip(skip).cloned().collect()
+    }
+
+    /// Keep `trace` as the latest cycle's decision trace.
+    pub fn set_trace(&mut self, trace: DecisionTrace) {
+        self.trace = Some(trace);
+    }
+
+    /// Decision trace of the latest cycle, `None` before the first one.
+    pub fn trace(&self) -> Option<&DecisionTrace> {
+        self.trace.as_ref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cycle(cycle_id: u64) -> CycleOutcome {
+        CycleOutcome::new(cycle_id, true, 0, 10, 100, 30_000)
+    }
+
+    fn ids(outcomes: &[CycleOutcome]) -> Vec<u64> {
+        outcomes.iter().map(|o| o.cycle_id).collect()
+    }
+
+    #[test]
+    fn a_full_buffer_evicts_the_oldest_cycle() {
+        let mut recent = RecentCycles::new(3);
+        for id in 1..=3 {
+            recent.push(cycle(id));
+        }
+        assert_eq!(ids(&recent.latest(10)), [1, 2, 3]);
+
+        recent.push(cycle(4));
+        assert_eq!(recent.len(), 3);
+        assert_eq!(ids(&recent.latest(10)), [2, 3, 4]);
+        assert_eq!(ids(&recent.latest(2)), [3, 4]);
+    }
+
+    #[test]
+    fn a_zero_capacity_keeps_nothing() {
+        let mut recent = RecentCycles::new(0);
+        recent.push(cycle(1));
+        assert!(recent.is_empty());
+    }
+}
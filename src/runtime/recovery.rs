@@ -0,0 +1,96 @@
+use log::{error, info, warn};
+
+use crate::handlers::indevolt::controller::{restore_auto_mode, stop};
+use crate::models::indevolt_models::WorkingMode;
+use crate::optimiser::decision::Decision;
+
+// --------------------------------------------------------------------------------------------------------------
+// Startup recovery
+//
+// A run that crashed (or was killed) mid-command can leave the inverter in real-time mode, still executing the
+// last charge or discharge with nobody steering it. Before the loop starts, the working mode is read once. If
+// the device is in real-time mode and the state file holds no decision that needed it (no state file, an
+// outdated one, or a last decision of `SelfConsumption`), the command is stopped and self-consumption restored.
+// When the persisted decision explains the mode, the device is left alone and the first cycle takes over.
+//
+// Running it again changes nothing: a device already in self-consumption is not written to. A mode that
+// couldn't be read is left to the first cycle, which sends its own decision regardless.
+// --------------------------------------------------------------------------------------------------------------
+
+/// True when the device is in real-time mode that `intent` (the persisted last decision) doesn't account for.
+pub fn unintended_realtime(mode: Option<WorkingMode>, intent: Option<&Decision>) -> bool {
+    mode == Some(WorkingMode::RealtimeControl)
+        && intent.is_none_or(|decision| *decision == Decision::SelfConsumption)
+}
+
+/// Stop and hand back a device left in real-time mode by a previous run. Returns true when it was reset.
+pub async fn recover_realtime_mode(
+    base_url: &str,
+    mode: Option<WorkingMode>,
+    intent: Option<&Decision>,
+) -> bool {
+    if !unintended_realtime(mode, intent) {
+        match (mode, intent) {
+            (None, _) => warn!("[EMS] Working mode unknown at startup - the first cycle takes over"),
+            (Some(WorkingMode::RealtimeControl), Some(decision)) => {
+                info!("[EMS] Device in real-time mode, continuing persisted {}", decision)
+            }
+            _ => {}
+        }
+        return false;
+    }
+
+    warn!("[EMS] Device left in real-time mode without a persisted decision - stopping and restoring auto mode");
+    if let Err(e) = stop(base_url).await {
+        error!("[EMS] Startup stop failed: {}", e);
+    }
+    match restore_auto_mode(base_url).await {
+        Ok(()) => {
+            info!("[EMS] Recovered: device back in self-consumption mode");
+            true
+        }
+        Err(e) => {
+            error!("[EMS] Startup restore of self-consumption mode failed: {}", e);
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::handlers::indevolt::registers::{REG_CONTROL, REG_WORKING_MODE};
+    use crate::handlers::indevolt::testing::MockIndevolt;
+
+    async fn accepting_device() -> MockIndevolt {
+        MockIndevolt::start(|_| (200, r#"{"result": true}"#.to_string())).await
+    }
+
+    #[tokio::test]
+    async fn realtime_mode_without_a_persisted_decision_is_reset_to_auto() {
+        let device = accepting_device().await;
+        assert!(recover_realtime_mode(&device.base_url, Some(WorkingMode::RealtimeControl), None).await);
+        assert_eq!(device.requests().len(), 2);
+        assert!(device.wrote(REG_CONTROL, "[0,0,0]"));
+        assert!(device.wrote(REG_WORKING_MODE, "[1]"));
+
+        // A persisted hand-back doesn't explain real-time mode either.
+        let device      = accepting_device().await;
+        let handed_back = Decision::SelfConsumption;
+        let realtime    = Some(WorkingMode::RealtimeControl);
+        assert!(recover_realtime_mode(&device.base_url, realtime, Some(&handed_back)).await);
+        assert!(device.wrote(REG_WORKING_MODE, "[1]"));
+    }
+
+    #[tokio::test]
+    async fn an_explained_or_unknown_mode_is_left_alone() {
+        let device    = accepting_device().await;
+        let discharge = Decision::DischargingToLoad { watts: 800, min_soc_percent: 20 };
+        let realtime  = Some(WorkingMode::RealtimeControl);
+        let auto      = Some(WorkingMode::SelfConsumedPrioritized);
+        assert!(!recover_realtime_mode(&device.base_url, realtime, Some(&discharge)).await);
+        assert!(!recover_realtime_mode(&device.base_url, auto, None).await);
+        assert!(!recover_realtime_mode(&device.base_url, None, None).await);
+        assert!(device.requests().is_empty());
+    }
+}
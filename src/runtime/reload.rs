@@ -0,0 +1,228 @@
+use log::{error, info};
+use std::sync::Arc;
+use tokio::sync::watch;
+
+use crate::configuration::config::{load_config, Config, ConfigError};
+use crate::configuration::effective::log_changed_settings;
+
+// --------------------------------------------------------------------------------------------------------------
+// Config reload
+//
+// On SIGHUP (`kill -HUP <pid>`) config.json is loaded again exactly as at start-up: parsed, `EMS_*` environment
+// overrides applied and validated, profiles and URLs included. Only a configuration that passes is published,
+// on the same kind of watch channel the price curve uses; otherwise the error is logged and the running
+// configuration stays. Every setting that changed is logged with its old and new value, secrets masked.
+//
+// The channel is the shared `RwLock<Config>` with change notification: readers borrow the latest value, and
+// the control loop is woken when it changes. It picks the new configuration up between cycles: the cycle in
+// progress finishes with the settings it started with, and a new poll interval or wall-clock alignment takes
+// effect for the next wait. The dispatcher and the detectors take their new settings without losing state;
+// the watchdog and the API read the running configuration whenever they need it.
+//
+// What is only read at start-up - the P1 HTTP client, the sink and price tasks, the API listener, the logger,
+// the watchdog timeout - cannot change without a restart. A reload that changes one of those settings keeps
+// the running value for it and logs an error naming it; the rest of the reload still applies.
+// --------------------------------------------------------------------------------------------------------------
+
+/// Reload the configuration on every SIGHUP and publish it on `tx` when it is valid.
+pub async fn reload_on_sighup(tx: watch::Sender<Arc<Config>>) {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+        let mut hangups = match signal(SignalKind::hangup()) {
+            Ok(hangups) => hangups,
+            Err(e) => {
+                error!("[Config] Cannot listen for SIGHUP: {} - reload disabled", e);
+                return;
+            }
+        };
+        while hangups.recv().await.is_some() {
+            info!("[Config] SIGHUP received - reloading config.json");
+            publish(&tx, load_config());
+        }
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = tx;
+        std::future::pending::<()>().await;
+    }
+}
+
+/// Publish a `loaded` configuration on `tx` when it differs from the running one. Returns whether it was
+/// published; a configuration that failed to load or validate keeps the running one.
+fn publish(tx: &watch::Sender<Arc<Config>>, loaded: Result<Config, ConfigError>) -> bool {
+    match loaded {
+        Ok(mut config) => {
+            let running = tx.borrow().clone();
+            let kept = keep_restart_only(&running, &mut config);
+            if !kept.is_empty() {
+                error!("[Config] Restart needed to change {} - keeping the running value(s)", kept.join(", "));
+            }
+            let changed = log_changed_settings(&running, &config);
+            if changed > 0 {
+                tx.send_replace(Arc::new(config));
+            }
+            info!("[Config] Reloaded - {} setting(s) changed", changed);
+            changed > 0
+        }
+        Err(e) => {
+            error!("[Config] Reload failed, keeping the running configuration: {}", e);
+            false
+        }
+    }
+}
+
+/// Put the running value back into `loaded` for every setting that is only read at start-up. Returns the
+/// names of the settings the reload tried to change.
+fn keep_restart_only(running: &Config, loaded: &mut Config) -> Vec<&'static str> {
+    let mut kept = Vec::new();
+    let mut keep = |name: &'static str, changed: bool| {
+        if changed {
+            kept.push(name);
+        }
+        changed
+    };
+
+    // The P1 HTTP client.
+    if keep("p1_ca_cert_path", loaded.p1_ca_cert_path != running.p1_ca_cert_path) {
+        loaded.p1_ca_cert_path = running.p1_ca_cert_path.clone();
+    }
+    if keep("p1_accept_invalid_certs", loaded.p1_accept_invalid_certs != running.p1_accept_invalid_certs) {
+        loaded.p1_accept_invalid_certs = running.p1_accept_invalid_certs;
+    }
+    if keep("p1_timeout_ms", loaded.p1_timeout_ms != running.p1_timeout_ms) {
+        loaded.p1_timeout_ms = running.p1_timeout_ms;
+    }
+    if keep("pool_idle_timeout_seconds", loaded.pool_idle_timeout_seconds != running.pool_idle_timeout_seconds) {
+        loaded.pool_idle_timeout_seconds = running.pool_idle_timeout_seconds;
+    }
+    if keep("pool_max_idle_per_host", loaded.pool_max_idle_per_host != running.pool_max_idle_per_host) {
+        loaded.pool_max_idle_per_host = running.pool_max_idle_per_host;
+    }
+
+    // Process-wide settings.
+    if keep("watchdog_timeout_seconds", loaded.watchdog_timeout_seconds != running.watchdog_timeout_seconds) {
+        loaded.watchdog_timeout_seconds = running.watchdog_timeout_seconds;
+    }
+    if keep("shutdown_timeout_seconds", loaded.shutdown_timeout_seconds != running.shutdown_timeout_seconds) {
+        loaded.shutdown_timeout_seconds = running.shutdown_timeout_seconds;
+    }
+    if keep("state_max_age_seconds", loaded.state_max_age_seconds != running.state_max_age_seconds) {
+        loaded.state_max_age_seconds = running.state_max_age_seconds;
+    }
+    if keep("log_level", loaded.log_level != running.log_level) {
+        loaded.log_level = running.log_level.clone();
+    }
+    if keep("otel_endpoint", loaded.otel_endpoint != running.otel_endpoint) {
+        loaded.otel_endpoint = running.otel_endpoint.clone();
+    }
+
+    // The API listener and its buffer.
+    if keep("api_listen", loaded.api_listen != running.api_listen) {
+        loaded.api_listen = running.api_listen.clone();
+    }
+    if keep("recent_cycles", loaded.recent_cycles != running.recent_cycles) {
+        loaded.recent_cycles = running.recent_cycles;
+    }
+
+    // The sink and price tasks.
+    if keep("influxdb_url", loaded.influxdb_url != running.influxdb_url) {
+        loaded.influxdb_url = running.influxdb_url.clone();
+    }
+    if keep("influxdb_token", loaded.influxdb_token != running.influxdb_token) {
+        loaded.influxdb_token = running.influxdb_token.clone();
+    }
+    if keep("influxdb_bucket", loaded.influxdb_bucket != running.influxdb_bucket) {
+        loaded.influxdb_bucket = running.influxdb_bucket.clone();
+    }
+    if keep("influxdb_org", loaded.influxdb_org != running.influxdb_org) {
+        loaded.influxdb_org = running.influxdb_org.clone();
+    }
+    if keep("price_source", loaded.price_source != running.price_source) {
+        loaded.price_source = running.price_source;
+    }
+    if keep("tibber_token", loaded.tibber_token != running.tibber_token) {
+        loaded.tibber_token = running.tibber_token.clone();
+    }
+    if keep("tibber_api_url", loaded.tibber_api_url != running.tibber_api_url) {
+        loaded.tibber_api_url = running.tibber_api_url.clone();
+    }
+    if keep("entsoe_token", loaded.entsoe_token != running.entsoe_token) {
+        loaded.entsoe_token = running.entsoe_token.clone();
+    }
+    if keep("entsoe_api_url", loaded.entsoe_api_url != running.entsoe_api_url) {
+        loaded.entsoe_api_url = running.entsoe_api_url.clone();
+    }
+    if keep("entsoe_area", loaded.entsoe_area != running.entsoe_area) {
+        loaded.entsoe_area = running.entsoe_area.clone();
+    }
+    if keep("price_csv_path", loaded.price_csv_path != running.price_csv_path) {
+        loaded.price_csv_path = running.price_csv_path.clone();
+    }
+    kept
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::configuration::config::{load_config_from, ENV_LOCK};
+
+    /// Reload from a config.json holding `json` into a loop running the defaults. Returns whether it was
+    /// published and the configuration the loop then runs.
+    fn reload(name: &str, json: &str) -> (bool, Arc<Config>) {
+        let path = std::env::temp_dir().join(format!("ems-reload-{}-{}.json", std::process::id(), name));
+        std::fs::write(&path, json).unwrap();
+        let (tx, rx) = watch::channel(Arc::new(Config::default()));
+        let published = {
+            let _env = ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+            publish(&tx, load_config_from(path.to_str().unwrap()))
+        };
+        std::fs::remove_file(&path).unwrap();
+        let running = rx.borrow().clone();
+        (published, running)
+    }
+
+    #[test]
+    fn a_valid_reload_is_published() {
+        let (published, running) = reload("valid", r#"{"poll_interval_seconds": 10}"#);
+        assert!(published);
+        assert_eq!(running.poll_interval_seconds, 10);
+    }
+
+    #[test]
+    fn a_reload_that_fails_validation_keeps_the_running_config() {
+        let invalid = [
+            ("soc", r#"{"battery_min_soc_percent": 100, "poll_interval_seconds": 10}"#),
+            ("url", r#"{"indevolt_url": "192.168.1.30", "poll_interval_seconds": 10}"#),
+            ("json", r#"{"poll_interval_seconds": "ten"}"#),
+        ];
+        for (name, json) in invalid {
+            let (published, running) = reload(name, json);
+            assert!(!published, "{}", name);
+            assert_eq!(running.poll_interval_seconds, Config::default().poll_interval_seconds);
+            assert_eq!(running.indevolt_url, Config::default().indevolt_url);
+        }
+    }
+
+    #[test]
+    fn a_setting_read_at_start_up_keeps_its_running_value() {
+        let json = r#"{"api_listen": "0.0.0.0:9000", "influxdb_bucket": "other", "poll_interval_seconds": 10}"#;
+        let (published, running) = reload("restart", json);
+        assert!(published);
+        assert_eq!(running.poll_interval_seconds, 10);
+        assert_eq!(running.api_listen, Config::default().api_listen);
+        assert_eq!(running.influxdb_bucket, Config::default().influxdb_bucket);
+    }
+
+    #[test]
+    fn only_settings_read_at_start_up_publishes_nothing() {
+        let (published, _) = reload("restart-only", r#"{"log_level": "trace"}"#);
+        assert!(!published);
+    }
+
+    #[test]
+    fn an_unchanged_file_publishes_nothing() {
+        let (published, _) = reload("same", "{}");
+        assert!(!published);
+    }
+}
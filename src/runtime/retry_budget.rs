@@ -0,0 +1,173 @@
+use log::{debug, warn};
+use std::future::Future;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+// --------------------------------------------------------------------------------------------------------------
+// Per-cycle retry budget
+//
+// A failed read may be retried, but retries of several reads together must not push the cycle past its
+// interval. Every cycle starts with `cycle_retry_budget_ms` of retry time, shared by all reads of that cycle
+// (the concurrent ones included). A retry only starts while budget is left, and is cut off when the rest runs
+// out; the read then keeps its failed result and the cycle carries on with its usual fallback (the Indevolt
+// meter for P1, held values for the battery). The first cycle that runs out is logged as a warning, following
+// ones at debug level until a cycle gets by within its budget again. A budget of 0 means no retries at all.
+// --------------------------------------------------------------------------------------------------------------
+
+/// Retries one read makes at most, budget permitting.
+pub const MAX_RETRIES: u32 = 2;
+/// Pause before each retry, counted against the budget.
+const RETRY_DELAY: Duration = Duration::from_millis(250);
+
+#[derive(Debug, Default)]
+struct Budget {
+    limit:         Duration,
+    spent:         Duration,
+    exhausted:     bool,
+    was_exhausted: bool,
+}
+
+/// Retry time left in the current cycle. Shared by reference between the reads running in that cycle.
+#[derive(Debug, Default)]
+pub struct RetryBudget {
+    budget: Mutex<Budget>,
+}
+
+impl RetryBudget {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Start a new cycle with `limit` of retry time.
+    pub fn start_cycle(&mut self, limit: Duration) {
+        let budget = self.budget.get_mut().unwrap_or_else(|e| e.into_inner());
+        budget.was_exhausted = budget.exhausted;
+        budget.limit         = limit;
+        budget.spent         = Duration::ZERO;
+        budget.exhausted     = false;
+    }
+
+    /// Retry time used so far this cycle.
+    pub fn spent(&self) -> Duration {
+        self.budget.lock().unwrap_or_else(|e| e.into_inner()).spent
+    }
+
+    /// Budget left for a retry of `what`, or `None` (logged) when it is used up.
+    fn remaining(&self, what: &str) -> Option<Duration> {
+        let mut budget = self.budget.lock().unwrap_or_else(|e| e.into_inner());
+        if budget.spent < budget.limit {
+            return Some(budget.limit - budget.spent);
+        }
+        if !budget.limit.is_zero() {
+            exhausted(&mut budget, what);
+        }
+        None
+    }
+
+    fn charge(&self, elapsed: Duration) {
+        self.budget.lock().unwrap_or_else(|e| e.into_inner()).spent += elapsed;
+    }
+
+    /// Run `op`, and retry it up to `MAX_RETRIES` times while `failed` holds and the budget allows.
+    /// A retry that doesn't finish within the remaining budget is abandoned; the last result is returned.
+    pub async fn run<T, F, Fut>(&self, what: &str, failed: impl Fn(&T) -> bool, mut op: F) -> T
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = T>,
+    {
+        let mut result = op().await;
+        for attempt in 1..=MAX_RETRIES {
+            if !failed(&result) {
+                break;
+            }
+            let Some(remaining) = self.remaining(what) else {
+                break;
+            };
+            debug!("[EMS] {} failed - retry {} of {}", what, attempt, MAX_RETRIES);
+            let start = Instant::now();
+            let retry = tokio::time::timeout(remaining, async {
+                tokio::time::sleep(RETRY_DELAY).await;
+                op().await
+            })
+            .await;
+            self.charge(start.elapsed());
+            match retry {
+                Ok(retried) => result = retried,
+                Err(_) => {
+                    exhausted(&mut self.budget.lock().unwrap_or_else(|e| e.into_inner()), what);
+                    break;
+                }
+            }
+        }
+        result
+    }
+}
+
+/// Note that the budget ran out on a retry of `what`.
+fn exhausted(budget: &mut Budget, what: &str) {
+    if !budget.exhausted && !budget.was_exhausted {
+        warn!(
+            "[EMS] Retry budget of {}ms used up - {} and any later read go without retries",
+            budget.limit.as_millis(), what
+        );
+    } else {
+        debug!("[EMS] Retry budget used up - no retry for {}", what);
+    }
+    budget.exhausted = true;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    /// Run an always-failing read against `budget`; returns how often it was attempted.
+    async fn attempts(budget: &RetryBudget) -> u32 {
+        let calls = Cell::new(0);
+        budget
+            .run("test read", |result: &Result<(), ()>| result.is_err(), || {
+                calls.set(calls.get() + 1);
+                async { Err(()) }
+            })
+            .await
+            .unwrap_err();
+        calls.get()
+    }
+
+    #[tokio::test]
+    async fn the_budget_runs_out_and_refills_next_cycle() {
+        let mut budget = RetryBudget::new();
+        budget.start_cycle(Duration::from_millis(300));
+
+        // The first retry spends 250 ms; the second can't finish in the 50 ms left and is cut off.
+        assert_eq!(attempts(&budget).await, 2);
+        assert!(budget.spent() >= Duration::from_millis(300), "{:?}", budget.spent());
+        // A second read in the same cycle gets no retry at all.
+        assert_eq!(attempts(&budget).await, 1);
+
+        budget.start_cycle(Duration::from_millis(1000));
+        assert_eq!(budget.spent(), Duration::ZERO);
+        assert_eq!(attempts(&budget).await, 1 + MAX_RETRIES);
+    }
+
+    #[tokio::test]
+    async fn a_zero_budget_never_retries() {
+        let mut budget = RetryBudget::new();
+        budget.start_cycle(Duration::ZERO);
+        assert_eq!(attempts(&budget).await, 1);
+    }
+
+    #[tokio::test]
+    async fn a_read_that_succeeds_is_not_retried() {
+        let mut budget = RetryBudget::new();
+        budget.start_cycle(Duration::from_millis(1000));
+        let calls  = Cell::new(0);
+        let result = budget
+            .run("test read", |result: &Result<u8, ()>| result.is_err(), || {
+                calls.set(calls.get() + 1);
+                async { Ok(7) }
+            })
+            .await;
+        assert_eq!((result, calls.get()), (Ok(7), 1));
+    }
+}
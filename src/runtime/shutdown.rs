@@ -0,0 +1,119 @@
+use log::{error, info};
+use std::future::pending;
+use tokio::sync::watch;
+use tokio::time::Instant;
+
+use crate::handlers::indevolt::controller::{restore_auto_mode, stop};
+
+// --------------------------------------------------------------------------------------------------------------
+// Ordered shutdown
+//
+// On SIGINT or SIGTERM the process doesn't just drop its tasks. The supervisor walks through fixed steps, all
+// within `shutdown_timeout_seconds`:
+//   1. services without anything to flush (API, watchdog, price refresh) are stopped;
+//   2. the control loop is told to stop: it finishes the cycle in progress, starts no new one, hands the
+//      inverter back to self-consumption mode and saves the state file (the report day included);
+//   3. once the loop has stopped, the sink workers drain what is left on the record channel and flush it
+//      until the deadline, logging how much was written and how much had to be dropped;
+//   4. whatever is still running at the deadline is aborted.
+// A `Shutdown` is owned by the supervisor; the loop and the sinks each hold a `ShutdownSignal` to wait on.
+// --------------------------------------------------------------------------------------------------------------
+
+/// Sending side of the shutdown steps.
+pub struct Shutdown {
+    stop:  watch::Sender<bool>,
+    drain: watch::Sender<Option<Instant>>,
+}
+
+impl Default for Shutdown {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Shutdown {
+    pub fn new() -> Self {
+        Self { stop: watch::channel(false).0, drain: watch::channel(None).0 }
+    }
+
+    /// A receiver for a task that takes part in the shutdown.
+    pub fn signal(&self) -> ShutdownSignal {
+        ShutdownSignal { stop: self.stop.subscribe(), drain: self.drain.subscribe() }
+    }
+
+    /// Step 2: the control loop stops after its current cycle.
+    pub fn stop_loop(&self) {
+        self.stop.send_replace(true);
+    }
+
+    /// Step 3: sinks drain and flush until `deadline`.
+    pub fn drain_sinks(&self, deadline: Instant) {
+        self.drain.send_replace(Some(deadline));
+    }
+}
+
+/// What a task waits on to learn that the process is shutting down.
+#[derive(Clone)]
+pub struct ShutdownSignal {
+    stop:  watch::Receiver<bool>,
+    drain: watch::Receiver<Option<Instant>>,
+}
+
+impl ShutdownSignal {
+    /// Resolves once the control loop is to stop.
+    pub async fn stopped(&mut self) {
+        let stopped = self.stop.wait_for(|stop| *stop).await.is_ok();
+        if !stopped {
+            pending::<()>().await;
+        }
+    }
+
+    /// Resolves once the sinks are to drain, with the deadline for their last flush.
+    pub async fn drain_deadline(&mut self) -> Instant {
+        let deadline = self.drain.wait_for(Option::is_some).await.map(|deadline| *deadline);
+        match deadline {
+            Ok(deadline) => deadline.unwrap_or_else(Instant::now),
+            Err(_)       => pending().await,
+        }
+    }
+}
+
+/// Step 2, from the control loop: stop any real-time command and put the inverter back in self-consumption mode.
+/// Returns true when the device confirmed the mode write.
+pub async fn hand_back(base_url: &str) -> bool {
+    if let Err(e) = stop(base_url).await {
+        error!("[EMS] Shutdown stop failed: {}", e);
+    }
+    match restore_auto_mode(base_url).await {
+        Ok(()) => {
+            info!("[EMS] Shutdown: device back in self-consumption mode");
+            true
+        }
+        Err(e) => {
+            error!("[EMS] Shutdown: restoring self-consumption mode failed: {}", e);
+            false
+        }
+    }
+}
+
+/// Wait for SIGINT (Ctrl-C) or, on Unix, SIGTERM.
+pub async fn wait_for_signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+        let Ok(mut sigterm) = signal(SignalKind::terminate()) else {
+            let _ = tokio::signal::ctrl_c().await;
+            info!("[EMS] SIGINT received - shutting down");
+            return;
+        };
+        tokio::select! {
+            _ = tokio::signal::ctrl_c() => info!("[EMS] SIGINT received - shutting down"),
+            _ = sigterm.recv()          => info!("[EMS] SIGTERM received - shutting down"),
+        }
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = tokio::signal::ctrl_c().await;
+        info!("[EMS] Ctrl-C received - shutting down");
+    }
+}
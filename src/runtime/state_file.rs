@@ -0,0 +1,141 @@
+use chrono::{DateTime, Utc};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+use crate::analytics::device_days::DeviceDayTracker;
+use crate::analytics::soh::SohTracker;
+use crate::optimiser::charge_cap::GridChargeCap;
+use crate::optimiser::decision::Decision;
+use crate::optimiser::peak::MonthlyPeak;
+use crate::optimiser::pre_peak::PrePeakCharge;
+use crate::optimiser::throughput_cap::ThroughputCap;
+use crate::reporting::DailyReporter;
+use super::learning::LearningPeriod;
+
+// --------------------------------------------------------------------------------------------------------------
+// Persistent loop state
+//
+// The parts of `LoopState` worth keeping across a restart are written to `state_file_path` every
+// `state_save_interval_seconds`, and read back at start-up when the file is younger than
+// `state_max_age_seconds`. A missing, stale or unreadable file simply means starting fresh.
+// Writes go to a temporary file that is then renamed over the old one, so a crash mid-write never leaves a
+// truncated state file behind.
+// --------------------------------------------------------------------------------------------------------------
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PersistedState {
+    pub saved_at:        DateTime<Utc>,
+    pub cycle_id:        u64,
+    pub last_decision:   Option<Decision>,
+    #[serde(default)]
+    pub monthly_peak:    Option<MonthlyPeak>,
+    #[serde(default)]
+    pub soh:             SohTracker,
+    #[serde(default)]
+    pub report:          DailyReporter,
+    #[serde(default)]
+    pub grid_charge_cap: GridChargeCap,
+    #[serde(default)]
+    pub throughput_cap:  ThroughputCap,
+    #[serde(default)]
+    pub pre_peak:        PrePeakCharge,
+    #[serde(default)]
+    pub device_days:     DeviceDayTracker,
+    #[serde(default)]
+    pub learning:        LearningPeriod,
+}
+
+/// Load the state file if it exists, parses, and is not older than `max_age_seconds`.
+pub fn load_state(path: &str, max_age_seconds: u64) -> Option<PersistedState> {
+    if !Path::new(path).exists() {
+        info!("[State] No state file at {} - starting fresh", path);
+        return None;
+    }
+
+    let text = match fs::read_to_string(path) {
+        Ok(t) => t,
+        Err(e) => {
+            warn!("[State] Cannot read {}: {} - starting fresh", path, e);
+            return None;
+        }
+    };
+    let state: PersistedState = match serde_json::from_str(&text) {
+        Ok(s) => s,
+        Err(e) => {
+            warn!("[State] Corrupt state file {}: {} - starting fresh", path, e);
+            return None;
+        }
+    };
+
+    let age = Utc::now() - state.saved_at;
+    if age.num_seconds() > max_age_seconds as i64 {
+        warn!("[State] State file {} is {} min old - ignoring it", path, age.num_minutes());
+        return None;
+    }
+
+    info!("[State] Restored state from {} (saved {}s ago)", path, age.num_seconds());
+    Some(state)
+}
+
+/// Atomically write the state file.
+pub fn save_state(path: &str, state: &PersistedState) -> Result<(), String> {
+    let json = serde_json::to_string_pretty(state)
+        .map_err(|e| format!("[State] Failed to serialise state: {}", e))?;
+    let tmp = format!("{}.tmp", path);
+    fs::write(&tmp, json).map_err(|e| format!("[State] Failed to write {}: {}", tmp, e))?;
+    fs::rename(&tmp, path).map_err(|e| format!("[State] Failed to replace {}: {}", path, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_path(name: &str) -> String {
+        let path = std::env::temp_dir().join(format!("ems-state-{}-{}.json", std::process::id(), name));
+        path.to_str().unwrap().to_string()
+    }
+
+    fn state(saved_at: DateTime<Utc>) -> PersistedState {
+        PersistedState {
+            saved_at,
+            cycle_id:        42,
+            last_decision:   Some(Decision::DischargingToLoad { watts: 1200, min_soc_percent: 20 }),
+            monthly_peak:    None,
+            soh:             SohTracker::default(),
+            report:          DailyReporter::default(),
+            grid_charge_cap: GridChargeCap::default(),
+            throughput_cap:  ThroughputCap::default(),
+            pre_peak:        PrePeakCharge::default(),
+            device_days:     DeviceDayTracker::default(),
+            learning:        LearningPeriod::default(),
+        }
+    }
+
+    #[test]
+    fn a_saved_state_loads_back() {
+        let path = temp_path("round-trip");
+        save_state(&path, &state(Utc::now())).unwrap();
+        let loaded = load_state(&path, 3600);
+        fs::remove_file(&path).unwrap();
+
+        let loaded = loaded.expect("state restored");
+        assert_eq!(loaded.cycle_id, 42);
+        assert_eq!(loaded.last_decision, state(Utc::now()).last_decision);
+        assert!(!Path::new(&format!("{}.tmp", path)).exists());
+    }
+
+    #[test]
+    fn a_corrupt_missing_or_stale_file_starts_fresh() {
+        let path = temp_path("corrupt");
+        fs::write(&path, r#"{"saved_at": "yesterday", "cycle_id": "#).unwrap();
+        assert!(load_state(&path, 3600).is_none());
+
+        save_state(&path, &state(Utc::now() - chrono::Duration::hours(2))).unwrap();
+        assert!(load_state(&path, 3600).is_none());
+
+        fs::remove_file(&path).unwrap();
+        assert!(load_state(&path, 3600).is_none());
+    }
+}
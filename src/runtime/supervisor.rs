@@ -0,0 +1,286 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+
+use log::{error, info, warn};
+use tokio::task::{AbortHandle, Id, JoinSet};
+use tokio::time::{sleep, timeout_at, Duration, Instant};
+
+use super::shutdown::Shutdown;
+
+// --------------------------------------------------------------------------------------------------------------
+// Task supervisor
+//
+// Every long-running subsystem (control loop, status server, publishers, ...) runs as its own tokio task inside
+// one JoinSet. When a task panics or returns, the supervisor logs it and spawns a fresh instance after an
+// exponential backoff, so one misbehaving subsystem can never take the others down with it. On a shutdown
+// signal restarts stop and the tasks are wound down in order (see `shutdown.rs`).
+// --------------------------------------------------------------------------------------------------------------
+
+/// First restart delay after a task exits or panics.
+const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+/// Upper bound for the restart delay of optional subsystems.
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+/// Upper bound for the restart delay of the primary task (the control loop).
+const MAX_PRIMARY_BACKOFF: Duration = Duration::from_secs(5);
+/// A task that ran at least this long before exiting is considered healthy; its backoff is reset.
+const STABLE_RUNTIME: Duration = Duration::from_secs(300);
+
+type TaskFactory = Arc<dyn Fn() -> Pin<Box<dyn Future<Output = ()> + Send>> + Send + Sync>;
+
+/// What a task does at shutdown.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Role {
+    /// The control loop: stopped first, after its current cycle.
+    Primary,
+    /// Drains and flushes once the primary task has stopped.
+    Sink,
+    /// Nothing to flush: aborted right away.
+    Service,
+}
+
+struct TaskSpec {
+    name:     &'static str,
+    role:     Role,
+    factory:  TaskFactory,
+    backoff:  Duration,
+    restarts: u32,
+    started:  Instant,
+    abort:    Option<AbortHandle>,
+}
+
+/// Owns all supervised tasks and restarts them when they end.
+pub struct Supervisor {
+    tasks: JoinSet<()>,
+    specs: HashMap<Id, TaskSpec>,
+}
+
+impl Default for Supervisor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Supervisor {
+    pub fn new() -> Self {
+        Self { tasks: JoinSet::new(), specs: HashMap::new() }
+    }
+
+    /// Register the primary task. It is restarted with a short, tightly capped backoff because keeping
+    /// the control loop alive is more important than anything else the process does.
+    pub fn spawn_primary<F, Fut>(&mut self, name: &'static str, factory: F)
+    where
+        F:   Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        self.register(name, Role::Primary, factory);
+    }
+
+    /// Register an optional subsystem. It is restarted with exponential backoff up to `MAX_BACKOFF`.
+    pub fn spawn<F, Fut>(&mut self, name: &'static str, factory: F)
+    where
+        F:   Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        self.register(name, Role::Service, factory);
+    }
+
+    /// Register a sink worker: restarted like an optional subsystem, but at shutdown it is left running
+    /// after the primary task has stopped, to flush what is still buffered.
+    pub fn spawn_sink<F, Fut>(&mut self, name: &'static str, factory: F)
+    where
+        F:   Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        self.register(name, Role::Sink, factory);
+    }
+
+    fn register<F, Fut>(&mut self, name: &'static str, role: Role, factory: F)
+    where
+        F:   Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let factory: TaskFactory = Arc::new(move || Box::pin(factory()));
+        let spec = TaskSpec {
+            name,
+            role,
+            factory,
+            backoff:  INITIAL_BACKOFF,
+            restarts: 0,
+            started:  Instant::now(),
+            abort:    None,
+        };
+        let primary = role == Role::Primary;
+        info!("[Supervisor] Starting task '{}'{}", name, if primary { " (primary)" } else { "" });
+        self.start(spec, Duration::ZERO);
+    }
+
+    fn start(&mut self, mut spec: TaskSpec, delay: Duration) {
+        let fut = (spec.factory)();
+        spec.started = Instant::now() + delay;
+        let handle = self.tasks.spawn(async move {
+            if !delay.is_zero() {
+                sleep(delay).await;
+            }
+            fut.await;
+        });
+        spec.abort = Some(handle.clone());
+        self.specs.insert(handle.id(), spec);
+    }
+
+    /// Wait for the next task to end and schedule its restart. Returns `false` once no tasks are left.
+    pub async fn supervise_next(&mut self) -> bool {
+        let (id, outcome) = match self.tasks.join_next_with_id().await {
+            Some(Ok((id, ())))  => (id, "exited"),
+            Some(Err(e)) if e.is_panic() => (e.id(), "panicked"),
+            Some(Err(e))        => (e.id(), "was cancelled"),
+            None                => return false,
+        };
+
+        let Some(mut spec) = self.specs.remove(&id) else {
+            return !self.specs.is_empty();
+        };
+
+        // A task that stayed up for a while gets a fresh backoff; a crash-looping one keeps doubling.
+        if spec.started.elapsed() >= STABLE_RUNTIME {
+            spec.backoff = INITIAL_BACKOFF;
+        }
+        let delay    = spec.backoff;
+        let primary  = spec.role == Role::Primary;
+        let max      = if primary { MAX_PRIMARY_BACKOFF } else { MAX_BACKOFF };
+        spec.backoff = (spec.backoff * 2).min(max);
+        spec.restarts += 1;
+
+        if primary {
+            error!(
+                "[Supervisor] Primary task '{}' {} - restart #{} in {:?}",
+                spec.name, outcome, spec.restarts, delay
+            );
+        } else {
+            warn!(
+                "[Supervisor] Task '{}' {} - restart #{} in {:?}",
+                spec.name, outcome, spec.restarts, delay
+            );
+        }
+
+        self.start(spec, delay);
+        true
+    }
+
+    /// Supervise until `signal` resolves, then shut down in order within `timeout`.
+    pub async fn run(mut self, signal: impl Future<Output = ()>, shutdown: Shutdown, timeout: Duration) {
+        tokio::select! {
+            _ = async { while self.supervise_next().await {} } => return,
+            _ = signal => {}
+        }
+
+        let deadline = Instant::now() + timeout;
+        info!("[Supervisor] Shutting down (deadline {}s)", timeout.as_secs());
+        self.abort_where(|role| role == Role::Service);
+        shutdown.stop_loop();
+        if !self.wait_while(deadline, |role| role == Role::Primary).await {
+            error!(
+                "[Supervisor] Control loop did not stop within {}s - aborted, the inverter may be left in \
+                 real-time mode until the next start",
+                timeout.as_secs()
+            );
+            self.abort_where(|role| role == Role::Primary);
+        }
+        shutdown.drain_sinks(deadline);
+        if !self.wait_while(deadline, |_| true).await {
+            for spec in self.specs.values() {
+                warn!("[Supervisor] Task '{}' still running at the deadline - aborted", spec.name);
+            }
+            self.abort_where(|_| true);
+        }
+        info!("[Supervisor] Shutdown complete");
+    }
+
+    fn abort_where(&self, matches: impl Fn(Role) -> bool) {
+        for spec in self.specs.values().filter(|spec| matches(spec.role)) {
+            if let Some(abort) = &spec.abort {
+                abort.abort();
+            }
+        }
+    }
+
+    /// Collect ended tasks, without restarting them, while any task matching `pending` is left.
+    /// Returns false when `deadline` passed first.
+    async fn wait_while(&mut self, deadline: Instant, pending: impl Fn(Role) -> bool) -> bool {
+        while self.specs.values().any(|spec| pending(spec.role)) {
+            let joined = match timeout_at(deadline, self.tasks.join_next_with_id()).await {
+                Ok(Some(joined)) => joined,
+                Ok(None)         => break,
+                Err(_)           => return false,
+            };
+            let (id, outcome) = match joined {
+                Ok((id, ()))                 => (id, "stopped"),
+                Err(e) if e.is_panic() => (e.id(), "panicked"),
+                Err(e)                       => (e.id(), "was cancelled"),
+            };
+            if let Some(spec) = self.specs.remove(&id) {
+                info!("[Supervisor] Task '{}' {}", spec.name, outcome);
+            }
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    #[tokio::test(start_paused = true)]
+    async fn panicking_task_is_restarted_while_the_primary_keeps_running() {
+        let cycles = Arc::new(AtomicU32::new(0));
+        let starts = Arc::new(AtomicU32::new(0));
+        let mut supervisor = Supervisor::new();
+
+        let counter = cycles.clone();
+        supervisor.spawn_primary("loop", move || {
+            let counter = counter.clone();
+            async move {
+                loop {
+                    counter.fetch_add(1, Ordering::SeqCst);
+                    sleep(Duration::from_millis(100)).await;
+                }
+            }
+        });
+        let counter = starts.clone();
+        supervisor.spawn("faulty", move || {
+            let counter = counter.clone();
+            async move {
+                counter.fetch_add(1, Ordering::SeqCst);
+                panic!("deliberate");
+            }
+        });
+
+        let _ = tokio::time::timeout(Duration::from_secs(10), async {
+            while supervisor.supervise_next().await {}
+        })
+        .await;
+
+        // Backoff 1 s, 2 s, 4 s: started at 0, 1, 3 and 7 s.
+        assert_eq!(starts.load(Ordering::SeqCst), 4);
+        assert!(cycles.load(Ordering::SeqCst) >= 99);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn exited_primary_is_restarted() {
+        let starts = Arc::new(AtomicU32::new(0));
+        let mut supervisor = Supervisor::new();
+        let counter = starts.clone();
+        supervisor.spawn_primary("loop", move || {
+            let counter = counter.clone();
+            async move {
+                counter.fetch_add(1, Ordering::SeqCst);
+            }
+        });
+
+        assert!(supervisor.supervise_next().await);
+        assert!(supervisor.supervise_next().await);
+        assert_eq!(starts.load(Ordering::SeqCst), 2);
+    }
+}
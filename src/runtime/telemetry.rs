@@ -0,0 +1,73 @@
+use crate::configuration::config::Config;
+
+// --------------------------------------------------------------------------------------------------------------
+// OpenTelemetry tracing
+//
+// The control loop wraps every cycle in a `cycle` span with child spans for the P1 read, the battery read, the
+// optimiser and the dispatch (and each Indevolt SetData inside it). Without a subscriber those spans cost next
+// to nothing. When the binary is built with `--features otel` and `otel_endpoint` is set, they are exported
+// over OTLP/HTTP to a collector. Logging stays on env_logger either way.
+// --------------------------------------------------------------------------------------------------------------
+
+/// Keeps the exporter alive; flushes pending spans when dropped.
+pub struct Telemetry {
+    #[cfg(feature = "otel")]
+    provider: opentelemetry_sdk::trace::SdkTracerProvider,
+}
+
+/// Install the OTLP exporter if `otel_endpoint` is configured.
+pub fn init(config: &Config) -> Option<Telemetry> {
+    if config.otel_endpoint.is_empty() {
+        return None;
+    }
+    init_exporter(&config.otel_endpoint)
+}
+
+#[cfg(feature = "otel")]
+fn init_exporter(endpoint: &str) -> Option<Telemetry> {
+    use opentelemetry::trace::TracerProvider as _;
+    use opentelemetry_otlp::WithExportConfig;
+    use tracing_subscriber::layer::SubscriberExt;
+    use tracing_subscriber::util::SubscriberInitExt;
+
+    let exporter = match opentelemetry_otlp::SpanExporter::builder().with_http().with_endpoint(endpoint).build() {
+        Ok(e) => e,
+        Err(e) => {
+            log::error!("[Telemetry] Failed to build OTLP exporter for {}: {}", endpoint, e);
+            return None;
+        }
+    };
+    let provider = opentelemetry_sdk::trace::SdkTracerProvider::builder()
+        .with_batch_exporter(exporter)
+        .with_resource(
+            opentelemetry_sdk::Resource::builder().with_service_name("energy_management_system").build(),
+        )
+        .build();
+    let tracer = provider.tracer("energy_management_system");
+
+    if let Err(e) = tracing_subscriber::registry()
+        .with(tracing_opentelemetry::layer().with_tracer(tracer))
+        .try_init()
+    {
+        log::error!("[Telemetry] Failed to install tracing subscriber: {}", e);
+        return None;
+    }
+
+    log::info!("[Telemetry] Exporting spans to {}", endpoint);
+    Some(Telemetry { provider })
+}
+
+#[cfg(not(feature = "otel"))]
+fn init_exporter(endpoint: &str) -> Option<Telemetry> {
+    log::warn!("[Telemetry] otel_endpoint {} ignored - built without the \"otel\" feature", endpoint);
+    None
+}
+
+impl Drop for Telemetry {
+    fn drop(&mut self) {
+        #[cfg(feature = "otel")]
+        if let Err(e) = self.provider.shutdown() {
+            log::warn!("[Telemetry] Span exporter shutdown failed: {}", e);
+        }
+    }
+}
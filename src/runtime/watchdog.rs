@@ -0,0 +1,127 @@
+use log::{error, info, warn};
+use std::sync::{Arc, Mutex};
+use tokio::sync::watch;
+use tokio::time::{sleep, timeout, Duration, Instant};
+
+use crate::configuration::config::Config;
+use crate::handlers::indevolt::controller::restore_auto_mode;
+
+// --------------------------------------------------------------------------------------------------------------
+// Dead-man's switch
+//
+// The per-request timeouts protect against a slow device, not against the control loop itself getting stuck
+// (an await that never resolves, a deadlock). The loop pets the watchdog at the start of every cycle; a
+// separate task checks that it keeps doing so. When the loop has been silent for `watchdog_timeout_seconds`,
+// the watchdog hands the inverter back to its own self-consumption mode so the battery isn't left running the
+// last real-time command, and, with `watchdog_abort`, exits the process so systemd (or whatever supervises
+// it) starts a fresh one. It fires once per stall and re-arms when the loop comes back. The device URL and
+// `watchdog_abort` are taken from the running configuration when it fires, so a reload reaches them.
+// --------------------------------------------------------------------------------------------------------------
+
+/// When the control loop last started a cycle; `None` until the first cycle.
+static LAST_PET: Mutex<Option<Instant>> = Mutex::new(None);
+
+/// Time allowed for the restore write itself.
+const RESTORE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Called by the control loop at the start of every cycle.
+pub fn pet() {
+    *LAST_PET.lock().unwrap_or_else(|e| e.into_inner()) = Some(Instant::now());
+}
+
+/// How long ago the loop last petted the watchdog.
+fn silent_for() -> Option<Duration> {
+    LAST_PET.lock().unwrap_or_else(|e| e.into_inner()).map(|at| at.elapsed())
+}
+
+/// Watch the control loop forever. Only spawn this when `watchdog_timeout_seconds` is non-zero.
+pub async fn watchdog(settings: watch::Receiver<Arc<Config>>) {
+    let config = settings.borrow().clone();
+    let limit = Duration::from_secs(config.watchdog_timeout_seconds);
+    let check = (limit / 4).clamp(Duration::from_millis(100), Duration::from_secs(5));
+    if config.watchdog_timeout_seconds <= config.poll_interval_seconds {
+        warn!(
+            "[Watchdog] Timeout {}s is not longer than the poll interval {}s - it will fire between cycles",
+            config.watchdog_timeout_seconds, config.poll_interval_seconds
+        );
+    }
+    info!("[Watchdog] Armed: control loop must start a cycle at least every {}s", limit.as_secs());
+
+    let mut fired = false;
+    loop {
+        sleep(check).await;
+        let Some(silent) = silent_for() else {
+            continue;
+        };
+        if silent < limit {
+            if fired {
+                info!("[Watchdog] Control loop is running again - re-armed");
+                fired = false;
+            }
+            continue;
+        }
+        if fired {
+            continue;
+        }
+        fired = true;
+
+        error!(
+            "[Watchdog] ALERT: control loop silent for {}s (limit {}s) - restoring self-consumption mode",
+            silent.as_secs(), limit.as_secs()
+        );
+        let config = settings.borrow().clone();
+        match timeout(RESTORE_TIMEOUT, restore_auto_mode(&config.indevolt_url)).await {
+            Ok(Ok(()))  => info!("[Watchdog] Self-consumption mode restored"),
+            Ok(Err(e))  => error!("[Watchdog] Failed to restore self-consumption mode: {}", e),
+            Err(_)      => error!("[Watchdog] Restoring self-consumption mode timed out"),
+        }
+        if config.watchdog_abort {
+            error!("[Watchdog] Exiting so the service manager restarts the process");
+            std::process::exit(1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::handlers::indevolt::registers::REG_WORKING_MODE;
+    use crate::handlers::indevolt::testing::MockIndevolt;
+
+    /// Wait up to `within` for the device to have received `count` requests.
+    async fn requests_reach(device: &MockIndevolt, count: usize, within: Duration) -> bool {
+        let deadline = Instant::now() + within;
+        while device.requests().len() < count && Instant::now() < deadline {
+            sleep(Duration::from_millis(50)).await;
+        }
+        device.requests().len() >= count
+    }
+
+    #[tokio::test]
+    async fn a_stalled_loop_hands_the_device_back_once_per_stall() {
+        let device = MockIndevolt::start(|_| (200, r#"{"result": true}"#.to_string())).await;
+        let config = Config {
+            indevolt_url:             device.base_url.clone(),
+            watchdog_timeout_seconds: 1,
+            poll_interval_seconds:    0,
+            ..Config::default()
+        };
+        let (_tx, settings) = watch::channel(Arc::new(config));
+        pet();
+        tokio::spawn(watchdog(settings));
+
+        // The loop stalls: after a second the device is put back in self-consumption mode, once.
+        assert!(requests_reach(&device, 1, Duration::from_secs(3)).await);
+        assert!(device.wrote(REG_WORKING_MODE, "[1]"));
+        sleep(Duration::from_millis(600)).await;
+        assert_eq!(device.requests().len(), 1);
+
+        // A running loop keeps it quiet; the next stall fires again.
+        for _ in 0..6 {
+            pet();
+            sleep(Duration::from_millis(200)).await;
+        }
+        assert_eq!(device.requests().len(), 1);
+        assert!(requests_reach(&device, 2, Duration::from_secs(3)).await);
+    }
+}
@@ -0,0 +1,110 @@
+use chrono::{DateTime, Utc};
+use log::{info, warn};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+
+// --------------------------------------------------------------------------------------------------------------
+// Sink health
+//
+// Every sink worker reports the result of each write here, so one sink failing doesn't hide how the others are
+// doing. Per sink: when it last wrote successfully, how many writes in a row have failed since, and the last
+// error. The table is created once at start-up and shared between the sink workers and the API task, which
+// serves it as `GET /sinks`; a supervised restart of a worker keeps its entry. A sink that starts failing and
+// one that recovers are each logged once here, the failures in between only by the sink itself.
+// --------------------------------------------------------------------------------------------------------------
+
+/// Health of one sink.
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct SinkHealth {
+    /// Last write that succeeded; `None` until the first one.
+    pub last_success:         Option<DateTime<Utc>>,
+    /// Writes that failed since the last success.
+    pub consecutive_failures: u32,
+    /// Error of the most recent failed write; cleared by a success.
+    pub last_error:           Option<String>,
+}
+
+/// Health of every sink that was started, by sink name.
+#[derive(Serialize, Debug, Default)]
+#[serde(transparent)]
+pub struct SinkHealthTable {
+    sinks: BTreeMap<&'static str, SinkHealth>,
+}
+
+/// The table as shared between the sink workers and the API task.
+pub type SharedSinkHealth = Arc<Mutex<SinkHealthTable>>;
+
+impl SinkHealthTable {
+    pub fn shared() -> SharedSinkHealth {
+        Arc::new(Mutex::new(Self::default()))
+    }
+
+    /// List `name` before its first write; an existing entry is kept.
+    pub fn register(&mut self, name: &'static str) {
+        self.sinks.entry(name).or_default();
+    }
+
+    /// Record the result of one write by `name` at `now`.
+    pub fn record(&mut self, name: &'static str, result: &Result<(), String>, now: DateTime<Utc>) {
+        let health = self.sinks.entry(name).or_default();
+        match result {
+            Ok(()) => {
+                if health.consecutive_failures > 0 {
+                    info!("[Sinks] {} recovered after {} failed write(s)", name, health.consecutive_failures);
+                }
+                health.last_success         = Some(now);
+                health.consecutive_failures = 0;
+                health.last_error           = None;
+            }
+            Err(e) => {
+                if health.consecutive_failures == 0 {
+                    warn!("[Sinks] {} failing: {} - other sinks carry on", name, e);
+                }
+                health.consecutive_failures += 1;
+                health.last_error            = Some(e.clone());
+            }
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&SinkHealth> {
+        self.sinks.get(name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn failures_are_counted_until_a_success() {
+        let at = |minute| Utc.with_ymd_and_hms(2026, 10, 15, 8, minute, 0).unwrap();
+        let mut table = SinkHealthTable::default();
+        table.register("influxdb");
+        assert_eq!(table.get("influxdb").unwrap().consecutive_failures, 0);
+
+        table.record("influxdb", &Ok(()), at(0));
+        table.record("influxdb", &Err("timeout".to_string()), at(1));
+        table.record("influxdb", &Err("HTTP 503".to_string()), at(2));
+        let failing = table.get("influxdb").unwrap();
+        assert_eq!(failing.consecutive_failures, 2);
+        assert_eq!(failing.last_error.as_deref(), Some("HTTP 503"));
+        assert_eq!(failing.last_success, Some(at(0)));
+
+        table.record("influxdb", &Ok(()), at(3));
+        let recovered = table.get("influxdb").unwrap();
+        assert_eq!(recovered.consecutive_failures, 0);
+        assert_eq!(recovered.last_error, None);
+        assert_eq!(recovered.last_success, Some(at(3)));
+    }
+
+    #[test]
+    fn registering_again_keeps_the_entry() {
+        let mut table = SinkHealthTable::default();
+        table.record("influxdb", &Err("timeout".to_string()), Utc::now());
+        table.register("influxdb");
+        assert_eq!(table.get("influxdb").unwrap().consecutive_failures, 1);
+        assert!(table.get("mqtt").is_none());
+    }
+}
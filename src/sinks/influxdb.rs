@@ -0,0 +1,353 @@
+use chrono::{DateTime, Utc};
+use log::{debug, error, info, warn};
+use reqwest::{Client, StatusCode};
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::time::{sleep, timeout_at, Instant};
+
+use crate::analytics::device_days::DeviceDay;
+use crate::configuration::config::Config;
+use crate::handlers::p1::reader::P1Reading;
+use crate::models::indevolt_models::BatterySnapshot;
+use crate::runtime::pipeline::CycleRecord;
+use crate::sinks::sink::Sink;
+
+// --------------------------------------------------------------------------------------------------------------
+// InfluxDB v2 sink
+//
+// Write: POST <influxdb_url>/api/v2/write?org=<org>&bucket=<bucket>&precision=s
+//        Header: Authorization: Token <token>
+//        Body:   line protocol, one line per measurement
+//
+// Each cycle produces one "battery" line and, when available, one "p1" line. Lines that could not be written
+// because of a transient failure (network error, 5xx, 429) stay buffered and are sent with the next cycle.
+// Failures are logged, reported in the sink's health and never interrupt the control loop. At shutdown the
+// records still on the channel are queued and the flush is retried until the shutdown deadline; what is left
+// then is dropped and counted.
+// --------------------------------------------------------------------------------------------------------------
+
+/// Upper bound on buffered lines; the oldest are dropped first (~2 h of data at a 30 s interval).
+const MAX_BUFFERED_LINES: usize = 500;
+const REQUEST_TIMEOUT:    Duration = Duration::from_secs(5);
+/// Pause between flush attempts while draining at shutdown.
+const DRAIN_RETRY_DELAY:  Duration = Duration::from_millis(500);
+
+pub struct InfluxDbSink {
+    client:    Client,
+    write_url: reqwest::Url,
+    token:     String,
+    buffer:    VecDeque<String>,
+}
+
+impl InfluxDbSink {
+    /// Build the sink from the config. Returns `None` when `influxdb_url` is not set.
+    pub fn from_config(config: &Config) -> Option<Self> {
+        if config.influxdb_url.is_empty() {
+            return None;
+        }
+
+        let url = format!("{}/api/v2/write", config.influxdb_url.trim_end_matches('/'));
+        let mut write_url = match reqwest::Url::parse(&url) {
+            Ok(url) => url,
+            Err(e) => {
+                error!("[InfluxDB] Invalid influxdb_url '{}': {} - sink disabled", config.influxdb_url, e);
+                return None;
+            }
+        };
+        write_url
+            .query_pairs_mut()
+            .append_pair("org", &config.influxdb_org)
+            .append_pair("bucket", &config.influxdb_bucket)
+            .append_pair("precision", "s");
+
+        let client = match Client::builder().timeout(REQUEST_TIMEOUT).build() {
+            Ok(c) => c,
+            Err(e) => {
+                error!("[InfluxDB] Failed to build HTTP client: {} - sink disabled", e);
+                return None;
+            }
+        };
+
+        Some(Self {
+            client,
+            write_url,
+            token:  config.influxdb_token.clone(),
+            buffer: VecDeque::new(),
+        })
+    }
+
+    fn queue(&mut self, lines: Vec<String>) {
+        self.buffer.extend(lines);
+        let overflow = self.buffer.len().saturating_sub(MAX_BUFFERED_LINES);
+        if overflow > 0 {
+            self.buffer.drain(..overflow);
+            warn!("[InfluxDB] Buffer full - dropped {} oldest line(s)", overflow);
+        }
+    }
+
+    /// Send the buffer. Returns the lines written; on an error they stay buffered for a retry unless rejected.
+    async fn flush(&mut self) -> Result<usize, String> {
+        let body = self.buffer.iter().cloned().collect::<Vec<_>>().join("\n");
+        let result = self.client
+            .post(self.write_url.clone())
+            .header("Authorization", format!("Token {}", self.token))
+            .header("Content-Type", "text/plain; charset=utf-8")
+            .body(body)
+            .send()
+            .await;
+
+        match result {
+            Ok(resp) if resp.status().is_success() => {
+                let written = self.buffer.len();
+                debug!("[InfluxDB] Wrote {} line(s)", written);
+                self.buffer.clear();
+                Ok(written)
+            }
+            Ok(resp) if is_transient(resp.status()) => {
+                warn!(
+                    "[InfluxDB] Write failed (HTTP {}) - keeping {} line(s) for retry",
+                    resp.status(), self.buffer.len()
+                );
+                Err(format!("HTTP {}", resp.status()))
+            }
+            Ok(resp) => {
+                let status = resp.status();
+                let body   = resp.text().await.unwrap_or_default();
+                error!(
+                    "[InfluxDB] Write rejected (HTTP {}): {} - dropping {} line(s)",
+                    status, body, self.buffer.len()
+                );
+                self.buffer.clear();
+                Err(format!("rejected (HTTP {})", status))
+            }
+            Err(e) => {
+                warn!("[InfluxDB] Write failed: {} - keeping {} line(s) for retry", e, self.buffer.len());
+                Err(e.to_string())
+            }
+        }
+    }
+}
+
+impl Sink for InfluxDbSink {
+    fn name(&self) -> &'static str {
+        "influxdb"
+    }
+
+    /// Queue this cycle's lines and try to flush everything buffered so far.
+    async fn write(&mut self, record: &CycleRecord) -> Result<(), String> {
+        self.queue(record.influx_lines.clone());
+        self.flush().await.map(|_| ())
+    }
+
+    /// Queue the records left on the channel and flush until `deadline`.
+    async fn drain(&mut self, records: Vec<Arc<CycleRecord>>, deadline: Instant) {
+        let count = records.len();
+        for record in records {
+            self.queue(record.influx_lines.clone());
+        }
+
+        let mut written = 0;
+        while !self.buffer.is_empty() {
+            match timeout_at(deadline, self.flush()).await {
+                Ok(Ok(lines)) => written += lines,
+                Ok(Err(_)) if Instant::now() + DRAIN_RETRY_DELAY < deadline => sleep(DRAIN_RETRY_DELAY).await,
+                _ => break,
+            }
+        }
+        if self.buffer.is_empty() {
+            info!("[InfluxDB] Shutdown: flushed {} line(s), {} record(s) left on the channel", written, count);
+        } else {
+            warn!(
+                "[InfluxDB] Shutdown: flushed {} line(s), dropped {} still buffered at the deadline",
+                written, self.buffer.len()
+            );
+        }
+    }
+}
+
+fn is_transient(status: StatusCode) -> bool {
+    status.is_server_error() || status == StatusCode::TOO_MANY_REQUESTS
+}
+
+// --------------------------------------------------------------------------------------------------------------
+// Line protocol formatting
+
+/// Escape a tag key/value: commas, spaces and equals signs need a backslash.
+fn escape_tag(value: &str) -> String {
+    value.replace('\\', "\\\\").replace(',', "\\,").replace(' ', "\\ ").replace('=', "\\=")
+}
+
+/// Quote a string field value.
+fn quote_field(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Line protocol for one battery snapshot, e.g.
+/// `battery,device_model=PowerFlex2000 cycle_id=42i,soc=82,power_w=-1200i,... 1718000000`
+pub fn battery_line(battery: &BatterySnapshot, cycle_id: u64, timestamp: DateTime<Utc>) -> String {
+    let mut fields = vec![
+        format!("cycle_id={}i", cycle_id),
+        format!("soc={}", battery.battery_soc),
+        format!("soc_raw={}", battery.battery_soc_raw),
+        format!("power_w={}i", battery.battery_power_w),
+        format!("meter_power_w={}i", battery.meter_power_w),
+        format!("dc_input_power1_w={}i", battery.dc_input_power1_w),
+        format!("dc_input_power2_w={}i", battery.dc_input_power2_w),
+        format!("total_dc_output_power_w={}i", battery.total_dc_output_power_w),
+        format!("total_ac_output_power_w={}i", battery.total_ac_output_power_w),
+        format!("total_ac_input_power_w={}i", battery.total_ac_input_power_w),
+        format!("daily_production_kwh={}", battery.daily_production_kwh),
+        format!("cumulative_production_kwh={}", battery.cumulative_production_kwh),
+        format!("daily_charging_kwh={}", battery.daily_charging_kwh),
+        format!("daily_discharging_kwh={}", battery.daily_discharging_kwh),
+        format!("total_charging_kwh={}", battery.total_charging_kwh),
+        format!("total_discharging_kwh={}", battery.total_discharging_kwh),
+        format!("total_ac_input_energy_kwh={}", battery.total_ac_input_energy_kwh),
+        format!("battery_state={}", quote_field(&battery.battery_state)),
+        format!("working_mode={}", quote_field(&battery.working_mode)),
+    ];
+    // Optional sensors only when polled, so a missing reading isn't written as 0.
+    fields.extend(battery.grid_frequency_hz.map(|hz| format!("grid_frequency_hz={}", hz)));
+    fields.extend(battery.grid_voltage_v.map(|v| format!("grid_voltage_v={}", v)));
+    format!(
+        "battery,device_model={} {} {}",
+        escape_tag(&battery.device_model),
+        fields.join(","),
+        timestamp.timestamp()
+    )
+}
+
+/// Line protocol for a device day closed by its counter reset, timestamped at its last reading, e.g.
+/// `device_day,date=2026-03-10 production_kwh=14.2,charging_kwh=6.1,discharging_kwh=5.8 1741647540`
+pub fn device_day_line(day: &DeviceDay) -> String {
+    format!(
+        "device_day,date={} production_kwh={},charging_kwh={},discharging_kwh={} {}",
+        day.date,
+        day.production_kwh,
+        day.charging_kwh,
+        day.discharging_kwh,
+        day.last_sample.timestamp()
+    )
+}
+
+/// Line protocol for one P1 reading, e.g.
+/// `p1,meter_model=ISKRA\ 2M550T-101 cycle_id=42i,active_power_w=2090,... 1718000000`
+pub fn p1_line(reading: &P1Reading, cycle_id: u64, timestamp: DateTime<Utc>) -> String {
+    let r = &reading.raw;
+    let fields = [
+        format!("cycle_id={}i", cycle_id),
+        format!("active_tariff={}i", r.active_tariff),
+        format!("active_power_w={}", r.active_power_w),
+        format!("active_power_l1_w={}", r.active_power_l1_w),
+        format!("active_power_l2_w={}", r.active_power_l2_w),
+        format!("active_power_l3_w={}", r.active_power_l3_w),
+        format!("active_voltage_l1_v={}", r.active_voltage_l1_v),
+        format!("active_voltage_l2_v={}", r.active_voltage_l2_v),
+        format!("active_voltage_l3_v={}", r.active_voltage_l3_v),
+        format!("active_power_average_w={}", r.active_power_average_w),
+        format!("monthly_power_peak_w={}", r.montly_power_peak_w),
+        format!("total_power_import_kwh={}", r.total_power_import_kwh),
+        format!("total_power_export_kwh={}", r.total_power_export_kwh),
+        format!("total_gas_m3={}", r.total_gas_m3),
+    ];
+    format!(
+        "p1,meter_model={} {} {}",
+        escape_tag(&r.meter_model),
+        fields.join(","),
+        timestamp.timestamp()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::handlers::indevolt::testing::MockIndevolt;
+    use crate::models::p1_models::P1Data;
+    use crate::runtime::outcome::CycleOutcome;
+    use chrono::TimeZone;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    fn at() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2026, 3, 10, 12, 0, 0).unwrap()
+    }
+
+    #[test]
+    fn tags_escape_commas_spaces_and_equals_signs() {
+        assert_eq!(escape_tag(r"ISKRA 2M550T,a=b\c"), r"ISKRA\ 2M550T\,a\=b\\c");
+    }
+
+    #[test]
+    fn string_fields_are_quoted_with_quotes_escaped() {
+        assert_eq!(quote_field(r#"say "hi"\"#), r#""say \"hi\"\\""#);
+    }
+
+    #[test]
+    fn a_battery_line_marks_integers_and_ends_in_whole_seconds() {
+        let battery = BatterySnapshot {
+            device_model:    "PowerFlex 2000".to_string(),
+            battery_soc:     82.5,
+            battery_power_w: -1200,
+            battery_state:   "Discharging".to_string(),
+            ..BatterySnapshot::default()
+        };
+        let line = battery_line(&battery, 42, at() + chrono::Duration::milliseconds(750));
+
+        assert!(line.starts_with(r"battery,device_model=PowerFlex\ 2000 cycle_id=42i,soc=82.5,"), "{line}");
+        assert!(line.contains(",power_w=-1200i,"), "{line}");
+        assert!(line.contains(r#",battery_state="Discharging","#), "{line}");
+        assert!(!line.contains("grid_frequency_hz"), "{line}");
+        // precision=s: the timestamp is whole seconds, the milliseconds dropped.
+        assert!(line.ends_with(&format!(" {}", at().timestamp())), "{line}");
+    }
+
+    #[test]
+    fn a_p1_line_escapes_the_meter_model() {
+        let reading = P1Reading {
+            raw: P1Data {
+                meter_model:    "ISKRA 2M550T-101".to_string(),
+                active_tariff:  2,
+                active_power_w: 2090.0,
+                ..P1Data::default()
+            },
+            monthly_power_peak_timestamp_utc: at(),
+            gas_timestamp_utc:                at(),
+            unchanged_for:                    Duration::ZERO,
+            dropped_fields:                   Vec::new(),
+        };
+        let line = p1_line(&reading, 7, at());
+
+        assert!(line.starts_with(r"p1,meter_model=ISKRA\ 2M550T-101 cycle_id=7i,active_tariff=2i,"), "{line}");
+        assert!(line.contains(",active_power_w=2090,"), "{line}");
+        assert!(line.ends_with(" 1773144000"), "{line}");
+    }
+
+    fn record(cycle_id: u64) -> Arc<CycleRecord> {
+        Arc::new(CycleRecord {
+            outcome:      CycleOutcome::new(cycle_id, true, 0, 10, 100, 30_000),
+            influx_lines: vec![format!("battery cycle_id={}i 1773144000", cycle_id)],
+        })
+    }
+
+    #[tokio::test]
+    async fn lines_buffered_after_a_failure_are_flushed_at_shutdown() {
+        // The first write gets a 503, every later one succeeds.
+        let calls  = Arc::new(AtomicUsize::new(0));
+        let seen   = Arc::clone(&calls);
+        let server = MockIndevolt::start(move |_| match seen.fetch_add(1, Ordering::SeqCst) {
+            0 => (503, String::new()),
+            _ => (204, String::new()),
+        })
+        .await;
+        let config   = Config { influxdb_url: server.base_url.clone(), ..Config::default() };
+        let mut sink = InfluxDbSink::from_config(&config).unwrap();
+
+        assert!(sink.write(&record(1)).await.is_err());
+        assert_eq!(sink.buffer.len(), 1);
+
+        // The record still on the channel and the one kept for the retry go out in one last write.
+        sink.drain(vec![record(2)], Instant::now() + Duration::from_secs(5)).await;
+        assert!(sink.buffer.is_empty());
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+}
@@ -0,0 +1,4 @@
+pub mod health;
+pub mod sink;
+#[cfg(feature = "influxdb")]
+pub mod influxdb;
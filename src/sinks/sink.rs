@@ -0,0 +1,131 @@
+use chrono::Utc;
+use std::future::Future;
+use std::sync::Arc;
+use tokio::sync::broadcast;
+use tokio::time::Instant;
+
+use super::health::SharedSinkHealth;
+use crate::runtime::pipeline::{drain_records, next_record, CycleRecord};
+use crate::runtime::shutdown::ShutdownSignal;
+
+// --------------------------------------------------------------------------------------------------------------
+// Sink trait and worker
+//
+// Every export integration implements `Sink` and runs in its own `sink_worker`, a supervised task subscribed to
+// the record channel (see `runtime/pipeline.rs`). The channel is the fan-out: each cycle's record reaches every
+// enabled sink at once, and a sink that is slow or failing only delays itself. Its failed writes are logged,
+// counted in the health table (see `health.rs`) and skipped; the worker goes on with the next record. At
+// shutdown the worker hands the sink the records still on the channel to write before the deadline.
+// --------------------------------------------------------------------------------------------------------------
+
+pub trait Sink: Send + 'static {
+    /// Name in the logs and in `GET /sinks`.
+    fn name(&self) -> &'static str;
+
+    /// Write one cycle's record. A sink that keeps data for a retry does so itself; the error is only reported.
+    fn write(&mut self, record: &CycleRecord) -> impl Future<Output = Result<(), String>> + Send;
+
+    /// Shutdown: write `records` and whatever the sink still holds, giving up at `deadline`.
+    fn drain(&mut self, records: Vec<Arc<CycleRecord>>, deadline: Instant) -> impl Future<Output = ()> + Send;
+}
+
+/// Write every record to `sink`, reporting each result in `health`. Returns when the control loop's sender is
+/// gone, or after the sink's final drain at shutdown.
+pub async fn sink_worker<S: Sink>(
+    mut sink: S,
+    mut rx: broadcast::Receiver<Arc<CycleRecord>>,
+    mut shutdown: ShutdownSignal,
+    health: SharedSinkHealth,
+) {
+    let name = sink.name();
+    health.lock().unwrap_or_else(|e| e.into_inner()).register(name);
+
+    loop {
+        tokio::select! {
+            record = next_record(&mut rx, name) => match record {
+                Some(record) => {
+                    let result = sink.write(&record).await;
+                    health.lock().unwrap_or_else(|e| e.into_inner()).record(name, &result, Utc::now());
+                }
+                None => return,
+            },
+            deadline = shutdown.drain_deadline() => {
+                let records = drain_records(&mut rx, name);
+                return sink.drain(records, deadline).await;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runtime::outcome::CycleOutcome;
+    use crate::runtime::pipeline::record_channel;
+    use crate::runtime::shutdown::Shutdown;
+    use crate::sinks::health::SinkHealthTable;
+    use std::sync::Mutex;
+
+    /// A sink that notes the cycle IDs it is given and fails every write when `failing`.
+    struct TestSink {
+        name:    &'static str,
+        failing: bool,
+        written: Arc<Mutex<Vec<u64>>>,
+    }
+
+    impl Sink for TestSink {
+        fn name(&self) -> &'static str {
+            self.name
+        }
+
+        async fn write(&mut self, record: &CycleRecord) -> Result<(), String> {
+            self.written.lock().unwrap().push(record.outcome.cycle_id);
+            if self.failing { Err("connection refused".to_string()) } else { Ok(()) }
+        }
+
+        async fn drain(&mut self, _records: Vec<Arc<CycleRecord>>, _deadline: Instant) {}
+    }
+
+    fn record(cycle_id: u64) -> Arc<CycleRecord> {
+        Arc::new(CycleRecord {
+            outcome:      CycleOutcome::new(cycle_id, true, 0, 10, 100, 30_000),
+            #[cfg(feature = "influxdb")]
+            influx_lines: Vec::new(),
+        })
+    }
+
+    #[tokio::test]
+    async fn a_failing_sink_does_not_hold_up_the_others() {
+        let tx       = record_channel();
+        let shutdown = Shutdown::new();
+        let health   = SinkHealthTable::shared();
+        let mut written = Vec::new();
+        let mut workers = Vec::new();
+        for (name, failing) in [("good", false), ("bad", true)] {
+            let sink = TestSink { name, failing, written: Arc::default() };
+            written.push(Arc::clone(&sink.written));
+            workers.push(tokio::spawn(sink_worker(sink, tx.subscribe(), shutdown.signal(), health.clone())));
+        }
+
+        for cycle_id in 1..=3 {
+            tx.send(record(cycle_id)).unwrap();
+        }
+        drop(tx);
+        for worker in workers {
+            worker.await.unwrap();
+        }
+
+        // Both sinks were given every record.
+        for sink in &written {
+            assert_eq!(*sink.lock().unwrap(), vec![1, 2, 3]);
+        }
+        let health = health.lock().unwrap();
+        let good   = health.get("good").unwrap();
+        assert!(good.last_success.is_some());
+        assert_eq!(good.consecutive_failures, 0);
+        let bad    = health.get("bad").unwrap();
+        assert!(bad.last_success.is_none());
+        assert_eq!(bad.consecutive_failures, 3);
+        assert_eq!(bad.last_error.as_deref(), Some("connection refused"));
+    }
+}